@@ -1,11 +1,14 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{collections::VecDeque, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
-    Router,
+    Json, Router,
 };
 use rlog_collector::IndexLogEntry;
+use serde_json::json;
 use tokio::{net::TcpListener, sync::RwLock};
 
 use crate::test_utils::BindAddresses;
@@ -14,22 +17,105 @@ use crate::test_utils::BindAddresses;
 
 pub struct MockQuickwitServer {
     received: Arc<RwLock<Vec<IndexLogEntry>>>,
+    v2_request_count: Arc<RwLock<usize>>,
+    ingest_delay: Arc<RwLock<Duration>>,
+    last_ingest_headers: Arc<RwLock<HeaderMap>>,
+    failures: Arc<RwLock<VecDeque<(StatusCode, String)>>>,
+    request_documents: Arc<RwLock<Vec<usize>>>,
+    max_payload_size: Arc<RwLock<Option<usize>>>,
+    index_config: Arc<RwLock<Option<serde_json::Value>>>,
+}
+
+/// Documents having this field are rejected by the ingest API v2 of the mock
+pub const MOCK_REJECT_FIELD: &str = "mock_reject";
+
+#[derive(Clone)]
+struct MockState {
+    received: Arc<RwLock<Vec<IndexLogEntry>>>,
+    v2_request_count: Arc<RwLock<usize>>,
+    ingest_delay: Arc<RwLock<Duration>>,
+    last_ingest_headers: Arc<RwLock<HeaderMap>>,
+    failures: Arc<RwLock<VecDeque<(StatusCode, String)>>>,
+    request_documents: Arc<RwLock<Vec<usize>>>,
+    max_payload_size: Arc<RwLock<Option<usize>>>,
+    index_config: Arc<RwLock<Option<serde_json::Value>>>,
+}
+
+impl MockState {
+    /// Counts the documents of an ingest request, accepted or not
+    async fn record_request(&self, body: &str) {
+        self.request_documents
+            .write()
+            .await
+            .push(body.lines().count());
+    }
+
+    async fn check_payload_size(&self, body: &str) -> Option<Response> {
+        match *self.max_payload_size.read().await {
+            Some(max_payload_size) if body.len() > max_payload_size => {
+                Some((StatusCode::BAD_REQUEST, "The request payload is too large").into_response())
+            }
+            _ => None,
+        }
+    }
 }
 
 impl MockQuickwitServer {
     pub fn start(index_id: &str, bind_addresses: &BindAddresses) -> Self {
-        let received = Arc::new(RwLock::new(vec![]));
+        Self::start_with_version(index_id, bind_addresses, None)
+    }
+
+    /// Start a mock quickwit server reporting the given `version` on `/api/v1/version`,
+    /// if `None` the version route is not available.
+    pub fn start_with_version(
+        index_id: &str,
+        bind_addresses: &BindAddresses,
+        version: Option<&str>,
+    ) -> Self {
+        let state = MockState {
+            received: Arc::new(RwLock::new(vec![])),
+            v2_request_count: Arc::new(RwLock::new(0)),
+            ingest_delay: Arc::new(RwLock::new(Duration::ZERO)),
+            last_ingest_headers: Arc::new(RwLock::new(HeaderMap::new())),
+            failures: Arc::new(RwLock::new(VecDeque::new())),
+            request_documents: Arc::new(RwLock::new(vec![])),
+            max_payload_size: Arc::new(RwLock::new(None)),
+            index_config: Arc::new(RwLock::new(None)),
+        };
 
         let ingest_route = format!("/api/v1/{index_id}/ingest");
-        let app = Router::new()
+        let ingest_v2_route = format!("/api/v1/{index_id}/ingest-v2");
+        let index_metadata_route = format!("/api/v1/indexes/{index_id}");
+        let mut app = Router::new()
             .route("/", get(|| async { "hello!" }))
+            .route(
+                &index_metadata_route,
+                get(|state: State<MockState>| async move {
+                    match state.index_config.read().await.clone() {
+                        Some(index_config) => {
+                            Json(json!({ "index_config": index_config })).into_response()
+                        }
+                        None => (StatusCode::NOT_FOUND, "index not found").into_response(),
+                    }
+                }),
+            )
             .route(
                 &ingest_route,
                 post(
-                    |received: State<Arc<RwLock<Vec<IndexLogEntry>>>>, body: String| async move {
+                    |state: State<MockState>, headers: HeaderMap, body: String| async move {
                         tracing::info!("Received: {body}");
+                        *state.last_ingest_headers.write().await = headers;
+                        state.record_request(&body).await;
+                        let ingest_delay = *state.ingest_delay.read().await;
+                        tokio::time::sleep(ingest_delay).await;
+                        if let Some(failure) = state.failures.write().await.pop_front() {
+                            return failure.into_response();
+                        }
+                        if let Some(response) = state.check_payload_size(&body).await {
+                            return response;
+                        }
 
-                        let mut received = received.write().await;
+                        let mut received = state.received.write().await;
 
                         for log in body.lines() {
                             match serde_json::from_str::<IndexLogEntry>(log) {
@@ -40,11 +126,65 @@ impl MockQuickwitServer {
                             }
                         }
 
-                        "TODO: a real quickwit response"
+                        "TODO: a real quickwit response".into_response()
                     },
                 ),
             )
-            .with_state(received.clone());
+            .route(
+                &ingest_v2_route,
+                post(
+                    |state: State<MockState>, headers: HeaderMap, body: String| async move {
+                        tracing::info!("Received (v2): {body}");
+                        *state.last_ingest_headers.write().await = headers;
+                        state.record_request(&body).await;
+                        *state.v2_request_count.write().await += 1;
+                        let ingest_delay = *state.ingest_delay.read().await;
+                        tokio::time::sleep(ingest_delay).await;
+                        if let Some(failure) = state.failures.write().await.pop_front() {
+                            return failure.into_response();
+                        }
+                        if let Some(response) = state.check_payload_size(&body).await {
+                            return response;
+                        }
+
+                        let mut received = state.received.write().await;
+                        let mut parse_failures = vec![];
+                        let mut num_docs = 0;
+
+                        for log in body.lines() {
+                            num_docs += 1;
+                            match serde_json::from_str::<IndexLogEntry>(log) {
+                                Ok(log_entry)
+                                    if !log_entry.free_fields.contains_key(MOCK_REJECT_FIELD) =>
+                                {
+                                    received.push(log_entry)
+                                }
+                                _ => parse_failures.push(json!({
+                                    "document": log,
+                                    "message": "rejected by mock",
+                                    "reason": "doc_parsing",
+                                })),
+                            }
+                        }
+
+                        Json(json!({
+                            "num_docs_for_processing": num_docs,
+                            "num_ingested_docs": num_docs - parse_failures.len(),
+                            "num_rejected_docs": parse_failures.len(),
+                            "parse_failures": parse_failures,
+                        }))
+                        .into_response()
+                    },
+                ),
+            );
+        if let Some(version) = version {
+            let version = version.to_string();
+            app = app.route(
+                "/api/v1/version",
+                get(|| async move { Json(json!({ "build": { "version": version } })) }),
+            );
+        }
+        let app = app.with_state(state.clone());
         let sock_addr = bind_addresses
             .quickwit_bind_address
             .parse::<SocketAddr>()
@@ -57,13 +197,118 @@ impl MockQuickwitServer {
             .await
             .unwrap();
         });
-        Self { received }
+        Self {
+            received: state.received,
+            v2_request_count: state.v2_request_count,
+            ingest_delay: state.ingest_delay,
+            last_ingest_headers: state.last_ingest_headers,
+            failures: state.failures,
+            request_documents: state.request_documents,
+            max_payload_size: state.max_payload_size,
+            index_config: state.index_config,
+        }
+    }
+
+    /// Ingest requests with a larger body (bytes) are rejected like quickwit does
+    pub async fn set_max_payload_size(&self, max_payload_size: Option<usize>) {
+        *self.max_payload_size.write().await = max_payload_size;
+    }
+
+    /// The next ingest requests (any API version) fail with the given status and body,
+    /// in order
+    pub async fn push_ingest_failure(&self, status: StatusCode, body: &str) {
+        self.failures
+            .write()
+            .await
+            .push_back((status, body.to_string()));
+    }
+
+    /// The next ingest request fails with the given status, the body being its reason phrase
+    pub async fn push_response(&self, status: StatusCode) {
+        self.push_ingest_failure(status, status.canonical_reason().unwrap_or_default())
+            .await;
+    }
+
+    /// The next `n` ingest requests fail with an internal server error
+    pub async fn fail_next(&self, n: usize) {
+        for _ in 0..n {
+            self.push_response(StatusCode::INTERNAL_SERVER_ERROR).await;
+        }
+    }
+
+    /// `index_config` of the index metadata route, the index is not found if not set
+    pub async fn set_index_config(&self, index_config: serde_json::Value) {
+        *self.index_config.write().await = Some(index_config);
+    }
+
+    /// Headers of the last ingest request (any API version)
+    pub async fn get_last_ingest_headers(&self) -> HeaderMap {
+        self.last_ingest_headers.read().await.clone()
+    }
+
+    /// Simulate a slow quickwit: ingest requests are answered after `delay`
+    pub async fn set_ingest_delay(&self, delay: Duration) {
+        *self.ingest_delay.write().await = delay;
     }
 
     pub async fn get_received(&self) -> Vec<IndexLogEntry> {
         self.received.read().await.iter().cloned().collect()
     }
 
+    /// Number of received documents
+    pub async fn count(&self) -> usize {
+        self.received.read().await.len()
+    }
+
+    /// Received documents of the given service, in the order they were received
+    pub async fn get_by_service(&self, service_name: &str) -> Vec<IndexLogEntry> {
+        self.get_received_matching(|entry| entry.service_name == service_name)
+            .await
+    }
+
+    /// Received documents with the given message, in the order they were received
+    pub async fn get_by_message(&self, message: &str) -> Vec<IndexLogEntry> {
+        self.get_received_matching(|entry| entry.message == message)
+            .await
+    }
+
+    /// The single received document with the given message, panics if there is none or
+    /// several
+    pub async fn get_single_by_message(&self, message: &str) -> IndexLogEntry {
+        let mut received = self.get_by_message(message).await;
+        assert_eq!(1, received.len(), "{message}: {received:?}");
+        received.remove(0)
+    }
+
+    async fn get_received_matching(
+        &self,
+        predicate: impl Fn(&IndexLogEntry) -> bool,
+    ) -> Vec<IndexLogEntry> {
+        self.received
+            .read()
+            .await
+            .iter()
+            .filter(|entry| predicate(entry))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of requests received on the ingest endpoints (any API version), failed or not
+    pub async fn get_request_count(&self) -> usize {
+        self.request_documents.read().await.len()
+    }
+
+    /// Number of documents of each ingest request (any API version), in order, failed
+    /// requests included
+    pub async fn get_request_documents(&self) -> Vec<usize> {
+        self.request_documents.read().await.clone()
+    }
+
+    /// Number of requests received on the ingest API v2 endpoint
+    pub async fn get_v2_request_count(&self) -> usize {
+        *self.v2_request_count.read().await
+    }
+
     pub fn url(bind_addresses: &BindAddresses) -> String {
         format!("http://{}/", bind_addresses.quickwit_bind_address)
     }
@@ -1,11 +1,38 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::HashMap,
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
 
-use rlog_collector::{CollectorServer, CollectorServerConfig};
-use rlog_grpc::tonic::transport::{Channel, Server, Uri};
-use rlog_shipper::{ServerConfig, ShipperServer};
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+use rlog_collector::{
+    CollectorServer, CollectorServerConfig, GrpcTlsConfig, HttpStatusTlsConfig, OutputBackend,
+    QuickwitOutput,
+};
+use rlog_common::log_signature::SIGNING_KEY_LEN;
+use rlog_grpc::{
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{
+        log_collector_client::LogCollectorClient, log_line::Line, GelfLogLine, LogLine,
+        SyslogSeverity,
+    },
+    tonic::transport::{Channel, Server, ServerTlsConfig, Uri},
+};
+use rlog_shipper::{
+    grpc_tls::PinnedTlsConnector, stdin_in::StdinReader, ServerConfig, ShipperServer,
+};
 use serde::Serialize;
-use syslog::{Facility, Severity};
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use syslog::{Facility, Formatter5424, LogFormat, Severity};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpSocket, TcpStream, UdpSocket},
+};
 
 use crate::quickwit_mock::MockQuickwitServer;
 
@@ -47,12 +74,37 @@ impl GelfLogger {
         })
     }
 
+    /// Connect from the given local IP (eg: `127.0.0.2` to get another loopback peer)
+    pub async fn new_from(addr: &str, local_ip: IpAddr) -> anyhow::Result<Self> {
+        let socket = match local_ip {
+            IpAddr::V4(_) => TcpSocket::new_v4()?,
+            IpAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.bind(SocketAddr::new(local_ip, 0))?;
+        Ok(Self {
+            stream: socket.connect(addr.parse()?).await?,
+        })
+    }
+
     pub async fn send_log<'a>(&mut self, log: &GelfLog<'a>) -> anyhow::Result<()> {
-        let bytes = serde_json::to_vec(&log)?;
-        self.stream.write_all(&bytes).await?;
+        self.send_frame(&serde_json::to_vec(&log)?).await
+    }
+
+    /// Send the bytes followed by the frame delimiter, eg: to send invalid JSON
+    pub async fn send_frame(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.stream.write_all(bytes).await?;
         self.stream.write_u8(0).await?;
         Ok(())
     }
+
+    /// Close the connection and wait for the shipper to close its side, which it does once
+    /// it has read all the sent frames (unless it was stopped before)
+    pub async fn close(mut self) -> anyhow::Result<()> {
+        self.stream.shutdown().await?;
+        let mut unexpected = vec![];
+        self.stream.read_to_end(&mut unexpected).await?;
+        Ok(())
+    }
 }
 
 #[derive(Serialize)]
@@ -71,6 +123,172 @@ pub struct GelfLog<'a> {
     pub extra_fields: serde_json::Value,
 }
 
+/// Payload compression allowed by the GELF UDP spec
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GelfCompression {
+    #[default]
+    None,
+    Gzip,
+    Zlib,
+}
+
+impl GelfCompression {
+    fn compress(&self, payload: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            GelfCompression::None => payload,
+            GelfCompression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(&payload)?;
+                encoder.finish()?
+            }
+            GelfCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                encoder.write_all(&payload)?;
+                encoder.finish()?
+            }
+        })
+    }
+}
+
+/// GELF chunk magic bytes
+pub const GELF_CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+/// magic bytes, message id, sequence number and sequence count
+pub const GELF_CHUNK_HEADER_SIZE: usize = 12;
+/// A GELF message cannot be sent in more chunks
+pub const GELF_MAX_CHUNKS: usize = 128;
+
+/// Split a GELF payload in datagrams of at most `max_datagram_size` bytes
+/// (chunk header included), a payload fitting in a single datagram is not chunked.
+pub fn gelf_chunks(
+    payload: &[u8],
+    max_datagram_size: usize,
+    message_id: u64,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    if payload.len() <= max_datagram_size {
+        return Ok(vec![payload.to_vec()]);
+    }
+    if max_datagram_size <= GELF_CHUNK_HEADER_SIZE {
+        anyhow::bail!("Datagram size {max_datagram_size} too small for GELF chunks");
+    }
+    let chunks = payload
+        .chunks(max_datagram_size - GELF_CHUNK_HEADER_SIZE)
+        .collect::<Vec<_>>();
+    if chunks.len() > GELF_MAX_CHUNKS {
+        anyhow::bail!(
+            "GELF message of {} bytes needs {} chunks (max {GELF_MAX_CHUNKS})",
+            payload.len(),
+            chunks.len()
+        );
+    }
+    let count = chunks.len() as u8;
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(sequence, chunk)| {
+            let mut datagram = Vec::with_capacity(GELF_CHUNK_HEADER_SIZE + chunk.len());
+            datagram.extend_from_slice(&GELF_CHUNK_MAGIC);
+            datagram.extend_from_slice(&message_id.to_be_bytes());
+            datagram.push(sequence as u8);
+            datagram.push(count);
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect())
+}
+
+/// GELF log line of `my_gelf_host` sent to the collector, timestamped 1_700_000_000
+pub fn gelf_log_line(short_message: impl Into<String>) -> LogLine {
+    gelf_log_line_with_extra(short_message, "{}")
+}
+
+/// GELF log line of `my_gelf_host` with the given additional fields (JSON)
+pub fn gelf_log_line_with_extra(
+    short_message: impl Into<String>,
+    extra: impl Into<String>,
+) -> LogLine {
+    LogLine {
+        host: "my_gelf_host".into(),
+        timestamp: Some(Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 0,
+        }),
+        line: Some(Line::Gelf(GelfLogLine {
+            short_message: short_message.into(),
+            severity: SyslogSeverity::Info as i32,
+            extra: extra.into(),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
+
+/// Sends GELF messages over UDP, chunked and optionally compressed
+pub struct GelfUdpLogger {
+    socket: UdpSocket,
+    max_datagram_size: usize,
+    compression: GelfCompression,
+}
+
+impl GelfUdpLogger {
+    /// Default maximum datagram size, suitable for a WAN
+    pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1420;
+
+    pub async fn new(addr: &str) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        socket.connect(addr).await?;
+        Ok(Self {
+            socket,
+            max_datagram_size: Self::DEFAULT_MAX_DATAGRAM_SIZE,
+            compression: GelfCompression::None,
+        })
+    }
+
+    pub fn with_max_datagram_size(mut self, max_datagram_size: usize) -> Self {
+        self.max_datagram_size = max_datagram_size;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: GelfCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub async fn send_log<'a>(&mut self, log: &GelfLog<'a>) -> anyhow::Result<()> {
+        let payload = self.compression.compress(serde_json::to_vec(&log)?)?;
+        for datagram in gelf_chunks(&payload, self.max_datagram_size, rand::random())? {
+            self.socket.send(&datagram).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends RFC 5424 syslog messages over TCP with octet-counting framing (RFC 6587)
+pub struct SyslogTcpLogger {
+    stream: TcpStream,
+    formatter: Formatter5424,
+}
+
+impl SyslogTcpLogger {
+    pub async fn new(addr: &str, formatter: Formatter5424) -> anyhow::Result<Self> {
+        Ok(Self {
+            stream: TcpStream::connect(addr).await?,
+            formatter,
+        })
+    }
+
+    pub async fn send(&mut self, msg: &str, severity: Severity) -> anyhow::Result<()> {
+        let mut message = vec![];
+        self.formatter
+            .format(&mut message, severity, (123, StructuredData::new(), msg))
+            .map_err(|e| anyhow::anyhow!("Unable to format syslog message: {e}"))?;
+        self.stream
+            .write_all(format!("{} ", message.len()).as_bytes())
+            .await?;
+        self.stream.write_all(&message).await?;
+        Ok(())
+    }
+}
+
 fn find_open_ports<const N: usize>() -> [u16; N] {
     find_open_ports_excluding(&[])
 }
@@ -93,20 +311,35 @@ pub struct BindAddresses {
     pub grpc_bind_address: String,
     pub shipper_gelf_bind: String,
     pub shipper_syslog_bind: String,
+    /// reserved for a GELF UDP input, not bound by the shipper yet (see [GelfUdpLogger])
+    pub shipper_gelf_udp_bind: String,
+    /// reserved for a syslog TCP input, not bound by the shipper yet (see [SyslogTcpLogger])
+    pub shipper_syslog_tcp_bind: String,
     pub collector_http_bind: String,
+    pub shipper_http_bind: String,
     pub quickwit_bind_address: String,
     used_ports: Vec<u16>,
 }
 
+/// Addresses of an additional collector, see [BindAddresses::new_collector_addresses]
+#[derive(Clone)]
+pub struct CollectorBindAddresses {
+    pub grpc_bind_address: String,
+    pub collector_http_bind: String,
+}
+
 impl Default for BindAddresses {
     fn default() -> Self {
-        let ports = find_open_ports::<5>();
+        let ports = find_open_ports::<8>();
         Self {
             grpc_bind_address: format!("127.0.0.1:{}", ports[0]),
             shipper_gelf_bind: format!("127.0.0.1:{}", ports[1]),
             shipper_syslog_bind: format!("127.0.0.1:{}", ports[2]),
             collector_http_bind: format!("127.0.0.1:{}", ports[3]),
             quickwit_bind_address: format!("127.0.0.1:{}", ports[4]),
+            shipper_gelf_udp_bind: format!("127.0.0.1:{}", ports[5]),
+            shipper_syslog_tcp_bind: format!("127.0.0.1:{}", ports[6]),
+            shipper_http_bind: format!("127.0.0.1:{}", ports[7]),
             used_ports: ports.to_vec(),
         }
     }
@@ -117,26 +350,226 @@ impl BindAddresses {
         MockQuickwitServer::start(index_id, &self)
     }
 
+    pub fn start_quickwit_with_version(
+        &self,
+        index_id: &str,
+        version: Option<&str>,
+    ) -> MockQuickwitServer {
+        MockQuickwitServer::start_with_version(index_id, self, version)
+    }
+
     pub fn start_collector(&self, index_id: &str) -> Result<CollectorServer, anyhow::Error> {
-        rlog_collector::CollectorServer::start_collector_server(CollectorServerConfig {
-            http_status_bind_address: self.collector_http_bind.clone(),
-            grpc_bind_address: self.grpc_bind_address.clone(),
-            quickwit_rest_url: MockQuickwitServer::url(&self),
-            quickwit_index_id: index_id.to_string(),
+        self.start_collector_with_headers(index_id, HashMap::new())
+    }
+
+    pub fn start_collector_with_headers(
+        &self,
+        index_id: &str,
+        quickwit_extra_headers: HashMap<String, String>,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.outputs = vec![OutputBackend::Quickwit(QuickwitOutput {
+            rest_url: MockQuickwitServer::url(self),
+            index_id: index_id.to_string(),
+            extra_headers: quickwit_extra_headers,
+        })];
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector sending the batches to `outputs` instead of the mock quickwit
+    pub fn start_collector_with_outputs(
+        &self,
+        outputs: Vec<OutputBackend>,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config("rlog")?;
+        config.outputs = outputs;
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector serving gRPC over TLS
+    pub fn start_collector_with_tls(
+        &self,
+        index_id: &str,
+        tls_config: ServerTlsConfig,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.server = Server::builder().tls_config(tls_config)?;
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector serving gRPC over TLS, handshakes made by rustls directly
+    pub fn start_collector_with_grpc_tls(
+        &self,
+        index_id: &str,
+        grpc_tls: GrpcTlsConfig,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.grpc_tls = Some(grpc_tls);
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector serving the HTTP status server over TLS
+    pub fn start_collector_with_http_status_tls(
+        &self,
+        index_id: &str,
+        http_status_tls: HttpStatusTlsConfig,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.http_status_tls = Some(http_status_tls);
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector with the gRPC reflection service enabled
+    pub fn start_collector_with_reflection(
+        &self,
+        index_id: &str,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.enable_reflection = true;
+        CollectorServer::start_collector_server(config)
+    }
+
+    /// Start a collector bound to `collector_addresses`, sending logs to the same quickwit
+    pub fn start_collector_at(
+        &self,
+        collector_addresses: &CollectorBindAddresses,
+        index_id: &str,
+    ) -> Result<CollectorServer, anyhow::Error> {
+        let mut config = self.collector_config(index_id)?;
+        config.grpc_bind_address = collector_addresses.grpc_bind_address.parse()?;
+        config.http_status_bind_address = collector_addresses.collector_http_bind.parse()?;
+        CollectorServer::start_collector_server(config)
+    }
+
+    fn collector_config(&self, index_id: &str) -> Result<CollectorServerConfig, anyhow::Error> {
+        Ok(CollectorServerConfig {
+            http_status_bind_address: self.collector_http_bind.parse()?,
+            http_status_tls: None,
+            grpc_bind_address: self.grpc_bind_address.parse()?,
+            grpc_tls: None,
+            enable_reflection: false,
+            outputs: vec![OutputBackend::Quickwit(QuickwitOutput {
+                rest_url: MockQuickwitServer::url(self),
+                index_id: index_id.to_string(),
+                extra_headers: HashMap::new(),
+            })],
+            index_sla_threshold_ms: None,
             server: Server::builder(),
         })
     }
 
     pub async fn start_shipper(&self) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(self.shipper_config()?).await
+    }
+
+    /// Start a shipper reaching the collector through the given HTTP proxy URL
+    pub async fn start_shipper_with_proxy(
+        &self,
+        grpc_proxy: Option<&str>,
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            grpc_proxy: grpc_proxy.map(str::parse).transpose()?,
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper connected to a TLS collector with the given pinning connector
+    pub async fn start_shipper_with_pinned_tls(
+        &self,
+        grpc_pinned_tls: PinnedTlsConnector,
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            grpc_pinned_tls: Some(grpc_pinned_tls),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper sending the given HTTP/2 `:authority` to the collector
+    pub async fn start_shipper_with_authority(
+        &self,
+        grpc_authority: &str,
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            grpc_authority: Some(grpc_authority.into()),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper failing if the collector is not reachable within `startup_timeout`
+    pub async fn start_shipper_requiring_collector(
+        &self,
+        startup_timeout: Duration,
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            require_collector_on_start: Some(startup_timeout),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper signing log lines with the given key (`sign_log_entries` must be set)
+    pub async fn start_shipper_with_signing_key(
+        &self,
+        log_signing_key: [u8; SIGNING_KEY_LEN],
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            log_signing_key: Some(log_signing_key),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper with its HTTP status server bound to a free port, written to
+    /// `port_file`
+    pub async fn start_shipper_with_port_file(
+        &self,
+        port_file: &str,
+    ) -> Result<ShipperServer, anyhow::Error> {
         rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            http_status_bind_address: Some("127.0.0.1:0".parse()?),
+            port_file: Some(port_file.into()),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    /// Start a shipper reading `stdin` as its standard input (`stdin_in` must be set)
+    pub async fn start_shipper_with_stdin(
+        &self,
+        stdin: StdinReader,
+    ) -> Result<ShipperServer, anyhow::Error> {
+        rlog_shipper::ShipperServer::start_shipper_server(ServerConfig {
+            stdin: Some(stdin),
+            ..self.shipper_config()?
+        })
+        .await
+    }
+
+    fn shipper_config(&self) -> Result<ServerConfig, anyhow::Error> {
+        Ok(ServerConfig {
             grpc_collector_endpoint: Channel::builder(Uri::from_str(&format!(
                 "http://{}",
                 self.grpc_bind_address
             ))?),
-            syslog_udp_bind_address: self.shipper_syslog_bind.clone(),
-            gelf_tcp_bind_address: self.shipper_gelf_bind.clone(),
+            grpc_proxy: None,
+            grpc_pinned_tls: None,
+            grpc_authority: None,
+            syslog_udp_bind_address: self.shipper_syslog_bind.parse()?,
+            gelf_tcp_bind_address: self.shipper_gelf_bind.parse()?,
+            require_collector_on_start: None,
+            log_signing_key: None,
+            http_status_bind_address: Some(self.shipper_http_bind.parse()?),
+            port_file: None,
+            stdin: None,
         })
-        .await
+    }
+
+    /// gRPC client directly connected to the collector (plain text)
+    pub async fn collector_client(&self) -> anyhow::Result<LogCollectorClient<Channel>> {
+        Ok(LogCollectorClient::connect(format!("http://{}", self.grpc_bind_address)).await?)
     }
 
     /// This will try to connect to gelf in TCP so the shipper server
@@ -145,20 +578,99 @@ impl BindAddresses {
         GelfLogger::new(&self.shipper_gelf_bind).await
     }
 
+    /// GELF UDP logger sending to `shipper_gelf_udp_bind`
+    pub async fn gelf_udp_logger(&self) -> anyhow::Result<GelfUdpLogger> {
+        GelfUdpLogger::new(&self.shipper_gelf_udp_bind).await
+    }
+
+    /// Syslog TCP logger connected to `shipper_syslog_tcp_bind`, the listener
+    /// must be started before calling this.
+    pub async fn syslog_tcp_logger(
+        &self,
+        process: &str,
+        hostname: &str,
+        pid: u32,
+        facility: Facility,
+    ) -> anyhow::Result<SyslogTcpLogger> {
+        SyslogTcpLogger::new(
+            &self.shipper_syslog_tcp_bind,
+            syslog::Formatter5424 {
+                facility,
+                hostname: Some(hostname.into()),
+                process: process.into(),
+                pid,
+            },
+        )
+        .await
+    }
+
     /// This must not be called on "child" BindAddressed
     pub fn new_shipper_addresses(&mut self) -> Self {
         if self.used_ports.len() == 0 {
             panic!("This must only be used on the root struct");
         }
-        let ports = find_open_ports_excluding::<2>(&self.used_ports);
+        let ports = find_open_ports_excluding::<5>(&self.used_ports);
         self.used_ports.extend_from_slice(&ports);
         Self {
             grpc_bind_address: self.grpc_bind_address.clone(),
             shipper_gelf_bind: format!("127.0.0.1:{}", ports[0]),
             shipper_syslog_bind: format!("127.0.0.1:{}", ports[1]),
+            shipper_gelf_udp_bind: format!("127.0.0.1:{}", ports[2]),
+            shipper_syslog_tcp_bind: format!("127.0.0.1:{}", ports[3]),
+            shipper_http_bind: format!("127.0.0.1:{}", ports[4]),
             collector_http_bind: self.collector_http_bind.clone(),
             quickwit_bind_address: self.quickwit_bind_address.clone(),
             used_ports: vec![],
         }
     }
+
+    /// Addresses of another collector, this must not be called on "child" BindAddresses
+    pub fn new_collector_addresses(&mut self) -> CollectorBindAddresses {
+        if self.used_ports.is_empty() {
+            panic!("This must only be used on the root struct");
+        }
+        let ports = find_open_ports_excluding::<2>(&self.used_ports);
+        self.used_ports.extend_from_slice(&ports);
+        CollectorBindAddresses {
+            grpc_bind_address: format!("127.0.0.1:{}", ports[0]),
+            collector_http_bind: format!("127.0.0.1:{}", ports[1]),
+        }
+    }
+}
+
+/// Test PKI: a CA, a `localhost` server certificate and a client certificate
+pub struct Pki {
+    pub ca: Certificate,
+    pub server: (Certificate, KeyPair),
+    pub client: (Certificate, KeyPair),
+}
+
+pub fn generate_pki() -> anyhow::Result<Pki> {
+    let mut ca_params = CertificateParams::default();
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "rlog test CA");
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_key = KeyPair::generate()?;
+    let ca = ca_params.self_signed(&ca_key)?;
+
+    let server_key = KeyPair::generate()?;
+    let server = CertificateParams::new(vec!["localhost".to_string()])?.signed_by(
+        &server_key,
+        &ca,
+        &ca_key,
+    )?;
+
+    let mut client_params = CertificateParams::default();
+    client_params
+        .distinguished_name
+        .push(DnType::CommonName, "my_shipper");
+    let client_key = KeyPair::generate()?;
+    let client = client_params.signed_by(&client_key, &ca, &ca_key)?;
+
+    Ok(Pki {
+        ca,
+        server: (server, server_key),
+        client: (client, client_key),
+    })
 }
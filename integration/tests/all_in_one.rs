@@ -0,0 +1,86 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{self, BindAddresses, GelfLog};
+use rlog_collector::{
+    config::{Config, CONFIG},
+    LogSystem,
+};
+use rlog_inputs::config::{GelfInputConfig, SyslogInputConfig};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn collector_inputs() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    CONFIG.store(Arc::new(Config {
+        gelf_in: Some(GelfInputConfig::default()),
+        collector_gelf_in_bind_address: bind_addresses.shipper_gelf_bind.parse()?,
+        syslog_in: Some(SyslogInputConfig::default()),
+        collector_syslog_in_bind_address: bind_addresses.shipper_syslog_bind.parse()?,
+        ..Default::default()
+    }));
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    // no shipper: logs are sent straight to the collector inputs
+    let collector = bind_addresses.start_collector("rlog")?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    test_utils::send_syslog(
+        "connect from localhost",
+        "postfix",
+        "my_host",
+        1234,
+        syslog::Facility::LOG_MAIL,
+        Severity::LOG_WARNING,
+        &bind_addresses,
+    );
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "hello gelf",
+            long_message: None,
+            level: Severity::LOG_ERR as usize,
+            service: "my_app",
+            host: "my_gelf_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({"custom_field": "custom"}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    // no order across inputs
+    assert_eq!(2, quickwit_server.count().await);
+    let syslog = quickwit_server
+        .get_single_by_message("connect from localhost")
+        .await;
+    let gelf = quickwit_server.get_single_by_message("hello gelf").await;
+
+    assert_eq!(LogSystem::Syslog, syslog.log_system);
+    assert_eq!("postfix", syslog.service_name);
+    assert_eq!("my_host", syslog.hostname);
+    assert_eq!("WARN", syslog.severity_text);
+
+    assert_eq!(LogSystem::Gelf, gelf.log_system);
+    assert_eq!("hello gelf", gelf.message);
+    assert_eq!("my_app", gelf.service_name);
+    assert_eq!("my_gelf_host", gelf.hostname);
+    assert_eq!("ERROR", gelf.severity_text);
+    assert_eq!("custom", gelf.free_fields.get("custom_field").unwrap());
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::{timeout, Instant};
+
+/// With a slow quickwit, batches are sent concurrently and a failed one is retried
+#[tokio::test]
+async fn batches_are_sent_concurrently() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_size: 1,
+        collector_quickwit_batch_max_interval: Duration::from_millis(200),
+        collector_batch_send_parallelism: 4,
+        ..Default::default()
+    }));
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    quickwit.set_ingest_delay(Duration::from_secs(1)).await;
+    quickwit.fail_next(1).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    let start = Instant::now();
+    for i in 0..8 {
+        client.log(gelf_log_line(format!("message {i}"))).await?;
+    }
+
+    timeout(Duration::from_secs(10), async {
+        while quickwit.get_received().await.len() < 8 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the batches");
+    // 8 sequential requests would take 9s (failed one included)
+    assert!(start.elapsed() < Duration::from_secs(6));
+    assert_eq!(9, quickwit.get_request_count().await);
+
+    let mut messages = quickwit
+        .get_received()
+        .await
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect::<Vec<_>>();
+    messages.sort();
+    assert_eq!(
+        (0..8).map(|i| format!("message {i}")).collect::<Vec<_>>(),
+        messages
+    );
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use integration::test_utils::BindAddresses;
+use rlog_common::bind_addr::BindAddr;
+
+#[tokio::test]
+async fn bind_errors_name_the_component_and_address() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _collector = bind_addresses.start_collector("rlog")?;
+    let error = bind_addresses
+        .start_collector("rlog")
+        .err()
+        .expect("the HTTP status address is already in use");
+    assert_eq!(
+        format!(
+            "Unable to bind HTTP status server to {}",
+            bind_addresses.collector_http_bind
+        ),
+        error.to_string()
+    );
+
+    let _gelf_listener = tokio::net::TcpListener::bind(&bind_addresses.shipper_gelf_bind).await?;
+    let error = bind_addresses
+        .start_shipper()
+        .await
+        .err()
+        .expect("the GELF address is already in use");
+    assert_eq!(
+        format!(
+            "Unable to bind GELF TCP server to {}",
+            bind_addresses.shipper_gelf_bind
+        ),
+        error.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn malformed_bind_addresses() {
+    for invalid in ["127.0.0.1", "127.0.0.1:99999", "0.0.0.0:port", "[::1:1234"] {
+        let error = invalid.parse::<BindAddr>().unwrap_err();
+        assert!(error.to_string().contains(invalid), "{error}");
+    }
+}
@@ -0,0 +1,135 @@
+//! Smoke tests of the GELF UDP & syslog TCP client helpers.
+//!
+//! The shipper only has a syslog UDP & a GELF TCP input: an embedded `ShipperServer` cannot
+//! receive what these helpers send, plain sockets bound to the reserved addresses decode it
+//! instead. The tests are to be moved to a running shipper (messages checked in the mock
+//! quickwit index) with the GELF UDP & syslog TCP inputs.
+
+use std::{
+    io::Read,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::read::GzDecoder;
+use integration::test_utils::{
+    BindAddresses, GelfCompression, GelfLog, GELF_CHUNK_HEADER_SIZE, GELF_CHUNK_MAGIC,
+};
+use serde_json::json;
+use syslog::{Facility, Severity};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, UdpSocket},
+    time::timeout,
+};
+
+#[tokio::test]
+async fn gelf_udp_logger() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let socket = UdpSocket::bind(&bind_addresses.shipper_gelf_udp_bind).await?;
+    let mut logger = bind_addresses
+        .gelf_udp_logger()
+        .await?
+        .with_max_datagram_size(100)
+        .with_compression(GelfCompression::Gzip);
+
+    let long_message = "x".repeat(2000);
+    logger
+        .send_log(&GelfLog {
+            short_message: "hello udp",
+            long_message: Some(&long_message),
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({ "random": rand::random::<u64>().to_string() }),
+        })
+        .await?;
+
+    // reassemble chunks
+    let mut buf = [0u8; 65507];
+    let mut chunks = vec![];
+    loop {
+        let n = timeout(Duration::from_secs(1), socket.recv(&mut buf)).await??;
+        assert!(n <= 100);
+        let datagram = &buf[..n];
+        assert_eq!(GELF_CHUNK_MAGIC, datagram[0..2]);
+        let count = datagram[11] as usize;
+        chunks.push((datagram[10], datagram[GELF_CHUNK_HEADER_SIZE..].to_vec()));
+        if chunks.len() == count {
+            break;
+        }
+    }
+    assert!(chunks.len() > 1, "message must be chunked");
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+    let payload = chunks
+        .into_iter()
+        .flat_map(|(_, chunk)| chunk)
+        .collect::<Vec<_>>();
+
+    let mut json = String::new();
+    GzDecoder::new(payload.as_slice()).read_to_string(&mut json)?;
+    let json: serde_json::Value = serde_json::from_str(&json)?;
+    assert_eq!("hello udp", json["short_message"]);
+    assert_eq!(long_message, json["long_message"]);
+
+    // small messages are sent as is
+    let mut logger = bind_addresses.gelf_udp_logger().await?;
+    logger
+        .send_log(&GelfLog {
+            short_message: "small",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: 1_700_000_000.0,
+            extra_fields: json!({}),
+        })
+        .await?;
+    let n = timeout(Duration::from_secs(1), socket.recv(&mut buf)).await??;
+    let json: serde_json::Value = serde_json::from_slice(&buf[..n])?;
+    assert_eq!("small", json["short_message"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn syslog_tcp_logger() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let listener = TcpListener::bind(&bind_addresses.shipper_syslog_tcp_bind).await?;
+
+    let mut logger = bind_addresses
+        .syslog_tcp_logger("my_app", "my_host", 1234, Facility::LOG_LOCAL0)
+        .await?;
+    let (mut stream, _) = listener.accept().await?;
+    logger.send("hello world", Severity::LOG_INFO).await?;
+    logger.send("hello\nmultiline", Severity::LOG_ERR).await?;
+    drop(logger);
+
+    let mut received = vec![];
+    timeout(Duration::from_secs(1), stream.read_to_end(&mut received)).await??;
+
+    // octet counting framing: `LEN SP MSG`
+    let mut messages = vec![];
+    let mut remaining = received.as_slice();
+    while !remaining.is_empty() {
+        let space = remaining.iter().position(|b| *b == b' ').unwrap();
+        let len: usize = std::str::from_utf8(&remaining[..space])?.parse()?;
+        let message = &remaining[space + 1..space + 1 + len];
+        messages.push(String::from_utf8(message.to_vec())?);
+        remaining = &remaining[space + 1 + len..];
+    }
+
+    assert_eq!(2, messages.len());
+    // local0.info
+    assert!(messages[0].starts_with("<134>1 "), "{}", messages[0]);
+    assert!(messages[0].contains(" my_host my_app 1234 "));
+    assert!(messages[0].ends_with("hello world"));
+    // local0.err
+    assert!(messages[1].starts_with("<131>1 "), "{}", messages[1]);
+    assert!(messages[1].ends_with("hello\nmultiline"));
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+/// Buffered entries are indexed on `/flush`, long before the batch max interval.
+#[tokio::test]
+async fn flush_delivers_buffered_entries() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_max_interval: Duration::from_secs(600),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit_with_version("rlog", Some("0.6.5"));
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    for i in 0..5 {
+        client.log(gelf_log_line(format!("log {i}"))).await?;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(quickwit.get_received().await.is_empty());
+
+    // the response is sent once quickwit accepted the flushed batch
+    quickwit.set_ingest_delay(Duration::from_millis(300)).await;
+    let flush_url = format!("http://{}/flush", bind_addresses.collector_http_bind);
+    let response = reqwest::Client::new().post(&flush_url).send().await?;
+    assert_eq!(200, response.status().as_u16());
+    assert!(response.text().await?.starts_with("Flushed in "));
+    let indexed = quickwit
+        .get_received()
+        .await
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect::<Vec<_>>();
+    assert_eq!(vec!["log 0", "log 1", "log 2", "log 3", "log 4"], indexed);
+
+    // nothing buffered
+    let response = reqwest::Client::new().post(&flush_url).send().await?;
+    assert_eq!(200, response.status().as_u16());
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+/// Logs are pumped while the collector shuts down in front of a slow quickwit:
+/// every log acknowledged by the collector must be indexed.
+#[tokio::test]
+async fn acknowledged_logs_are_indexed_on_shutdown() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_size: 10,
+        collector_quickwit_batch_max_interval: Duration::from_millis(200),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit_with_version("rlog", Some("0.6.5"));
+    quickwit.set_ingest_delay(Duration::from_millis(300)).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    let pump = tokio::spawn(async move {
+        let mut acknowledged = vec![];
+        for i in 0.. {
+            let message = format!("log {i}");
+            match client.log(gelf_log_line(message.clone())).await {
+                Ok(_) => acknowledged.push(message),
+                // shutdown in progress
+                Err(_) => break,
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        acknowledged
+    });
+
+    // quickwit is now lagging behind with several pending batches
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    timeout(Duration::from_secs(30), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    let acknowledged = timeout(Duration::from_secs(5), pump).await??;
+    let indexed = quickwit
+        .get_received()
+        .await
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect::<Vec<_>>();
+
+    assert!(acknowledged.len() > 50, "{} logs sent", acknowledged.len());
+    assert_eq!(acknowledged, indexed);
+
+    Ok(())
+}
+
+/// The HTTP status server is stopped with the collector, its address can be bound again
+#[tokio::test]
+async fn http_status_server_is_stopped_on_shutdown() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let health_url = format!("http://{}/health", bind_addresses.collector_http_bind);
+    // keep-alive connection, closed by the graceful shutdown
+    let client = reqwest::Client::new();
+    assert_eq!("OK", client.get(&health_url).send().await?.text().await?);
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    assert!(client.get(&health_url).send().await.is_err());
+    std::net::TcpListener::bind(&bind_addresses.collector_http_bind)?;
+    Ok(())
+}
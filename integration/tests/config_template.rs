@@ -0,0 +1,55 @@
+use rlog_common::config::parse_config_strict;
+
+/// Every mapping key of the template is preceded by its comment
+fn assert_keys_commented(template: &str) {
+    let lines = template.lines().collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+        let content = line.trim_start();
+        if content.starts_with(['#', '-']) || !content.contains(':') {
+            continue;
+        }
+        assert!(
+            i > 0 && lines[i - 1].trim_start().starts_with('#'),
+            "`{line}` is not commented in:\n{template}"
+        );
+    }
+}
+
+#[test]
+fn templates_are_valid_configs() -> anyhow::Result<()> {
+    let template = rlog_shipper::config::Config::template_yaml()?;
+    assert!(template.starts_with("# Syslog UDP input"), "{template}");
+    assert!(
+        template.contains("  # Timeout of each request sent to the collector"),
+        "{template}"
+    );
+    assert_keys_commented(&template);
+    let parsed: rlog_shipper::config::Config = parse_config_strict(&template)?;
+    assert!(parsed == rlog_shipper::config::Config::template());
+
+    let template = rlog_collector::config::Config::template_yaml()?;
+    assert_keys_commented(&template);
+    let parsed: rlog_collector::config::Config = parse_config_strict(&template)?;
+    assert!(parsed.gelf_in.is_some() && parsed.syslog_in.is_some());
+    Ok(())
+}
+
+#[test]
+fn effective_config_is_valid() -> anyhow::Result<()> {
+    use rlog_shipper::config::{Config, GelfInputConfig, GrpcOutConfig, SyslogInputConfig};
+
+    let config: Config = parse_config_strict("gelf_in:\n  max_buffer_size: 10\n")?;
+    let effective = config.effective_yaml()?;
+    let parsed: Config = parse_config_strict(&effective)?;
+    // the set options are kept
+    assert_eq!(10, parsed.gelf_in.as_ref().unwrap().common.max_buffer_size);
+    assert!(parsed.gelf_in != Some(GelfInputConfig::default()));
+    // the sections used with their defaults are filled
+    assert!(parsed.syslog_in == Some(SyslogInputConfig::default()));
+    assert!(parsed.grpc_out == Some(GrpcOutConfig::default()));
+    assert!(parsed.max_buffered_bytes.is_some() && parsed.debug_sample_rate.is_some());
+    // disabled if not set
+    assert!(parsed.heartbeat.is_none() && parsed.recent_inputs.is_none());
+    assert_eq!(effective, parsed.effective_yaml()?);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_common::delivery_window::{log_line_hash, WindowDigest, INCARNATION_METADATA};
+use rlog_grpc::{
+    rlog_service_protocol::{DeliveryWindow, LogLine, Metrics},
+    tonic::Request,
+};
+use rlog_shipper::config::{Config, GrpcOutConfig, SyslogInputConfig, CONFIG};
+use tokio::{net::UdpSocket, time::timeout};
+
+const CHAOS_INCARNATION: &str = "5c4b8a3e-6f0e-4c1e-9b3a-2f8d7e6a1b0c";
+
+/// Delivery windows reported to the collector by verification result
+async fn delivery_windows(status_url: &str, result: &str) -> anyhow::Result<f64> {
+    let metrics: serde_json::Value = reqwest::get(format!("{status_url}/metrics?format=json"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(metrics
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|family| family["name"] == "rlog_collector_delivery_window_count")
+        .flat_map(|family| family["metrics"].as_array().unwrap())
+        .filter(|metric| metric["labels"]["result"] == result)
+        .map(|metric| metric["value"].as_f64().unwrap())
+        .sum())
+}
+
+#[tokio::test]
+async fn delivery_verification() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        syslog_in: Some(SyslogInputConfig::default()),
+        grpc_out: Some(GrpcOutConfig {
+            metrics_report_interval: Duration::from_secs(1),
+            delivery_verification: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    for i in 0..20 {
+        let frame = format!("<12>1 2024-01-02T03:04:05Z my_host my_app 1234 - - message {i}");
+        socket
+            .send_to(frame.as_bytes(), &bind_addresses.shipper_syslog_bind)
+            .await?;
+    }
+    timeout(Duration::from_secs(10), async {
+        while quickwit_server.get_received().await.len() < 20 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the logs to be indexed");
+    // the windows of the log lines are reported
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert!(delivery_windows(&status_url, "match").await? >= 2.0);
+    assert_eq!(0.0, delivery_windows(&status_url, "mismatch").await?);
+
+    // a shipper process losing log lines in transit: they are part of its window but never
+    // reach the collector
+    let mut client = bind_addresses.collector_client().await?;
+    let report = |number: u64, digest: &WindowDigest| Metrics {
+        hostname: "chaos_host".into(),
+        incarnation_id: CHAOS_INCARNATION.into(),
+        delivery_window: Some(DeliveryWindow {
+            number,
+            count: digest.count(),
+            hash: digest.hash(),
+        }),
+        ..Default::default()
+    };
+    client
+        .report_metrics(report(0, &WindowDigest::default()))
+        .await?;
+    let mut sent = WindowDigest::default();
+    for i in 0..10 {
+        let mut log_line = LogLine {
+            labels: [("env".to_string(), "test".to_string())].into(),
+            ..gelf_log_line(format!("chaos {i}"))
+        };
+        sent.add(log_line_hash(&mut log_line));
+        if i % 3 == 0 {
+            // dropped
+            continue;
+        }
+        let mut request = Request::new(log_line);
+        request
+            .metadata_mut()
+            .insert(INCARNATION_METADATA, CHAOS_INCARNATION.parse()?);
+        client.log(request).await?;
+    }
+    client.report_metrics(report(1, &sent)).await?;
+    assert_eq!(1.0, delivery_windows(&status_url, "mismatch").await?);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(5), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{CommonInputConfig, Config, GelfInputConfig, SyslogInputConfig, CONFIG};
+use tokio::{
+    net::{TcpListener, UdpSocket},
+    time::timeout,
+};
+
+#[tokio::test]
+async fn disabled_inputs_do_not_bind() -> anyhow::Result<()> {
+    let disabled = || CommonInputConfig {
+        enabled: false,
+        ..Default::default()
+    };
+    CONFIG.store(Arc::new(Config {
+        gelf_in: Some(GelfInputConfig {
+            common: disabled(),
+            ..Default::default()
+        }),
+        syslog_in: Some(SyslogInputConfig {
+            common: disabled(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let shipper = bind_addresses.start_shipper().await?;
+
+    // the ports of the disabled inputs are free
+    let _gelf_listener = TcpListener::bind(&bind_addresses.shipper_gelf_bind).await?;
+    let _syslog_socket = UdpSocket::bind(&bind_addresses.shipper_syslog_bind).await?;
+
+    timeout(Duration::from_secs(2), shipper.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -62,6 +62,13 @@ async fn nominal_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
                 map.insert("env".into(), "prod".into());
                 map
             },
+            log_rotation_strategy: Default::default(),
+            log_system: None,
+            severity_mapping: HashMap::new(),
+            enabled: true,
+            labels: HashMap::new(),
+            normalize_message: None,
+            startup_retry: None,
         },
     );
 
@@ -106,8 +113,6 @@ async fn nominal_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
         &bind_addresses,
     );
 
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
     // also send some gelf stuff
     let mut gelf_logger = bind_addresses.gelf_logger().await?;
     gelf_logger
@@ -161,50 +166,70 @@ async fn nominal_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
         })
         .await?;
 
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
     // write to the file ; 1 log only
     tmp_file.write_all(format!("[2023-02-13T08:46:53,195][INFO ][o.e.p.PluginsService     ] [sug6-dev-1] loaded module [analysis-common]").as_bytes())?;
 
     tokio::time::sleep(Duration::from_secs(2)).await;
 
     // batch shall be sent now...
+    assert_eq!(
+        quickwit_server.count().await,
+        6,
+        "We should have received 6 logs by now!"
+    );
+    // log lines of an input are indexed in the order they were received, not across inputs
     let received = quickwit_server.get_received().await;
+    let position = |message: &str| received.iter().position(|entry| entry.message == message);
+    assert!(position("hello gelf short message") < position("hello gelf short message 2"));
+
+    let hello_world = quickwit_server.get_single_by_message("hello world").await;
+    let hello_world2 = quickwit_server.get_single_by_message("hello world2").await;
+    let gelf = quickwit_server
+        .get_single_by_message("hello gelf short message")
+        .await;
+    let gelf2 = quickwit_server
+        .get_single_by_message("hello gelf short message 2")
+        .await;
+    let foobar = quickwit_server.get_single_by_message("foobar :)").await;
+    let file = quickwit_server
+        .get_single_by_message("loaded module [analysis-common]")
+        .await;
+
+    assert_eq!("my_app", hello_world.service_name);
+    assert_eq!("my_app2", hello_world2.service_name);
+    assert_eq!(
+        2,
+        quickwit_server
+            .get_by_service("my_java_old_stuff")
+            .await
+            .len()
+    );
+    assert_eq!("my_java_old_stuff", gelf.service_name);
+    assert_eq!("my_java_old_stuff", gelf2.service_name);
+    assert_eq!("my_java_new_stuff", foobar.service_name);
+
+    assert_eq!("my_host", hello_world.hostname);
+    assert_eq!("my_host", hello_world2.hostname);
+    assert_eq!("my_gelf_host", gelf.hostname);
+    assert_eq!("my_gelf_host", gelf2.hostname);
+
+    assert_eq!("INFO", hello_world.severity_text);
+    assert_eq!("ERROR", hello_world2.severity_text);
+    assert_eq!("INFO", gelf.severity_text);
+    assert_eq!("ERROR", gelf2.severity_text);
+
+    assert_eq!(LogSystem::Syslog, hello_world.log_system);
+    assert_eq!(LogSystem::Syslog, hello_world2.log_system);
+    assert_eq!(LogSystem::Gelf, gelf.log_system);
+    assert_eq!(LogSystem::Gelf, gelf2.log_system);
+    assert_eq!(LogSystem::Generic("file_in".into()), file.log_system);
 
-    assert_eq!(received.len(), 6, "We should have received 6 logs by now!");
-    assert_eq!("hello world", received[0].message);
-    assert_eq!("hello world2", received[1].message);
-    assert_eq!("hello gelf short message", received[2].message);
-    assert_eq!("hello gelf short message 2", received[3].message);
-    assert_eq!("foobar :)", received[4].message);
-
-    assert_eq!("my_app", received[0].service_name);
-    assert_eq!("my_app2", received[1].service_name);
-    assert_eq!("my_java_old_stuff", received[2].service_name);
-    assert_eq!("my_java_old_stuff", received[3].service_name);
-    assert_eq!("my_java_new_stuff", received[4].service_name);
-
-    assert_eq!("my_host", received[0].hostname);
-    assert_eq!("my_host", received[1].hostname);
-    assert_eq!("my_gelf_host", received[2].hostname);
-    assert_eq!("my_gelf_host", received[3].hostname);
-
-    assert_eq!("INFO", received[0].severity_text);
-    assert_eq!("ERROR", received[1].severity_text);
-    assert_eq!("INFO", received[2].severity_text);
-    assert_eq!("ERROR", received[3].severity_text);
-
-    assert_eq!(LogSystem::Syslog, received[0].log_system);
-    assert_eq!(LogSystem::Syslog, received[1].log_system);
-    assert_eq!(LogSystem::Gelf, received[2].log_system);
-    assert_eq!(LogSystem::Gelf, received[3].log_system);
-
-    assert_eq!("local0", received[0].free_fields.get("facility").unwrap());
-    assert_eq!("mail", received[1].free_fields.get("facility").unwrap());
+    assert_eq!("local0", hello_world.free_fields.get("facility").unwrap());
+    assert_eq!("mail", hello_world2.free_fields.get("facility").unwrap());
 
     assert_eq!(
         1234,
-        received[0]
+        hello_world
             .free_fields
             .get("proc_pid")
             .unwrap()
@@ -213,25 +238,25 @@ async fn nominal_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
     );
     assert_eq!(
         12345,
-        received[1]
+        hello_world2
             .free_fields
             .get("proc_pid")
             .unwrap()
             .as_i64()
             .unwrap()
     );
-    assert_eq!(0, received[2].free_fields.len());
+    assert_eq!(0, gelf.free_fields.len());
     assert_eq!(
         "This is my long message and should replace short message",
-        received[3].free_fields.get("long_message").unwrap()
+        gelf2.free_fields.get("long_message").unwrap()
     );
     assert_eq!(
         "this is really custom!",
-        received[3].free_fields.get("custom_field").unwrap()
+        gelf2.free_fields.get("custom_field").unwrap()
     );
     assert_eq!(
         123456,
-        received[3]
+        gelf2
             .free_fields
             .get("custom_int")
             .unwrap()
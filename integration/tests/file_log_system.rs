@@ -0,0 +1,70 @@
+use std::{collections::HashMap, io::Write, sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_collector::LogSystem;
+use rlog_shipper::config::{
+    eqregex::EqRegex, Config, FieldMapping, FieldType, FileMappingConfig, FileParseConfig, CONFIG,
+};
+use tempfile::NamedTempFile;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn file_log_system_override() -> anyhow::Result<()> {
+    let mut tmp_file = NamedTempFile::new()?;
+    let path = tmp_file.path().to_string_lossy().to_string();
+    CONFIG.store(Arc::new(Config {
+        files_in: HashMap::from([(
+            path,
+            FileParseConfig {
+                mapping: FileMappingConfig::Regex {
+                    pattern: EqRegex::new(r"^\[([^\]]+)\] (.*)$").unwrap(),
+                    mapping: vec![
+                        FieldMapping {
+                            name: "service_name".into(),
+                            field_type: FieldType::String,
+                        },
+                        FieldMapping {
+                            name: "message".into(),
+                            field_type: FieldType::String,
+                        },
+                    ],
+                },
+                static_fields: HashMap::new(),
+                log_rotation_strategy: Default::default(),
+                log_system: Some("elasticsearch".into()),
+                severity_mapping: HashMap::new(),
+                enabled: true,
+                labels: HashMap::new(),
+                normalize_message: None,
+                startup_retry: None,
+            },
+        )]),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    writeln!(
+        tmp_file,
+        "[o.e.p.PluginsService] loaded module [analysis-common]"
+    )?;
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let entry = quickwit_server
+        .get_single_by_message("loaded module [analysis-common]")
+        .await;
+    assert_eq!(LogSystem::Generic("elasticsearch".into()), entry.log_system);
+    // mapped from the `service_name` group instead of the file name
+    assert_eq!("o.e.p.PluginsService", entry.service_name);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, io::Write, sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{
+    eqregex::EqRegex, Config, FieldMapping, FieldType, FileMappingConfig, FileParseConfig, CONFIG,
+};
+use tempfile::NamedTempFile;
+use tokio::time::timeout;
+
+fn message_only() -> FileParseConfig {
+    FileParseConfig {
+        mapping: FileMappingConfig::Regex {
+            pattern: EqRegex::new(r"^(.*)$").unwrap(),
+            mapping: vec![FieldMapping {
+                name: "message".into(),
+                field_type: FieldType::String,
+            }],
+        },
+        static_fields: HashMap::new(),
+        log_rotation_strategy: Default::default(),
+        log_system: None,
+        severity_mapping: HashMap::new(),
+        enabled: true,
+        labels: HashMap::new(),
+        normalize_message: None,
+        startup_retry: None,
+    }
+}
+
+#[tokio::test]
+async fn file_sequence_numbers() -> anyhow::Result<()> {
+    let mut files = [NamedTempFile::new()?, NamedTempFile::new()?];
+    let paths = files
+        .iter()
+        .map(|file| file.path().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    CONFIG.store(Arc::new(Config {
+        sequence_numbers: true,
+        files_in: paths
+            .iter()
+            .map(|path| (path.clone(), message_only()))
+            .collect(),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    for i in 0..3 {
+        for (f, file) in files.iter_mut().enumerate() {
+            writeln!(file, "file {f} line {i}")?;
+        }
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // each file is numbered on its own
+    for (f, path) in paths.iter().enumerate() {
+        for i in 0..3 {
+            let entry = quickwit_server
+                .get_single_by_message(&format!("file {f} line {i}"))
+                .await;
+            assert_eq!(format!("files_in:{path}"), entry.free_fields["_rlog_input"]);
+            assert_eq!(i, entry.free_fields["_rlog_seq"].as_u64().unwrap());
+        }
+    }
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
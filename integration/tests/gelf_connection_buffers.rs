@@ -0,0 +1,104 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_inputs::metrics::GELF_CONNECTION_BUFFERS_BYTES;
+use rlog_shipper::config::{Config, GelfInputConfig, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+const MAX_CONNECTION_BUFFERS_BYTES: u64 = 1024 * 1024;
+
+/// Wait for the gauge of the connection buffers to match `predicate`
+async fn wait_for_gauge(predicate: impl Fn(u64) -> bool) -> u64 {
+    for _ in 0..100 {
+        let bytes = GELF_CONNECTION_BUFFERS_BYTES.load(Ordering::Relaxed);
+        if predicate(bytes) {
+            return bytes;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    GELF_CONNECTION_BUFFERS_BYTES.load(Ordering::Relaxed)
+}
+
+#[tokio::test]
+async fn gelf_connection_buffers() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        gelf_in: Some(GelfInputConfig {
+            max_connection_buffers_bytes: MAX_CONNECTION_BUFFERS_BYTES,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+
+    // streams JSON without ever sending the NUL terminator
+    let mut never_terminated = TcpStream::connect(&bind_addresses.shipper_gelf_bind).await?;
+    let mut frame = br#"{"short_message": ""#.to_vec();
+    frame.resize(2 * MAX_CONNECTION_BUFFERS_BYTES as usize, b'a');
+    // the connection may be closed before the whole frame is sent
+    let _ = never_terminated.write_all(&frame).await;
+
+    let peak = wait_for_gauge(|bytes| bytes > MAX_CONNECTION_BUFFERS_BYTES).await;
+    assert!(peak > MAX_CONNECTION_BUFFERS_BYTES, "{peak}");
+
+    // closed by the shipper: end of stream or connection reset
+    let mut buf = [0; 16];
+    let read = timeout(Duration::from_secs(5), never_terminated.read(&mut buf))
+        .await
+        .expect("The connection should be closed");
+    assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+
+    let bytes = wait_for_gauge(|bytes| bytes <= MAX_CONNECTION_BUFFERS_BYTES).await;
+    assert!(bytes <= MAX_CONNECTION_BUFFERS_BYTES, "{bytes}");
+
+    // the other connections are kept
+    gelf_logger
+        .send_log(&GelfLog {
+            short_message: "still connected",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_app",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+    for _ in 0..50 {
+        if quickwit_server.count().await > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert_eq!(
+        1,
+        quickwit_server
+            .get_by_message("still connected")
+            .await
+            .len()
+    );
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog, GelfLogger};
+use rlog_inputs::metrics::GELF_PEERS;
+use rlog_shipper::config::{Config, GelfInputConfig, CONFIG};
+use serde_json::{json, Value};
+use syslog::Severity;
+use tokio::time::timeout;
+
+fn gelf_log(short_message: &str) -> GelfLog<'_> {
+    GelfLog {
+        short_message,
+        long_message: None,
+        level: Severity::LOG_INFO as usize,
+        service: "my_app",
+        host: "my_host",
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64(),
+        // overridden by the peer IP
+        extra_fields: json!({"_source_ip": "10.0.0.1"}),
+    }
+}
+
+#[tokio::test]
+async fn gelf_peers() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        gelf_in: Some(GelfInputConfig {
+            add_source_ip: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let first_ip: IpAddr = "127.0.0.1".parse()?;
+    let second_ip: IpAddr = "127.0.0.2".parse()?;
+    // concurrent connections of two peers
+    let mut first = GelfLogger::new_from(&bind_addresses.shipper_gelf_bind, first_ip).await?;
+    let mut second = GelfLogger::new_from(&bind_addresses.shipper_gelf_bind, second_ip).await?;
+    for i in 0..3 {
+        first.send_log(&gelf_log(&format!("first {i}"))).await?;
+        if i < 2 {
+            second.send_log(&gelf_log(&format!("second {i}"))).await?;
+        }
+    }
+    second.send_frame(b"{not json").await?;
+
+    let mut received = Vec::new();
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        received = quickwit_server.get_received().await;
+        if received.len() >= 5 {
+            break;
+        }
+    }
+    assert_eq!(5, received.len());
+    for entry in &received {
+        let expected = if entry.message.starts_with("first") {
+            "127.0.0.1"
+        } else {
+            "127.0.0.2"
+        };
+        assert_eq!(
+            expected, entry.free_fields["source_ip"],
+            "{}",
+            entry.message
+        );
+    }
+
+    let peers = GELF_PEERS.top(10);
+    assert_eq!(2, peers.len());
+    assert_eq!(
+        (first_ip, 3, 0, 0),
+        (
+            peers[0].peer,
+            peers[0].frames,
+            peers[0].parse_errors,
+            peers[0].dropped
+        )
+    );
+    assert_eq!(
+        (second_ip, 3, 1, 0),
+        (
+            peers[1].peer,
+            peers[1].frames,
+            peers[1].parse_errors,
+            peers[1].dropped
+        )
+    );
+
+    let status = reqwest::get(format!(
+        "http://{}/gelf_peers",
+        bind_addresses.shipper_http_bind
+    ))
+    .await?
+    .error_for_status()?
+    .json::<Value>()
+    .await?;
+    assert_eq!(
+        json!([
+            {"peer": "127.0.0.1", "frames": 3, "parse_errors": 0, "dropped": 0},
+            {"peer": "127.0.0.2", "frames": 3, "parse_errors": 1, "dropped": 0},
+        ]),
+        status
+    );
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
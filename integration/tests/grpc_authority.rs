@@ -0,0 +1,134 @@
+use std::{
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_grpc::{
+    rlog_service_protocol::{
+        log_collector_server::{LogCollector, LogCollectorServer},
+        LogLine, Metrics, PingRequest, PingResponse,
+    },
+    tonic::{
+        self, async_trait,
+        codegen::{http, Service},
+        server::NamedService,
+        transport::Server,
+        Status,
+    },
+};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+/// Accepts everything
+struct MockCollector;
+
+#[async_trait]
+impl LogCollector for MockCollector {
+    async fn log(&self, _request: tonic::Request<LogLine>) -> Result<tonic::Response<()>, Status> {
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn report_metrics(
+        &self,
+        _request: tonic::Request<Metrics>,
+    ) -> Result<tonic::Response<()>, Status> {
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn ping(
+        &self,
+        request: tonic::Request<PingRequest>,
+    ) -> Result<tonic::Response<PingResponse>, Status> {
+        Ok(tonic::Response::new(PingResponse {
+            message: request.into_inner().message,
+            server_timestamp: None,
+        }))
+    }
+}
+
+/// Records the `:authority` of every request
+#[derive(Clone)]
+struct AuthorityRecorder<S> {
+    inner: S,
+    authorities: Arc<Mutex<Vec<String>>>,
+}
+
+impl<S: NamedService> NamedService for AuthorityRecorder<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S: Service<http::Request<B>>, B> Service<http::Request<B>> for AuthorityRecorder<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if let Some(authority) = request.uri().authority() {
+            self.authorities.lock().unwrap().push(authority.to_string());
+        }
+        self.inner.call(request)
+    }
+}
+
+#[tokio::test]
+async fn grpc_authority() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+
+    let authorities = Arc::new(Mutex::new(vec![]));
+    let collector = AuthorityRecorder {
+        inner: LogCollectorServer::new(MockCollector),
+        authorities: authorities.clone(),
+    };
+    let grpc_bind_address = bind_addresses.grpc_bind_address.parse()?;
+    tokio::spawn(
+        Server::builder()
+            .add_service(collector)
+            .serve(grpc_bind_address),
+    );
+
+    let shipper = bind_addresses
+        .start_shipper_with_authority("logs.example.com:8443")
+        .await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "virtual hosting",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let authorities = authorities.lock().unwrap().clone();
+    assert!(!authorities.is_empty());
+    assert!(
+        authorities
+            .iter()
+            .all(|authority| authority == "logs.example.com:8443"),
+        "{authorities:?}"
+    );
+
+    timeout(Duration::from_secs(2), shipper.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
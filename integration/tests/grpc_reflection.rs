@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use integration::test_utils::BindAddresses;
+use rlog_grpc::tonic::{transport::Channel, Code};
+use tokio::time::timeout;
+use tonic_reflection::pb::{
+    server_reflection_client::ServerReflectionClient, server_reflection_request::MessageRequest,
+    server_reflection_response::MessageResponse, ServerReflectionRequest,
+};
+
+async fn list_services(bind_addresses: &BindAddresses) -> anyhow::Result<Vec<String>> {
+    let channel = Channel::from_shared(format!("http://{}", bind_addresses.grpc_bind_address))?
+        .connect()
+        .await?;
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = ServerReflectionClient::new(channel)
+        .server_reflection_info(futures::stream::iter(vec![request]))
+        .await?
+        .into_inner();
+    let response = responses.message().await?.unwrap();
+    match response.message_response {
+        Some(MessageResponse::ListServicesResponse(list)) => {
+            Ok(list.service.into_iter().map(|s| s.name).collect())
+        }
+        other => anyhow::bail!("Unexpected reflection response {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn reflection_enabled() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector_with_reflection("rlog")?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let services = list_services(&bind_addresses).await?;
+    assert!(
+        services.contains(&"rlog_service_protocol.LogCollector".to_string()),
+        "{services:?}"
+    );
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
+
+#[tokio::test]
+async fn reflection_disabled_by_default() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let status = list_services(&bind_addresses)
+        .await
+        .unwrap_err()
+        .downcast::<rlog_grpc::tonic::Status>()?;
+    assert_eq!(Code::Unimplemented, status.code());
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
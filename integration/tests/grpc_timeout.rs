@@ -0,0 +1,86 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_shipper::config::{Config, GrpcOutConfig, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::{io::AsyncReadExt, net::TcpListener, sync::Mutex, time::timeout};
+
+/// Client connection preface of HTTP/2
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// The collector accepts connections but stalls after the HTTP/2 preface: the requests time
+/// out and the log line is retried until the collector responds
+#[tokio::test]
+async fn unresponsive_collector() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        grpc_out: Some(GrpcOutConfig {
+            connect_timeout: Duration::from_millis(500),
+            timeout: Duration::from_millis(500),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let listener = TcpListener::bind(&bind_addresses.grpc_bind_address).await?;
+    let connections = Arc::new(Mutex::new(vec![]));
+    let stalled = connections.clone();
+    let unresponsive_collector = tokio::spawn(async move {
+        // read the preface then keep the connections open without reading nor writing
+        // anything, not even the server settings
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut preface = [0; H2_PREFACE.len()];
+            if stream.read_exact(&mut preface).await.is_ok() && preface == H2_PREFACE {
+                stalled.lock().await.push(stream);
+            }
+        }
+    });
+
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "acknowledged once the collector responds",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+
+    // initial metrics report (2 timeouts), then the log line times out at least once
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    assert!(!connections.lock().await.is_empty());
+    assert_eq!(0, quickwit_server.count().await);
+
+    // the stalled connections are closed, the shipper reconnects to a responsive collector
+    unresponsive_collector.abort();
+    let _ = unresponsive_collector.await;
+    connections.lock().await.clear();
+    let collector = bind_addresses.start_collector("rlog")?;
+
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    quickwit_server
+        .get_single_by_message("acknowledged once the collector responds")
+        .await;
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(3), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_collector::LogSystem;
+use rlog_shipper::config::{Config, HeartbeatConfig, CONFIG};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn heartbeat() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        heartbeat: Some(HeartbeatConfig {
+            interval: Duration::from_millis(500),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    // heartbeats at 0s, 0.5s, ... 2.5s
+    tokio::time::sleep(Duration::from_millis(2700)).await;
+    timeout(Duration::from_secs(5), shipper.shutdown())
+        .await
+        .expect("Timed out while waiting for shipper shutdown");
+    // wait for the last batch
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert!(
+        (5..=7).contains(&received.len()),
+        "{} heartbeats received",
+        received.len()
+    );
+    for heartbeat in &received {
+        assert_eq!("_rlog_heartbeat", heartbeat.service_name);
+        assert_eq!("heartbeat", heartbeat.message);
+        assert_eq!(LogSystem::Generic("rlog".into()), heartbeat.log_system);
+        assert_eq!(
+            rlog_shipper::VERSION,
+            heartbeat.free_fields["shipper_version"]
+        );
+        assert!(heartbeat.free_fields["uptime_secs"].is_u64());
+        for queue in ["files_in", "gelf_in", "syslog_in", "grpc_out"] {
+            assert!(
+                heartbeat.free_fields["queue_count"][queue].is_u64(),
+                "{queue} queue count"
+            );
+        }
+    }
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for collector shutdown");
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_shipper::config::{eqregex::EqRegex, Config, HostnameMapping, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn hostname_mappings() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        hostname_mappings: vec![HostnameMapping {
+            pattern: EqRegex::new(r"^(.+)-[0-9a-f]+-[0-9a-z]{5}$")?,
+            replacement: "$1".into(),
+        }],
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for host in ["myapp-deployment-7d6f4b9-xkqj2", "db-1"] {
+        gelf_logger
+            .send_log(&GelfLog {
+                short_message: "hello gelf",
+                long_message: None,
+                level: Severity::LOG_INFO as usize,
+                service: "my_app",
+                host,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64(),
+                extra_fields: json!({}),
+            })
+            .await?;
+    }
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert_eq!(2, received.len());
+
+    assert_eq!("myapp-deployment", received[0].hostname);
+    assert_eq!(
+        "myapp-deployment-7d6f4b9-xkqj2",
+        received[0].free_fields.get("raw_hostname").unwrap()
+    );
+    // not mapped
+    assert_eq!("db-1", received[1].hostname);
+    assert!(!received[1].free_fields.contains_key("raw_hostname"));
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
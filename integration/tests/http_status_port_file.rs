@@ -0,0 +1,35 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{Config, CONFIG};
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn http_status_port_file() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config::default()));
+    let dir = tempdir()?;
+    let port_file = dir.path().join("shipper.port");
+
+    let bind_addresses = BindAddresses::default();
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses
+        .start_shipper_with_port_file(port_file.to_str().unwrap())
+        .await?;
+
+    // bound to a free port
+    let port: u16 = std::fs::read_to_string(&port_file)?.parse()?;
+    assert_ne!(0, port);
+    let health = reqwest::get(format!("http://127.0.0.1:{port}/health"))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    assert_eq!("OK", health);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
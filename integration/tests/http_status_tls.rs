@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use integration::test_utils::BindAddresses;
+use rcgen::{CertificateParams, KeyPair};
+use rlog_collector::HttpStatusTlsConfig;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn http_status_tls() -> anyhow::Result<()> {
+    let key_pair = KeyPair::generate()?;
+    let certificate =
+        CertificateParams::new(vec!["localhost".to_string()])?.self_signed(&key_pair)?;
+
+    let bind_addresses = BindAddresses::default();
+    let _quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector_with_http_status_tls(
+        "rlog",
+        HttpStatusTlsConfig {
+            certificate_pem: certificate.pem().into_bytes(),
+            private_key_pem: key_pair.serialize_pem().into_bytes(),
+        },
+    )?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let port = bind_addresses
+        .collector_http_bind
+        .split(':')
+        .next_back()
+        .unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(
+            certificate.pem().as_bytes(),
+        )?)
+        .build()?;
+    let health = client
+        .get(format!("https://localhost:{port}/health"))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    assert_eq!("OK", health);
+
+    // plain HTTP is not served
+    assert!(reqwest::get(format!("http://localhost:{port}/health"))
+        .await
+        .is_err());
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    // the listener is closed with the collector
+    assert!(client
+        .get(format!("https://localhost:{port}/health"))
+        .send()
+        .await
+        .is_err());
+    Ok(())
+}
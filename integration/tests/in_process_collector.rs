@@ -0,0 +1,63 @@
+use integration::test_utils::{gelf_log_line, gelf_log_line_with_extra};
+use rlog_collector::{in_process::InProcessCollector, LogSystem};
+use rlog_grpc::{
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{log_line::Line, SyslogSeverity},
+    tonic::Code,
+};
+
+#[tokio::test]
+async fn logs_are_converted_and_batched() -> anyhow::Result<()> {
+    let collector = InProcessCollector::start();
+
+    // a warning logged at 1_700_000_000.123
+    let mut warning = gelf_log_line_with_extra("hello", r#"{"service":"my_service","foo":"bar"}"#);
+    warning.timestamp = Some(Timestamp {
+        seconds: 1_700_000_000,
+        nanos: 123_000_000,
+    });
+    if let Some(Line::Gelf(gelf)) = &mut warning.line {
+        gelf.severity = SyslogSeverity::Warning as i32;
+    }
+    collector.log(warning).await?;
+    collector.log(gelf_log_line("world")).await?;
+
+    collector.shutdown();
+    let batch = collector.next_batch().await.expect("No batch received");
+    assert!(collector.next_batch().await.is_none());
+
+    assert_eq!(2, batch.len());
+    assert_eq!("hello", batch[0].message);
+    assert_eq!("my_service", batch[0].service_name);
+    assert_eq!("bar", batch[0].free_fields.get("foo").unwrap());
+    assert_eq!("WARN", batch[0].severity_text);
+    assert_eq!(1_700_000_000_123, batch[0].timestamp);
+    assert_eq!(LogSystem::Gelf, batch[0].log_system);
+    assert_eq!("world", batch[1].message);
+    assert_eq!("unknown", batch[1].service_name);
+
+    // the collector is now shut down
+    let status = collector.log(gelf_log_line("late")).await.unwrap_err();
+    assert_eq!(Code::Unavailable, status.code());
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_logs_are_rejected() -> anyhow::Result<()> {
+    let collector = InProcessCollector::start();
+
+    let mut no_timestamp = gelf_log_line("hello");
+    no_timestamp.timestamp = None;
+    let status = collector.log(no_timestamp).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+
+    let status = collector
+        .log(gelf_log_line_with_extra("hello", "not json"))
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+
+    collector.shutdown();
+    assert!(collector.next_batch().await.is_none());
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{self, BindAddresses, GelfLog};
+use rlog_shipper::config::{
+    eqregex::EqRegex, CommonInputConfig, Config, FieldMapping, FieldType, FileMappingConfig,
+    FileParseConfig, GelfInputConfig, SyslogInputConfig, CONFIG,
+};
+use serde_json::json;
+use syslog::Severity;
+use tempfile::NamedTempFile;
+use tokio::time::timeout;
+
+fn labels(labels: &[(&str, &str)]) -> HashMap<String, String> {
+    labels
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn labels_of_all_inputs() -> anyhow::Result<()> {
+    let mut tmp_file = NamedTempFile::new()?;
+    let path = tmp_file.path().to_string_lossy().to_string();
+    let file_config = || FileParseConfig {
+        mapping: FileMappingConfig::Regex {
+            pattern: EqRegex::new(r"^(.*)$").unwrap(),
+            mapping: vec![FieldMapping {
+                name: "message".into(),
+                field_type: FieldType::String,
+            }],
+        },
+        static_fields: HashMap::new(),
+        log_rotation_strategy: Default::default(),
+        log_system: Some("my_file_app".into()),
+        severity_mapping: HashMap::new(),
+        enabled: true,
+        labels: labels(&[("team", "files")]),
+        normalize_message: None,
+        startup_retry: None,
+    };
+    let config = |global_labels| Config {
+        labels: labels(global_labels),
+        syslog_in: Some(SyslogInputConfig {
+            common: CommonInputConfig {
+                labels: labels(&[("team", "syslog")]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        gelf_in: Some(GelfInputConfig::default()),
+        files_in: HashMap::from([(path.clone(), file_config())]),
+        ..Default::default()
+    };
+    CONFIG.store(Arc::new(config(&[("env", "prod"), ("team", "core")])));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    test_utils::send_syslog(
+        "hello syslog",
+        "my_app",
+        "my_host",
+        1234,
+        syslog::Facility::LOG_LOCAL0,
+        Severity::LOG_INFO,
+        &bind_addresses,
+    );
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "hello gelf",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_gelf_app",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            // fields of the log itself take precedence over the labels
+            extra_fields: json!({"env": "dev"}),
+        })
+        .await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    writeln!(tmp_file, "hello file")?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // labels are reloaded with the configuration
+    CONFIG.store(Arc::new(config(&[("env", "staging"), ("team", "core")])));
+    test_utils::send_syslog(
+        "hello again",
+        "my_app",
+        "my_host",
+        1234,
+        syslog::Facility::LOG_LOCAL0,
+        Severity::LOG_INFO,
+        &bind_addresses,
+    );
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    let labels_of = |message: &str| {
+        let entry = received
+            .iter()
+            .find(|entry| entry.message == message)
+            .unwrap_or_else(|| panic!("{message} not received"));
+        (
+            entry.free_fields["env"].clone(),
+            entry.free_fields["team"].clone(),
+        )
+    };
+    assert_eq!((json!("prod"), json!("syslog")), labels_of("hello syslog"));
+    assert_eq!((json!("dev"), json!("core")), labels_of("hello gelf"));
+    assert_eq!((json!("prod"), json!("files")), labels_of("hello file"));
+    assert_eq!(
+        (json!("staging"), json!("syslog")),
+        labels_of("hello again")
+    );
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
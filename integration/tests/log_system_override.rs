@@ -0,0 +1,99 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{self, BindAddresses, GelfLog};
+use rlog_collector::LogSystem;
+use rlog_shipper::config::{Config, GelfInputConfig, SyslogInputConfig, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn log_system_override() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        gelf_in: Some(GelfInputConfig {
+            log_system: Some("app_json".into()),
+            ..Default::default()
+        }),
+        syslog_in: Some(SyslogInputConfig {
+            log_system: Some("nginx_access".into()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    test_utils::send_syslog(
+        "GET /index.html",
+        "nginx",
+        "my_host",
+        1234,
+        syslog::Facility::LOG_LOCAL0,
+        Severity::LOG_WARNING,
+        &bind_addresses,
+    );
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "hello gelf",
+            long_message: None,
+            level: Severity::LOG_ERR as usize,
+            service: "my_app",
+            host: "my_gelf_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({"custom_field": "custom"}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    // no order across inputs
+    assert_eq!(2, quickwit_server.count().await);
+    let syslog = quickwit_server
+        .get_single_by_message("GET /index.html")
+        .await;
+    let gelf = quickwit_server.get_single_by_message("hello gelf").await;
+
+    assert_eq!(LogSystem::Generic("nginx_access".into()), syslog.log_system);
+    assert_eq!("nginx", syslog.service_name);
+    assert_eq!("my_host", syslog.hostname);
+    assert_eq!("WARN", syslog.severity_text);
+    assert_eq!("local0", syslog.free_fields.get("facility").unwrap());
+    assert_eq!(
+        1234,
+        syslog
+            .free_fields
+            .get("proc_pid")
+            .unwrap()
+            .as_i64()
+            .unwrap()
+    );
+
+    assert_eq!(LogSystem::Generic("app_json".into()), gelf.log_system);
+    assert_eq!("hello gelf", gelf.message);
+    assert_eq!("my_app", gelf.service_name);
+    assert_eq!("my_gelf_host", gelf.hostname);
+    assert_eq!("ERROR", gelf.severity_text);
+    assert_eq!("custom", gelf.free_fields.get("custom_field").unwrap());
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -17,13 +17,20 @@ async fn many_shippers() -> anyhow::Result<()> {
 
     let quickwit = bind_addresses.start_quickwit("rlog");
     let collector = bind_addresses.start_collector("rlog")?;
+    // half of the shippers are connected to a second collector
+    let second_collector_addresses = bind_addresses.new_collector_addresses();
+    let second_collector =
+        bind_addresses.start_collector_at(&second_collector_addresses, "rlog")?;
 
     let mut shippers = vec![];
 
     let counter: &'static AtomicU64 = Box::leak(Box::new(AtomicU64::new(0)));
 
-    for _ in 0..100 {
-        let ba = bind_addresses.new_shipper_addresses();
+    for i in 0..100 {
+        let mut ba = bind_addresses.new_shipper_addresses();
+        if i % 2 == 1 {
+            ba.grpc_bind_address = second_collector_addresses.grpc_bind_address.clone();
+        }
         shippers.push(tokio::spawn(async move {
             let shipper = ba.start_shipper().await?;
             tokio::time::sleep(Duration::from_secs(2)).await;
@@ -69,7 +76,11 @@ async fn many_shippers() -> anyhow::Result<()> {
     // let time for batches to be shipped
     tokio::time::sleep(Duration::from_secs(1)).await;
 
-    timeout(Duration::from_secs(120), collector.shutdown()).await?;
+    timeout(
+        Duration::from_secs(120),
+        futures::future::join(collector.shutdown(), second_collector.shutdown()),
+    )
+    .await?;
 
     let received = quickwit.get_received().await;
 
@@ -0,0 +1,73 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_collector::{
+    config::{Config, CONFIG},
+    metrics::COLLECTOR_TOO_OLD_COUNT,
+};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn old_logs_are_dropped() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_max_log_age: Some(Duration::from_secs(30 * 24 * 3600)),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for (short_message, service, timestamp) in [
+        ("replayed", "old_service", now - 31.0 * 24.0 * 3600.0),
+        ("recent", "my_service", now - 29.0 * 24.0 * 3600.0),
+    ] {
+        gelf_logger
+            .send_log(&GelfLog {
+                short_message,
+                long_message: None,
+                level: Severity::LOG_INFO as usize,
+                service,
+                host: "my_host",
+                timestamp,
+                extra_fields: json!({}),
+            })
+            .await?;
+    }
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert_eq!(1, received.len());
+    assert_eq!("recent", received[0].message);
+    assert_eq!(
+        1,
+        COLLECTOR_TOO_OLD_COUNT
+            .with_label_values(&["old_service"])
+            .get()
+    );
+    assert_eq!(
+        0,
+        COLLECTOR_TOO_OLD_COUNT
+            .with_label_values(&["my_service"])
+            .get()
+    );
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{
+    CommonInputConfig, Config, GelfInputConfig, MessageNormalization, SyslogInputConfig, CONFIG,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+#[tokio::test]
+async fn message_normalization() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        syslog_in: Some(SyslogInputConfig {
+            common: CommonInputConfig {
+                normalize_message: Some(MessageNormalization {
+                    normalize_line_endings: true,
+                    trim_whitespace: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        gelf_in: Some(GelfInputConfig {
+            common: CommonInputConfig {
+                normalize_message: Some(MessageNormalization {
+                    collapse_internal_whitespace: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    socket
+        .send_to(
+            b"<12>1 2024-01-02T03:04:05Z my_host my_app 1234 - - first line\r\nsecond line \r\n",
+            &bind_addresses.shipper_syslog_bind,
+        )
+        .await?;
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    gelf_logger
+        .send_frame(
+            br#"{"version":"1.1","host":"my_gelf_host","short_message":" padded   gelf  message ","timestamp":1700000000,"level":6}"#,
+        )
+        .await?;
+
+    let mut received = Vec::new();
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        received = quickwit_server.get_received().await;
+        if received.len() >= 2 {
+            break;
+        }
+    }
+    let mut messages = received
+        .iter()
+        .map(|entry| entry.message.as_str())
+        .collect::<Vec<_>>();
+    messages.sort();
+    assert_eq!(
+        vec![" padded gelf message ", "first line\nsecond line"],
+        messages
+    );
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use integration::test_utils::{generate_pki, BindAddresses};
+use rlog_collector::{
+    config::{Config, MetricsIdentity, CONFIG},
+    metrics::COLLECTOR_HOSTNAME_MISMATCH_COUNT,
+    GrpcTlsConfig,
+};
+use rlog_common::tls::TlsVersion;
+use rlog_grpc::{
+    rlog_service_protocol::{log_collector_client::LogCollectorClient, Metrics},
+    tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity, Uri},
+};
+use tokio::time::timeout;
+
+/// Hostnames listed by the `/shippers.json` status page
+async fn shipper_hostnames(status_url: &str) -> anyhow::Result<Vec<String>> {
+    let shippers: serde_json::Value = reqwest::get(format!("{status_url}/shippers.json"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut hostnames = shippers
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|shipper| shipper["hostname"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    hostnames.sort();
+    Ok(hostnames)
+}
+
+#[tokio::test]
+async fn metrics_identity() -> anyhow::Result<()> {
+    let pki = generate_pki()?;
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector_with_grpc_tls(
+        "rlog",
+        GrpcTlsConfig {
+            ca_certificate_pem: pki.ca.pem().into_bytes(),
+            certificate_pem: pki.server.0.pem().into_bytes(),
+            private_key_pem: pki.server.1.serialize_pem().into_bytes(),
+            min_tls_version: TlsVersion::Tls12,
+        },
+    )?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+    // the client certificate is named "my_shipper"
+    let channel = Channel::builder(Uri::from_str(&format!(
+        "https://{}",
+        bind_addresses.grpc_bind_address
+    ))?)
+    .tls_config(
+        ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(pki.ca.pem()))
+            .identity(Identity::from_pem(
+                pki.client.0.pem(),
+                pki.client.1.serialize_pem(),
+            ))
+            .domain_name("localhost"),
+    )?
+    .connect()
+    .await?;
+    let mut client = LogCollectorClient::new(channel);
+    let report = |hostname: &str| Metrics {
+        hostname: hostname.into(),
+        queue_count: [("grpc_out".to_string(), 1)].into(),
+        ..Default::default()
+    };
+    let set_identity = |collector_metrics_identity| {
+        CONFIG.store(Arc::new(Config {
+            collector_metrics_identity,
+            ..Default::default()
+        }))
+    };
+
+    // default: the self-reported hostname
+    client.report_metrics(report("cloned_host")).await?;
+    assert_eq!(vec!["cloned_host"], shipper_hostnames(&status_url).await?);
+
+    set_identity(MetricsIdentity::Certificate);
+    let mismatches = COLLECTOR_HOSTNAME_MISMATCH_COUNT.get();
+    client.report_metrics(report("cloned_host")).await?;
+    assert_eq!(
+        vec!["cloned_host", "my_shipper"],
+        shipper_hostnames(&status_url).await?
+    );
+    assert_eq!(mismatches, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+
+    set_identity(MetricsIdentity::Both);
+    client.report_metrics(report("cloned_host")).await?;
+    client.report_metrics(report("other_clone")).await?;
+    // still keyed by the certificate name, each mismatch is counted
+    assert_eq!(
+        vec!["cloned_host", "my_shipper"],
+        shipper_hostnames(&status_url).await?
+    );
+    assert_eq!(mismatches + 2, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+    client.report_metrics(report("my_shipper")).await?;
+    assert_eq!(mismatches + 2, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+
+    let connected = reqwest::get(format!("{status_url}/connected-shippers"))
+        .await?
+        .text()
+        .await?;
+    assert!(
+        connected.lines().any(|host| host == "my_shipper"),
+        "{connected}"
+    );
+    assert!(
+        !connected.lines().any(|host| host == "other_clone"),
+        "{connected}"
+    );
+
+    timeout(Duration::from_secs(2), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
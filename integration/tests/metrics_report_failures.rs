@@ -0,0 +1,105 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_grpc::{
+    rlog_service_protocol::{
+        log_collector_server::{LogCollector, LogCollectorServer},
+        LogLine, Metrics, PingRequest, PingResponse,
+    },
+    tonic::{async_trait, transport::Server, Request, Response, Status},
+};
+use rlog_shipper::config::{Config, GrpcOutConfig, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+/// Collector accepting log lines and rejecting every metrics report
+#[derive(Clone, Default)]
+struct MetricsRejectingCollector {
+    log_lines: Arc<Mutex<Vec<LogLine>>>,
+    reports: Arc<Mutex<Vec<Metrics>>>,
+}
+
+#[async_trait]
+impl LogCollector for MetricsRejectingCollector {
+    async fn log(&self, request: Request<LogLine>) -> Result<Response<()>, Status> {
+        self.log_lines.lock().unwrap().push(request.into_inner());
+        Ok(Response::new(()))
+    }
+
+    async fn report_metrics(&self, request: Request<Metrics>) -> Result<Response<()>, Status> {
+        self.reports.lock().unwrap().push(request.into_inner());
+        Err(Status::unavailable("metrics reports are blocked"))
+    }
+
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        Err(Status::unimplemented("ping"))
+    }
+}
+
+#[tokio::test]
+async fn log_lines_delivered_while_metrics_reports_fail() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        grpc_out: Some(GrpcOutConfig {
+            metrics_report_interval: Duration::from_millis(300),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let collector = MetricsRejectingCollector::default();
+    let grpc_bind_address = bind_addresses.grpc_bind_address.parse()?;
+    let service = LogCollectorServer::new(collector.clone());
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(service)
+            .serve(grpc_bind_address)
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for i in 0..5 {
+        gelf_logger
+            .send_log(&GelfLog {
+                short_message: &format!("message {i}"),
+                long_message: None,
+                level: Severity::LOG_INFO as usize,
+                service: "my_service",
+                host: "my_host",
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64(),
+                extra_fields: json!({}),
+            })
+            .await?;
+        tokio::time::sleep(Duration::from_millis(800)).await;
+    }
+
+    timeout(Duration::from_secs(5), shipper.shutdown())
+        .await
+        .expect("Timed out while waiting for shipper shutdown");
+
+    let log_lines = collector.log_lines.lock().unwrap();
+    assert_eq!(5, log_lines.len());
+
+    // each report counts the previous failures, retries included
+    let reports = collector.reports.lock().unwrap();
+    assert!(reports.len() >= 4, "{} metrics reports", reports.len());
+    let failures = reports
+        .iter()
+        .map(|report| report.error_count["metrics_report"])
+        .collect::<Vec<_>>();
+    for (i, count) in failures.iter().enumerate() {
+        assert_eq!(i as u64, *count, "{failures:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::{str::FromStr, time::Duration};
+
+use integration::test_utils::{generate_pki, BindAddresses};
+use rlog_grpc::tonic::transport::{
+    Certificate, Channel, ClientTlsConfig, Identity, ServerTlsConfig, Uri,
+};
+use rlog_helper::ping::ping;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn ping_collector() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let endpoint = Channel::builder(Uri::from_str(&format!(
+        "http://{}",
+        bind_addresses.grpc_bind_address
+    ))?);
+    let mut out = Vec::new();
+    let stats = ping(endpoint, 3, Duration::from_millis(50), &mut out).await?;
+    assert_eq!(3, stats.transmitted);
+    assert_eq!(3, stats.rtts.len());
+
+    let out = String::from_utf8(out)?;
+    assert!(out.contains("seq=3 time="), "{out}");
+    assert!(out.contains("3 requests transmitted, 3 received, 0% loss"));
+    assert!(out.contains("rtt min/avg/max = "));
+
+    timeout(Duration::from_secs(2), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
+
+#[tokio::test]
+async fn ping_untrusted_collector() -> anyhow::Result<()> {
+    let collector_pki = generate_pki()?;
+    let shipper_pki = generate_pki()?;
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector_with_tls(
+        "rlog",
+        ServerTlsConfig::new()
+            .identity(Identity::from_pem(
+                collector_pki.server.0.pem(),
+                collector_pki.server.1.serialize_pem(),
+            ))
+            .client_ca_root(Certificate::from_pem(collector_pki.ca.pem())),
+    )?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // the collector certificate is not signed by the shipper CA
+    let endpoint = Channel::builder(Uri::from_str(&format!(
+        "https://{}",
+        bind_addresses.grpc_bind_address
+    ))?)
+    .tls_config(
+        ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(shipper_pki.ca.pem()))
+            .identity(Identity::from_pem(
+                shipper_pki.client.0.pem(),
+                shipper_pki.client.1.serialize_pem(),
+            ))
+            .domain_name("localhost"),
+    )?;
+    let error = ping(endpoint, 1, Duration::from_millis(50), &mut Vec::new())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Unable to connect to collector"));
+
+    timeout(Duration::from_secs(2), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog, GelfLogger};
+use rlog_shipper::config::{CommonInputConfig, Config, GelfInputConfig, GrpcOutConfig, CONFIG};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+/// Error log lines sent while the shipper is overloaded with info log lines are delivered
+#[tokio::test]
+async fn priority_queues() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        grpc_out: Some(GrpcOutConfig {
+            max_buffer_size: 10,
+            priority_queues: true,
+            ..Default::default()
+        }),
+        gelf_in: Some(GelfInputConfig {
+            common: CommonInputConfig {
+                max_buffer_size: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    // the collector is not started yet: nothing is sent until it is
+    let shipper = bind_addresses.start_shipper().await?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for i in 0..1000 {
+        send(
+            &bind_addresses,
+            &mut gelf_logger,
+            &format!("noise {i}"),
+            Severity::LOG_INFO,
+        )
+        .await?;
+    }
+    // let the shipper fill its queues
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    // the noise connection may have been closed by the shipper
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for i in 0..5 {
+        send(
+            &bind_addresses,
+            &mut gelf_logger,
+            &format!("error {i}"),
+            Severity::LOG_ERR,
+        )
+        .await?;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+
+    tokio::time::sleep(Duration::from_secs(4)).await;
+
+    let received = quickwit_server.get_received().await;
+    let errors = received
+        .iter()
+        .filter(|entry| entry.severity_text == "ERROR")
+        .count();
+    assert_eq!(5, errors, "{} received", received.len());
+    // some noise is dropped
+    assert!(received.len() < 1005, "{}", received.len());
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(5), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
+
+/// The shipper closes GELF connections when its input buffer is full: reconnect
+async fn send(
+    bind_addresses: &BindAddresses,
+    gelf_logger: &mut GelfLogger,
+    short_message: &str,
+    level: Severity,
+) -> anyhow::Result<()> {
+    let log = GelfLog {
+        short_message,
+        long_message: None,
+        level: level as usize,
+        service: "my_service",
+        host: "my_host",
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64(),
+        extra_fields: json!({}),
+    };
+    if gelf_logger.send_log(&log).await.is_err() {
+        *gelf_logger = bind_addresses.gelf_logger().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use integration::{
+    quickwit_mock::MOCK_REJECT_FIELD,
+    test_utils::{gelf_log_line, gelf_log_line_with_extra, BindAddresses},
+};
+use rlog_collector::metrics::COLLECTOR_REJECTED_COUNT;
+use serde_json::json;
+use tokio::time::timeout;
+
+/// send 3 logs, the second one will be rejected by the mock if it uses the ingest API v2
+async fn send_logs(
+    quickwit_version: Option<&str>,
+) -> anyhow::Result<(Vec<rlog_collector::IndexLogEntry>, usize)> {
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit_with_version("rlog", quickwit_version);
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    client.log(gelf_log_line("first")).await?;
+    client
+        .log(gelf_log_line_with_extra(
+            "rejected",
+            json!({ MOCK_REJECT_FIELD: true }).to_string(),
+        ))
+        .await?;
+    client.log(gelf_log_line("third")).await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    timeout(Duration::from_secs(5), collector.shutdown()).await?;
+
+    Ok((
+        quickwit.get_received().await,
+        quickwit.get_v2_request_count().await,
+    ))
+}
+
+#[tokio::test]
+async fn quickwit_v1() -> anyhow::Result<()> {
+    let (received, v2_request_count) = send_logs(Some("0.6.5")).await?;
+    assert_eq!(0, v2_request_count);
+    // v1 endpoint of the mock does not reject anything
+    assert_eq!(3, received.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn quickwit_version_probe_failure_fallback_to_v1() -> anyhow::Result<()> {
+    let (received, v2_request_count) = send_logs(None).await?;
+    assert_eq!(0, v2_request_count);
+    assert_eq!(3, received.len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn quickwit_v2_partial_failure() -> anyhow::Result<()> {
+    let (received, v2_request_count) = send_logs(Some("0.8.1")).await?;
+    assert!(v2_request_count > 0);
+    // only the offending document has been dropped
+    assert_eq!(2, received.len());
+    assert_eq!("first", received[0].message);
+    assert_eq!("third", received[1].message);
+    assert_eq!(1, COLLECTOR_REJECTED_COUNT.get());
+    Ok(())
+}
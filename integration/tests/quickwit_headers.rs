@@ -0,0 +1,51 @@
+use std::{collections::HashMap, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn extra_headers_are_sent_to_quickwit() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    let mut headers = HashMap::new();
+    headers.insert("X-Quickwit-Tenant".to_string(), "my-org".to_string());
+    let collector = bind_addresses.start_collector_with_headers("rlog", headers)?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    bind_addresses
+        .collector_client()
+        .await?
+        .log(gelf_log_line("hello"))
+        .await?;
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    assert_eq!(1, quickwit.get_received().await.len());
+    assert_eq!(
+        "my-org",
+        quickwit.get_last_ingest_headers().await["x-quickwit-tenant"]
+    );
+    assert_eq!(
+        "application/json",
+        quickwit.get_last_ingest_headers().await["content-type"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_extra_headers_are_rejected_at_startup() {
+    let bind_addresses = BindAddresses::default();
+    let mut headers = HashMap::new();
+    headers.insert("X-Invalid Name".to_string(), "value".to_string());
+    assert!(bind_addresses
+        .start_collector_with_headers("rlog", headers)
+        .is_err());
+
+    let mut headers = HashMap::new();
+    headers.insert("X-Tenant".to_string(), "invalid\nvalue".to_string());
+    assert!(bind_addresses
+        .start_collector_with_headers("rlog", headers)
+        .is_err());
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn quickwit_errors_are_exposed() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    quickwit
+        .push_ingest_failure(StatusCode::INTERNAL_SERVER_ERROR, "first failure")
+        .await;
+    quickwit
+        .push_ingest_failure(StatusCode::SERVICE_UNAVAILABLE, "second failure")
+        .await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    bind_addresses
+        .collector_client()
+        .await?
+        .log(gelf_log_line("hello"))
+        .await?;
+
+    // batched within 1s, then 2 failures retried every second
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    assert_eq!(1, quickwit.get_received().await.len());
+
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+    let last_errors: serde_json::Value = reqwest::get(format!("{status_url}/last-errors"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let last_errors = last_errors.as_array().unwrap();
+    assert_eq!(2, last_errors.len());
+    // most recent first
+    assert_eq!(503, last_errors[0]["status_code"]);
+    assert_eq!("second failure", last_errors[0]["body"]);
+    assert_eq!(1, last_errors[0]["batch_size"]);
+    assert_eq!(500, last_errors[1]["status_code"]);
+    assert_eq!("first failure", last_errors[1]["body"]);
+    assert!(last_errors[1]["timestamp"].as_u64().unwrap() > 1_700_000_000);
+
+    let metrics = reqwest::get(format!("{status_url}/metrics"))
+        .await?
+        .text()
+        .await?;
+    assert!(metrics.contains("rlog_collector_last_output_error_timestamp_seconds"));
+
+    let metrics: serde_json::Value = reqwest::get(format!("{status_url}/metrics?format=json"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let output_requests = metrics
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|family| family["name"] == "rlog_collector_output_request_count")
+        .unwrap();
+    assert_eq!("counter", output_requests["type"]);
+    assert!(output_requests["metrics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|metric| metric["labels"]["status"] == "error" && metric["value"] == 2.0));
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn disabled_quickwit_metrics_route() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_metrics_route: false,
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+    let health = reqwest::get(format!("{status_url}/health")).await?;
+    assert_eq!(200, health.status().as_u16());
+    // quickwit is not called, the route does not exist
+    let quickwit_metrics = reqwest::get(format!("{status_url}/quickwit/metrics")).await?;
+    assert_eq!(404, quickwit_metrics.status().as_u16());
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
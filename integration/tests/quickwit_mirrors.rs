@@ -0,0 +1,114 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::{
+    quickwit_mock::MockQuickwitServer,
+    test_utils::{gelf_log_line, BindAddresses},
+};
+use rlog_collector::{
+    config::{Config, QuickwitMirrorConfig, CONFIG},
+    metrics::{COLLECTOR_MIRROR_DROPPED_COUNT, COLLECTOR_OUTPUT_COUNT},
+};
+use tokio::time::timeout;
+
+async fn wait_received(quickwit: &MockQuickwitServer, count: usize) {
+    timeout(Duration::from_secs(5), async {
+        while quickwit.get_received().await.len() < count {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the logs");
+}
+
+/// A failing mirror drops its batches without delaying the primary quickwit & the other
+/// mirrors
+#[tokio::test]
+async fn failing_mirror_does_not_block_primary() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let healthy_addresses = BindAddresses::default();
+    let failing_addresses = BindAddresses::default();
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_size: 1,
+        collector_quickwit_batch_max_interval: Duration::from_millis(200),
+        collector_shutdown_flush_timeout: Duration::from_secs(2),
+        collector_quickwit_mirrors: vec![
+            QuickwitMirrorConfig {
+                name: Some("healthy".into()),
+                rest_url: MockQuickwitServer::url(&healthy_addresses),
+                index_id: "rlog-mirror".into(),
+                max_retries: 3,
+                queue_size: 100,
+            },
+            QuickwitMirrorConfig {
+                name: Some("failing".into()),
+                rest_url: MockQuickwitServer::url(&failing_addresses),
+                index_id: "rlog-mirror".into(),
+                max_retries: 1,
+                queue_size: 2,
+            },
+        ],
+        ..Default::default()
+    }));
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    let healthy_mirror = healthy_addresses.start_quickwit("rlog-mirror");
+    let failing_mirror = failing_addresses.start_quickwit("rlog-mirror");
+    failing_mirror
+        .set_ingest_delay(Duration::from_millis(500))
+        .await;
+    failing_mirror.fail_next(100).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    for i in 0..10 {
+        client.log(gelf_log_line(format!("message {i}"))).await?;
+    }
+
+    wait_received(&quickwit, 10).await;
+    wait_received(&healthy_mirror, 10).await;
+    assert!(failing_mirror.get_received().await.is_empty());
+    assert_eq!(
+        10,
+        COLLECTOR_OUTPUT_COUNT
+            .with_label_values(&["quickwit", "primary", "ok"])
+            .get()
+    );
+    assert_eq!(
+        10,
+        COLLECTOR_OUTPUT_COUNT
+            .with_label_values(&["quickwit", "healthy", "ok"])
+            .get()
+    );
+    assert!(
+        COLLECTOR_MIRROR_DROPPED_COUNT
+            .with_label_values(&["failing", "queue_full"])
+            .get()
+            > 0
+    );
+
+    timeout(Duration::from_secs(10), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    let dropped = ["queue_full", "retries_exhausted", "shutdown"]
+        .into_iter()
+        .map(|reason| {
+            COLLECTOR_MIRROR_DROPPED_COUNT
+                .with_label_values(&["failing", reason])
+                .get()
+        })
+        .sum::<u64>();
+    assert_eq!(10, dropped);
+    assert!(
+        COLLECTOR_OUTPUT_COUNT
+            .with_label_values(&["quickwit", "failing", "error"])
+            .get()
+            > 0
+    );
+    assert_eq!(
+        0,
+        COLLECTOR_MIRROR_DROPPED_COUNT
+            .with_label_values(&["healthy", "queue_full"])
+            .get()
+    );
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+/// Batches too large for quickwit are split until accepted, single logs too
+/// large are discarded.
+#[tokio::test]
+async fn too_large_batches_are_split() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_size: 20,
+        collector_quickwit_batch_max_interval: Duration::from_millis(500),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    // about 5 documents per request
+    quickwit.set_max_payload_size(Some(1000)).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    let mut sent = vec![];
+    for i in 0..20 {
+        let message = format!("log {i}");
+        client.log(gelf_log_line(message.clone())).await?;
+        sent.push(message);
+    }
+    // batched with the next logs, then alone after splits
+    client.log(gelf_log_line("x".repeat(2000))).await?;
+    for i in 20..25 {
+        let message = format!("log {i}");
+        client.log(gelf_log_line(message.clone())).await?;
+        sent.push(message);
+    }
+
+    // each split is retried after 1s
+    timeout(Duration::from_secs(15), async {
+        while quickwit.get_received().await.len() < sent.len() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the logs to be indexed");
+    // the discarded log would be indexed by now
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut indexed = quickwit
+        .get_received()
+        .await
+        .into_iter()
+        .map(|entry| entry.message)
+        .collect::<Vec<_>>();
+    indexed.sort();
+    sent.sort();
+    assert_eq!(sent, indexed);
+    // the first batch is halved until its requests fit, every document is sent at least
+    // twice
+    let request_documents = quickwit.get_request_documents().await;
+    assert_eq!(20, request_documents[0]);
+    assert!(request_documents.iter().sum::<usize>() >= 2 * (sent.len() + 1));
+
+    // the indexer is not stuck on the discarded log
+    client.log(gelf_log_line("after")).await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(
+        Some("after".to_string()),
+        quickwit
+            .get_received()
+            .await
+            .last()
+            .map(|entry| entry.message.clone())
+    );
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
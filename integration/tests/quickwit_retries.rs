@@ -0,0 +1,87 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_collector::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+fn store_config() {
+    CONFIG.store(Arc::new(Config {
+        collector_quickwit_batch_max_interval: Duration::from_millis(200),
+        ..Default::default()
+    }));
+}
+
+/// An overloaded quickwit (429) gets the same batch again after 5s
+#[tokio::test]
+async fn too_many_requests_are_retried() -> anyhow::Result<()> {
+    store_config();
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    quickwit.push_response(StatusCode::TOO_MANY_REQUESTS).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut client = bind_addresses.collector_client().await?;
+    client.log(gelf_log_line("first")).await?;
+    client.log(gelf_log_line("second")).await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(1, quickwit.get_request_count().await);
+    assert!(quickwit.get_received().await.is_empty());
+
+    timeout(Duration::from_secs(10), async {
+        while quickwit.get_received().await.len() < 2 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the retry");
+    assert_eq!(vec![2, 2], quickwit.get_request_documents().await);
+
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+    let last_errors: serde_json::Value = reqwest::get(format!("{status_url}/last-errors"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    assert_eq!(429, last_errors[0]["status_code"]);
+    assert_eq!("Too Many Requests", last_errors[0]["body"]);
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
+
+/// Server errors are retried every second until quickwit accepts the batch
+#[tokio::test]
+async fn server_errors_are_retried() -> anyhow::Result<()> {
+    store_config();
+    let bind_addresses = BindAddresses::default();
+    let quickwit = bind_addresses.start_quickwit("rlog");
+    quickwit.fail_next(3).await;
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    bind_addresses
+        .collector_client()
+        .await?
+        .log(gelf_log_line("hello"))
+        .await?;
+
+    timeout(Duration::from_secs(10), async {
+        while quickwit.get_received().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("Timed out while waiting for the retries");
+    assert_eq!(vec![1; 4], quickwit.get_request_documents().await);
+    assert_eq!(1, quickwit.get_received().await.len());
+
+    timeout(Duration::from_secs(5), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, time::Duration};
+
+use integration::{quickwit_mock::MockQuickwitServer, test_utils::BindAddresses};
+use rlog_collector::{IndexLogEntry, LogSystem};
+use rlog_helper::quickwit_schema::{bundled_doc_mapping, compare, fetch_doc_mapping};
+use serde_json::json;
+
+/// Live index schema fetched from the quickwit index metadata
+#[tokio::test]
+async fn check_quickwit_schema() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let quickwit_rest_url = MockQuickwitServer::url(&bind_addresses);
+
+    // index not created yet
+    assert!(fetch_doc_mapping(&quickwit_rest_url, "rlog").await.is_err());
+
+    quickwit_server
+        .set_index_config(json!({
+            "index_id": "rlog",
+            "doc_mapping": {
+                "mode": "dynamic",
+                "field_mappings": [
+                    {"name": "timestamp", "type": "datetime", "fast": true, "stored": true},
+                    {"name": "hostname", "type": "text", "fast": false, "stored": true},
+                    {"name": "service_name", "type": "text", "fast": false, "stored": true},
+                    {"name": "severity_text", "type": "text", "fast": false, "stored": true},
+                    {"name": "severity_number", "type": "u64", "fast": false, "stored": true},
+                    {"name": "body", "type": "json", "fast": false, "stored": true},
+                    {"name": "hmac_verified", "type": "bool", "fast": true, "stored": true},
+                    {"name": "indexed_fields", "type": "json", "fast": true, "stored": true},
+                    {"name": "doc_id", "type": "text", "fast": true, "stored": true},
+                    {"name": "doc_id_v", "type": "u64", "fast": false, "stored": true},
+                    {"name": "message", "type": "text", "fast": false, "stored": true},
+                ]
+            }
+        }))
+        .await;
+    let bundled = bundled_doc_mapping()?;
+    let live = fetch_doc_mapping(&quickwit_rest_url, "rlog").await?;
+    assert!(compare(&bundled, &live).is_empty());
+
+    // older index: missing field & incompatible type
+    quickwit_server
+        .set_index_config(json!({
+            "index_id": "rlog",
+            "doc_mapping": {
+                "field_mappings": [
+                    {"name": "timestamp", "type": "datetime", "fast": true},
+                    {"name": "hostname", "type": "text"},
+                    {"name": "service_name", "type": "text"},
+                    {"name": "severity_text", "type": "text"},
+                    {"name": "severity_number", "type": "i64"},
+                    {"name": "body", "type": "json"},
+                    {"name": "message", "type": "text"},
+                ]
+            }
+        }))
+        .await;
+    let live = fetch_doc_mapping(&quickwit_rest_url, "rlog").await?;
+    let differences = compare(&bundled, &live)
+        .iter()
+        .map(|difference| (difference.to_string(), difference.is_incompatible()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (
+                "! severity_number: type i64 in the live index, u64 expected".to_string(),
+                true
+            ),
+            (
+                "+ hmac_verified (bool): missing from the live index".to_string(),
+                false
+            ),
+            (
+                "+ indexed_fields (json): missing from the live index".to_string(),
+                false
+            ),
+            (
+                "+ doc_id (text): missing from the live index".to_string(),
+                false
+            ),
+            (
+                "+ doc_id_v (u64): missing from the live index".to_string(),
+                false
+            ),
+        ],
+        differences
+    );
+
+    Ok(())
+}
+
+/// Promoted free fields are serialized as an object, as expected by the `indexed_fields` json
+/// field of the bundled schema
+#[test]
+fn indexed_fields_mapping() -> anyhow::Result<()> {
+    let bundled = bundled_doc_mapping()?;
+    let mapping = bundled
+        .field_mappings
+        .iter()
+        .find(|mapping| mapping.name == "indexed_fields")
+        .expect("indexed_fields is not mapped");
+    assert_eq!("json", mapping.field_type);
+
+    let entry = IndexLogEntry {
+        message: "hello".into(),
+        timestamp: 1_700_000_000_000,
+        hostname: "my_host".into(),
+        service_name: "my_service".into(),
+        severity_text: "INFO".into(),
+        severity_number: 9,
+        log_system: LogSystem::Gelf,
+        hmac_verified: false,
+        indexed_fields: HashMap::from([
+            ("pid".to_string(), json!(42)),
+            ("request".to_string(), json!({"method": "GET"})),
+        ]),
+        doc_id: None,
+        doc_id_v: None,
+        free_fields: HashMap::new(),
+    };
+    let document = serde_json::to_value(&entry)?;
+    assert_eq!(
+        json!({"pid": 42, "request": {"method": "GET"}}),
+        document["indexed_fields"]
+    );
+    Ok(())
+}
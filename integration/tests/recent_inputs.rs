@@ -0,0 +1,108 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_shipper::config::{eqregex::EqRegex, Config, RecentInputsConfig, Redaction, CONFIG};
+use serde_json::{json, Value};
+use syslog::Severity;
+use tokio::{net::UdpSocket, time::timeout};
+
+#[tokio::test]
+async fn recent_inputs() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        recent_inputs: Some(RecentInputsConfig {
+            max_entries: 3,
+            redactions: vec![Redaction {
+                pattern: EqRegex::new(r"password=\S+")?,
+                replacement: "password=***".into(),
+            }],
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let _quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    for i in 0..5 {
+        let frame =
+            format!("<12>1 2024-01-02T03:04:05Z my_host login 1234 - - user{i} password=secret{i}");
+        socket
+            .send_to(frame.as_bytes(), &bind_addresses.shipper_syslog_bind)
+            .await?;
+    }
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "hello gelf",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_app",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let status_url = format!("http://{}", bind_addresses.shipper_http_bind);
+    let get = |query: &str| {
+        let url = format!("{status_url}/debug/recent{query}");
+        async move {
+            anyhow::Ok(
+                reqwest::get(url)
+                    .await?
+                    .error_for_status()?
+                    .json::<Value>()
+                    .await?,
+            )
+        }
+    };
+
+    assert_eq!(json!(["gelf_in", "syslog_in"]), get("").await?);
+
+    // the 3 last messages, redacted
+    let syslog_in = get("?source=syslog_in").await?;
+    let messages = syslog_in
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|input| input["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "<12>1 2024-01-02T03:04:05Z my_host login 1234 - - user2 password=***",
+            "<12>1 2024-01-02T03:04:05Z my_host login 1234 - - user3 password=***",
+            "<12>1 2024-01-02T03:04:05Z my_host login 1234 - - user4 password=***",
+        ],
+        messages
+    );
+    assert!(syslog_in[0]["received_at"].is_string());
+
+    let gelf_in = get("?source=gelf_in").await?;
+    assert_eq!(1, gelf_in.as_array().unwrap().len());
+    let gelf_message: Value = serde_json::from_str(gelf_in[0]["message"].as_str().unwrap())?;
+    assert_eq!("hello gelf", gelf_message["short_message"]);
+
+    assert_eq!(json!([]), get("?source=/var/log/unknown.log").await?);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+use integration::test_utils::BindAddresses;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn require_collector_on_start() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+
+    // no collector
+    let start = Instant::now();
+    let error = bind_addresses
+        .start_shipper_requiring_collector(Duration::from_secs(2))
+        .await
+        .err()
+        .expect("shipper must not start without collector");
+    assert!(error.to_string().contains("Collector unreachable"));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let _quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let shipper = bind_addresses
+        .start_shipper_requiring_collector(Duration::from_secs(2))
+        .await?;
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::{
+    collections::HashSet,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use integration::test_utils::BindAddresses;
+use rlog_inputs::metrics::{SYSLOG_ERROR_COUNT, SYSLOG_SEQUENCE};
+use rlog_shipper::config::{Config, GrpcOutConfig, SyslogInputConfig, CONFIG};
+use tokio::{net::UdpSocket, time::timeout};
+
+const FLOOD_SIZE: u64 = 2000;
+
+#[tokio::test]
+async fn sequence_numbers() -> anyhow::Result<()> {
+    let mut syslog_in = SyslogInputConfig::default();
+    syslog_in.common.max_buffer_size = 1;
+    CONFIG.store(Arc::new(Config {
+        sequence_numbers: true,
+        syslog_in: Some(syslog_in),
+        grpc_out: Some(GrpcOutConfig {
+            max_buffer_size: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    for i in 0..FLOOD_SIZE {
+        let frame = format!("<12>1 2024-01-02T03:04:05Z my_host flood 1234 - - message {i}");
+        socket
+            .send_to(frame.as_bytes(), &bind_addresses.shipper_syslog_bind)
+            .await?;
+    }
+
+    // wait for the accepted log lines to be indexed
+    let (mut accepted, mut dropped, mut received) = (0, 0, Vec::new());
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        accepted = SYSLOG_SEQUENCE.load(Ordering::Relaxed);
+        dropped = SYSLOG_ERROR_COUNT.load(Ordering::Relaxed);
+        received = quickwit_server.get_received().await;
+        if received.len() as u64 + dropped >= accepted {
+            break;
+        }
+    }
+    assert!(dropped > 0, "no message dropped, flood too small?");
+
+    let mut numbers = HashSet::new();
+    for entry in &received {
+        assert_eq!("syslog_in", entry.free_fields["_rlog_input"]);
+        assert_eq!(
+            received[0].free_fields["_rlog_incarnation"],
+            entry.free_fields["_rlog_incarnation"]
+        );
+        assert!(numbers.insert(entry.free_fields["_rlog_seq"].as_u64().unwrap()));
+    }
+    // each gap is a dropped message
+    let gaps = (0..accepted).filter(|n| !numbers.contains(n)).count() as u64;
+    assert_eq!(dropped, gaps);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
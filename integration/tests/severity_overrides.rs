@@ -0,0 +1,78 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use regex::Regex;
+use rlog_collector::config::{
+    self, Config, SeverityOverride, SeverityOverrideAction, SeverityOverrideMatch, CONFIG,
+};
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn severity_overrides() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        collector_severity_overrides: vec![SeverityOverride {
+            name: None,
+            matcher: SeverityOverrideMatch {
+                service_name: Some(Regex::new("^appliance$")?),
+                ..Default::default()
+            },
+            action: SeverityOverrideAction::SetMaxSeverity(config::Severity::Warning),
+        }],
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    for service in ["appliance", "my_app"] {
+        gelf_logger
+            .send_log(&GelfLog {
+                short_message: "everything is on fire",
+                long_message: None,
+                level: Severity::LOG_ERR as usize,
+                service,
+                host: "my_host",
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64(),
+                extra_fields: json!({}),
+            })
+            .await?;
+    }
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert_eq!(2, received.len());
+
+    assert_eq!("appliance", received[0].service_name);
+    assert_eq!("WARN", received[0].severity_text);
+    assert_eq!(13, received[0].severity_number);
+    assert_eq!(
+        "ERROR",
+        received[0].free_fields.get("original_severity").unwrap()
+    );
+
+    assert_eq!("my_app", received[1].service_name);
+    assert_eq!("ERROR", received[1].severity_text);
+    assert!(!received[1].free_fields.contains_key("original_severity"));
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
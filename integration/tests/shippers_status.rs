@@ -0,0 +1,85 @@
+use std::{collections::HashMap, time::Duration};
+
+use integration::test_utils::{gelf_log_line, BindAddresses};
+use rlog_grpc::rlog_service_protocol::Metrics;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn shippers_json() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+    let _quickwit = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let status_url = format!("http://{}", bind_addresses.collector_http_bind);
+    let mut client = bind_addresses.collector_client().await?;
+
+    // log lines received from an address which never reported metrics
+    client.log(gelf_log_line("hello")).await?;
+    let shippers: serde_json::Value = reqwest::get(format!("{status_url}/shippers.json"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let shippers = shippers.as_array().unwrap();
+    assert_eq!(1, shippers.len());
+    assert_eq!("never_reported", shippers[0]["status"]);
+    assert_eq!("127.0.0.1", shippers[0]["hostname"]);
+    assert!(shippers[0]["last_seen_timestamp"].is_null());
+    assert!(shippers[0]["last_log_timestamp"].as_u64().unwrap() > 1_700_000_000);
+    let connected = reqwest::get(format!("{status_url}/connected-shippers"))
+        .await?
+        .text()
+        .await?;
+    assert_eq!("", connected);
+
+    let counts = |values: &[(&str, u64)]| -> HashMap<String, u64> {
+        values.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    };
+    client
+        .report_metrics(Metrics {
+            hostname: "backed_up_shipper".into(),
+            queue_count: counts(&[("grpc_out", 9000), ("files_in", 1)]),
+            queue_capacity: counts(&[("grpc_out", 10000)]),
+            processed_count: counts(&[("grpc_out", 42)]),
+            error_count: counts(&[("grpc_out", 3)]),
+            process_start_time: None,
+            incarnation_id: "d9428888-122b-11e1-b85c-61cd3cbb3210".into(),
+            delivery_window: None,
+        })
+        .await?;
+
+    let connected = reqwest::get(format!("{status_url}/connected-shippers"))
+        .await?
+        .text()
+        .await?;
+    assert_eq!("backed_up_shipper\n", connected);
+
+    let shippers: serde_json::Value = reqwest::get(format!("{status_url}/shippers.json"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let shippers = shippers.as_array().unwrap();
+    assert_eq!(1, shippers.len());
+    assert_eq!("backed_up_shipper", shippers[0]["hostname"]);
+    assert_eq!("reporting", shippers[0]["status"]);
+    assert_eq!("127.0.0.1", shippers[0]["peer"]);
+    assert_eq!(
+        "d9428888-122b-11e1-b85c-61cd3cbb3210",
+        shippers[0]["incarnation_id"]
+    );
+    assert_eq!(9000, shippers[0]["queue_count"]["grpc_out"]);
+    assert_eq!(1, shippers[0]["queue_count"]["files_in"]);
+    assert_eq!(10000, shippers[0]["queue_capacity"]["grpc_out"]);
+    assert_eq!(42, shippers[0]["processed_count"]["grpc_out"]);
+    assert_eq!(3, shippers[0]["error_count"]["grpc_out"]);
+    assert!(shippers[0]["last_seen_timestamp"].as_u64().unwrap() > 1_700_000_000);
+    // the log lines are attributed to the shipper reporting from the same address
+    assert!(shippers[0]["last_log_timestamp"].as_u64().unwrap() > 1_700_000_000);
+
+    timeout(Duration::from_secs(2), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
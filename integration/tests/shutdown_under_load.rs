@@ -0,0 +1,98 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use integration::test_utils::{BindAddresses, GelfLog, GelfLogger};
+use rlog_inputs::metrics::GELF_DISCARDED_COUNT;
+use rlog_shipper::config::{Config, CONFIG};
+use tokio::time::timeout;
+
+const SHIPPER_COUNT: usize = 5;
+const MESSAGE_COUNT: usize = 1000;
+
+/// Shippers are shut down while the messages flooding their GELF inputs are still in their
+/// pipelines: every sent message must either be indexed or discarded by a shipper.
+///
+/// The messages are sent over TCP and the connections are drained (closed by the shippers
+/// once they have read every frame) before the shutdowns, no message is lost in a socket
+/// buffer.
+#[tokio::test]
+async fn shutdown_under_load() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config::default()));
+
+    let mut bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+
+    let sent: &'static AtomicU64 = Box::leak(Box::new(AtomicU64::new(0)));
+    let mut shippers = vec![];
+    for i in 0..SHIPPER_COUNT {
+        let ba = bind_addresses.new_shipper_addresses();
+        let shipper = ba.start_shipper().await?;
+        shippers.push((ba, shipper, i));
+    }
+    // let the shippers connect to the collector
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut tasks = vec![];
+    for (ba, shipper, i) in shippers {
+        tasks.push(tokio::spawn(async move {
+            let mut logger = GelfLogger::new(&ba.shipper_gelf_bind).await?;
+            let host = format!("host_{i}");
+            for n in 0..MESSAGE_COUNT {
+                let short_message = format!("shipper {i} message {n}");
+                logger
+                    .send_log(&GelfLog {
+                        short_message: &short_message,
+                        long_message: None,
+                        level: 6,
+                        service: "my_app",
+                        host: &host,
+                        timestamp: chrono::Utc::now().timestamp_micros() as f64 / 1_000_000.0,
+                        extra_fields: serde_json::json!({}),
+                    })
+                    .await?;
+                sent.fetch_add(1, Ordering::Relaxed);
+            }
+            logger.close().await?;
+            // the messages are still being shipped
+            timeout(Duration::from_secs(30), shipper.shutdown())
+                .await
+                .expect("Timed out while waiting for shipper shutdown");
+            Ok::<_, anyhow::Error>(())
+        }));
+    }
+    for result in futures::future::join_all(tasks).await {
+        result??;
+    }
+
+    timeout(Duration::from_secs(30), collector.shutdown())
+        .await
+        .expect("Timed out while waiting for collector shutdown");
+
+    let received = quickwit_server.get_received().await;
+    // full queue or exhausted byte budget
+    let dead_letters = GELF_DISCARDED_COUNT.load(Ordering::Relaxed);
+    let total_sent = sent.load(Ordering::Relaxed);
+    tracing::info!(
+        "sent: {total_sent}, received: {}, dead letters: {dead_letters}",
+        received.len()
+    );
+
+    assert_eq!((SHIPPER_COUNT * MESSAGE_COUNT) as u64, total_sent);
+    assert!(!received.is_empty());
+    assert_eq!(total_sent, received.len() as u64 + dead_letters);
+    // no message indexed twice
+    let messages = received
+        .iter()
+        .map(|entry| &entry.message)
+        .collect::<HashSet<_>>();
+    assert_eq!(received.len(), messages.len());
+
+    Ok(())
+}
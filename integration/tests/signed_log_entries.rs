@@ -0,0 +1,76 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{BindAddresses, GelfLog};
+use rlog_collector::config::{LogSignatureKey, CONFIG as COLLECTOR_CONFIG};
+use rlog_common::log_signature::encode_signing_key;
+use rlog_shipper::config::CONFIG as SHIPPER_CONFIG;
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn signed_log_entries() -> anyhow::Result<()> {
+    let signing_key = [42; 32];
+    let signature_key = |key: &[u8; 32]| LogSignatureKey::try_from(encode_signing_key(key));
+    SHIPPER_CONFIG.store(Arc::new(rlog_shipper::config::Config {
+        sign_log_entries: true,
+        ..Default::default()
+    }));
+    COLLECTOR_CONFIG.store(Arc::new(rlog_collector::config::Config {
+        collector_verify_log_signatures: true,
+        collector_log_signature_keys: [
+            ("signed_host".to_string(), signature_key(&signing_key)?),
+            ("other_host".to_string(), signature_key(&[7; 32])?),
+        ]
+        .into(),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    // the key is mandatory
+    assert!(bind_addresses.start_shipper().await.is_err());
+    let shipper = bind_addresses
+        .start_shipper_with_signing_key(signing_key)
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut gelf_logger = bind_addresses.gelf_logger().await?;
+    // rejected: wrong key, no key
+    for host in ["other_host", "unknown_host", "signed_host"] {
+        gelf_logger
+            .send_log(&GelfLog {
+                short_message: "hello gelf",
+                long_message: None,
+                level: Severity::LOG_INFO as usize,
+                service: "my_app",
+                host,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64(),
+                extra_fields: json!({}),
+            })
+            .await?;
+    }
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert_eq!(1, received.len());
+    assert_eq!("signed_host", received[0].hostname);
+    assert!(received[0].hmac_verified);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
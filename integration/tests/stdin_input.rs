@@ -0,0 +1,87 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{
+    eqregex::EqRegex, Config, FieldMapping, FieldType, FileMappingConfig, FileParseConfig, CONFIG,
+};
+use tokio::{io::AsyncWriteExt, time::timeout};
+
+#[tokio::test]
+async fn stdin_input() -> anyhow::Result<()> {
+    let bind_addresses = BindAddresses::default();
+
+    // the stdin_in configuration section is mandatory
+    let (_stdin, reader) = tokio::io::duplex(1024);
+    let Err(e) = bind_addresses
+        .start_shipper_with_stdin(Box::new(reader))
+        .await
+    else {
+        panic!("the shipper should not start without stdin_in configuration");
+    };
+    assert!(e.to_string().contains("stdin_in"));
+
+    CONFIG.store(Arc::new(Config {
+        stdin_in: Some(FileParseConfig {
+            mapping: FileMappingConfig::Regex {
+                pattern: EqRegex::new(r"^(\w+) (.*)$")?,
+                mapping: vec![
+                    FieldMapping {
+                        name: "severity".into(),
+                        field_type: FieldType::SyslogLevelText,
+                    },
+                    FieldMapping {
+                        name: "message".into(),
+                        field_type: FieldType::String,
+                    },
+                ],
+            },
+            static_fields: HashMap::new(),
+            log_rotation_strategy: Default::default(),
+            log_system: Some("my_container".into()),
+            severity_mapping: HashMap::new(),
+            enabled: true,
+            labels: HashMap::new(),
+            normalize_message: None,
+            startup_retry: None,
+        }),
+        ..Default::default()
+    }));
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let (mut stdin, reader) = tokio::io::duplex(1024);
+    let shipper = bind_addresses
+        .start_shipper_with_stdin(Box::new(reader))
+        .await?;
+
+    stdin
+        .write_all(b"INFO hello stdin\nunparsable\r\nERROR last line without newline")
+        .await?;
+    // end of the standard input: only the stdin input stops
+    drop(stdin);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let received = quickwit_server.get_received().await;
+    assert_eq!(2, received.len());
+    assert_eq!("hello stdin", received[0].message);
+    assert_eq!("INFO", received[0].severity_text);
+    assert_eq!("stdin", received[0].service_name);
+    assert_eq!("last line without newline", received[1].message);
+    assert_eq!("ERROR", received[1].severity_text);
+
+    // the shipper is still serving its other inputs
+    reqwest::get(format!(
+        "http://{}/health",
+        bind_addresses.shipper_http_bind
+    ))
+    .await?
+    .error_for_status()?;
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::{sync::Arc, time::Duration};
+
+use integration::test_utils::BindAddresses;
+use rlog_shipper::config::{Config, SyslogOutConfig, SyslogOutProtocol, CONFIG};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, UdpSocket},
+    time::timeout,
+};
+
+const FRAME: &str =
+    "<12>1 2024-01-02T03:04:05Z my_host postfix 1234 - - connect from localhost\nsecond line";
+
+#[tokio::test]
+async fn syslog_out() -> anyhow::Result<()> {
+    let downstream = TcpListener::bind("127.0.0.1:0").await?;
+    CONFIG.store(Arc::new(Config {
+        syslog_out: Some(SyslogOutConfig {
+            address: downstream.local_addr()?.to_string(),
+            protocol: SyslogOutProtocol::Tcp,
+            grpc: false,
+            max_buffer_size: 10,
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    socket
+        .send_to(FRAME.as_bytes(), &bind_addresses.shipper_syslog_bind)
+        .await?;
+
+    let (mut stream, _) = timeout(Duration::from_secs(2), downstream.accept()).await??;
+    // octet counting framing, the frame is relayed as received
+    let expected = format!("{} {FRAME}", FRAME.len());
+    let mut received = vec![0; expected.len()];
+    timeout(Duration::from_secs(2), stream.read_exact(&mut received)).await??;
+    assert_eq!(expected, String::from_utf8(received)?);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    // not shipped through gRPC
+    assert!(quickwit_server.get_received().await.is_empty());
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
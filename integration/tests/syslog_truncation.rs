@@ -0,0 +1,72 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use integration::test_utils::BindAddresses;
+use rlog_inputs::metrics::SYSLOG_TRUNCATED_COUNT;
+use rlog_shipper::config::{Config, SyslogInputConfig, CONFIG};
+use tokio::{net::UdpSocket, time::timeout};
+
+const MAX_DATAGRAM_BYTES: usize = 100;
+
+#[tokio::test]
+async fn truncated_datagram() -> anyhow::Result<()> {
+    CONFIG.store(Arc::new(Config {
+        syslog_in: Some(SyslogInputConfig {
+            max_datagram_bytes: Some(MAX_DATAGRAM_BYTES),
+            recv_buffer_bytes: Some(1 << 20),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+
+    let bind_addresses = BindAddresses::default();
+
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector("rlog")?;
+    let shipper = bind_addresses.start_shipper().await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let header = "<12>1 2024-01-02T03:04:05Z my_host my_app 1234 - - ";
+    for message in ["short message".to_string(), "x".repeat(200)] {
+        socket
+            .send_to(
+                format!("{header}{message}").as_bytes(),
+                &bind_addresses.shipper_syslog_bind,
+            )
+            .await?;
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        received = quickwit_server.get_received().await;
+        if received.len() >= 2 {
+            break;
+        }
+    }
+    assert_eq!(2, received.len());
+    assert_eq!(1, SYSLOG_TRUNCATED_COUNT.load(Ordering::Relaxed));
+
+    let short = received
+        .iter()
+        .find(|entry| entry.message == "short message")
+        .unwrap();
+    assert!(!short.free_fields.contains_key("_truncated"));
+    let truncated = received
+        .iter()
+        .find(|entry| entry.message.starts_with('x'))
+        .unwrap();
+    assert_eq!("true", truncated.free_fields["_truncated"]);
+    assert_eq!(MAX_DATAGRAM_BYTES - header.len(), truncated.message.len());
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(2), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+
+    Ok(())
+}
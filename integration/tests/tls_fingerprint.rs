@@ -0,0 +1,84 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use integration::test_utils::{generate_pki, BindAddresses, GelfLog};
+use rlog_common::tls::TlsVersion;
+use rlog_grpc::tonic::transport::{self, Identity, ServerTlsConfig};
+use rlog_shipper::grpc_tls::PinnedTlsConnector;
+use serde_json::json;
+use syslog::Severity;
+use tokio::time::timeout;
+
+async fn ship_one_log(expected_fingerprint: &str) -> anyhow::Result<usize> {
+    let pki = generate_pki()?;
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector = bind_addresses.start_collector_with_tls(
+        "rlog",
+        ServerTlsConfig::new()
+            .identity(Identity::from_pem(
+                pki.server.0.pem(),
+                pki.server.1.serialize_pem(),
+            ))
+            .client_ca_root(transport::Certificate::from_pem(pki.ca.pem())),
+    )?;
+    let expected_fingerprint = match expected_fingerprint {
+        "server" => ring::digest::digest(&ring::digest::SHA256, pki.server.0.der())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+        other => other.to_string(),
+    };
+    let shipper = bind_addresses
+        .start_shipper_with_pinned_tls(PinnedTlsConnector::new(
+            pki.ca.pem().as_bytes(),
+            pki.client.0.pem().as_bytes(),
+            pki.client.1.serialize_pem().as_bytes(),
+            Some(&expected_fingerprint),
+            Some("localhost".into()),
+            TlsVersion::Tls12,
+        )?)
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "pinned",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let received = quickwit_server.get_received().await.len();
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(3), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(received)
+}
+
+#[tokio::test]
+async fn matching_fingerprint() -> anyhow::Result<()> {
+    assert_eq!(1, ship_one_log("server").await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn mismatching_fingerprint() -> anyhow::Result<()> {
+    // the collector certificate is signed by the trusted CA, but is not the pinned one
+    let pinned = ["AB"; 32].join(":");
+    assert_eq!(0, ship_one_log(&pinned).await?);
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use integration::test_utils::{generate_pki, BindAddresses, GelfLog, Pki};
+use rlog_collector::GrpcTlsConfig;
+use rlog_common::tls::TlsVersion;
+use rlog_shipper::grpc_tls::PinnedTlsConnector;
+use serde_json::json;
+use syslog::Severity;
+use tokio::{net::TcpStream, time::timeout};
+use tokio_rustls::{
+    rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName},
+        version::{TLS12, TLS13},
+        ClientConfig, RootCertStore, SupportedProtocolVersion,
+    },
+    TlsConnector,
+};
+
+fn grpc_tls(pki: &Pki, min_tls_version: TlsVersion) -> GrpcTlsConfig {
+    GrpcTlsConfig {
+        ca_certificate_pem: pki.ca.pem().into_bytes(),
+        certificate_pem: pki.server.0.pem().into_bytes(),
+        private_key_pem: pki.server.1.serialize_pem().into_bytes(),
+        min_tls_version,
+    }
+}
+
+/// Plain TLS handshake with the collector, restricted to the given protocol version
+async fn handshake(
+    pki: &Pki,
+    addr: &str,
+    version: &'static SupportedProtocolVersion,
+) -> anyhow::Result<()> {
+    let mut roots = RootCertStore::empty();
+    roots.add(pki.ca.der().clone())?;
+    let config = ClientConfig::builder_with_protocol_versions(&[version])
+        .with_root_certificates(roots)
+        .with_client_auth_cert(
+            vec![pki.client.0.der().clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(pki.client.1.serialize_der())),
+        )?;
+    let stream = TcpStream::connect(addr).await?;
+    TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost")?, stream)
+        .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls13_only_collector() -> anyhow::Result<()> {
+    let pki = generate_pki()?;
+    let bind_addresses = BindAddresses::default();
+    let quickwit_server = bind_addresses.start_quickwit("rlog");
+    let collector =
+        bind_addresses.start_collector_with_grpc_tls("rlog", grpc_tls(&pki, TlsVersion::Tls13))?;
+    let shipper = bind_addresses
+        .start_shipper_with_pinned_tls(PinnedTlsConnector::new(
+            pki.ca.pem().as_bytes(),
+            pki.client.0.pem().as_bytes(),
+            pki.client.1.serialize_pem().as_bytes(),
+            None,
+            Some("localhost".into()),
+            TlsVersion::Tls13,
+        )?)
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let grpc_addr = &bind_addresses.grpc_bind_address;
+    assert!(handshake(&pki, grpc_addr, &TLS12).await.is_err());
+    handshake(&pki, grpc_addr, &TLS13).await?;
+
+    bind_addresses
+        .gelf_logger()
+        .await?
+        .send_log(&GelfLog {
+            short_message: "tls 1.3",
+            long_message: None,
+            level: Severity::LOG_INFO as usize,
+            service: "my_service",
+            host: "my_host",
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            extra_fields: json!({}),
+        })
+        .await?;
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let received = quickwit_server.get_received().await;
+    assert_eq!(1, received.len());
+    assert_eq!("tls 1.3", received[0].message);
+
+    let shutdown = futures::future::join(collector.shutdown(), shipper.shutdown());
+    timeout(Duration::from_secs(3), shutdown)
+        .await
+        .expect("Timed out while waiting for shutdown");
+    Ok(())
+}
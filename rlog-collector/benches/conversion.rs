@@ -0,0 +1,82 @@
+//! Conversion of 100k synthetic GELF, syslog & generic log lines to index entries, compare
+//! two revisions with criterion baselines:
+//!
+//! ```sh
+//! cargo bench -p rlog-collector --bench conversion -- --save-baseline before
+//! # switch to the other revision
+//! cargo bench -p rlog-collector --bench conversion -- --baseline before
+//! ```
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rlog_collector::IndexLogEntry;
+use rlog_grpc::{
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{
+        log_line::Line, GelfLogLine, GenericLogLine, LogLine, SyslogFacility, SyslogLogLine,
+        SyslogSeverity,
+    },
+};
+
+const LOG_LINES: u64 = 100_000;
+
+fn log_line(i: u64) -> LogLine {
+    let severity = SyslogSeverity::from(i % 8) as i32;
+    let line = match i % 3 {
+        0 => Line::Gelf(GelfLogLine {
+            short_message: format!("GET /api/users/{i} 200"),
+            full_message: None,
+            severity,
+            extra: r#"{"service":"api","env":"production"}"#.into(),
+        }),
+        1 => Line::Syslog(SyslogLogLine {
+            facility: SyslogFacility::Daemon as i32,
+            severity,
+            appname: Some("postfix".into()),
+            proc_pid: Some(1234),
+            proc_name: None,
+            msgid: None,
+            msg: format!("connect from localhost[127.0.0.1] #{i}"),
+            service_name: None,
+        }),
+        _ => Line::GenericLog(GenericLogLine {
+            message: format!("worker {i} done"),
+            severity,
+            service_name: "worker".into(),
+            extra: "{}".into(),
+            log_system: "file_in".into(),
+        }),
+    };
+    LogLine {
+        host: "my_host".into(),
+        raw_host: None,
+        hmac: Vec::new(),
+        sequence: None,
+        labels: Default::default(),
+        timestamp: Some(Timestamp {
+            seconds: 1_700_000_000 + i as i64,
+            nanos: 0,
+        }),
+        line: Some(line),
+    }
+}
+
+fn convert(c: &mut Criterion) {
+    let log_lines = (0..LOG_LINES).map(log_line).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("conversion");
+    group.sample_size(10);
+    group.bench_function("mixed_100k", |b| {
+        b.iter_batched(
+            || log_lines.clone(),
+            |log_lines| {
+                for log_line in log_lines {
+                    IndexLogEntry::try_from(log_line).unwrap();
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, convert);
+criterion_main!(benches);
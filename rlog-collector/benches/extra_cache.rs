@@ -0,0 +1,77 @@
+//! GELF log lines conversion with 90% of repeated `extra` fields, with and without the
+//! parsed extra cache (`collector_extra_cache_size`).
+//!
+//! Run it without `RUST_BACKTRACE`: backtraces captured by errors would dominate.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rlog_collector::{
+    config::{Config, CONFIG},
+    IndexLogEntry,
+};
+use rlog_grpc::{
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{log_line::Line, GelfLogLine, LogLine, SyslogSeverity},
+};
+
+fn gelf_log_line(extra: String) -> LogLine {
+    LogLine {
+        host: "my_gelf_host".into(),
+        raw_host: None,
+        hmac: Vec::new(),
+        sequence: None,
+        labels: Default::default(),
+        timestamp: Some(Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 0,
+        }),
+        line: Some(Line::Gelf(GelfLogLine {
+            short_message: "GET /api/users 200".into(),
+            full_message: None,
+            severity: SyslogSeverity::Info as i32,
+            extra,
+        })),
+    }
+}
+
+/// 1000 log lines of 10 services: 90% have the static fields of their service only
+fn log_lines() -> Vec<LogLine> {
+    (0..1000)
+        .map(|i| {
+            let service = i % 10;
+            let request_id = if i % 10 == 0 {
+                format!(r#","request_id":"{i:08x}""#)
+            } else {
+                String::new()
+            };
+            gelf_log_line(format!(
+                r#"{{"service":"service_{service}","env":"production","region":"eu-west-3","version":"1.42.{service}","team":"backend","container":{{"image":"registry.example.com/service_{service}:1.42","runtime":"containerd"}}{request_id}}}"#
+            ))
+        })
+        .collect()
+}
+
+fn convert(c: &mut Criterion) {
+    let log_lines = log_lines();
+    for (name, collector_extra_cache_size) in [("no_cache", 0), ("cache", 1024)] {
+        CONFIG.store(Arc::new(Config {
+            collector_extra_cache_size,
+            ..Default::default()
+        }));
+        c.bench_function(&format!("gelf_extra_{name}"), |b| {
+            b.iter_batched(
+                || log_lines.clone(),
+                |log_lines| {
+                    for log_line in log_lines {
+                        IndexLogEntry::try_from(log_line).unwrap();
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, convert);
+criterion_main!(benches);
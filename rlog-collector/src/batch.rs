@@ -2,9 +2,44 @@ use std::time::Duration;
 
 use arc_swap::access::Access;
 use async_channel::{Receiver, SendError, Sender};
-use tokio::select;
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+};
 use tokio_util::sync::CancellationToken;
 
+/// Sent to the index loop after the batches emitted by a flush request, `done` must be
+/// notified once the first `batches` batches have been accepted by quickwit
+pub struct FlushMarker {
+    pub batches: u64,
+    pub done: oneshot::Sender<()>,
+}
+
+/// Output of the batch task, consumed by the index loop
+pub struct Batches<T> {
+    pub receiver: Receiver<Vec<T>>,
+    pub flush_markers: mpsc::UnboundedReceiver<FlushMarker>,
+}
+
+/// Requests the batch task to emit its buffer immediately
+#[derive(Clone)]
+pub struct Flusher(mpsc::Sender<oneshot::Sender<()>>);
+
+impl Flusher {
+    /// Resolves once the buffered entries have been accepted by quickwit, fails if the
+    /// collector is shutting down
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.0
+            .send(done)
+            .await
+            .map_err(|_| anyhow::anyhow!("Batch task stopped"))?;
+        flushed
+            .await
+            .map_err(|_| anyhow::anyhow!("Flush interrupted by shutdown"))
+    }
+}
+
 // working with arc-swapped config is rather extreme in term of generic stuff
 // maybe this is a bit over-engineered!
 pub fn launch_batch_collector<T, D, S, IS, OS>(
@@ -13,7 +48,7 @@ pub fn launch_batch_collector<T, D, S, IS, OS>(
     input_buffer_size: IS,
     output_buffer_size: OS,
     shutdown_token: CancellationToken,
-) -> (Sender<T>, Receiver<Vec<T>>)
+) -> (Sender<T>, Batches<T>, Flusher)
 where
     T: Send + 'static,
     D: Access<Duration> + Send + 'static,
@@ -24,9 +59,13 @@ where
     let (sender, receiver) = async_channel::bounded(*input_buffer_size.load());
 
     let (batch_sender, batch_receiver) = async_channel::bounded(*output_buffer_size.load());
+    let (flush_sender, mut flush_requests) = mpsc::channel(16);
+    let (marker_sender, marker_receiver) = mpsc::unbounded_channel();
 
     tokio::spawn(async move {
         let mut buffer = Vec::with_capacity(*max_batch_size.load());
+        // number of batches sent to the index loop, referenced by the flush markers
+        let mut sent_batches = 0;
 
         loop {
             let max_wait = tokio::time::sleep(*max_wait_time.load());
@@ -40,14 +79,24 @@ where
                         buffer.push(item);
                     }
                     // send buffer & exit
-                    if let Err(_) = send_buffer(&mut buffer, &batch_sender).await{
+                    if send_buffer(&mut buffer, &batch_sender, &mut sent_batches).await.is_err() {
                         tracing::error!("Batch channel closed!");
                     }
+                    // batch_sender is dropped only now: the indexer sees the
+                    // channel closed after receiving the last batch
                     return;
                 }
+                Some(done) = flush_requests.recv() => {
+                    if send_buffer(&mut buffer, &batch_sender, &mut sent_batches).await.is_err() {
+                        tracing::error!("Batch channel closed!");
+                    }
+                    // the marker follows the flushed batch, ignore errors: there is no
+                    // index loop in the in process collector
+                    let _ = marker_sender.send(FlushMarker { batches: sent_batches, done });
+                }
                 _ = max_wait => {
                     // waited too long, send the buffer
-                    if let Err(_) =  send_buffer(&mut buffer, &batch_sender).await{
+                    if send_buffer(&mut buffer, &batch_sender, &mut sent_batches).await.is_err() {
                         tracing::error!("Batch channel closed!");
                     }
                 }
@@ -57,7 +106,7 @@ where
                     buffer.push(log_line);
                     if buffer.len() == *max_batch_size.load(){
                         // batch completed!
-                        if let Err(_) =  send_buffer(&mut buffer, &batch_sender).await{
+                        if send_buffer(&mut buffer, &batch_sender, &mut sent_batches).await.is_err() {
                             tracing::error!("Batch channel closed!");
                         }
                     }
@@ -66,15 +115,21 @@ where
         }
     });
 
-    (sender, batch_receiver)
+    let batches = Batches {
+        receiver: batch_receiver,
+        flush_markers: marker_receiver,
+    };
+    (sender, batches, Flusher(flush_sender))
 }
 
 async fn send_buffer<T>(
     buffer: &mut Vec<T>,
     batch_sender: &Sender<Vec<T>>,
+    sent_batches: &mut u64,
 ) -> Result<(), SendError<Vec<T>>> {
     if buffer.len() > 0 {
         let batch = buffer.drain(..).collect::<Vec<_>>();
+        *sent_batches += 1;
         // ignore send errors
         batch_sender.send(batch).await
     } else {
@@ -1,25 +1,522 @@
 use arc_swap::ArcSwap;
 use lazy_static::lazy_static;
+use regex::Regex;
+use ring::hmac;
+use rlog_common::{
+    bind_addr::BindAddr,
+    config::{yaml_template, ConfigDoc},
+    log_signature::parse_signing_key,
+    metrics_sanitizer::MetricsSanitizerConfig,
+};
+use rlog_grpc::{
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{SyslogFacility, SyslogSeverity},
+};
+use rlog_inputs::config::{GelfInputConfig, SyslogInputConfig};
 use serde::{Deserialize, Serialize};
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 lazy_static! {
     pub static ref CONFIG: ArcSwap<Config> = ArcSwap::new(Arc::new(Config::default()));
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ConfigDoc)]
 pub struct Config {
-    /// Size of the input buffer queue size (queue used before batch aggregation)
+    /// Size of the input queue (log lines waiting to be batched), > 0 (not hot reloaded)
     pub collector_input_buffer_size: usize,
-    /// Size of quickwit output buffer size (queue that contains batches)
+    /// Size of the output queue (batches waiting to be sent to quickwit), > 0
+    /// (not hot reloaded)
     pub collector_quickwit_output_buffer_size: usize,
-    /// Size of each log batch sent to quickwit
+    /// Number of log lines of each batch sent to quickwit, > 0
     pub collector_quickwit_batch_size: usize,
     /// Maximum interval between each batches: if there is not enough logs to
     /// emit a full batch a partial batch will be emitted if the last batch was
     /// emitted before this time
     #[serde(with = "humantime_serde")]
     pub collector_quickwit_batch_max_interval: Duration,
+    /// Maximum number of ingest requests sent concurrently to quickwit, > 0, 1 sends the
+    /// batches one at a time
+    #[serde(default = "default_batch_send_parallelism")]
+    pub collector_batch_send_parallelism: usize,
+    /// Free fields promoted to the `indexed_fields` object of the quickwit documents (fast
+    /// field in the quickwit index schema) instead of being dynamically indexed, queried
+    /// with the `indexed_fields.` prefix (eg: `indexed_fields.request_id:abc`)
+    #[serde(default)]
+    pub collector_indexed_fields: Vec<String>,
+    /// Quickwit ingest API version: auto (detected from the quickwit version), v1 or v2
+    /// (not hot reloaded)
+    #[serde(default)]
+    pub collector_quickwit_api_version: QuickwitApiVersion,
+    /// `commit` parameter of the ingest requests (both API versions): auto, wait_for or
+    /// force (not hot reloaded)
+    #[serde(default)]
+    pub collector_quickwit_commit: QuickwitCommitMode,
+    /// Unit of the `timestamp` field of the quickwit documents: milliseconds, microseconds
+    /// or nanoseconds
+    #[serde(default)]
+    pub collector_timestamp_precision: TimestampPrecision,
+    /// A deterministic `doc_id` (hash of the hostname, service name, timestamp & message) and
+    /// its version `doc_id_v` are added to the quickwit documents, the same log entry ingested
+    /// twice has the same id
+    #[serde(default)]
+    pub collector_emit_doc_id: bool,
+    /// Log entries older than this age (eg: replayed backlogs past the index retention)
+    /// are dropped instead of being indexed, disabled if not set or 0
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub collector_max_log_age: Option<Duration>,
+    /// Only 1 in N received logs is dumped in debug logs, 0 disables the dumps
+    #[serde(default = "default_debug_sample_rate")]
+    pub collector_debug_sample_rate: u64,
+    /// On shutdown, pending batches are retried until this timeout is reached,
+    /// remaining logs are then lost
+    #[serde(default = "default_shutdown_flush_timeout", with = "humantime_serde")]
+    pub collector_shutdown_flush_timeout: Duration,
+    /// Interval of the process metrics collection (Linux only), 0 disables it, read once at
+    /// startup
+    #[serde(default = "default_process_metrics_interval", with = "humantime_serde")]
+    pub collector_process_metrics_interval: Duration,
+    /// Number of quickwit output errors kept for the `/last-errors` status route
+    #[serde(default = "default_last_errors_capacity")]
+    pub collector_last_errors_capacity: usize,
+    /// `/quickwit/metrics` status route proxying the quickwit metrics, disable it if
+    /// quickwit is scraped directly (not hot reloaded)
+    #[serde(default = "default_true")]
+    pub collector_quickwit_metrics_route: bool,
+    /// Difference of log lines count tolerated between a delivery window reported by a
+    /// shipper (`grpc_out.delivery_verification`) and the log lines received from it
+    #[serde(default = "default_delivery_verification_tolerance")]
+    pub collector_delivery_verification_tolerance: u64,
+    /// Nested objects of free fields flattened into keys joined by a separator
+    #[serde(default)]
+    #[config_doc(nested)]
+    pub collector_flatten_free_fields: FlattenFreeFieldsConfig,
+    /// Length cap of the free field values
+    #[serde(default)]
+    #[config_doc(nested)]
+    pub collector_free_field_max_length: FreeFieldMaxLengthConfig,
+    /// Severity rewrite rules, the first matching rule is applied
+    #[serde(default)]
+    pub collector_severity_overrides: Vec<SeverityOverride>,
+    /// Service name, severity & labels of the syslog log entries by facility (eg: `mail`),
+    /// the service name sent by the shipper is kept for the facilities without mapping
+    #[serde(default)]
+    pub collector_syslog_facility_mapping: HashMap<FacilityName, SyslogFacilityMapping>,
+    /// `Content-Type` of the ingest requests sent to quickwit
+    #[serde(default = "default_quickwit_content_type")]
+    pub collector_quickwit_content_type: String,
+    /// Quickwit indexes (`rest_url`, `index_id`) receiving a best-effort copy of every batch
+    /// (eg: migration between quickwit clusters), see config-sample.yaml (not hot reloaded)
+    #[serde(default)]
+    pub collector_quickwit_mirrors: Vec<QuickwitMirrorConfig>,
+    /// All-in-one deployment: GELF TCP input started if set, same options as the
+    /// shipper `gelf_in` section (not hot reloaded: `max_buffer_size`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[config_doc(nested)]
+    pub gelf_in: Option<GelfInputConfig>,
+    /// Bind address of the `gelf_in` input (not hot reloaded)
+    #[serde(default = "default_gelf_in_bind_address")]
+    pub collector_gelf_in_bind_address: BindAddr,
+    /// All-in-one deployment: syslog UDP input started if set, same options as the
+    /// shipper `syslog_in` section (not hot reloaded: `max_buffer_size`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[config_doc(nested)]
+    pub syslog_in: Option<SyslogInputConfig>,
+    /// Bind address of the `syslog_in` input (not hot reloaded)
+    #[serde(default = "default_syslog_in_bind_address")]
+    pub collector_syslog_in_bind_address: BindAddr,
+    /// Number of parsed GELF & generic log `extra` fields kept in cache (services often send
+    /// the same static fields on every line), 0 disables the cache
+    #[serde(default = "default_extra_cache_size")]
+    pub collector_extra_cache_size: usize,
+    /// Signatures of received log lines (shipper `sign_log_entries`) are checked, unsigned
+    /// log lines & log lines of hosts without key are rejected
+    #[serde(default)]
+    pub collector_verify_log_signatures: bool,
+    /// Signing key of each shipper by hostname (before the shipper hostname mappings), as
+    /// printed by `rlog-helper cert signing-key`
+    #[serde(default)]
+    pub collector_log_signature_keys: HashMap<String, LogSignatureKey>,
+    /// Rewrite & cardinality limit of the hostname label of the shipper metrics
+    /// (not hot reloaded)
+    #[serde(default)]
+    #[config_doc(nested)]
+    pub collector_metrics_sanitizer: MetricsSanitizerConfig,
+    /// Name keying the shipper metrics & the connected shippers: report (reported
+    /// hostname), certificate (common name of the shipper TLS certificate) or both
+    /// (certificate, mismatches counted in `rlog_collector_hostname_mismatch_total`)
+    #[serde(default)]
+    pub collector_metrics_identity: MetricsIdentity,
+}
+
+/// Source of the name of a shipper reporting metrics
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsIdentity {
+    /// the hostname reported in the metrics, set by the shipper itself
+    #[default]
+    Report,
+    /// the common name of the client certificate, the reported hostname if the shipper is
+    /// not authenticated (plain text gRPC)
+    Certificate,
+    /// the common name of the client certificate, mismatches with the reported hostname are
+    /// counted in `rlog_collector_hostname_mismatch_total`
+    Both,
+}
+
+/// Base64 encoded log signing key
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LogSignatureKey {
+    encoded: String,
+    pub key: hmac::Key,
+}
+
+impl TryFrom<String> for LogSignatureKey {
+    type Error = anyhow::Error;
+
+    fn try_from(encoded: String) -> Result<Self, Self::Error> {
+        Ok(Self {
+            key: parse_signing_key(&encoded)?,
+            encoded,
+        })
+    }
+}
+
+impl From<LogSignatureKey> for String {
+    fn from(value: LogSignatureKey) -> Self {
+        value.encoded
+    }
+}
+
+fn default_debug_sample_rate() -> u64 {
+    1
+}
+
+fn default_batch_send_parallelism() -> usize {
+    1
+}
+
+fn default_shutdown_flush_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_process_metrics_interval() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_last_errors_capacity() -> usize {
+    20
+}
+
+fn default_delivery_verification_tolerance() -> u64 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_quickwit_content_type() -> String {
+    "application/json".into()
+}
+
+fn default_extra_cache_size() -> usize {
+    1024
+}
+
+fn default_gelf_in_bind_address() -> BindAddr {
+    SocketAddr::from(([127, 0, 0, 1], 12201)).into()
+}
+
+fn default_syslog_in_bind_address() -> BindAddr {
+    SocketAddr::from(([127, 0, 0, 1], 21054)).into()
+}
+
+#[derive(Serialize, Deserialize, ConfigDoc, Clone, Debug, PartialEq, Eq)]
+pub struct FlattenFreeFieldsConfig {
+    /// Flattening is disabled by default
+    #[serde(default)]
+    pub enabled: bool,
+    /// Separator of the flattened keys
+    #[serde(default = "default_flatten_separator")]
+    pub separator: String,
+    /// Objects nested deeper are kept as JSON values
+    #[serde(default = "default_flatten_max_depth")]
+    pub max_depth: usize,
+    /// Arrays are also flattened, using the element index as key
+    #[serde(default)]
+    pub arrays: bool,
+    /// A field is kept as is if flattening it would raise the number of free fields
+    /// of the log entry above this limit
+    #[serde(default = "default_flatten_max_keys")]
+    pub max_keys: usize,
+}
+
+fn default_flatten_separator() -> String {
+    ".".into()
+}
+
+fn default_flatten_max_depth() -> usize {
+    3
+}
+
+fn default_flatten_max_keys() -> usize {
+    100
+}
+
+impl Default for FlattenFreeFieldsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            separator: default_flatten_separator(),
+            max_depth: default_flatten_max_depth(),
+            arrays: false,
+            max_keys: default_flatten_max_keys(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ConfigDoc, Clone, Debug)]
+pub struct FreeFieldMaxLengthConfig {
+    /// Maximum length (in bytes) of the free field values, 0 means no limit
+    #[serde(default)]
+    pub max_length: usize,
+    /// Maximum length of some fields by name (after flattening), 0 means no limit
+    #[serde(default)]
+    pub fields: HashMap<String, usize>,
+    /// Appended to the truncated string values, counted in the maximum length
+    #[serde(default = "default_truncation_marker")]
+    pub marker: String,
+    /// Objects & arrays longer than the limit once serialized: stringify or drop
+    #[serde(default)]
+    pub nested: NestedFieldTruncation,
+}
+
+fn default_truncation_marker() -> String {
+    "...[truncated]".into()
+}
+
+impl Default for FreeFieldMaxLengthConfig {
+    fn default() -> Self {
+        Self {
+            max_length: 0,
+            fields: HashMap::new(),
+            marker: default_truncation_marker(),
+            nested: NestedFieldTruncation::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NestedFieldTruncation {
+    /// serialized to JSON and truncated like string values
+    #[default]
+    Stringify,
+    /// removed from the log entry
+    Drop,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SeverityOverride {
+    /// label of the rule in the `rlog_collector_severity_override_count` metric, the index
+    /// of the rule if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "match", default)]
+    pub matcher: SeverityOverrideMatch,
+    #[serde(flatten)]
+    pub action: SeverityOverrideAction,
+}
+
+/// All the given patterns must match, a rule without pattern matches every log entry
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SeverityOverrideMatch {
+    #[serde(default, with = "serde_regex", skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<Regex>,
+    #[serde(default, with = "serde_regex", skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<Regex>,
+    #[serde(default, with = "serde_regex", skip_serializing_if = "Option::is_none")]
+    pub message: Option<Regex>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeverityOverrideAction {
+    /// more severe log entries are downgraded to this severity
+    SetMaxSeverity(Severity),
+    SetSeverity(Severity),
+    /// number of syslog levels added to the severity (eg: -1 turns errors into warnings),
+    /// capped to emergency & debug
+    ShiftSeverity(i8),
+}
+
+/// Syslog facility name (eg: `mail`, `local0`)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct FacilityName(pub SyslogFacility);
+
+impl TryFrom<String> for FacilityName {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SyslogFacility::from_str_name(&value.to_ascii_lowercase())
+            .map(Self)
+            .ok_or_else(|| anyhow::anyhow!("Unknown syslog facility {value}"))
+    }
+}
+
+impl From<FacilityName> for String {
+    fn from(value: FacilityName) -> Self {
+        value.0.as_str_name().into()
+    }
+}
+
+/// Applied to the syslog log entries of a facility, before the severity overrides
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyslogFacilityMapping {
+    /// replaces the service name sent by the shipper (the application name by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// free fields added to the log entries
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+}
+
+/// Syslog severity names
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl From<Severity> for SyslogSeverity {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Emergency => Self::Emergency,
+            Severity::Alert => Self::Alert,
+            Severity::Critical => Self::Critical,
+            Severity::Error => Self::Error,
+            Severity::Warning => Self::Warning,
+            Severity::Notice => Self::Notice,
+            Severity::Info => Self::Info,
+            Severity::Debug => Self::Debug,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuickwitApiVersion {
+    /// probe quickwit version at startup and periodically, fallback to v1
+    #[default]
+    Auto,
+    /// legacy `ingest` endpoint
+    V1,
+    /// `ingest-v2` endpoint (quickwit 0.7+) with per document failures
+    V2,
+}
+
+/// Quickwit ingest `commit` parameter
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickwitCommitMode {
+    /// parameter not sent, documents are committed on the index commit timeout
+    #[default]
+    Auto,
+    /// the response is sent once the documents are committed
+    WaitFor,
+    /// the documents are committed immediately, the response is sent once committed
+    Force,
+}
+
+impl QuickwitCommitMode {
+    /// Value of the `commit` parameter, `None` for the quickwit default
+    pub fn parameter(self) -> Option<&'static str> {
+        match self {
+            QuickwitCommitMode::Auto => None,
+            QuickwitCommitMode::WaitFor => Some("wait_for"),
+            QuickwitCommitMode::Force => Some("force"),
+        }
+    }
+}
+
+/// Quickwit index receiving a copy of the batches sent to the primary quickwit, its failures
+/// never block the primary quickwit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QuickwitMirrorConfig {
+    /// `destination` label of the output metrics, the index id if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub rest_url: String,
+    pub index_id: String,
+    /// A batch is dropped after this number of failed retries
+    #[serde(default = "default_mirror_max_retries")]
+    pub max_retries: u32,
+    /// Batches waiting to be sent to the mirror, the next batches are dropped when full
+    #[serde(default = "default_mirror_queue_size")]
+    pub queue_size: usize,
+}
+
+impl QuickwitMirrorConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.index_id)
+    }
+}
+
+fn default_mirror_max_retries() -> u32 {
+    3
+}
+
+fn default_mirror_queue_size() -> usize {
+    100
+}
+
+/// Unit of the document timestamps from EPOCH, detected by quickwit (`unix_timestamp` input
+/// format)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampPrecision {
+    #[default]
+    Milliseconds,
+    /// preserve the order of high frequency logs
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampPrecision {
+    fn units_per_second(self) -> i64 {
+        match self {
+            TimestampPrecision::Milliseconds => 1_000,
+            TimestampPrecision::Microseconds => 1_000_000,
+            TimestampPrecision::Nanoseconds => 1_000_000_000,
+        }
+    }
+
+    /// Timestamp from EPOCH in this unit, sub-unit precision is truncated
+    pub fn from_timestamp(self, timestamp: &Timestamp) -> u64 {
+        let nanos_per_unit = 1_000_000_000 / self.units_per_second();
+        (timestamp.seconds * self.units_per_second() + timestamp.nanos as i64 / nanos_per_unit)
+            as u64
+    }
+
+    /// Milliseconds from EPOCH of a timestamp in this unit
+    pub fn to_millis(self, timestamp: u64) -> u64 {
+        timestamp / (self.units_per_second() as u64 / 1_000)
+    }
 }
 
 impl Default for Config {
@@ -29,6 +526,51 @@ impl Default for Config {
             collector_quickwit_output_buffer_size: 1000,
             collector_quickwit_batch_size: 100,
             collector_quickwit_batch_max_interval: Duration::from_secs(1),
+            collector_batch_send_parallelism: default_batch_send_parallelism(),
+            collector_indexed_fields: Vec::new(),
+            collector_quickwit_api_version: QuickwitApiVersion::Auto,
+            collector_quickwit_commit: QuickwitCommitMode::Auto,
+            collector_timestamp_precision: TimestampPrecision::default(),
+            collector_emit_doc_id: false,
+            collector_max_log_age: None,
+            collector_debug_sample_rate: default_debug_sample_rate(),
+            collector_shutdown_flush_timeout: default_shutdown_flush_timeout(),
+            collector_process_metrics_interval: default_process_metrics_interval(),
+            collector_last_errors_capacity: default_last_errors_capacity(),
+            collector_quickwit_metrics_route: true,
+            collector_delivery_verification_tolerance: default_delivery_verification_tolerance(),
+            collector_flatten_free_fields: FlattenFreeFieldsConfig::default(),
+            collector_free_field_max_length: FreeFieldMaxLengthConfig::default(),
+            collector_severity_overrides: Vec::new(),
+            collector_syslog_facility_mapping: HashMap::new(),
+            collector_quickwit_content_type: default_quickwit_content_type(),
+            collector_quickwit_mirrors: Vec::new(),
+            gelf_in: None,
+            collector_gelf_in_bind_address: default_gelf_in_bind_address(),
+            syslog_in: None,
+            collector_syslog_in_bind_address: default_syslog_in_bind_address(),
+            collector_extra_cache_size: default_extra_cache_size(),
+            collector_verify_log_signatures: false,
+            collector_log_signature_keys: HashMap::new(),
+            collector_metrics_sanitizer: MetricsSanitizerConfig::default(),
+            collector_metrics_identity: MetricsIdentity::Report,
         }
     }
 }
+
+impl Config {
+    /// Default configuration with the all-in-one inputs set to their defaults
+    pub fn template() -> Self {
+        Self {
+            gelf_in: Some(Default::default()),
+            syslog_in: Some(Default::default()),
+            ..Default::default()
+        }
+    }
+
+    /// YAML of [Config::template], each key commented (`--generate-config`), see
+    /// `config-sample.yaml` for the options without default value
+    pub fn template_yaml() -> anyhow::Result<String> {
+        yaml_template(&Self::template())
+    }
+}
@@ -0,0 +1,233 @@
+//! Delivery verification (`grpc_out.delivery_verification` of the shippers): the log lines
+//! received from a shipper process between 2 of its metrics reports are compared to the
+//! delivery window of the report.
+//!
+//! A shipper sends again a window whose report failed, extended with the next log lines:
+//! the report may have been received anyway, the window is then compared to the log lines
+//! received since the closing of the previous window.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use rlog_common::delivery_window::WindowDigest;
+use rlog_grpc::rlog_service_protocol::DeliveryWindow;
+
+/// Windows of the shipper processes which neither sent log lines nor reported metrics for
+/// this duration are forgotten
+const WINDOWS_RETENTION: Duration = Duration::from_secs(3600);
+
+lazy_static! {
+    pub static ref DELIVERY_WINDOWS: Mutex<DeliveryWindows> =
+        Mutex::new(DeliveryWindows::default());
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Verification {
+    /// first window of the shipper process seen by this collector, or windows lost
+    Unverified,
+    Match,
+    /// count within the tolerance, the hash can not be compared
+    Tolerated,
+    Mismatch,
+}
+
+impl Verification {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verification::Unverified => "unverified",
+            Verification::Match => "match",
+            Verification::Tolerated => "tolerated",
+            Verification::Mismatch => "mismatch",
+        }
+    }
+}
+
+/// Log lines received from a shipper process
+struct ReceivedWindow {
+    /// number of the last reported window
+    last_number: Option<u64>,
+    /// since the closing of the last window
+    current: WindowDigest,
+    /// since the closing of the window before the last one: a window sent again
+    extended: WindowDigest,
+    last_seen: Instant,
+}
+
+#[derive(Default)]
+pub struct DeliveryWindows {
+    /// by incarnation id
+    windows: HashMap<String, ReceivedWindow>,
+}
+
+impl DeliveryWindows {
+    fn window(&mut self, incarnation_id: &str, now: Instant) -> &mut ReceivedWindow {
+        if !self.windows.contains_key(incarnation_id) {
+            self.windows.retain(|_, window| {
+                now.saturating_duration_since(window.last_seen) < WINDOWS_RETENTION
+            });
+        }
+        let window = self
+            .windows
+            .entry(incarnation_id.to_string())
+            .or_insert_with(|| ReceivedWindow {
+                last_number: None,
+                current: WindowDigest::default(),
+                extended: WindowDigest::default(),
+                last_seen: now,
+            });
+        window.last_seen = now;
+        window
+    }
+
+    /// A log line accepted from the shipper process
+    pub fn received(&mut self, incarnation_id: &str, log_line_hash: u64, now: Instant) {
+        let window = self.window(incarnation_id, now);
+        window.current.add(log_line_hash);
+        window.extended.add(log_line_hash);
+    }
+
+    /// Compare the reported window to the received log lines & close it, the counts may
+    /// differ by `tolerance` log lines: a log line timed out on the shipper side may have
+    /// been received, then sent again.
+    pub fn report(
+        &mut self,
+        incarnation_id: &str,
+        reported: &DeliveryWindow,
+        tolerance: u64,
+        now: Instant,
+    ) -> (Verification, u64) {
+        let window = self.window(incarnation_id, now);
+        let current = std::mem::take(&mut window.current);
+        let (verification, received) = match window.last_number {
+            Some(last_number) if reported.number == last_number + 1 => {
+                let verification = compare(reported, &current, tolerance);
+                window.extended = current.clone();
+                (verification, current.count())
+            }
+            // the previous report has been received, its response was lost
+            Some(last_number) if reported.number == last_number => (
+                compare(reported, &window.extended, tolerance),
+                window.extended.count(),
+            ),
+            _ => {
+                window.extended = current.clone();
+                (Verification::Unverified, current.count())
+            }
+        };
+        window.last_number = Some(reported.number);
+        (verification, received)
+    }
+}
+
+fn compare(reported: &DeliveryWindow, received: &WindowDigest, tolerance: u64) -> Verification {
+    if reported.count == received.count() {
+        if reported.hash == received.hash() {
+            Verification::Match
+        } else {
+            Verification::Mismatch
+        }
+    } else if reported.count.abs_diff(received.count()) <= tolerance {
+        Verification::Tolerated
+    } else {
+        Verification::Mismatch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use rlog_common::delivery_window::WindowDigest;
+    use rlog_grpc::rlog_service_protocol::DeliveryWindow;
+
+    use super::{DeliveryWindows, Verification};
+
+    /// Window of the log lines hashed 0..count
+    fn window(number: u64, hashes: std::ops::Range<u64>) -> DeliveryWindow {
+        let mut digest = WindowDigest::default();
+        for hash in hashes {
+            digest.add(hash);
+        }
+        DeliveryWindow {
+            number,
+            count: digest.count(),
+            hash: digest.hash(),
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let now = Instant::now();
+        let mut windows = DeliveryWindows::default();
+        let receive = |windows: &mut DeliveryWindows, hashes: std::ops::Range<u64>| {
+            for hash in hashes {
+                windows.received("shipper", hash, now);
+            }
+        };
+
+        // lines received before the first report may come from another collector
+        receive(&mut windows, 0..5);
+        assert_eq!(
+            (Verification::Unverified, 5),
+            windows.report("shipper", &window(3, 0..10), 1, now)
+        );
+
+        receive(&mut windows, 10..20);
+        assert_eq!(
+            (Verification::Match, 10),
+            windows.report("shipper", &window(4, 10..20), 1, now)
+        );
+        // same count, other content
+        receive(&mut windows, 20..30);
+        assert_eq!(
+            Verification::Mismatch,
+            windows.report("shipper", &window(5, 21..31), 1, now).0
+        );
+        // a log line sent again after a timeout
+        receive(&mut windows, 30..41);
+        assert_eq!(
+            Verification::Tolerated,
+            windows.report("shipper", &window(6, 30..40), 1, now).0
+        );
+        // lost log lines
+        receive(&mut windows, 40..45);
+        assert_eq!(
+            (Verification::Mismatch, 5),
+            windows.report("shipper", &window(7, 40..50), 1, now)
+        );
+
+        // the report of window 8 has been received but the shipper considers it failed,
+        // the window is sent again with the next log lines
+        receive(&mut windows, 50..60);
+        assert_eq!(
+            Verification::Match,
+            windows.report("shipper", &window(8, 50..60), 1, now).0
+        );
+        receive(&mut windows, 60..70);
+        assert_eq!(
+            (Verification::Match, 20),
+            windows.report("shipper", &window(8, 50..70), 1, now)
+        );
+        receive(&mut windows, 70..80);
+        assert_eq!(
+            Verification::Match,
+            windows.report("shipper", &window(9, 70..80), 1, now).0
+        );
+
+        // lost reports
+        receive(&mut windows, 80..90);
+        assert_eq!(
+            Verification::Unverified,
+            windows.report("shipper", &window(12, 80..90), 1, now).0
+        );
+
+        // other shipper processes are verified separately, stale ones are forgotten
+        let later = now + Duration::from_secs(7200);
+        windows.received("other", 0, later);
+        assert!(!windows.windows.contains_key("shipper"));
+    }
+}
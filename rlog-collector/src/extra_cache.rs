@@ -0,0 +1,135 @@
+use std::{collections::HashMap, num::NonZeroUsize, sync::Mutex};
+
+use anyhow::Context;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use serde_json::Value;
+
+use crate::config::CONFIG;
+use crate::metrics::{COLLECTOR_EXTRA_CACHE_HIT_COUNT, COLLECTOR_EXTRA_CACHE_MISS_COUNT};
+
+/// Larger `extra` fields are always parsed: many unique big payloads would blow the memory
+const MAX_CACHED_EXTRA_SIZE: usize = 4096;
+
+lazy_static! {
+    static ref EXTRA_CACHE: ExtraCache = ExtraCache::default();
+}
+
+/// Parse the `extra` JSON object of GELF & generic log lines, services often send the same
+/// static fields on every line: parsed objects are cached, see [`ExtraCache`]
+pub fn parse_extra(extra: &str) -> anyhow::Result<HashMap<String, Value>> {
+    // nothing to parse nor to clone from the cache
+    if extra == "{}" {
+        return Ok(HashMap::new());
+    }
+    EXTRA_CACHE.parse(extra, CONFIG.load().collector_extra_cache_size)
+}
+
+/// LRU cache of parsed `extra` fields, keyed by the JSON string. Hits are cloned: callers
+/// own the returned map and may mutate it.
+#[derive(Default)]
+pub struct ExtraCache {
+    entries: Mutex<Option<LruCache<String, HashMap<String, Value>>>>,
+}
+
+impl ExtraCache {
+    /// `capacity` (number of entries) is read on each call to follow configuration
+    /// reloads, 0 disables the cache.
+    pub fn parse(&self, extra: &str, capacity: usize) -> anyhow::Result<HashMap<String, Value>> {
+        let Some(capacity) = NonZeroUsize::new(capacity) else {
+            return parse(extra);
+        };
+        if extra.len() > MAX_CACHED_EXTRA_SIZE {
+            return parse(extra);
+        }
+        if let Some(fields) = self
+            .entries
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|entries| entries.get(extra))
+        {
+            COLLECTOR_EXTRA_CACHE_HIT_COUNT.inc();
+            return Ok(fields.clone());
+        }
+        COLLECTOR_EXTRA_CACHE_MISS_COUNT.inc();
+        // parsed outside of the lock
+        let fields = parse(extra)?;
+        let mut entries = self.entries.lock().unwrap();
+        let entries = entries.get_or_insert_with(|| LruCache::new(capacity));
+        if entries.cap() != capacity {
+            entries.resize(capacity);
+        }
+        entries.put(extra.to_string(), fields.clone());
+        Ok(fields)
+    }
+}
+
+fn parse(extra: &str) -> anyhow::Result<HashMap<String, Value>> {
+    serde_json::from_str(extra).context("`extra` field is not a valid json object")
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{ExtraCache, MAX_CACHED_EXTRA_SIZE};
+
+    #[test]
+    fn test_cache_hits_are_independent() {
+        let cache = ExtraCache::default();
+        let extra = r#"{"service": "my_app", "env": "prod"}"#;
+
+        let mut first = cache.parse(extra, 10).unwrap();
+        first.remove("service");
+        first.insert("env".into(), json!("mutated"));
+
+        for _ in 0..2 {
+            let hit = cache.parse(extra, 10).unwrap();
+            assert_eq!(Some(&json!("my_app")), hit.get("service"));
+            assert_eq!(Some(&json!("prod")), hit.get("env"));
+        }
+        assert_eq!(1, cache.entries.lock().unwrap().as_ref().unwrap().len());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let cache = ExtraCache::default();
+        let len = || {
+            cache
+                .entries
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|entries| entries.len())
+        };
+
+        // disabled
+        assert!(cache.parse(r#"{"a": 1}"#, 0).is_ok());
+        assert_eq!(None, len());
+
+        for i in 0..5 {
+            cache.parse(&format!(r#"{{"i": {i}}}"#), 3).unwrap();
+        }
+        assert_eq!(Some(3), len());
+        // configuration reloaded
+        cache.parse(r#"{"i": 5}"#, 2).unwrap();
+        assert_eq!(Some(2), len());
+
+        // too large to be cached
+        let large = format!(r#"{{"a": "{}"}}"#, "a".repeat(MAX_CACHED_EXTRA_SIZE));
+        assert!(cache.parse(&large, 2).is_ok());
+        assert!(!cache
+            .entries
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .contains(&large));
+
+        // errors are not cached
+        assert!(cache.parse("not json", 2).is_err());
+        assert!(cache.parse("[1, 2]", 2).is_err());
+        assert_eq!(Some(2), len());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::config::FlattenFreeFieldsConfig;
+
+/// Flatten nested objects (and arrays if configured) of `fields` into keys joined by the
+/// configured separator, eg: `{"context": {"user": {"id": 1}}}` -> `{"context.user.id": 1}`.
+///
+/// A field is kept as is if one of its flattened keys already exists or if flattening it
+/// would raise the number of fields above `max_keys`. Scalars are untouched.
+pub(crate) fn flatten_fields(
+    fields: &mut HashMap<String, Value>,
+    config: &FlattenFreeFieldsConfig,
+) {
+    let mut nested = fields
+        .iter()
+        .filter(|(_, value)| is_flattenable(value, config))
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    // deterministic outcome when max_keys is reached
+    nested.sort();
+
+    for key in nested {
+        let Some(value) = fields.remove(&key) else {
+            continue;
+        };
+        let mut flattened = Vec::new();
+        flatten_value(key.clone(), value.clone(), 1, config, &mut flattened);
+        if fields.len() + flattened.len() > config.max_keys
+            || flattened.iter().any(|(key, _)| fields.contains_key(key))
+        {
+            fields.insert(key, value);
+        } else {
+            fields.extend(flattened);
+        }
+    }
+}
+
+fn is_flattenable(value: &Value, config: &FlattenFreeFieldsConfig) -> bool {
+    match value {
+        // empty containers would vanish
+        Value::Object(object) => !object.is_empty(),
+        Value::Array(array) => config.arrays && !array.is_empty(),
+        _ => false,
+    }
+}
+
+fn flatten_value(
+    prefix: String,
+    value: Value,
+    depth: usize,
+    config: &FlattenFreeFieldsConfig,
+    flattened: &mut Vec<(String, Value)>,
+) {
+    if depth > config.max_depth || !is_flattenable(&value, config) {
+        flattened.push((prefix, value));
+        return;
+    }
+    let children: Vec<(String, Value)> = match value {
+        Value::Object(object) => object.into_iter().collect(),
+        Value::Array(array) => array
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index.to_string(), value))
+            .collect(),
+        _ => unreachable!("only objects and arrays are flattenable"),
+    };
+    for (key, value) in children {
+        flatten_value(
+            format!("{prefix}{}{key}", config.separator),
+            value,
+            depth + 1,
+            config,
+            flattened,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use super::flatten_fields;
+    use crate::config::FlattenFreeFieldsConfig;
+
+    fn flatten(fields: Value, config: &FlattenFreeFieldsConfig) -> Value {
+        let mut fields: HashMap<String, Value> = serde_json::from_value(fields).unwrap();
+        flatten_fields(&mut fields, config);
+        serde_json::to_value(fields).unwrap()
+    }
+
+    #[test]
+    fn test_nested_objects() {
+        let config = FlattenFreeFieldsConfig::default();
+        assert_eq!(
+            json!({
+                "request_id": "abc",
+                "context.user.id": 1,
+                "context.user.name": "bob",
+                "context.path": "/",
+                "empty": {},
+                "tags": ["a", "b"],
+            }),
+            flatten(
+                json!({
+                    "request_id": "abc",
+                    "context": {"user": {"id": 1, "name": "bob"}, "path": "/"},
+                    "empty": {},
+                    "tags": ["a", "b"],
+                }),
+                &config
+            )
+        );
+    }
+
+    #[test]
+    fn test_arrays() {
+        let config = FlattenFreeFieldsConfig {
+            arrays: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            json!({"tags.0": "a", "tags.1": "b", "users.0.id": 1, "empty": []}),
+            flatten(
+                json!({"tags": ["a", "b"], "users": [{"id": 1}], "empty": []}),
+                &config
+            )
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let config = FlattenFreeFieldsConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            json!({"a.b.c": {"d": 1}, "x.y": 2}),
+            flatten(json!({"a": {"b": {"c": {"d": 1}}}, "x": {"y": 2}}), &config)
+        );
+    }
+
+    #[test]
+    fn test_separator_and_limits() {
+        let config = FlattenFreeFieldsConfig {
+            separator: "_".into(),
+            max_keys: 4,
+            ..Default::default()
+        };
+        // user_id collides with an existing key, c would raise the number of keys to 5
+        assert_eq!(
+            json!({
+                "a_b": 1,
+                "user": {"id": 2},
+                "user_id": 3,
+                "c": {"d": 4, "e": 5},
+            }),
+            flatten(
+                json!({"a": {"b": 1}, "user": {"id": 2}, "user_id": 3, "c": {"d": 4, "e": 5}}),
+                &config
+            )
+        );
+    }
+}
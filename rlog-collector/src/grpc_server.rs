@@ -1,17 +1,55 @@
 use async_channel::Sender;
-use rlog_common::utils::format_error;
+use lazy_static::lazy_static;
+use prometheus::IntCounter;
+use rlog_common::{
+    delivery_window::{log_line_hash, INCARNATION_METADATA},
+    log_signature,
+    utils::format_error,
+};
 use rlog_grpc::{
-    rlog_service_protocol::{LogLine, Metrics},
+    prost_wkt_types::Timestamp,
+    rlog_service_protocol::{
+        log_line::Line, DeliveryWindow, LogLine, Metrics, PingRequest, PingResponse,
+    },
     tonic::{self, async_trait, Status},
 };
-use tracing::instrument;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{instrument, Level, Span};
 
 use crate::{
-    http_status_server::report_connected_host,
-    index::IndexLogEntry,
-    metrics::{SHIPPER_ERROR_COUNT, SHIPPER_PROCESSED_COUNT, SHIPPER_QUEUE_COUNT},
+    config::CONFIG,
+    delivery_windows::{Verification, DELIVERY_WINDOWS},
+    http_status_server::{report_connected_host, report_log_peer},
+    index::{is_too_old, now_ms, IndexLogEntry},
+    metrics::{
+        COLLECTOR_DELIVERY_WINDOW_COUNT, COLLECTOR_TOO_OLD_COUNT, METRICS_SANITIZER,
+        SHIPPER_ERROR_COUNT, SHIPPER_PROCESSED_COUNT, SHIPPER_QUEUE_CAPACITY, SHIPPER_QUEUE_COUNT,
+        SHIPPER_RESTARTS,
+    },
+    shipper_identity::{certificate_name, metrics_key},
+    shipper_incarnations::{Incarnation, SHIPPER_INCARNATIONS},
 };
 
+/// Counts of the hosts which did not report metrics for this duration are forgotten
+const SHARED_COUNTS_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Last counts reported by a shipper by (counter, queue) & time of the report
+type ReportedCounts = (Instant, HashMap<(&'static str, String), u64>);
+
+lazy_static! {
+    /// Counts of the shippers whose hostname label is shared with other shippers (rewritten
+    /// by the metrics sanitizer), by hostname
+    static ref SHARED_LABEL_COUNTS: Mutex<HashMap<String, ReportedCounts>> =
+        Mutex::new(HashMap::new());
+}
+
 pub struct LogCollectorServer {
     /// each IndexLogEntry will be sent here
     sender: Sender<IndexLogEntry>,
@@ -22,28 +60,248 @@ impl LogCollectorServer {
         Self { sender }
     }
 }
+/// Fields of the request span which can be extracted without conversion
+fn span_fields(log_line: &LogLine) -> (String, Option<String>) {
+    match &log_line.line {
+        Some(Line::Gelf(_)) => ("gelf".into(), None),
+        Some(Line::Syslog(syslog)) => (
+            "syslog".into(),
+            syslog.service_name.clone().or(syslog.appname.clone()),
+        ),
+        Some(Line::GenericLog(generic)) => (
+            generic.log_system.clone(),
+            Some(generic.service_name.clone()),
+        ),
+        None => ("unknown".into(), None),
+    }
+}
+
+/// Bring a shipper counter to the `count` reported by the given shipper incarnation
+fn sync_counter(counter: &IntCounter, count: u64, incarnation: Incarnation) {
+    let current = counter.get();
+    match incarnation {
+        Incarnation::Restarted => {
+            counter.reset();
+            counter.inc_by(count);
+        }
+        // out of order report of the same process
+        Incarnation::Current if count < current => {}
+        // a lower count without start time is a restart
+        _ if count < current => {
+            counter.reset();
+            counter.inc_by(count);
+        }
+        _ => counter.inc_by(count - current),
+    }
+}
+
+/// Add to a counter shared by several shippers the progress of the `count` reported by the
+/// given shipper since its previous report
+fn add_to_shared_counter(
+    counter: &IntCounter,
+    hostname: &str,
+    key: (&'static str, &str),
+    count: u64,
+    incarnation: Incarnation,
+) {
+    let now = Instant::now();
+    let mut shared_counts = SHARED_LABEL_COUNTS.lock().unwrap();
+    if !shared_counts.contains_key(hostname) {
+        shared_counts.retain(|_, (last_report, _)| {
+            now.saturating_duration_since(*last_report) < SHARED_COUNTS_RETENTION
+        });
+    }
+    let (last_report, counts) = shared_counts
+        .entry(hostname.to_string())
+        .or_insert_with(|| (now, HashMap::new()));
+    *last_report = now;
+    let previous = counts.entry((key.0, key.1.to_string())).or_insert(0);
+    let increment = match incarnation {
+        Incarnation::Restarted => count,
+        // out of order report of the same process
+        Incarnation::Current if count < *previous => return,
+        // a lower count without start time is a restart
+        _ if count < *previous => count,
+        _ => count - *previous,
+    };
+    *previous = count;
+    counter.inc_by(increment);
+}
+
+/// Compare a delivery window reported by a shipper to the log lines received from it
+fn verify_delivery_window(
+    hostname: &str,
+    hostname_label: &str,
+    incarnation_id: &str,
+    window: &DeliveryWindow,
+) {
+    let tolerance = CONFIG.load().collector_delivery_verification_tolerance;
+    let (verification, received) =
+        DELIVERY_WINDOWS
+            .lock()
+            .unwrap()
+            .report(incarnation_id, window, tolerance, Instant::now());
+    match verification {
+        Verification::Mismatch => tracing::warn!(
+            hostname,
+            window = window.number,
+            sent = window.count,
+            received,
+            "Delivery window mismatch"
+        ),
+        Verification::Tolerated => tracing::debug!(
+            hostname,
+            window = window.number,
+            sent = window.count,
+            received,
+            "Delivery window count within tolerance"
+        ),
+        Verification::Match | Verification::Unverified => {}
+    }
+    COLLECTOR_DELIVERY_WINDOW_COUNT
+        .with_label_values(&[hostname_label, verification.label()])
+        .inc();
+}
+
+/// `Ok(true)` if the signature of the log line is valid, signatures are neither checked nor
+/// required if `collector_verify_log_signatures` is disabled
+fn check_signature(log_line: &mut LogLine) -> Result<bool, String> {
+    let config = CONFIG.load();
+    if !config.collector_verify_log_signatures {
+        return Ok(false);
+    }
+    let host = log_line.raw_host.as_deref().unwrap_or(&log_line.host);
+    let Some(signature_key) = config.collector_log_signature_keys.get(host) else {
+        return Err(format!("No signature key for host {host}"));
+    };
+    if log_line.hmac.is_empty() {
+        return Err("Unsigned LogLine".into());
+    }
+    if !log_signature::verify(&signature_key.key, log_line) {
+        return Err("Invalid LogLine signature".into());
+    }
+    Ok(true)
+}
+
+/// Only 1 in `rate` requests is dumped in debug logs (never if `rate` is 0), `counter`
+/// counts the requests
+fn is_debug_sampled(rate: u64, counter: &AtomicU64) -> bool {
+    match rate {
+        0 => false,
+        rate => counter.fetch_add(1, Ordering::Relaxed).is_multiple_of(rate),
+    }
+}
+
 #[async_trait]
 impl rlog_grpc::rlog_service_protocol::log_collector_server::LogCollector for LogCollectorServer {
-    #[instrument(skip(self, request))]
+    #[instrument(skip(self, request), fields(host, log_system, service))]
     async fn log(
         &self,
         request: tonic::Request<LogLine>,
     ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
-        let log_line = request.into_inner();
+        let peer = request.remote_addr().map(|addr| addr.ip());
+        let incarnation_id = request
+            .metadata()
+            .get(INCARNATION_METADATA)
+            .and_then(|incarnation_id| incarnation_id.to_str().ok())
+            .map(str::to_string);
+        let mut log_line = request.into_inner();
+        // hashed as received, before the signature check
+        let delivery =
+            incarnation_id.map(|incarnation_id| (incarnation_id, log_line_hash(&mut log_line)));
+        let delivered = || {
+            if let Some((incarnation_id, log_line_hash)) = &delivery {
+                DELIVERY_WINDOWS.lock().unwrap().received(
+                    incarnation_id,
+                    *log_line_hash,
+                    Instant::now(),
+                );
+            }
+        };
 
-        tracing::debug!("Received {log_line:#?}");
+        let span = Span::current();
+        let host = log_line.host.clone();
+        let (log_system, service) = span_fields(&log_line);
+        span.record("host", host.as_str());
+        span.record("log_system", log_system);
+        if let Some(service) = service.as_deref() {
+            span.record("service", service);
+        }
 
-        let log_entry = IndexLogEntry::try_from(log_line)
-            // Reject the request if the received LogLine is invalid
-            .map_err(|e| {
-                Status::invalid_argument(format!("Invalid LogLine {}", format_error(e)))
-            })?;
+        static DEBUG_SAMPLE_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let sampled = tracing::enabled!(Level::DEBUG)
+            && is_debug_sampled(
+                CONFIG.load().collector_debug_sample_rate,
+                &DEBUG_SAMPLE_COUNTER,
+            );
+        if sampled {
+            tracing::debug!("Received {log_line:#?}");
+        }
 
-        tracing::debug!("Converted to {log_entry:#?}");
+        let hmac_verified = match check_signature(&mut log_line) {
+            Ok(hmac_verified) => hmac_verified,
+            Err(reason) => {
+                tracing::error!(host = %host, service = ?service, reason = %reason, "Rejected LogLine");
+                return Err(Status::unauthenticated(reason));
+            }
+        };
+
+        let mut log_entry = match IndexLogEntry::try_from(log_line) {
+            Ok(log_entry) => log_entry,
+            Err(e) => {
+                // Reject the request if the received LogLine is invalid
+                let reason = format_error(e);
+                tracing::error!(
+                    host = %host,
+                    service = ?service,
+                    reason = %reason,
+                    "Rejected invalid LogLine"
+                );
+                return Err(Status::invalid_argument(format!(
+                    "Invalid LogLine {reason}"
+                )));
+            }
+        };
+        span.record("service", log_entry.service_name.as_str());
+        log_entry.hmac_verified = hmac_verified;
+
+        if sampled {
+            tracing::debug!("Converted to {log_entry:#?}");
+        }
+
+        let config = CONFIG.load();
+        if let Some(max_age) = config.collector_max_log_age {
+            if is_too_old(
+                &log_entry,
+                now_ms(),
+                max_age,
+                config.collector_timestamp_precision,
+            ) {
+                tracing::debug!(
+                    host = %host,
+                    service = log_entry.service_name,
+                    timestamp = log_entry.timestamp,
+                    "Dropped LogLine older than collector_max_log_age"
+                );
+                COLLECTOR_TOO_OLD_COUNT
+                    .with_label_values(&[&log_entry.service_name])
+                    .inc();
+                if let Some(peer) = peer {
+                    report_log_peer(peer);
+                }
+                delivered();
+                // the shipper must not send it again
+                return Ok(tonic::Response::new(()));
+            }
+        }
 
         if let Err(_e) = self.sender.send(log_entry).await {
             Err(tonic::Status::unavailable("shutdown in progress"))
         } else {
+            if let Some(peer) = peer {
+                report_log_peer(peer);
+            }
+            delivered();
             Ok(tonic::Response::new(()))
         }
     }
@@ -52,42 +310,296 @@ impl rlog_grpc::rlog_service_protocol::log_collector_server::LogCollector for Lo
         &self,
         request: tonic::Request<Metrics>,
     ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
-        let metrics = request.into_inner();
+        let peer = request.remote_addr().map(|addr| addr.ip());
+        // the first certificate of the chain is the shipper one
+        let certificate_name = request.peer_certs().and_then(|certificates| {
+            certificates
+                .first()
+                .and_then(|certificate| certificate_name(certificate.get_ref()))
+        });
+        let mut metrics = request.into_inner();
         tracing::debug!("{metrics:#?}");
-        report_connected_host(&metrics.hostname).await;
+        metrics.hostname = metrics_key(
+            CONFIG.load().collector_metrics_identity,
+            std::mem::take(&mut metrics.hostname),
+            certificate_name,
+        );
+        let incarnation = SHIPPER_INCARNATIONS.lock().unwrap().report(
+            &metrics.hostname,
+            metrics.process_start_time.as_ref(),
+            Instant::now(),
+        );
+        let hostname = METRICS_SANITIZER.sanitize_hostname(&metrics.hostname);
+        // several shippers are reported under this label
+        let shared_label = hostname != metrics.hostname;
+        match incarnation {
+            Incarnation::Stale => {
+                tracing::debug!(
+                    hostname = metrics.hostname,
+                    "Ignored metrics of a previous shipper process"
+                );
+                return Ok(tonic::Response::new(()));
+            }
+            Incarnation::Restarted => {
+                tracing::info!(hostname = metrics.hostname, "Shipper restarted");
+                SHIPPER_RESTARTS.with_label_values(&[&hostname]).inc();
+            }
+            Incarnation::Unknown | Incarnation::Current => {}
+        }
+        report_connected_host(&metrics, peer).await;
+
+        if let Some(window) = &metrics.delivery_window {
+            verify_delivery_window(
+                &metrics.hostname,
+                &hostname,
+                &metrics.incarnation_id,
+                window,
+            );
+        }
 
         for (queue_name, count) in metrics.queue_count {
             SHIPPER_QUEUE_COUNT
-                .get_metric_with_label_values(&[&metrics.hostname, &queue_name])
+                .get_metric_with_label_values(&[&hostname, &queue_name])
                 .unwrap()
                 .set(count as i64);
         }
 
-        for (queue_name, count) in metrics.processed_count {
-            let counter = SHIPPER_PROCESSED_COUNT
-                .get_metric_with_label_values(&[&metrics.hostname, &queue_name])
-                .unwrap();
-            let current = counter.get();
-            if count > current {
-                counter.inc_by(count - current);
-            } else {
-                counter.reset();
-                counter.inc_by(count);
-            }
+        for (queue_name, capacity) in metrics.queue_capacity {
+            SHIPPER_QUEUE_CAPACITY
+                .get_metric_with_label_values(&[&hostname, &queue_name])
+                .unwrap()
+                .set(capacity as i64);
         }
-        for (queue_name, count) in metrics.error_count {
-            let counter = SHIPPER_ERROR_COUNT
-                .get_metric_with_label_values(&[&metrics.hostname, &queue_name])
-                .unwrap();
-            let current = counter.get();
-            if count > current {
-                counter.inc_by(count - current);
-            } else {
-                counter.reset();
-                counter.inc_by(count);
+
+        for (counter_name, counter_vec, counts) in [
+            (
+                "processed",
+                &*SHIPPER_PROCESSED_COUNT,
+                metrics.processed_count,
+            ),
+            ("error", &*SHIPPER_ERROR_COUNT, metrics.error_count),
+        ] {
+            for (queue_name, count) in counts {
+                let counter = counter_vec
+                    .get_metric_with_label_values(&[&hostname, &queue_name])
+                    .unwrap();
+                if shared_label {
+                    add_to_shared_counter(
+                        &counter,
+                        &metrics.hostname,
+                        (counter_name, &queue_name),
+                        count,
+                        incarnation,
+                    );
+                } else {
+                    sync_counter(&counter, count, incarnation);
+                }
             }
         }
 
         Ok(tonic::Response::new(()))
     }
+
+    #[instrument(skip(self, request))]
+    async fn ping(
+        &self,
+        request: tonic::Request<PingRequest>,
+    ) -> std::result::Result<tonic::Response<PingResponse>, tonic::Status> {
+        Ok(tonic::Response::new(PingResponse {
+            message: request.into_inner().message,
+            server_timestamp: Some(Timestamp::from(SystemTime::now())),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{atomic::AtomicU64, Arc, Mutex};
+
+    use prometheus::IntCounter;
+
+    use rlog_grpc::{
+        prost_wkt_types::Timestamp,
+        rlog_service_protocol::{
+            log_collector_server::LogCollector, log_line::Line, GenericLogLine, LogLine, Metrics,
+        },
+        tonic::Request,
+    };
+    use tracing::Level;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::{add_to_shared_counter, is_debug_sampled, LogCollectorServer};
+    use crate::{
+        metrics::{SHIPPER_PROCESSED_COUNT, SHIPPER_RESTARTS},
+        shipper_incarnations::Incarnation,
+    };
+
+    #[derive(Clone, Default)]
+    struct TestWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for TestWriter {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn log_line(host: &str, with_timestamp: bool) -> LogLine {
+        LogLine {
+            host: host.into(),
+            raw_host: None,
+            hmac: Vec::new(),
+            sequence: None,
+            labels: Default::default(),
+            timestamp: with_timestamp.then_some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+            line: Some(Line::GenericLog(GenericLogLine {
+                message: "hello".into(),
+                severity: 6,
+                service_name: "my_service".into(),
+                extra: "{}".into(),
+                log_system: "file_in".into(),
+            })),
+        }
+    }
+
+    #[test]
+    fn test_debug_sampled() {
+        let counter = AtomicU64::new(0);
+        let sampled = (0..9)
+            .map(|_| is_debug_sampled(3, &counter))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![true, false, false, true, false, false, true, false, false],
+            sampled
+        );
+        assert!((0..3).all(|_| is_debug_sampled(1, &counter)));
+        assert!((0..3).all(|_| !is_debug_sampled(0, &counter)));
+    }
+
+    #[tokio::test]
+    async fn test_debug_logs_and_rejection() {
+        let writer = TestWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(writer.clone())
+            .with_max_level(Level::DEBUG)
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // every request is dumped with the default `collector_debug_sample_rate`
+        let (sender, _receiver) = async_channel::bounded(100);
+        let server = LogCollectorServer::new(sender);
+        server
+            .log(Request::new(log_line("my_host", true)))
+            .await
+            .unwrap();
+        let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(1, output.matches("Received LogLine").count());
+        assert_eq!(1, output.matches("Converted to IndexLogEntry").count());
+        assert!(output.contains("host=\"my_host\""));
+        assert!(output.contains("service=\"my_service\""));
+
+        writer.0.lock().unwrap().clear();
+        server
+            .log(Request::new(log_line("bad_host", false)))
+            .await
+            .unwrap_err();
+        let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let error = output
+            .lines()
+            .find(|l| l.contains("ERROR"))
+            .expect("No error event");
+        assert!(error.contains("Rejected invalid LogLine"));
+        assert!(error.contains("host=bad_host"));
+        assert!(error.contains("`timestamp` field is mandatory"));
+    }
+
+    #[tokio::test]
+    async fn test_report_metrics_incarnations() {
+        let (sender, _receiver) = async_channel::bounded(1);
+        let server = LogCollectorServer::new(sender);
+        let report = |start: i64, processed: u64| {
+            server.report_metrics(Request::new(Metrics {
+                hostname: "restarting_host".into(),
+                processed_count: [("grpc_out".to_string(), processed)].into(),
+                process_start_time: Some(Timestamp {
+                    seconds: start,
+                    nanos: 0,
+                }),
+                ..Default::default()
+            }))
+        };
+        let processed = || {
+            SHIPPER_PROCESSED_COUNT
+                .with_label_values(&["restarting_host", "grpc_out"])
+                .get()
+        };
+        let restarts = || {
+            SHIPPER_RESTARTS
+                .with_label_values(&["restarting_host"])
+                .get()
+        };
+
+        report(100, 10).await.unwrap();
+        assert_eq!(10, processed());
+        report(100, 30).await.unwrap();
+        assert_eq!(30, processed());
+        // out of order report of the same process
+        report(100, 20).await.unwrap();
+        assert_eq!(30, processed());
+        assert_eq!(0, restarts());
+
+        // restart: counters start again from zero
+        report(200, 5).await.unwrap();
+        assert_eq!(5, processed());
+        assert_eq!(1, restarts());
+        // late report of the previous process
+        report(100, 40).await.unwrap();
+        assert_eq!(5, processed());
+        report(200, 8).await.unwrap();
+        assert_eq!(8, processed());
+        assert_eq!(1, restarts());
+    }
+
+    #[test]
+    fn test_shared_counter() {
+        let counter = IntCounter::new("shared", "shared").unwrap();
+        let add = |hostname, count, incarnation| {
+            add_to_shared_counter(
+                &counter,
+                hostname,
+                ("processed", "grpc_out"),
+                count,
+                incarnation,
+            )
+        };
+
+        add("api-1", 10, Incarnation::Unknown);
+        add("api-2", 5, Incarnation::Unknown);
+        assert_eq!(15, counter.get());
+        add("api-1", 12, Incarnation::Current);
+        // out of order report of the same process
+        add("api-2", 3, Incarnation::Current);
+        assert_eq!(17, counter.get());
+        // restarts never decrease the shared counter
+        add("api-2", 4, Incarnation::Restarted);
+        assert_eq!(21, counter.get());
+        add("api-1", 1, Incarnation::Unknown);
+        assert_eq!(22, counter.get());
+    }
 }
@@ -0,0 +1,91 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use async_channel::Receiver;
+use rlog_common::tls::TlsVersion;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{
+    rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+/// h2 alpn, as negotiated by tonic
+const ALPN_H2: &[u8] = b"h2";
+
+/// mTLS configuration (PEM) of the gRPC server, used instead of the tonic TLS configuration
+/// when the minimum TLS version is enforced (tonic does not expose the protocol versions)
+#[derive(Clone)]
+pub struct GrpcTlsConfig {
+    pub ca_certificate_pem: Vec<u8>,
+    pub certificate_pem: Vec<u8>,
+    pub private_key_pem: Vec<u8>,
+    pub min_tls_version: TlsVersion,
+}
+
+impl GrpcTlsConfig {
+    pub(crate) fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let mut roots = RootCertStore::empty();
+        for ca_certificate in rustls_pemfile::certs(&mut &self.ca_certificate_pem[..]) {
+            roots
+                .add(ca_certificate.context("Invalid ca certificate")?)
+                .context("Invalid ca certificate")?;
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build()?;
+        let certificates = rustls_pemfile::certs(&mut &self.certificate_pem[..])
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid certificate")?;
+        let private_key = rustls_pemfile::private_key(&mut &self.private_key_pem[..])
+            .context("Invalid private key")?
+            .ok_or_else(|| anyhow!("No private key found"))?;
+
+        let mut config =
+            ServerConfig::builder_with_protocol_versions(self.min_tls_version.protocol_versions())
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certificates, private_key)?;
+        config.alpn_protocols.push(ALPN_H2.into());
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Accept TLS connections on `addr`, handshakes are made concurrently: a slow or
+/// rejected client (eg: TLS version below the minimum) does not block the other ones.
+pub(crate) async fn tls_incoming(
+    addr: SocketAddr,
+    acceptor: TlsAcceptor,
+) -> anyhow::Result<Receiver<TlsStream<TcpStream>>> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Unable to bind gRPC server to {addr}"))?;
+    let (sender, receiver) = async_channel::bounded(16);
+    tokio::spawn(async move {
+        loop {
+            let (stream, remote_addr) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::error!("Unable to accept incoming connection! {e}");
+                    continue;
+                }
+            };
+            if sender.is_closed() {
+                return;
+            }
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                if let Err(e) = stream.set_nodelay(true) {
+                    tracing::warn!("Unable to set TCP_NODELAY on {remote_addr}: {e}");
+                }
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = sender.send(stream).await;
+                    }
+                    Err(e) => {
+                        tracing::warn!("TLS handshake with {remote_addr} failed: {e}");
+                    }
+                }
+            });
+        }
+    });
+    Ok(receiver)
+}
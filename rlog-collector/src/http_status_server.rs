@@ -1,62 +1,298 @@
 use std::{
-    collections::BTreeMap,
-    net::SocketAddr,
-    time::{Duration, Instant},
+    collections::{BTreeMap, HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Context;
+use axum::extract::{ConnectInfo, Query};
 use axum::http::StatusCode;
-use axum::{routing::get, Router};
+use axum::response::{IntoResponse, Response};
+use axum::{
+    routing::{get, post},
+    Json, Router,
+};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
 use lazy_static::lazy_static;
 use reqwest::Url;
-use tokio::sync::RwLock;
+use rlog_common::bind_addr::BindAddr;
+use rlog_grpc::rlog_service_protocol::Metrics;
+use tokio::{sync::RwLock, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use serde::{Deserialize, Serialize};
 
-use crate::metrics::generate_metrics;
+use crate::batch::Flusher;
+use crate::config::CONFIG;
+use crate::metrics::{generate_json_metrics, generate_metrics};
+use crate::output_errors::OutputErrors;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+/// `/flush` responds with a timeout if the buffered entries are not accepted by quickwit
+/// within this duration
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// On shutdown, connections to the HTTPS status server still open after this delay are
+/// closed, idle keep-alive connections would otherwise be waited for
+const TLS_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// shipper reports metrics every 30s, 90s should  be a very safe default
+const REPORT_TIMEOUT: Duration = Duration::from_secs(90);
+
 lazy_static! {
-    static ref CONNECTED_SHIPPERS: RwLock<BTreeMap<String, Instant>> = RwLock::new(BTreeMap::new());
+    static ref CONNECTED_SHIPPERS: RwLock<BTreeMap<String, ConnectedShipper>> =
+        RwLock::new(BTreeMap::new());
+    /// Last log line received from each peer address
+    static ref LOG_PEERS: Mutex<HashMap<IpAddr, LogActivity>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Copy)]
+struct LogActivity {
+    at: Instant,
+    /// seconds from EPOCH
+    timestamp: u64,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ShipperStatus {
+    /// metrics reported recently
+    Reporting,
+    /// no metrics reported recently, log lines still received from the shipper address
+    Stale,
+    /// log lines received from an address which never reported metrics
+    NeverReported,
+}
+
+/// Last metrics reported by a shipper, served by `/shippers.json`
+#[derive(Serialize, Clone)]
+struct ConnectedShipper {
+    /// peer address if the shipper never reported metrics
+    hostname: String,
+    status: ShipperStatus,
+    /// random id of the shipper process
+    #[serde(skip_serializing_if = "String::is_empty")]
+    incarnation_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peer: Option<IpAddr>,
+    #[serde(skip)]
+    last_seen: Instant,
+    /// seconds from EPOCH of the last metrics report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seen_timestamp: Option<u64>,
+    /// seconds from EPOCH of the last log line received from the peer address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_log_timestamp: Option<u64>,
+    queue_count: BTreeMap<String, u64>,
+    queue_capacity: BTreeMap<String, u64>,
+    processed_count: BTreeMap<String, u64>,
+    error_count: BTreeMap<String, u64>,
+}
+
+fn epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
-pub async fn report_connected_host(hostname: &str) {
+/// Record a log line received from `peer`, a shipper which does not report metrics anymore
+/// is kept while log lines are received from its address
+pub fn report_log_peer(peer: IpAddr) {
+    let activity = LogActivity {
+        at: Instant::now(),
+        timestamp: epoch_secs(),
+    };
+    LOG_PEERS.lock().unwrap().insert(peer, activity);
+}
+
+pub async fn report_connected_host(metrics: &Metrics, peer: Option<IpAddr>) {
+    let sorted = |map: &HashMap<String, u64>| {
+        map.iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let shipper = ConnectedShipper {
+        hostname: metrics.hostname.clone(),
+        status: ShipperStatus::Reporting,
+        incarnation_id: metrics.incarnation_id.clone(),
+        peer,
+        last_seen: Instant::now(),
+        last_seen_timestamp: Some(epoch_secs()),
+        last_log_timestamp: None,
+        queue_count: sorted(&metrics.queue_count),
+        queue_capacity: sorted(&metrics.queue_capacity),
+        processed_count: sorted(&metrics.processed_count),
+        error_count: sorted(&metrics.error_count),
+    };
     let mut shippers = CONNECTED_SHIPPERS.write().await;
-    shippers.insert(hostname.into(), Instant::now());
+    shippers.insert(metrics.hostname.clone(), shipper);
+}
+
+/// Peers which sent log lines recently
+fn active_log_peers(now: Instant) -> HashMap<IpAddr, LogActivity> {
+    let mut log_peers = LOG_PEERS.lock().unwrap();
+    log_peers.retain(|_, activity| now.duration_since(activity.at) <= REPORT_TIMEOUT);
+    log_peers.clone()
 }
 
 async fn clear_disconnected_hosts() {
+    let now = Instant::now();
+    let log_peers = active_log_peers(now);
     let mut shippers = CONNECTED_SHIPPERS.write().await;
-    let mut disconnected = Vec::new();
+    shippers.retain(|_, shipper| {
+        now.duration_since(shipper.last_seen) <= REPORT_TIMEOUT
+            || shipper
+                .peer
+                .is_some_and(|peer| log_peers.contains_key(&peer))
+    });
+}
+
+/// Known shippers with their status, then the peers which never reported metrics
+async fn shippers_status() -> Vec<ConnectedShipper> {
     let now = Instant::now();
-    for (host, last_seen) in shippers.iter() {
-        // shipper reports metrics every 30s, 90s should  be a very safe default
-        if now.duration_since(last_seen.clone()) > Duration::from_secs(90) {
-            disconnected.push(host.clone());
+    let log_peers = active_log_peers(now);
+    let shippers = CONNECTED_SHIPPERS.read().await;
+    let mut reporting_peers = HashSet::new();
+    let mut ret = Vec::with_capacity(shippers.len());
+    for shipper in shippers.values() {
+        let mut shipper = shipper.clone();
+        if now.duration_since(shipper.last_seen) > REPORT_TIMEOUT {
+            shipper.status = ShipperStatus::Stale;
+        }
+        if let Some(peer) = shipper.peer {
+            reporting_peers.insert(peer);
+            shipper.last_log_timestamp = log_peers.get(&peer).map(|activity| activity.timestamp);
         }
+        ret.push(shipper);
+    }
+    let mut never_reported = log_peers
+        .into_iter()
+        .filter(|(peer, _)| !reporting_peers.contains(peer))
+        .collect::<Vec<_>>();
+    never_reported.sort_by_key(|(peer, _)| *peer);
+    for (peer, activity) in never_reported {
+        ret.push(ConnectedShipper {
+            hostname: peer.to_string(),
+            status: ShipperStatus::NeverReported,
+            incarnation_id: String::new(),
+            peer: Some(peer),
+            last_seen: activity.at,
+            last_seen_timestamp: None,
+            last_log_timestamp: Some(activity.timestamp),
+            queue_count: BTreeMap::new(),
+            queue_capacity: BTreeMap::new(),
+            processed_count: BTreeMap::new(),
+            error_count: BTreeMap::new(),
+        });
+    }
+    ret
+}
+
+/// Certificate & private key (PEM) of the HTTP status server, the gRPC ones can be reused
+#[derive(Clone)]
+pub struct HttpStatusTlsConfig {
+    pub certificate_pem: Vec<u8>,
+    pub private_key_pem: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct MetricsQuery {
+    /// `text` (prometheus text format, default) or `json`
+    format: Option<String>,
+}
+
+async fn metrics(Query(query): Query<MetricsQuery>) -> Response {
+    match query.format.as_deref() {
+        None | Some("text") => generate_metrics().into_response(),
+        Some("json") => Json(generate_json_metrics()).into_response(),
+        Some(format) => (
+            StatusCode::BAD_REQUEST,
+            format!("Unsupported metrics format {format}, expected text or json"),
+        )
+            .into_response(),
     }
-    for disconnected in disconnected {
-        shippers.remove(&disconnected);
+}
+
+/// Emit the buffered entries immediately, only allowed from the local host
+async fn flush(flusher: Flusher, peer: SocketAddr) -> Response {
+    if !peer.ip().is_loopback() {
+        return (
+            StatusCode::FORBIDDEN,
+            "Flush is only allowed from localhost\n",
+        )
+            .into_response();
     }
+    let start = Instant::now();
+    match tokio::time::timeout(FLUSH_TIMEOUT, flusher.flush()).await {
+        Ok(Ok(())) => {
+            let elapsed = start.elapsed();
+            tracing::info!("Buffered entries flushed to quickwit in {elapsed:?}");
+            format!("Flushed in {elapsed:?}\n").into_response()
+        }
+        Ok(Err(e)) => (StatusCode::SERVICE_UNAVAILABLE, format!("{e}\n")).into_response(),
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("Flush not accepted by quickwit after {FLUSH_TIMEOUT:?}\n"),
+        )
+            .into_response(),
+    }
+}
+
+enum Listener {
+    Plain(tokio::net::TcpListener),
+    Tls(std::net::TcpListener, HttpStatusTlsConfig),
 }
 
-pub fn launch_server(bind_address: &str, quickwit_rest_url: &str) -> anyhow::Result<()> {
-    tokio::spawn(async {
+pub fn launch_server(
+    bind_address: BindAddr,
+    quickwit_rest_url: Option<&str>,
+    output_errors: Arc<OutputErrors>,
+    flusher: Flusher,
+    tls: Option<&HttpStatusTlsConfig>,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<JoinHandle<()>> {
+    let clear_shutdown_token = shutdown_token.clone();
+    tokio::spawn(async move {
         loop {
-            tokio::time::sleep(Duration::from_secs(30)).await;
-            clear_disconnected_hosts().await;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(30)) => clear_disconnected_hosts().await,
+                _ = clear_shutdown_token.cancelled() => break,
+            }
         }
     });
 
-    let sock_addr = bind_address
-        .parse::<SocketAddr>()
-        .context("Invalid http status server bind address")?;
+    // bind early to report errors to the caller
+    let listener = std::net::TcpListener::bind(bind_address.socket_addr())
+        .and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        })
+        .with_context(|| format!("Unable to bind HTTP status server to {bind_address}"))?;
+    let listener = match tls {
+        Some(tls) => Listener::Tls(listener, tls.clone()),
+        None => Listener::Plain(
+            tokio::net::TcpListener::from_std(listener)
+                .with_context(|| format!("Unable to bind HTTP status server to {bind_address}"))?,
+        ),
+    };
 
-    let quickwit_metrics_url = Url::parse(quickwit_rest_url)
-        .context("Unable to parse quickwit rest url")?
-        .join("/metrics")?;
+    // not served without quickwit output
+    let quickwit_metrics_url = quickwit_rest_url
+        .filter(|_| CONFIG.load().collector_quickwit_metrics_route)
+        .map(|quickwit_rest_url| {
+            anyhow::Ok(
+                Url::parse(quickwit_rest_url)
+                    .context("Unable to parse quickwit rest url")?
+                    .join("/metrics")?,
+            )
+        })
+        .transpose()?;
 
-    tokio::spawn(async move {
-        let app = Router::new()
+    Ok(tokio::spawn(async move {
+        let mut app = Router::new()
             .route("/version", get(|| async { VERSION }))
             .route("/health", get(|| async { "OK" }))
             .route(
@@ -71,8 +307,21 @@ pub fn launch_server(bind_address: &str, quickwit_rest_url: &str) -> anyhow::Res
                     ret
                 }),
             )
-            .route("/metrics", get(|| async { generate_metrics() }))
             .route(
+                "/shippers.json",
+                get(|| async { Json(shippers_status().await) }),
+            )
+            .route("/metrics", get(metrics))
+            .route(
+                "/flush",
+                post(|ConnectInfo(peer): ConnectInfo<SocketAddr>| flush(flusher, peer)),
+            )
+            .route(
+                "/last-errors",
+                get(|| async move { Json(output_errors.last_errors()) }),
+            );
+        if let Some(quickwit_metrics_url) = quickwit_metrics_url {
+            app = app.route(
                 "/quickwit/metrics",
                 get(|| async move {
                     match async {
@@ -92,14 +341,46 @@ pub fn launch_server(bind_address: &str, quickwit_rest_url: &str) -> anyhow::Res
                     }
                 }),
             );
-        tracing::info!("Starting HTTP status server {sock_addr}");
-        let listener = tokio::net::TcpListener::bind(&sock_addr)
-            .await
-            .expect("Unable to open listening socket");
-        axum::serve(listener, app.into_make_service())
-            .await
-            .unwrap();
-    });
-
-    Ok(())
+        }
+        match listener {
+            Listener::Plain(listener) => {
+                tracing::info!("Starting HTTP status server {bind_address}");
+                if let Err(e) = axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown_token.cancelled_owned())
+                .await
+                {
+                    tracing::error!("HTTP status server error: {e}");
+                }
+            }
+            Listener::Tls(listener, tls) => {
+                let tls =
+                    match RustlsConfig::from_pem(tls.certificate_pem, tls.private_key_pem).await {
+                        Ok(tls) => tls,
+                        Err(e) => {
+                            tracing::error!("Invalid HTTP status server TLS configuration: {e}");
+                            std::process::exit(1);
+                        }
+                    };
+                tracing::info!("Starting HTTPS status server {bind_address}");
+                let handle = Handle::new();
+                tokio::spawn({
+                    let handle = handle.clone();
+                    async move {
+                        shutdown_token.cancelled().await;
+                        handle.graceful_shutdown(Some(TLS_SHUTDOWN_GRACE_PERIOD));
+                    }
+                });
+                if let Err(e) = axum_server::from_tcp_rustls(listener, tls)
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                    .await
+                {
+                    tracing::error!("HTTPS status server error: {e}");
+                }
+            }
+        }
+    }))
 }
@@ -0,0 +1,69 @@
+//! In process collector, mainly useful for tests: log lines are fed directly to the
+//! gRPC handler (without any transport) and batches are not sent to quickwit
+//! but made available to the caller.
+
+use async_channel::Receiver;
+use rlog_grpc::{
+    rlog_service_protocol::{log_collector_server::LogCollector, LogLine, Metrics},
+    tonic::{Request, Status},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    batch,
+    config::{Config, CONFIG},
+    grpc_server::LogCollectorServer,
+    index::IndexLogEntry,
+};
+
+pub struct InProcessCollector {
+    server: LogCollectorServer,
+    batch_receiver: Receiver<Vec<IndexLogEntry>>,
+    shutdown_token: CancellationToken,
+}
+
+impl InProcessCollector {
+    /// Start the batch task, batches are sized according to the collector config.
+    pub fn start() -> Self {
+        let shutdown_token = CancellationToken::new();
+        let (log_sender, batches, _) = batch::launch_batch_collector(
+            CONFIG.map(|c: &Config| &c.collector_quickwit_batch_max_interval),
+            CONFIG.map(|c: &Config| &c.collector_quickwit_batch_size),
+            CONFIG.map(|c: &Config| &c.collector_input_buffer_size),
+            CONFIG.map(|c: &Config| &c.collector_quickwit_output_buffer_size),
+            shutdown_token.child_token(),
+        );
+        Self {
+            server: LogCollectorServer::new(log_sender),
+            batch_receiver: batches.receiver,
+            shutdown_token,
+        }
+    }
+
+    /// Call the `Log` handler as if `log_line` was sent by a shipper
+    pub async fn log(&self, log_line: LogLine) -> Result<(), Status> {
+        self.server
+            .log(Request::new(log_line))
+            .await
+            .map(|r| r.into_inner())
+    }
+
+    /// Call the `ReportMetrics` handler as if `metrics` was sent by a shipper
+    pub async fn report_metrics(&self, metrics: Metrics) -> Result<(), Status> {
+        self.server
+            .report_metrics(Request::new(metrics))
+            .await
+            .map(|r| r.into_inner())
+    }
+
+    /// Wait for the next batch, `None` once shutdown and all batches received.
+    pub async fn next_batch(&self) -> Option<Vec<IndexLogEntry>> {
+        self.batch_receiver.recv().await.ok()
+    }
+
+    /// Flush the pending logs as a last batch, further logs will be rejected
+    /// with an `unavailable` status.
+    pub fn shutdown(&self) {
+        self.shutdown_token.cancel();
+    }
+}
@@ -1,19 +1,51 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{anyhow, Context};
-use async_channel::Receiver;
-use futures::FutureExt;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use itertools::Itertools;
-use reqwest::{Client, StatusCode, Url};
-use rlog_grpc::{rlog_service_protocol::LogLine, OTELSeverity};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Client, StatusCode, Url,
+};
+use ring::digest;
+use rlog_common::utils::{format_error, hex};
+use rlog_grpc::{
+    rlog_service_protocol::{LogLine, SyslogSeverity},
+    OTELSeverity,
+};
 use serde::{Deserialize, Serialize};
-use tokio::task::JoinHandle;
+use tokio::{
+    select,
+    task::{JoinError, JoinHandle},
+};
+use tokio_util::sync::CancellationToken;
 
+use crate::batch::{Batches, FlushMarker};
+use crate::config::{
+    FacilityName, QuickwitApiVersion, QuickwitCommitMode, SyslogFacilityMapping,
+    TimestampPrecision, CONFIG,
+};
+use crate::extra_cache::parse_extra;
+use crate::flatten;
 use crate::metrics::{
-    COLLECTOR_INDEXED_COUNT, COLLECTOR_OUTPUT_COUNT, OUTPUT_STATUS_ERROR_LABEL_VALUE,
+    COLLECTOR_INDEXED_COUNT, COLLECTOR_OUTPUT_COUNT, COLLECTOR_REJECTED_COUNT,
+    COLLECTOR_SLA_VIOLATION_COUNT, COLLECTOR_TRUNCATED_FIELD_COUNT,
+    OUTPUT_DESTINATION_PRIMARY_LABEL_VALUE, OUTPUT_STATUS_ERROR_LABEL_VALUE,
     OUTPUT_STATUS_OK_LABEL_VALUE, OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE,
     OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
 };
+use crate::mirror::Mirrors;
+use crate::output::{launch_fanout, OutputBackend, OutputQueue, QuickwitOutput};
+use crate::output_errors::OutputErrors;
+use crate::s3;
+use crate::truncate;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -32,7 +64,8 @@ pub struct IndexLogEntry {
     /// - milliseconds from EPOCh
     /// - microseconds from EPOCH
     /// - nanosecondes from EPOCH
-    /// Quickwit will detect the right format...
+    ///
+    /// Quickwit will detect the right format... (see `collector_timestamp_precision`)
     pub timestamp: u64,
     pub hostname: String,
     pub service_name: String,
@@ -42,22 +75,85 @@ pub struct IndexLogEntry {
 
     pub log_system: LogSystem,
 
+    /// the log line signature has been checked (`collector_verify_log_signatures`)
+    #[serde(default)]
+    pub hmac_verified: bool,
+
+    /// free fields promoted to indexed columns (see `Config::collector_indexed_fields`),
+    /// queried as `indexed_fields.<name>`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub indexed_fields: HashMap<String, serde_json::Value>,
+
+    /// deterministic id of the log entry (see `Config::collector_emit_doc_id`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_id: Option<String>,
+    /// version of the `doc_id` hash input composition (see `DOC_ID_VERSION`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc_id_v: Option<u32>,
+
     #[serde(flatten)]
     pub free_fields: HashMap<String, serde_json::Value>,
 }
 
+/// Version of the `doc_id` hash input composition, to bump whenever `IndexLogEntry::doc_id`
+/// hashes other fields or encodes them differently.
+///
+/// Version 1: SHA-256 of the hostname, service name, timestamp & message. Strings are
+/// prefixed by their length (u64, big endian) so that fields can't bleed into each other, the
+/// timestamp is a big endian u64 in `collector_timestamp_precision` units.
+pub const DOC_ID_VERSION: u32 = 1;
+
+impl IndexLogEntry {
+    /// Deterministic id of the entry (hex encoded), see `DOC_ID_VERSION`
+    pub fn doc_id(&self) -> String {
+        let mut context = digest::Context::new(&digest::SHA256);
+        for field in [&self.hostname, &self.service_name] {
+            context.update(&(field.len() as u64).to_be_bytes());
+            context.update(field.as_bytes());
+        }
+        context.update(&self.timestamp.to_be_bytes());
+        context.update(&(self.message.len() as u64).to_be_bytes());
+        context.update(self.message.as_bytes());
+        hex(context.finish().as_ref())
+    }
+
+    /// Move the given free fields to the indexed fields.
+    fn promote_fields(&mut self, names: &[String]) {
+        for name in names {
+            if let Some(value) = self.free_fields.remove(name) {
+                self.indexed_fields.insert(name.clone(), value);
+            }
+        }
+    }
+}
+
 enum Batch<T> {
-    Single(Vec<T>),
-    Splitted { to_send: Vec<T>, remaining: Vec<T> },
+    /// `body` is the serialization of the elements, kept while they are retried
+    Single {
+        elements: Vec<T>,
+        body: Option<String>,
+    },
+    Splitted {
+        to_send: Vec<T>,
+        remaining: Vec<T>,
+    },
     None,
 }
 impl<T> Batch<T> {
-    fn pop_elements(&mut self) -> Option<Vec<T>> {
+    fn single(elements: Vec<T>) -> Self {
+        Batch::Single {
+            elements,
+            body: None,
+        }
+    }
+
+    /// Elements to send and their serialization if they have already been sent as is
+    fn pop_elements(&mut self) -> Option<(Vec<T>, Option<String>)> {
         match std::mem::replace(self, Batch::None) {
-            Batch::Single(elements) => Some(elements),
+            Batch::Single { elements, body } => Some((elements, body)),
             Batch::Splitted { to_send, remaining } => {
-                *self = Batch::Single(remaining);
-                Some(to_send)
+                *self = Batch::single(remaining);
+                Some((to_send, None))
             }
             Batch::None => None,
         }
@@ -70,17 +166,27 @@ impl<T> Batch<T> {
         }
         // it is sure we have at least 2 elements in our elements vector.
         let remaining = elements.split_off(elements.len() / 2);
+        // the serialized body of the whole batch is dropped with it
         *self = match std::mem::replace(self, Batch::None) {
-            Batch::Single(single) => Batch::Splitted {
+            Batch::Single {
+                elements: single,
+                body: _,
+            } => Batch::Splitted {
                 to_send: elements,
                 remaining: remaining.into_iter().chain(single).collect(),
             },
+            // another request failed before the previous split has been sent
             Batch::Splitted {
-                to_send: _,
-                remaining: _,
-            } => {
-                panic!("split_because_of_err called twice without calling pop_elements in between")
-            }
+                to_send,
+                remaining: splitted,
+            } => Batch::Splitted {
+                to_send,
+                remaining: splitted
+                    .into_iter()
+                    .chain(elements)
+                    .chain(remaining)
+                    .collect(),
+            },
             Batch::None => Batch::Splitted {
                 to_send: elements,
                 remaining,
@@ -89,19 +195,50 @@ impl<T> Batch<T> {
     }
     fn push_elements(&mut self, elements: Vec<T>) {
         match self {
-            Batch::Single(existing) => existing.extend(elements.into_iter()),
+            Batch::Single {
+                elements: existing,
+                body,
+            } => {
+                existing.extend(elements);
+                *body = None;
+            }
             Batch::Splitted {
                 to_send: _,
                 remaining,
             } => remaining.extend(elements.into_iter()),
             // Note: only this case is nominal, using the other cases may fill the RAM
-            Batch::None => *self = Batch::Single(elements),
+            Batch::None => *self = Batch::single(elements),
+        }
+    }
+
+    /// Put back elements which have not been accepted, their serialized `body` is reused
+    /// by the next attempt if they are sent alone
+    fn retry_elements(&mut self, elements: Vec<T>, body: String) {
+        match self {
+            Batch::None => {
+                *self = Batch::Single {
+                    elements,
+                    body: Some(body),
+                }
+            }
+            _ => self.push_elements(elements),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Batch::Single { elements, body: _ } => elements.len(),
+            Batch::Splitted { to_send, remaining } => to_send.len() + remaining.len(),
+            Batch::None => 0,
         }
     }
 
     fn is_empty(&self) -> bool {
         match self {
-            Batch::Single(_) => false,
+            Batch::Single {
+                elements: _,
+                body: _,
+            } => false,
             Batch::Splitted {
                 to_send: _,
                 remaining: _,
@@ -111,139 +248,712 @@ impl<T> Batch<T> {
     }
 }
 
+/// Send every batch to all the `outputs` (and to the quickwit mirrors), the returned task
+/// exits once all the batches have been sent (bounded by the
+/// `collector_shutdown_flush_timeout`)
 pub fn launch_index_loop(
-    quickwit_rest_url: &str,
-    index_id: &str,
-    batch_receiver: Receiver<Vec<IndexLogEntry>>,
+    outputs: &[OutputBackend],
+    batches: Batches<IndexLogEntry>,
+    output_errors: Arc<OutputErrors>,
+    sla_threshold_ms: Option<u64>,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<JoinHandle<()>> {
+    anyhow::ensure!(!outputs.is_empty(), "No output configured");
+    let mut queues = Vec::with_capacity(outputs.len());
+    let mut output_handles = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        let (queue, output_batches) = OutputQueue::new(output.name());
+        output_handles.push(match output {
+            OutputBackend::Quickwit(quickwit) => launch_quickwit_output(
+                quickwit,
+                output_batches,
+                output_errors.clone(),
+                sla_threshold_ms,
+                shutdown_token.clone(),
+            )?,
+            OutputBackend::S3(s3) => {
+                s3::launch_s3_output(s3, output_batches, shutdown_token.clone())?
+            }
+        });
+        queues.push(queue);
+    }
+
+    // every batch is also queued for the mirrors, their tasks exit after the outputs
+    let (mirrors, mirror_handles) = Mirrors::launch(
+        &CONFIG.load().collector_quickwit_mirrors,
+        shutdown_token.clone(),
+    )?;
+
+    Ok(launch_fanout(
+        batches,
+        queues,
+        output_handles,
+        mirrors,
+        mirror_handles,
+    ))
+}
+
+/// Send the batches to quickwit, retried until accepted
+fn launch_quickwit_output(
+    output: &QuickwitOutput,
+    batches: Batches<IndexLogEntry>,
+    output_errors: Arc<OutputErrors>,
+    sla_threshold_ms: Option<u64>,
+    shutdown_token: CancellationToken,
 ) -> anyhow::Result<JoinHandle<()>> {
     // parse url & setup http client
-    let quickwit_rest_url: Url = quickwit_rest_url
+    let quickwit_rest_url: Url = output
+        .rest_url
         .parse()
         .context("invalid quickwit REST url")?;
-    let ingest_url = quickwit_rest_url.join(&format!("api/v1/{index_id}/ingest"))?;
     let http_client = Client::builder()
         .connect_timeout(Duration::from_secs(5))
+        .default_headers(to_header_map(&output.extra_headers)?)
         .build()?;
+    let mut ingest_api = IngestApi::new(
+        quickwit_rest_url,
+        &output.index_id,
+        http_client.clone(),
+        CONFIG.load().collector_quickwit_api_version,
+        CONFIG.load().collector_quickwit_commit,
+    )?;
 
+    let Batches {
+        receiver: batch_receiver,
+        mut flush_markers,
+    } = batches;
     Ok(tokio::spawn(
         async move {
             let mut batch_to_send = Batch::None;
+            // oldest received batch whose elements may be in `batch_to_send`
+            let mut queued_since: Option<u64> = None;
+            let mut in_flight = FuturesUnordered::<IngestTask>::new();
+            // after a failure, no request is sent before this instant
+            let mut retry_at: Option<tokio::time::Instant> = None;
+            let mut flush_deadline = FlushDeadline::new(shutdown_token);
+            let mut received_batches = 0;
+            let mut pending_flushes: Vec<FlushMarker> = Vec::new();
+            let mut input_closed = false;
             loop {
-                if let Some(batch) = batch_to_send.pop_elements() {
-                    let body = batch
-                        .iter()
-                        .map(|j| serde_json::to_string(&j).unwrap())
-                        .join("\n");
+                // every batch received before the oldest pending one has been accepted
+                let oldest_pending = in_flight
+                    .iter()
+                    .map(|task| task.first_batch)
+                    .chain(queued_since)
+                    .min();
+                let (flushed, pending) =
+                    pending_flushes
+                        .into_iter()
+                        .partition::<Vec<_>, _>(|marker| {
+                            marker.batches <= received_batches
+                                && oldest_pending.is_none_or(|oldest| marker.batches < oldest)
+                        });
+                pending_flushes = pending;
+                for marker in flushed {
+                    let _ = marker.done.send(());
+                }
+
+                if retry_at.is_some_and(|retry_at| retry_at <= tokio::time::Instant::now()) {
+                    retry_at = None;
+                }
+                let parallelism = CONFIG.load().collector_batch_send_parallelism.max(1);
+                // retried & split elements are always sent before the new batches
+                while retry_at.is_none() && in_flight.len() < parallelism {
+                    let Some((batch, body)) = batch_to_send.pop_elements() else {
+                        break;
+                    };
+                    let first_batch = queued_since.unwrap_or(received_batches);
+                    if batch_to_send.is_empty() {
+                        queued_since = None;
+                    }
+                    ingest_api.refresh_version().await;
+                    // serialized only once, retries reuse the body
+                    let body = body.unwrap_or_else(|| serialize_batch(&batch));
                     tracing::debug!("Sending to quickwit {} items:\n{body}", batch.len());
-                    // send the stuff
-                    match http_client.post(ingest_url.clone()).body(body).send().await {
-                        Ok(quickwit_response) => {
-                            match quickwit_response.status() {
-                                StatusCode::OK => {
-                                    // consume response
-                                    let _response = quickwit_response.text().await;
-                                    tracing::debug!("OK");
-                                    COLLECTOR_INDEXED_COUNT.inc_by(batch.len() as u64);
-                                    COLLECTOR_OUTPUT_COUNT
-                                        .with_label_values(&[
-                                            OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
-                                            OUTPUT_STATUS_OK_LABEL_VALUE,
-                                        ])
-                                        .inc();
-                                    // nothing to do here, this has been successfully accepted by quickwit
-                                }
-                                StatusCode::TOO_MANY_REQUESTS => {
-                                    // consume response
-                                    let _response = quickwit_response.text().await;
-                                    tracing::warn!(
-                                        "Quickwit overloaded (429), wait 5 seconds before retrying"
-                                    );
-                                    batch_to_send.push_elements(batch);
-                                    COLLECTOR_OUTPUT_COUNT
-                                        .with_label_values(&[
-                                            OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
-                                            OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE,
-                                        ])
-                                        .inc();
-                                    tokio::time::sleep(Duration::from_secs(5)).await;
-                                    continue;
+                    in_flight.push(IngestTask {
+                        first_batch,
+                        documents: batch.len(),
+                        handle: tokio::spawn(send_batch(
+                            http_client.clone(),
+                            ingest_api.ingest_url().clone(),
+                            ingest_api.version,
+                            batch,
+                            body,
+                            output_errors.clone(),
+                            sla_threshold_ms,
+                        )),
+                    });
+                }
+                if input_closed && in_flight.is_empty() && batch_to_send.is_empty() {
+                    break;
+                }
+
+                // new batches are received once the previous ones have been sent
+                let receive_batch = !input_closed
+                    && retry_at.is_none()
+                    && batch_to_send.is_empty()
+                    && in_flight.len() < parallelism;
+                let retry_sleep =
+                    tokio::time::sleep_until(retry_at.unwrap_or_else(tokio::time::Instant::now));
+                let Some(event) = flush_deadline
+                    .bounded(async {
+                        select! {
+                            Some(sent) = in_flight.next() => IndexEvent::Sent(sent),
+                            _ = retry_sleep, if retry_at.is_some() => IndexEvent::RetryDue,
+                            batch = batch_receiver.recv(), if receive_batch => {
+                                IndexEvent::Batch(batch.ok())
+                            }
+                            Some(marker) = flush_markers.recv() => IndexEvent::FlushMarker(marker),
+                        }
+                    })
+                    .await
+                else {
+                    break;
+                };
+                match event {
+                    IndexEvent::Sent((first_batch, documents, result)) => {
+                        let retry_delay = match result {
+                            Ok(BatchResult {
+                                elements,
+                                body,
+                                outcome,
+                            }) => match outcome {
+                                BatchOutcome::Accepted => None,
+                                BatchOutcome::Retry(delay) => {
+                                    batch_to_send.retry_elements(elements, body);
+                                    Some(delay)
                                 }
-                                other => {
-                                    let response = quickwit_response.text().await;
-
-                                    if other == StatusCode::BAD_REQUEST
-                                        && response
-                                            .as_ref()
-                                            .map(|r| r.contains("The request payload is too large"))
-                                            .unwrap_or(false)
-                                    {
-                                        // payload too large
-                                        tracing::warn!(
-                                            "Payload too large for quickwit, trying to split it!"
-                                        );
-                                        batch_to_send.split_because_of_err(batch);
-                                    } else {
-                                        tracing::error!(
-                                            "Unhandled status code {other} - {response:?}"
-                                        );
-                                        // retry batch
-                                        batch_to_send.push_elements(batch);
-                                        COLLECTOR_OUTPUT_COUNT
-                                            .with_label_values(&[
-                                                OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
-                                                OUTPUT_STATUS_ERROR_LABEL_VALUE,
-                                            ])
-                                            .inc();
-                                    }
-
-                                    tokio::time::sleep(Duration::from_secs(1)).await;
-                                    continue;
+                                BatchOutcome::Split => {
+                                    batch_to_send.split_because_of_err(elements);
+                                    Some(Duration::from_secs(1))
                                 }
+                            },
+                            Err(e) => {
+                                tracing::error!(
+                                    "Quickwit ingest task failed, {documents} logs lost - {e}"
+                                );
+                                None
                             }
-                        }
-                        Err(quickwit_error) => {
-                            // connect error or some low level error, we must retry
-                            tracing::error!(
-                                "Error sending batch to quickwit, retry in 1s - {quickwit_error}"
+                        };
+                        if !batch_to_send.is_empty() {
+                            queued_since = Some(
+                                queued_since.map_or(first_batch, |since| since.min(first_batch)),
                             );
-                            batch_to_send.push_elements(batch);
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue;
                         }
-                    }
-                }
-                if batch_to_send.is_empty() {
-                    match batch_receiver.recv().await {
-                        Ok(batch) => {
-                            batch_to_send.push_elements(batch);
-                        }
-                        // channel close (server shutdown)
-                        Err(_) => {
-                            tracing::info!("Input channel closed.");
-                            break;
+                        if let Some(delay) = retry_delay {
+                            let at = tokio::time::Instant::now() + delay;
+                            retry_at = Some(retry_at.map_or(at, |retry_at| retry_at.max(at)));
                         }
                     }
+                    // checked at the beginning of the loop
+                    IndexEvent::RetryDue => {}
+                    IndexEvent::Batch(Some(batch)) => {
+                        received_batches += 1;
+                        batch_to_send.push_elements(batch);
+                        queued_since.get_or_insert(received_batches);
+                    }
+                    // channel close (server shutdown)
+                    IndexEvent::Batch(None) => {
+                        tracing::info!("Input channel closed.");
+                        input_closed = true;
+                    }
+                    IndexEvent::FlushMarker(marker) => pending_flushes.push(marker),
                 }
             }
+            // only if the flush deadline has been reached
+            batch_receiver.close();
+            let mut lost = batch_to_send.len();
+            for task in in_flight.iter() {
+                task.handle.abort();
+                lost += task.documents;
+            }
+            while let Ok(batch) = batch_receiver.try_recv() {
+                lost += batch.len();
+            }
+            if lost > 0 {
+                tracing::error!("Shutdown flush timeout reached, {lost} logs not sent to quickwit");
+            }
         }
-        .then(|_| async { tracing::info!("Exited indexing task.") }),
+        .then(|_| async { tracing::info!("Exited quickwit output task.") }),
     ))
 }
 
+/// Outcome of an ingest request, the index loop puts back the elements which have not been
+/// accepted
+enum BatchOutcome {
+    Accepted,
+    /// send the elements again after this delay
+    Retry(Duration),
+    /// payload too large, the elements are split in two requests
+    Split,
+}
+
+/// Returned by the ingest tasks to the index loop
+struct BatchResult {
+    elements: Vec<IndexLogEntry>,
+    /// serialization of the elements, reused by the retries
+    body: String,
+    outcome: BatchOutcome,
+}
+
+/// Ingest request sent concurrently to quickwit, resolves with the received batches
+/// accounting of the request even if the task failed
+struct IngestTask {
+    /// oldest received batch whose elements may be in the request
+    first_batch: u64,
+    documents: usize,
+    handle: JoinHandle<BatchResult>,
+}
+
+impl Future for IngestTask {
+    type Output = (u64, usize, Result<BatchResult, JoinError>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let (first_batch, documents) = (self.first_batch, self.documents);
+        self.handle
+            .poll_unpin(cx)
+            .map(|result| (first_batch, documents, result))
+    }
+}
+
+enum IndexEvent {
+    Sent((u64, usize, Result<BatchResult, JoinError>)),
+    RetryDue,
+    Batch(Option<Vec<IndexLogEntry>>),
+    FlushMarker(FlushMarker),
+}
+
+/// Send an ingest request to quickwit, reporting its metrics & output errors
+async fn send_batch(
+    http_client: Client,
+    ingest_url: Url,
+    api_version: QuickwitApiVersion,
+    batch: Vec<IndexLogEntry>,
+    body: String,
+    output_errors: Arc<OutputErrors>,
+    sla_threshold_ms: Option<u64>,
+) -> BatchResult {
+    let content_type = CONFIG.load().collector_quickwit_content_type.clone();
+    // send the stuff
+    let response = http_client
+        .post(ingest_url)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.clone())
+        .send()
+        .await;
+    let outcome = match response {
+        Ok(quickwit_response) => match quickwit_response.status() {
+            StatusCode::OK => {
+                // consume response
+                let response = quickwit_response.text().await;
+                tracing::debug!("OK");
+                let rejected = match api_version {
+                    QuickwitApiVersion::V2 => count_rejected_documents(response, batch.len()),
+                    _ => 0,
+                };
+                COLLECTOR_INDEXED_COUNT.inc_by(batch.len() as u64 - rejected);
+                COLLECTOR_REJECTED_COUNT.inc_by(rejected);
+                if let Some(threshold_ms) = sla_threshold_ms {
+                    COLLECTOR_SLA_VIOLATION_COUNT.inc_by(count_sla_violations(
+                        &batch,
+                        now_ms(),
+                        threshold_ms,
+                        CONFIG.load().collector_timestamp_precision,
+                    ));
+                }
+                COLLECTOR_OUTPUT_COUNT
+                    .with_label_values(&[
+                        OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
+                        OUTPUT_DESTINATION_PRIMARY_LABEL_VALUE,
+                        OUTPUT_STATUS_OK_LABEL_VALUE,
+                    ])
+                    .inc();
+                // nothing to do here, this has been successfully accepted by quickwit
+                BatchOutcome::Accepted
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                // consume response
+                let response = quickwit_response.text().await;
+                tracing::warn!("Quickwit overloaded (429), wait 5 seconds before retrying");
+                output_errors.report(
+                    Some(StatusCode::TOO_MANY_REQUESTS),
+                    response.as_deref().unwrap_or_default(),
+                    batch.len(),
+                );
+                COLLECTOR_OUTPUT_COUNT
+                    .with_label_values(&[
+                        OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
+                        OUTPUT_DESTINATION_PRIMARY_LABEL_VALUE,
+                        OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE,
+                    ])
+                    .inc();
+                BatchOutcome::Retry(Duration::from_secs(5))
+            }
+            other => {
+                let response = quickwit_response.text().await;
+                output_errors.report(
+                    Some(other),
+                    response.as_deref().unwrap_or_default(),
+                    batch.len(),
+                );
+
+                if other == StatusCode::BAD_REQUEST
+                    && response
+                        .as_ref()
+                        .map(|r| r.contains("The request payload is too large"))
+                        .unwrap_or(false)
+                {
+                    // payload too large
+                    tracing::warn!("Payload too large for quickwit, trying to split it!");
+                    BatchOutcome::Split
+                } else {
+                    tracing::error!("Unhandled status code {other} - {response:?}");
+                    COLLECTOR_OUTPUT_COUNT
+                        .with_label_values(&[
+                            OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
+                            OUTPUT_DESTINATION_PRIMARY_LABEL_VALUE,
+                            OUTPUT_STATUS_ERROR_LABEL_VALUE,
+                        ])
+                        .inc();
+                    // retry batch
+                    BatchOutcome::Retry(Duration::from_secs(1))
+                }
+            }
+        },
+        Err(quickwit_error) => {
+            // connect error or some low level error, we must retry
+            tracing::error!("Error sending batch to quickwit, retry in 1s - {quickwit_error}");
+            output_errors.report(None, &quickwit_error.to_string(), batch.len());
+            BatchOutcome::Retry(Duration::from_secs(1))
+        }
+    };
+    BatchResult {
+        elements: batch,
+        body,
+        outcome,
+    }
+}
+
+/// Body of the ingest requests: one JSON document by line
+pub(crate) fn serialize_batch(batch: &[IndexLogEntry]) -> String {
+    batch
+        .iter()
+        .map(|j| serde_json::to_string(&j).unwrap())
+        .join("\n")
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The entry timestamp is more than `max_age` before `now_ms`, a zero `max_age` disables
+/// the check
+pub(crate) fn is_too_old(
+    entry: &IndexLogEntry,
+    now_ms: u64,
+    max_age: Duration,
+    precision: TimestampPrecision,
+) -> bool {
+    !max_age.is_zero()
+        && now_ms.saturating_sub(precision.to_millis(entry.timestamp)) > max_age.as_millis() as u64
+}
+
+/// Number of entries indexed more than `threshold_ms` after their timestamp (the documents
+/// rejected by quickwit are not known individually and are also counted)
+fn count_sla_violations(
+    batch: &[IndexLogEntry],
+    now_ms: u64,
+    threshold_ms: u64,
+    precision: TimestampPrecision,
+) -> u64 {
+    batch
+        .iter()
+        .filter(|entry| now_ms.saturating_sub(precision.to_millis(entry.timestamp)) > threshold_ms)
+        .count() as u64
+}
+
+fn to_header_map(headers: &HashMap<String, String>) -> anyhow::Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        header_map.insert(
+            HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid quickwit header name {name}"))?,
+            HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid quickwit header value for {name}"))?,
+        );
+    }
+    Ok(header_map)
+}
+
+/// Once the shutdown is requested, quickwit requests and retries are bounded by
+/// the `collector_shutdown_flush_timeout`
+pub(crate) struct FlushDeadline {
+    shutdown_token: CancellationToken,
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl FlushDeadline {
+    pub(crate) fn new(shutdown_token: CancellationToken) -> Self {
+        Self {
+            shutdown_token,
+            deadline: None,
+        }
+    }
+
+    fn get(&mut self) -> Option<tokio::time::Instant> {
+        if self.deadline.is_none() && self.shutdown_token.is_cancelled() {
+            self.deadline =
+                Some(tokio::time::Instant::now() + CONFIG.load().collector_shutdown_flush_timeout);
+        }
+        self.deadline
+    }
+
+    /// Run `future` to completion, `None` if the deadline is reached before
+    pub(crate) async fn bounded<F: Future>(&mut self, future: F) -> Option<F::Output> {
+        tokio::pin!(future);
+        if self.get().is_none() {
+            select! {
+                output = &mut future => return Some(output),
+                _ = self.shutdown_token.cancelled() => {}
+            }
+        }
+        tokio::time::timeout_at(self.get()?, future).await.ok()
+    }
+}
+
+/// Quickwit ingest endpoint, depending on the ingest API version
+pub(crate) struct IngestApi {
+    quickwit_rest_url: Url,
+    v1_ingest_url: Url,
+    v2_ingest_url: Url,
+    http_client: Client,
+    configured_version: QuickwitApiVersion,
+    /// resolved version (never `Auto`)
+    version: QuickwitApiVersion,
+    last_probe: Option<Instant>,
+}
+
+impl IngestApi {
+    /// In auto mode, the quickwit version is probed again at this interval
+    const PROBE_INTERVAL: Duration = Duration::from_secs(300);
+
+    pub(crate) fn new(
+        quickwit_rest_url: Url,
+        index_id: &str,
+        http_client: Client,
+        configured_version: QuickwitApiVersion,
+        commit_mode: QuickwitCommitMode,
+    ) -> anyhow::Result<Self> {
+        let mut v1_ingest_url = quickwit_rest_url.join(&format!("api/v1/{index_id}/ingest"))?;
+        let mut v2_ingest_url = quickwit_rest_url.join(&format!(
+            "api/v1/{index_id}/ingest-v2?detailed_response=true"
+        ))?;
+        if let Some(commit) = commit_mode.parameter() {
+            for url in [&mut v1_ingest_url, &mut v2_ingest_url] {
+                url.query_pairs_mut().append_pair("commit", commit);
+            }
+        }
+        Ok(Self {
+            v1_ingest_url,
+            v2_ingest_url,
+            quickwit_rest_url,
+            http_client,
+            configured_version,
+            version: match configured_version {
+                QuickwitApiVersion::Auto => QuickwitApiVersion::V1,
+                version => version,
+            },
+            last_probe: None,
+        })
+    }
+
+    /// Probe quickwit version if needed, fallback to v1 if the probe fails
+    pub(crate) async fn refresh_version(&mut self) {
+        if self.configured_version != QuickwitApiVersion::Auto
+            || self
+                .last_probe
+                .map(|last_probe| last_probe.elapsed() < Self::PROBE_INTERVAL)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        self.last_probe = Some(Instant::now());
+        let version = match probe_api_version(&self.http_client, &self.quickwit_rest_url).await {
+            Ok(version) => version,
+            Err(e) => {
+                tracing::warn!(
+                    error = %format_error(e),
+                    "Unable to probe quickwit version, using ingest API v1"
+                );
+                QuickwitApiVersion::V1
+            }
+        };
+        if version != self.version {
+            tracing::info!("Using quickwit ingest API {version:?}");
+        }
+        self.version = version;
+    }
+
+    pub(crate) fn ingest_url(&self) -> &Url {
+        match self.version {
+            QuickwitApiVersion::V2 => &self.v2_ingest_url,
+            _ => &self.v1_ingest_url,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct QuickwitVersionResponse {
+    build: QuickwitBuildInfo,
+}
+
+#[derive(Deserialize)]
+struct QuickwitBuildInfo {
+    version: String,
+}
+
+/// Quickwit 0.7+ supports the ingest API v2
+async fn probe_api_version(
+    http_client: &Client,
+    quickwit_rest_url: &Url,
+) -> anyhow::Result<QuickwitApiVersion> {
+    let response: QuickwitVersionResponse = http_client
+        .get(quickwit_rest_url.join("api/v1/version")?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let version = response.build.version;
+    let mut numbers = version
+        .trim_start_matches('v')
+        .split('.')
+        .map(|n| n.parse::<u64>());
+    match (numbers.next(), numbers.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) if major > 0 || minor >= 7 => Ok(QuickwitApiVersion::V2),
+        (Some(Ok(_)), Some(Ok(_))) => Ok(QuickwitApiVersion::V1),
+        _ => Err(anyhow!("Unable to parse quickwit version {version}")),
+    }
+}
+
 #[derive(Deserialize)]
 #[allow(unused)]
 struct QuickwitIngestResponse {
     num_docs_for_processing: u64,
+    /// only in ingest API v2 detailed responses
+    #[serde(default)]
+    num_rejected_docs: Option<u64>,
+    #[serde(default)]
+    parse_failures: Vec<QuickwitParseFailure>,
+}
+
+#[derive(Deserialize)]
+struct QuickwitParseFailure {
+    #[serde(default)]
+    document: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Number of documents of a batch of `batch_len` documents rejected by quickwit according
+/// to an ingest API v2 response
+fn count_rejected_documents(response: reqwest::Result<String>, batch_len: usize) -> u64 {
+    let response = match response
+        .map_err(anyhow::Error::from)
+        .and_then(|r| Ok(serde_json::from_str::<QuickwitIngestResponse>(&r)?))
+    {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Unable to parse quickwit ingest response: {e}");
+            return 0;
+        }
+    };
+    for failure in &response.parse_failures {
+        tracing::error!(
+            "Document rejected by quickwit: {} --- {}",
+            failure.message,
+            failure.document
+        );
+    }
+    response
+        .num_rejected_docs
+        .unwrap_or(response.parse_failures.len() as u64)
+        // not trusted to fit the batch
+        .min(batch_len as u64)
 }
 
 impl TryFrom<LogLine> for IndexLogEntry {
     type Error = anyhow::Error;
 
-    fn try_from(value: LogLine) -> Result<Self, Self::Error> {
+    fn try_from(mut value: LogLine) -> Result<Self, Self::Error> {
+        let raw_host = value.raw_host.take();
+        let sequence = value.sequence.take();
+        let labels = std::mem::take(&mut value.labels);
+        let config = CONFIG.load();
+        let mut entry = IndexLogEntry::try_from_line(
+            value,
+            config.collector_timestamp_precision,
+            &config.collector_syslog_facility_mapping,
+        )?;
+        // fields of the log line take precedence over the shipper labels
+        for (name, value) in labels {
+            entry.free_fields.entry(name).or_insert(value.into());
+        }
+        if let Some(raw_host) = raw_host {
+            entry
+                .free_fields
+                .insert("raw_hostname".into(), raw_host.into());
+        }
+        if let Some(sequence) = sequence {
+            entry
+                .free_fields
+                .insert("_rlog_seq".into(), sequence.number.into());
+            entry
+                .free_fields
+                .insert("_rlog_incarnation".into(), sequence.incarnation_id.into());
+            entry
+                .free_fields
+                .insert("_rlog_input".into(), sequence.input.into());
+        }
+        if config.collector_flatten_free_fields.enabled {
+            flatten::flatten_fields(
+                &mut entry.free_fields,
+                &config.collector_flatten_free_fields,
+            );
+        }
+        let truncated = truncate::truncate_fields(
+            &mut entry.free_fields,
+            &config.collector_free_field_max_length,
+        );
+        if truncated > 0 {
+            COLLECTOR_TRUNCATED_FIELD_COUNT.inc_by(truncated);
+        }
+        // flattened keys can be promoted
+        entry.promote_fields(&config.collector_indexed_fields);
+        entry.override_severity(&config.collector_severity_overrides);
+        if config.collector_emit_doc_id {
+            entry.doc_id = Some(entry.doc_id());
+            entry.doc_id_v = Some(DOC_ID_VERSION);
+        }
+        Ok(entry)
+    }
+}
+
+impl IndexLogEntry {
+    fn try_from_line(
+        value: LogLine,
+        precision: TimestampPrecision,
+        facility_mapping: &HashMap<FacilityName, SyslogFacilityMapping>,
+    ) -> anyhow::Result<Self> {
         let hostname = value.host;
-        let timestamp = value
-            .timestamp
-            .ok_or(anyhow!("`timestamp` field is mandatory"))?;
+        let timestamp = precision.from_timestamp(
+            &value
+                .timestamp
+                .ok_or(anyhow!("`timestamp` field is mandatory"))?,
+        );
         let line = value.line.ok_or(anyhow!("`line` field is mandatory"))?;
 
         match line {
@@ -258,34 +968,35 @@ impl TryFrom<LogLine> for IndexLogEntry {
                         _ => gelf.short_message,
                     }
                 };
-                let mut extra: HashMap<String, serde_json::Value> =
-                    serde_json::from_str(&gelf.extra)
-                        .context("`extra` field is not a valid json object")?;
-                let service_name = extra
-                    .remove("service")
-                    .map(|s| s.as_str().map(|s| s.to_string()))
-                    .flatten()
-                    .unwrap_or_else(|| "unknown".to_string());
-                let severity_text = severity.to_string();
-                let severity_number = severity as u8;
-                let timestamp_ms = timestamp.seconds * 1000 + (timestamp.nanos as i64) / 1_000_000;
+                // owned copy of the cached fields
+                let mut extra = parse_extra(&gelf.extra)?;
+                let service_name = match extra.remove("service") {
+                    Some(serde_json::Value::String(service_name)) => service_name,
+                    _ => "unknown".to_string(),
+                };
                 Ok(IndexLogEntry {
                     message,
-                    timestamp: timestamp_ms as u64,
+                    timestamp,
                     hostname,
                     service_name,
-                    severity_text,
-                    severity_number: severity_number as u64,
+                    severity_text: severity.severity_text().into(),
+                    severity_number: severity as u64,
                     log_system: LogSystem::Gelf,
+                    hmac_verified: false,
+                    indexed_fields: HashMap::new(),
+                    doc_id: None,
+                    doc_id_v: None,
                     free_fields: extra,
                 })
             }
             rlog_grpc::rlog_service_protocol::log_line::Line::Syslog(syslog) => {
-                let severity = OTELSeverity::from(syslog.severity());
-                let severity_text = severity.to_string();
-                let severity_number = severity as u8;
+                let mapping = facility_mapping.get(&FacilityName(syslog.facility()));
+                let severity = match mapping.and_then(|mapping| mapping.severity) {
+                    Some(severity) => OTELSeverity::from(SyslogSeverity::from(severity)),
+                    None => OTELSeverity::from(syslog.severity()),
+                };
 
-                let mut free_fields: HashMap<String, serde_json::Value> = HashMap::new();
+                let mut free_fields: HashMap<String, serde_json::Value> = HashMap::with_capacity(4);
                 free_fields.insert("facility".into(), syslog.facility().as_str_name().into());
                 if let Some(pid) = syslog.proc_pid {
                     free_fields.insert("proc_pid".into(), pid.into());
@@ -297,41 +1008,390 @@ impl TryFrom<LogLine> for IndexLogEntry {
                     free_fields.insert("msgid".into(), msgid.into());
                 }
                 let message = syslog.msg;
-                let service_name = syslog.appname.unwrap_or_else(|| "_syslog".into());
-                let timestamp_ms = timestamp.seconds * 1000 + (timestamp.nanos as i64) / 1_000_000;
+                let service_name = match mapping.and_then(|mapping| mapping.service_name.clone()) {
+                    Some(service_name) => service_name,
+                    None => syslog
+                        .service_name
+                        .or(syslog.appname)
+                        .unwrap_or_else(|| "_syslog".into()),
+                };
+                if let Some(mapping) = mapping {
+                    for (name, value) in &mapping.labels {
+                        free_fields.insert(name.clone(), value.clone().into());
+                    }
+                }
 
                 Ok(IndexLogEntry {
                     message,
-                    timestamp: timestamp_ms as u64,
+                    timestamp,
                     hostname,
                     service_name,
-                    severity_text,
-                    severity_number: severity_number as u64,
+                    severity_text: severity.severity_text().into(),
+                    severity_number: severity as u64,
                     log_system: LogSystem::Syslog,
+                    hmac_verified: false,
+                    indexed_fields: HashMap::new(),
+                    doc_id: None,
+                    doc_id_v: None,
                     free_fields,
                 })
             }
             rlog_grpc::rlog_service_protocol::log_line::Line::GenericLog(generic) => {
                 let severity = OTELSeverity::from(generic.severity());
                 let message = generic.message;
-                let extra: HashMap<String, serde_json::Value> =
-                    serde_json::from_str(&generic.extra)
-                        .context("`extra` field is not a valid json object")?;
+                let extra = parse_extra(&generic.extra)?;
 
-                let severity_text = severity.to_string();
-                let severity_number = severity as u8;
-                let timestamp_ms = timestamp.seconds * 1000 + (timestamp.nanos as i64) / 1_000_000;
                 Ok(IndexLogEntry {
                     message,
-                    timestamp: timestamp_ms as u64,
+                    timestamp,
                     hostname,
                     service_name: generic.service_name,
-                    severity_text,
-                    severity_number: severity_number as u64,
+                    severity_text: severity.severity_text().into(),
+                    severity_number: severity as u64,
                     log_system: LogSystem::Generic(generic.log_system),
+                    hmac_verified: false,
+                    indexed_fields: HashMap::new(),
+                    doc_id: None,
+                    doc_id_v: None,
                     free_fields: extra,
                 })
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, time::Duration};
+
+    use rlog_grpc::{
+        prost_wkt_types::Timestamp,
+        rlog_service_protocol::{
+            log_line::Line, GenericLogLine, LogLine, SyslogFacility, SyslogLogLine, SyslogSeverity,
+        },
+    };
+    use serde_json::json;
+
+    use super::{
+        count_rejected_documents, count_sla_violations, is_too_old, Batch, IndexLogEntry,
+        IngestApi, LogSystem,
+    };
+    use crate::config::{
+        FacilityName, QuickwitApiVersion, QuickwitCommitMode, Severity, SyslogFacilityMapping,
+        TimestampPrecision,
+    };
+
+    fn entry(timestamp: u64) -> IndexLogEntry {
+        IndexLogEntry {
+            message: "hello".into(),
+            timestamp,
+            hostname: "my_host".into(),
+            service_name: "my_service".into(),
+            severity_text: "INFO".into(),
+            severity_number: 9,
+            log_system: LogSystem::Syslog,
+            hmac_verified: false,
+            indexed_fields: HashMap::new(),
+            doc_id: None,
+            doc_id_v: None,
+            free_fields: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_count_sla_violations() {
+        let now_ms = 1_700_000_300_000;
+        let batch = [
+            // 5 minutes late
+            entry(1_700_000_000_000),
+            entry(1_700_000_000_001),
+            entry(1_699_999_999_999),
+            // from the future
+            entry(1_700_000_400_000),
+        ];
+        let ms = TimestampPrecision::Milliseconds;
+        assert_eq!(1, count_sla_violations(&batch, now_ms, 300_000, ms));
+        assert_eq!(0, count_sla_violations(&batch, now_ms, 600_000, ms));
+        assert_eq!(3, count_sla_violations(&batch, now_ms, 0, ms));
+
+        let batch = [
+            entry(1_699_999_999_999_999_999),
+            entry(1_700_000_000_001_000_000),
+        ];
+        let ns = TimestampPrecision::Nanoseconds;
+        assert_eq!(1, count_sla_violations(&batch, now_ms, 300_000, ns));
+    }
+
+    #[test]
+    fn test_count_rejected_documents() {
+        let response = |body: &str| Ok(body.to_string());
+        assert_eq!(
+            1,
+            count_rejected_documents(
+                response(r#"{"num_docs_for_processing": 3, "num_rejected_docs": 1}"#),
+                3
+            )
+        );
+        assert_eq!(
+            2,
+            count_rejected_documents(
+                response(
+                    r#"{"num_docs_for_processing": 3, "parse_failures": [{"message": "a"}, {"message": "b"}]}"#
+                ),
+                3
+            )
+        );
+        // more rejected documents than sent
+        assert_eq!(
+            3,
+            count_rejected_documents(
+                response(r#"{"num_docs_for_processing": 3, "num_rejected_docs": 5}"#),
+                3
+            )
+        );
+        assert_eq!(0, count_rejected_documents(response("not json"), 3));
+    }
+
+    #[test]
+    fn test_doc_id() {
+        let id = entry(1_700_000_000_000).doc_id();
+        assert_eq!(64, id.len());
+        assert_eq!(id, entry(1_700_000_000_000).doc_id());
+        // fields not hashed
+        let mut other = entry(1_700_000_000_000);
+        other.severity_number = 17;
+        other.free_fields.insert("pid".into(), 42.into());
+        assert_eq!(id, other.doc_id());
+
+        let mut changed = Vec::new();
+        changed.push(entry(1_700_000_000_001));
+        let mut other = entry(1_700_000_000_000);
+        other.message = "hello!".into();
+        changed.push(other);
+        let mut other = entry(1_700_000_000_000);
+        other.hostname = "my_host2".into();
+        changed.push(other);
+        let mut other = entry(1_700_000_000_000);
+        other.service_name = "my_service2".into();
+        changed.push(other);
+        // moving characters from a field to another
+        let mut other = entry(1_700_000_000_000);
+        other.hostname = "my_hostm".into();
+        other.service_name = "y_service".into();
+        changed.push(other);
+        for other in changed {
+            assert_ne!(id, other.doc_id(), "{other:?}");
+        }
+    }
+
+    #[test]
+    fn test_promote_fields() {
+        let mut promoted = entry(1_700_000_000_000);
+        promoted.free_fields.insert("pid".into(), 42.into());
+        promoted
+            .free_fields
+            .insert("request".into(), json!({"method": "GET", "ids": [1, 2]}));
+        promoted.free_fields.insert("thread".into(), "main".into());
+
+        promoted.promote_fields(&["pid".into(), "request".into(), "missing".into()]);
+        assert_eq!(
+            HashMap::from([
+                ("pid".to_string(), json!(42)),
+                // non scalar values are promoted as is
+                (
+                    "request".to_string(),
+                    json!({"method": "GET", "ids": [1, 2]})
+                ),
+            ]),
+            promoted.indexed_fields
+        );
+        assert_eq!(
+            HashMap::from([("thread".to_string(), json!("main"))]),
+            promoted.free_fields
+        );
+
+        // the `indexed_fields` json field of the quickwit schema is an object
+        let document = serde_json::to_value(&promoted).unwrap();
+        assert_eq!(
+            json!({"pid": 42, "request": {"method": "GET", "ids": [1, 2]}}),
+            document["indexed_fields"]
+        );
+        assert_eq!(json!("main"), document["thread"]);
+        // nothing promoted: no `indexed_fields` field
+        let document = serde_json::to_value(entry(1_700_000_000_000)).unwrap();
+        assert!(document.get("indexed_fields").is_none());
+    }
+
+    #[test]
+    fn test_is_too_old() {
+        let now_ms = 1_700_000_300_000;
+        let max_age = Duration::from_secs(300);
+        let ms = TimestampPrecision::Milliseconds;
+        // exactly at the limit
+        assert!(!is_too_old(&entry(1_700_000_000_000), now_ms, max_age, ms));
+        assert!(is_too_old(&entry(1_699_999_999_999), now_ms, max_age, ms));
+        assert!(!is_too_old(&entry(1_700_000_400_000), now_ms, max_age, ms));
+        assert!(!is_too_old(&entry(0), now_ms, Duration::ZERO, ms));
+
+        let us = TimestampPrecision::Microseconds;
+        assert!(!is_too_old(
+            &entry(1_700_000_000_000_999),
+            now_ms,
+            max_age,
+            us
+        ));
+        assert!(is_too_old(
+            &entry(1_699_999_999_999_999),
+            now_ms,
+            max_age,
+            us
+        ));
+    }
+
+    #[test]
+    fn test_timestamp_precision() {
+        let log_line = || LogLine {
+            timestamp: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 123_456_789,
+            }),
+            line: Some(Line::GenericLog(GenericLogLine {
+                log_system: "app".into(),
+                extra: "{}".into(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        for (precision, timestamp) in [
+            (TimestampPrecision::Milliseconds, 1_700_000_000_123),
+            (TimestampPrecision::Microseconds, 1_700_000_000_123_456),
+            (TimestampPrecision::Nanoseconds, 1_700_000_000_123_456_789),
+        ] {
+            let entry =
+                IndexLogEntry::try_from_line(log_line(), precision, &HashMap::new()).unwrap();
+            assert_eq!(timestamp, entry.timestamp);
+            assert_eq!(1_700_000_000_123, precision.to_millis(entry.timestamp));
+        }
+    }
+
+    #[test]
+    fn test_syslog_facility_mapping() {
+        let log_line = |facility: SyslogFacility| LogLine {
+            timestamp: Some(Timestamp::default()),
+            line: Some(Line::Syslog(SyslogLogLine {
+                facility: facility as i32,
+                severity: SyslogSeverity::Info as i32,
+                appname: Some("postfix".into()),
+                service_name: Some("postfix".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let facility_mapping = HashMap::from([
+            (
+                FacilityName(SyslogFacility::Mail),
+                SyslogFacilityMapping {
+                    service_name: Some("mail".into()),
+                    severity: None,
+                    labels: HashMap::from([("group".into(), "messaging".into())]),
+                },
+            ),
+            (
+                FacilityName(SyslogFacility::Authpriv),
+                SyslogFacilityMapping {
+                    severity: Some(Severity::Notice),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let entry = |facility| {
+            IndexLogEntry::try_from_line(
+                log_line(facility),
+                TimestampPrecision::Milliseconds,
+                &facility_mapping,
+            )
+            .unwrap()
+        };
+
+        let mail = entry(SyslogFacility::Mail);
+        assert_eq!("mail", mail.service_name);
+        assert_eq!("INFO", mail.severity_text);
+        assert_eq!("messaging", mail.free_fields["group"]);
+        assert_eq!("mail", mail.free_fields["facility"]);
+
+        let authpriv = entry(SyslogFacility::Authpriv);
+        assert_eq!("postfix", authpriv.service_name);
+        assert_eq!("INFO3", authpriv.severity_text);
+
+        // not mapped
+        let daemon = entry(SyslogFacility::Daemon);
+        assert_eq!("postfix", daemon.service_name);
+        assert_eq!("INFO", daemon.severity_text);
+        assert!(!daemon.free_fields.contains_key("group"));
+    }
+
+    #[test]
+    fn test_ingest_urls() {
+        let ingest_urls = |commit_mode| {
+            let mut ingest_api = IngestApi::new(
+                "http://quickwit:7280/".parse().unwrap(),
+                "rlog",
+                reqwest::Client::new(),
+                QuickwitApiVersion::V1,
+                commit_mode,
+            )
+            .unwrap();
+            let v1 = ingest_api.ingest_url().to_string();
+            ingest_api.version = QuickwitApiVersion::V2;
+            (v1, ingest_api.ingest_url().to_string())
+        };
+        assert_eq!(
+            (
+                "http://quickwit:7280/api/v1/rlog/ingest".to_string(),
+                "http://quickwit:7280/api/v1/rlog/ingest-v2?detailed_response=true".to_string()
+            ),
+            ingest_urls(QuickwitCommitMode::Auto)
+        );
+        assert_eq!(
+            (
+                "http://quickwit:7280/api/v1/rlog/ingest?commit=force".to_string(),
+                "http://quickwit:7280/api/v1/rlog/ingest-v2?detailed_response=true&commit=force"
+                    .to_string()
+            ),
+            ingest_urls(QuickwitCommitMode::Force)
+        );
+    }
+
+    #[test]
+    fn test_retried_body() {
+        let mut batch = Batch::None;
+        batch.push_elements(vec![1, 2, 3, 4]);
+        let (elements, body) = batch.pop_elements().unwrap();
+        assert_eq!(None, body);
+
+        // retried as is: the body is reused
+        batch.retry_elements(elements, "1\n2\n3\n4".into());
+        let (elements, body) = batch.pop_elements().unwrap();
+        assert_eq!(vec![1, 2, 3, 4], elements);
+        assert_eq!(Some("1\n2\n3\n4".into()), body);
+
+        // split: serialized again
+        batch.split_because_of_err(elements);
+        assert_eq!(Some((vec![1, 2], None)), batch.pop_elements());
+        batch.retry_elements(vec![1, 2], "1\n2".into());
+        // sent with the remaining elements
+        assert_eq!(Some((vec![3, 4, 1, 2], None)), batch.pop_elements());
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_splits() {
+        let mut batch = Batch::None;
+        batch.split_because_of_err(vec![1, 2, 3, 4]);
+        // another concurrent request is too large before the first half is sent
+        batch.split_because_of_err(vec![5, 6, 7, 8]);
+        assert_eq!(Some((vec![1, 2], None)), batch.pop_elements());
+        assert_eq!(Some((vec![3, 4, 5, 6, 7, 8], None)), batch.pop_elements());
+        assert!(batch.is_empty());
+    }
+}
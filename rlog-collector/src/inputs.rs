@@ -0,0 +1,161 @@
+//! All-in-one deployment: the GELF & syslog inputs of the shipper are started by the
+//! collector, received log lines are fed to the gRPC handler (without any transport).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_channel::{Receiver, Sender};
+use rlog_common::utils::format_error;
+use rlog_grpc::{
+    rlog_service_protocol::{log_collector_server::LogCollector, LogLine},
+    tonic::{Code, Request},
+};
+use rlog_inputs::{
+    byte_budget::{Budgeted, ByteBudget, Reservation, DEFAULT_MAX_BUFFERED_BYTES},
+    config::CommonInputConfig,
+    gelf_server::launch_gelf_server,
+    metrics::{
+        GELF_ERROR_COUNT, GELF_PROCESSED_COUNT, GELF_QUEUE_COUNT, SYSLOG_ERROR_COUNT,
+        SYSLOG_PROCESSED_COUNT, SYSLOG_QUEUE_COUNT,
+    },
+    recent_inputs::DEBUG_SAMPLE_RATE,
+    syslog_server::launch_syslog_udp_server,
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{Config, CONFIG},
+    grpc_server::LogCollectorServer,
+    index::IndexLogEntry,
+};
+
+/// Budget shared by the inputs, released once log lines are in the batch queue
+static INPUTS_BYTE_BUDGET: ByteBudget = ByteBudget::new();
+
+fn reserve(size: usize) -> Option<Reservation> {
+    INPUTS_BYTE_BUDGET.try_reserve(size, DEFAULT_MAX_BUFFERED_BYTES)
+}
+
+struct InputMetrics {
+    queue_count: &'static AtomicU64,
+    processed_count: &'static AtomicU64,
+    error_count: &'static AtomicU64,
+}
+
+/// Start the enabled inputs configured in the `gelf_in` & `syslog_in` sections, the process
+/// exits if an input cannot be bound.
+pub fn launch_inputs(
+    log_sender: &Sender<IndexLogEntry>,
+    shutdown_token: CancellationToken,
+) -> Vec<JoinHandle<()>> {
+    let config = CONFIG.load();
+    // raw inputs are dumped at the rate of the received log lines
+    DEBUG_SAMPLE_RATE.store(config.collector_debug_sample_rate, Ordering::Relaxed);
+    let mut inputs = Vec::new();
+    if config.gelf_in.as_ref().is_some_and(|c| c.common.enabled) {
+        let bind_address = config.collector_gelf_in_bind_address;
+        let server = LogCollectorServer::new(log_sender.clone());
+        let shutdown_token = shutdown_token.child_token();
+        inputs.push(tokio::spawn(async move {
+            let receiver = match launch_gelf_server(
+                bind_address,
+                CONFIG.map(|c: &Config| &c.gelf_in),
+                reserve,
+                shutdown_token,
+            )
+            .await
+            {
+                Ok(receiver) => receiver,
+                Err(e) => {
+                    tracing::error!(error = %format_error(e), "Unable to launch gelf_in input");
+                    std::process::exit(1);
+                }
+            };
+            forward_loop(
+                receiver,
+                |log| log.into_log_line(CONFIG.load().gelf_in.as_ref()),
+                server,
+                "gelf_in",
+                |c| c.gelf_in.as_ref().map(|c| &c.common),
+                InputMetrics {
+                    queue_count: &GELF_QUEUE_COUNT,
+                    processed_count: &GELF_PROCESSED_COUNT,
+                    error_count: &GELF_ERROR_COUNT,
+                },
+            )
+            .await
+        }));
+    }
+    if config.syslog_in.as_ref().is_some_and(|c| c.common.enabled) {
+        let bind_address = config.collector_syslog_in_bind_address;
+        let server = LogCollectorServer::new(log_sender.clone());
+        let shutdown_token = shutdown_token.child_token();
+        inputs.push(tokio::spawn(async move {
+            let receiver = match launch_syslog_udp_server(
+                bind_address,
+                CONFIG.map(|c: &Config| &c.syslog_in),
+                reserve,
+                shutdown_token,
+            )
+            .await
+            {
+                Ok(receiver) => receiver,
+                Err(e) => {
+                    tracing::error!(error = %format_error(e), "Unable to launch syslog_in input");
+                    std::process::exit(1);
+                }
+            };
+            forward_loop(
+                receiver,
+                |log| log.into_log_line(CONFIG.load().syslog_in.as_ref()),
+                server,
+                "syslog_in",
+                |c| c.syslog_in.as_ref().map(|c| &c.common),
+                InputMetrics {
+                    queue_count: &SYSLOG_QUEUE_COUNT,
+                    processed_count: &SYSLOG_PROCESSED_COUNT,
+                    error_count: &SYSLOG_ERROR_COUNT,
+                },
+            )
+            .await
+        }));
+    }
+    inputs
+}
+
+async fn forward_loop<T>(
+    input: Receiver<Budgeted<T>>,
+    into_log_line: fn(T) -> anyhow::Result<LogLine>,
+    server: LogCollectorServer,
+    input_name: &str,
+    input_config: fn(&Config) -> Option<&CommonInputConfig>,
+    metrics: InputMetrics,
+) {
+    while let Ok(log) = input.recv().await {
+        metrics.queue_count.fetch_sub(1, Ordering::Relaxed);
+        metrics.processed_count.fetch_add(1, Ordering::Relaxed);
+        let mut log_line = match log.try_map(into_log_line) {
+            Ok(log_line) => log_line,
+            Err(e) => {
+                metrics.error_count.fetch_add(1, Ordering::Relaxed);
+                tracing::error!(
+                    error = %format_error(e),
+                    "received an invalid log from {input_name}!"
+                );
+                continue;
+            }
+        };
+        if let Some(input_config) = input_config(&CONFIG.load()) {
+            log_line.value.labels = input_config.labels.clone();
+        }
+        // invalid log lines are reported by the handler
+        if let Err(status) = server.log(Request::new(log_line.value)).await {
+            metrics.error_count.fetch_add(1, Ordering::Relaxed);
+            if status.code() == Code::Unavailable {
+                tracing::error!("Batch channel closed! {}", status.message());
+                break;
+            }
+        }
+    }
+    tracing::info!("{input_name} input channel closed, {input_name} forward task stopped.");
+}
@@ -1,4 +1,9 @@
+use std::sync::Arc;
+
 use anyhow::Context;
+
+use futures::{future::join_all, StreamExt};
+use rlog_common::bind_addr::BindAddr;
 use rlog_grpc::{
     rlog_service_protocol::log_collector_server::LogCollectorServer, tonic::transport::Server,
 };
@@ -6,42 +11,75 @@ use tokio::{join, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
 
 use crate::config::{Config, CONFIG};
+use crate::output_errors::OutputErrors;
 
 mod batch;
 pub mod config;
+mod delivery_windows;
+mod extra_cache;
+mod flatten;
 mod grpc_server;
+mod grpc_tls;
 mod http_status_server;
+pub mod in_process;
 mod index;
+mod inputs;
 pub mod metrics;
+mod mirror;
+mod output;
+mod output_errors;
+mod s3;
+mod severity_overrides;
+mod shipper_identity;
+mod shipper_incarnations;
+mod truncate;
 
+pub use crate::grpc_tls::GrpcTlsConfig;
+pub use crate::http_status_server::HttpStatusTlsConfig;
 pub use crate::index::IndexLogEntry;
 pub use crate::index::LogSystem;
+pub use crate::output::{OutputBackend, QuickwitOutput};
+pub use crate::s3::S3Output;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 pub struct CollectorServer {
     shutdown_token: CancellationToken,
     indexer_handle: JoinHandle<()>,
+    /// all-in-one GELF & syslog inputs, stopped before the batch task
+    inputs: Vec<JoinHandle<()>>,
+    inputs_shutdown_token: CancellationToken,
+    /// stopped with the batch task, in flight requests are answered
+    http_status_server: JoinHandle<()>,
 }
 
 pub struct CollectorServerConfig {
-    pub http_status_bind_address: String,
-    pub grpc_bind_address: String,
-    pub quickwit_rest_url: String,
-    pub quickwit_index_id: String,
+    pub http_status_bind_address: BindAddr,
+    /// the HTTP status server is served over plain HTTP if not set
+    pub http_status_tls: Option<HttpStatusTlsConfig>,
+    pub grpc_bind_address: BindAddr,
+    /// if set, gRPC TLS is handled by rlog instead of the `server` TLS config (which must
+    /// not be set), eg: to enforce a minimum TLS version
+    pub grpc_tls: Option<GrpcTlsConfig>,
+    /// serve the gRPC reflection service (`grpcurl` & co), exposes the protocol schema
+    pub enable_reflection: bool,
+    /// every batch is sent to all the outputs concurrently, the flushes are done once all of
+    /// them have accepted the batches, the first quickwit output serves the
+    /// `/quickwit/metrics` status route
+    pub outputs: Vec<OutputBackend>,
+    /// log entries indexed more than this delay (milliseconds) after their timestamp are
+    /// counted in `rlog_collector_sla_violation_count`, disabled if not set
+    pub index_sla_threshold_ms: Option<u64>,
     pub server: Server,
 }
 
 impl CollectorServer {
     pub fn start_collector_server(config: CollectorServerConfig) -> anyhow::Result<Self> {
-        http_status_server::launch_server(
-            &config.http_status_bind_address,
-            &config.quickwit_rest_url,
-        )?;
+        let output_errors = Arc::new(OutputErrors::default());
 
         let shutdown_token = CancellationToken::new();
 
-        let (log_sender, batch_log_receiver) = batch::launch_batch_collector(
+        let (log_sender, batches, flusher) = batch::launch_batch_collector(
             CONFIG.map(|c: &Config| &c.collector_quickwit_batch_max_interval),
             CONFIG.map(|c: &Config| &c.collector_quickwit_batch_size),
             CONFIG.map(|c: &Config| &c.collector_input_buffer_size),
@@ -50,42 +88,93 @@ impl CollectorServer {
         );
 
         let indexer_handle = index::launch_index_loop(
-            &config.quickwit_rest_url,
-            &config.quickwit_index_id,
-            batch_log_receiver,
+            &config.outputs,
+            batches,
+            output_errors.clone(),
+            config.index_sla_threshold_ms,
+            shutdown_token.child_token(),
+        )?;
+
+        let http_status_server = http_status_server::launch_server(
+            config.http_status_bind_address,
+            config.outputs.iter().find_map(|output| match output {
+                OutputBackend::Quickwit(quickwit) => Some(quickwit.rest_url.as_str()),
+                _ => None,
+            }),
+            output_errors,
+            flusher,
+            config.http_status_tls.as_ref(),
+            shutdown_token.child_token(),
         )?;
-        let addr = config
-            .grpc_bind_address
-            .parse()
-            .context("Invalid grpc bind address")?;
+
+        let inputs_shutdown_token = CancellationToken::new();
+        let inputs = inputs::launch_inputs(&log_sender, inputs_shutdown_token.clone());
+
+        let addr = config.grpc_bind_address.socket_addr();
+        let reflection = if config.enable_reflection {
+            Some(
+                tonic_reflection::server::Builder::configure()
+                    .register_encoded_file_descriptor_set(rlog_grpc::FILE_DESCRIPTOR_SET)
+                    .build()
+                    .context("Unable to build the gRPC reflection service")?,
+            )
+        } else {
+            None
+        };
+
+        let tls_acceptor = config
+            .grpc_tls
+            .as_ref()
+            .map(GrpcTlsConfig::acceptor)
+            .transpose()
+            .context("Invalid TLS configuration")?;
 
         tracing::info!("Starting rlog-collector gRPC server at {addr}");
         tokio::spawn(async move {
             let mut server = config.server;
-            if let Err(e) = server
+            let router = server
                 .add_service(LogCollectorServer::new(
                     grpc_server::LogCollectorServer::new(log_sender),
                 ))
-                .serve(addr)
-                .await
-            {
-                tracing::error!("Unable to launch gRPC server: {e}");
+                .add_optional_service(reflection);
+            let result = match tls_acceptor {
+                Some(tls_acceptor) => match grpc_tls::tls_incoming(addr, tls_acceptor).await {
+                    Ok(incoming) => router
+                        .serve_with_incoming(incoming.map(Ok::<_, std::io::Error>))
+                        .await
+                        .map_err(anyhow::Error::from),
+                    Err(e) => Err(e),
+                },
+                None => router.serve(addr).await.map_err(anyhow::Error::from),
+            };
+            if let Err(e) = result {
+                tracing::error!("Unable to launch gRPC server at {addr}: {e}");
                 std::process::exit(1);
             }
         });
         Ok(Self {
             shutdown_token,
             indexer_handle,
+            inputs,
+            inputs_shutdown_token,
+            http_status_server,
         })
     }
 
     pub async fn shutdown(self) {
+        // logs received by the all-in-one inputs are sent to the batch task first
+        self.inputs_shutdown_token.cancel();
+        join_all(self.inputs).await;
         self.shutdown_token.cancel();
         // we only need to wait for the indexer task to terminate
         // the shutdown_token will properly terminate the batch task this will
-        // - close the batch channel after laft batch
         // - close the send channel to the batch task, the server will
-        //   always answer "unavailable" to shippers
-        let _ = join!(self.indexer_handle);
+        //   always answer "unavailable" to shippers, every acknowledged log
+        //   is in the channel
+        // - drain the send channel in a last batch
+        // - close the batch channel once the last batch is sent, the indexer
+        //   sends all remaining batches before exiting
+        // pending batches are retried until `collector_shutdown_flush_timeout`
+        let _ = join!(self.indexer_handle, self.http_status_server);
     }
 }
@@ -2,9 +2,15 @@ use std::time::Duration;
 
 use anyhow::Context;
 use clap::Parser;
-use rlog_collector::{config::CONFIG, CollectorServer, CollectorServerConfig};
+use rlog_collector::{
+    config::{Config, CONFIG},
+    CollectorServer, CollectorServerConfig, GrpcTlsConfig, HttpStatusTlsConfig, OutputBackend,
+    QuickwitOutput, S3Output,
+};
 use rlog_common::{
+    bind_addr::BindAddr,
     config::setup_config_from_file,
+    tls::TlsVersion,
     utils::{init_logging, read_file},
 };
 use rlog_grpc::tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
@@ -16,17 +22,25 @@ use rlog_collector::metrics::launch_async_process_collector;
 #[derive(Debug, Parser)]
 struct Opts {
     /// trusted CA certificate used for mTLS connection
-    #[arg(long, env)]
-    tls_ca_certificate: String,
+    #[arg(long, env, required_unless_present = "generate_config")]
+    tls_ca_certificate: Option<String>,
     /// private key used for mTLS connection
-    #[arg(long, env)]
-    tls_private_key: String,
+    #[arg(long, env, required_unless_present = "generate_config")]
+    tls_private_key: Option<String>,
     /// certificate, signed by the CA corresponding to the private key
-    #[arg(long, env)]
-    tls_certificate: String,
+    #[arg(long, env, required_unless_present = "generate_config")]
+    tls_certificate: Option<String>,
+    /// Minimum TLS version of the gRPC connections: `1.2` or `1.3`, shippers using an older
+    /// version are rejected during the handshake
+    #[arg(long, env, default_value = "1.2")]
+    tls_min_version: TlsVersion,
 
+    #[arg(long, env, required_unless_present = "generate_config")]
+    grpc_bind_address: Option<BindAddr>,
+
+    /// Serve the gRPC reflection service (for `grpcurl` & co), exposes the protocol schema
     #[arg(long, env)]
-    grpc_bind_address: String,
+    enable_reflection: bool,
 
     #[arg(long, env, default_value = "http://127.0.0.1:7280")]
     quickwit_rest_url: String,
@@ -34,13 +48,73 @@ struct Opts {
     #[arg(long, env, default_value = "rlog")]
     quickwit_index_id: String,
 
+    /// extra header added to all quickwit requests (not sent to the quickwit mirrors),
+    /// `name: value`, can be repeated
+    #[arg(long, env, value_parser = parse_header)]
+    quickwit_header: Vec<(String, String)>,
+
+    /// S3 bucket receiving a copy of every batch (gzipped JSON lines objects, eg: for cold
+    /// storage), in addition to quickwit, disabled if not set
+    #[arg(long, env)]
+    s3_output_bucket: Option<String>,
+
+    /// S3 endpoint, the objects are addressed path-style (`<endpoint>/<bucket>/<key>`),
+    /// default: `https://s3.<region>.amazonaws.com`
+    #[arg(long, env)]
+    s3_output_endpoint: Option<String>,
+
+    #[arg(long, env, default_value = "us-east-1")]
+    s3_output_region: String,
+
+    /// prefix of the object keys, followed by `YYYY/MM/DD/<timestamp>-<uuid>.ndjson.gz`
+    #[arg(long, env, default_value = "rlog/")]
+    s3_output_prefix: String,
+
+    /// a batch is not uploaded to S3 once this number of retries failed
+    #[arg(long, env, default_value = "5")]
+    s3_output_max_retries: u32,
+
+    #[arg(long, env, requires = "s3_output_bucket")]
+    aws_access_key_id: Option<String>,
+
+    #[arg(long, env, requires = "s3_output_bucket", hide_env_values = true)]
+    aws_secret_access_key: Option<String>,
+
+    /// Log entries indexed more than this number of milliseconds after their timestamp are
+    /// counted in the `rlog_collector_sla_violation_count` metric, disabled if not set
+    #[arg(long, env)]
+    index_sla_threshold_ms: Option<u64>,
+
     /// HTTP status server (/health, /metrics)
     #[arg(long, env, default_value = "0.0.0.0:21040")]
-    http_status_bind_address: String,
+    http_status_bind_address: BindAddr,
+
+    /// Serve the HTTP status server over TLS, using the gRPC certificate & private key unless
+    /// --http-status-tls-certificate & --http-status-tls-private-key are given
+    #[arg(long, env)]
+    http_status_tls: bool,
+    /// certificate of the HTTP status server, implies --http-status-tls
+    #[arg(long, env, requires = "http_status_tls_private_key")]
+    http_status_tls_certificate: Option<String>,
+    /// private key of the HTTP status server, implies --http-status-tls
+    #[arg(long, env, requires = "http_status_tls_certificate")]
+    http_status_tls_private_key: Option<String>,
 
     /// Configuration file, if not provided, a minimal default configuration will be used
     #[arg(long, short, env)]
     config: Option<String>,
+
+    /// Print the configuration with the default values, each option commented (purpose,
+    /// valid values, hot reload support), and exit.
+    #[arg(long, alias = "print-default-config")]
+    generate_config: bool,
+}
+
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    header
+        .split_once(':')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("{header} is not a `name: value` header"))
 }
 
 #[tokio::main]
@@ -50,6 +124,11 @@ async fn main() -> anyhow::Result<()> {
     };
     let opts = Opts::parse();
 
+    if opts.generate_config {
+        print!("{}", Config::template_yaml()?);
+        return Ok(());
+    }
+
     init_logging();
 
     if let Some(path) = opts.config.as_ref() {
@@ -62,29 +141,96 @@ async fn main() -> anyhow::Result<()> {
         serde_yaml::to_string(CONFIG.load().as_ref())?
     );
 
-    launch_async_process_collector(Duration::from_millis(500));
+    launch_async_process_collector(CONFIG.load().collector_process_metrics_interval);
+
+    // only optional with --generate-config
+    let tls_certificate = opts.tls_certificate.unwrap_or_default();
+    let tls_private_key = opts.tls_private_key.unwrap_or_default();
+    let tls_ca_certificate = opts.tls_ca_certificate.unwrap_or_default();
+    let grpc_bind_address = opts
+        .grpc_bind_address
+        .context("--grpc-bind-address is required")?;
+
+    let certificate = read_file(&tls_certificate).context("Cannot open certificate")?;
+    let private_key = read_file(&tls_private_key).context("Cannot open private key")?;
+    let ca_certificate = read_file(&tls_ca_certificate).context("Cannot open ca certificate")?;
 
     let server = Server::builder()
         // always setup tcp keepalive
-        .tcp_keepalive(Some(Duration::from_secs(25)))
-        // tls config
-        .tls_config(
-            ServerTlsConfig::new()
-                .identity(Identity::from_pem(
-                    read_file(&opts.tls_certificate).context("Cannot open certificate")?,
-                    read_file(&opts.tls_private_key).context("Cannot open private key")?,
-                ))
-                .client_ca_root(Certificate::from_pem(
-                    read_file(&opts.tls_ca_certificate).context("Cannot open ca certificate")?,
-                )),
+        .tcp_keepalive(Some(Duration::from_secs(25)));
+    // tonic TLS is TLS 1.2+
+    let (server, grpc_tls) = if opts.tls_min_version > TlsVersion::Tls12 {
+        (
+            server,
+            Some(GrpcTlsConfig {
+                ca_certificate_pem: ca_certificate,
+                certificate_pem: certificate,
+                private_key_pem: private_key,
+                min_tls_version: opts.tls_min_version,
+            }),
         )
-        .context("Invalid TLS configuration")?;
+    } else {
+        (
+            server
+                .tls_config(
+                    ServerTlsConfig::new()
+                        .identity(Identity::from_pem(certificate, private_key))
+                        .client_ca_root(Certificate::from_pem(ca_certificate)),
+                )
+                .context("Invalid TLS configuration")?,
+            None,
+        )
+    };
+
+    let http_status_tls = match (
+        &opts.http_status_tls_certificate,
+        &opts.http_status_tls_private_key,
+    ) {
+        (Some(certificate), Some(private_key)) => Some((certificate, private_key)),
+        _ if opts.http_status_tls => Some((&tls_certificate, &tls_private_key)),
+        _ => None,
+    }
+    .map(|(certificate, private_key)| {
+        anyhow::Ok(HttpStatusTlsConfig {
+            certificate_pem: read_file(certificate)
+                .context("Cannot open HTTP status server certificate")?,
+            private_key_pem: read_file(private_key)
+                .context("Cannot open HTTP status server private key")?,
+        })
+    })
+    .transpose()?;
+
+    let mut outputs = vec![OutputBackend::Quickwit(QuickwitOutput {
+        rest_url: opts.quickwit_rest_url,
+        index_id: opts.quickwit_index_id,
+        extra_headers: opts.quickwit_header.into_iter().collect(),
+    })];
+    if let Some(bucket) = opts.s3_output_bucket {
+        outputs.push(OutputBackend::S3(S3Output {
+            endpoint: opts
+                .s3_output_endpoint
+                .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", opts.s3_output_region)),
+            region: opts.s3_output_region,
+            bucket,
+            prefix: opts.s3_output_prefix,
+            access_key_id: opts
+                .aws_access_key_id
+                .context("--aws-access-key-id is required with --s3-output-bucket")?,
+            secret_access_key: opts
+                .aws_secret_access_key
+                .context("--aws-secret-access-key is required with --s3-output-bucket")?,
+            max_retries: opts.s3_output_max_retries,
+        }));
+    }
 
     let collector_server = CollectorServer::start_collector_server(CollectorServerConfig {
         http_status_bind_address: opts.http_status_bind_address,
-        grpc_bind_address: opts.grpc_bind_address,
-        quickwit_rest_url: opts.quickwit_rest_url,
-        quickwit_index_id: opts.quickwit_index_id,
+        http_status_tls,
+        grpc_bind_address,
+        grpc_tls,
+        enable_reflection: opts.enable_reflection,
+        outputs,
+        index_sla_threshold_ms: opts.index_sla_threshold_ms,
         server,
     })?;
 
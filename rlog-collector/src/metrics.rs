@@ -1,10 +1,18 @@
-use std::time::Duration;
+use std::{sync::atomic::Ordering, time::Duration};
 
 use lazy_static::lazy_static;
 use prometheus::{
-    register_int_counter, register_int_counter_vec, register_int_gauge_vec, Encoder, IntCounter,
-    IntCounterVec, IntGaugeVec, TextEncoder,
+    proto::{Metric, MetricFamily, MetricType},
+    register_int_counter, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    Encoder, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
 };
+use rlog_common::{
+    config::{CONFIG_LAST_RELOAD_TIMESTAMP, CONFIG_RELOAD_ERROR_COUNT, CONFIG_RELOAD_OK_COUNT},
+    metrics_sanitizer::MetricsSanitizer,
+};
+use serde_json::{json, Map, Value};
+
+use crate::config::CONFIG;
 
 lazy_static! {
     pub static ref SHIPPER_QUEUE_COUNT: IntGaugeVec = register_int_gauge_vec!(
@@ -13,6 +21,12 @@ lazy_static! {
         &["hostname", "queue_name"]
     )
     .unwrap();
+    pub static ref SHIPPER_QUEUE_CAPACITY: IntGaugeVec = register_int_gauge_vec!(
+        "rlog_shipper_queue_capacity",
+        "Maximum number of elements buffered in queues",
+        &["hostname", "queue_name"]
+    )
+    .unwrap();
     pub static ref SHIPPER_PROCESSED_COUNT: IntCounterVec = register_int_counter_vec!(
         "rlog_shipper_processed_count",
         "Number of elements buffered in queues",
@@ -25,27 +39,119 @@ lazy_static! {
         &["hostname", "queue_name"]
     )
     .unwrap();
+    pub static ref SHIPPER_RESTARTS: IntCounterVec = register_int_counter_vec!(
+        "rlog_shipper_restarts_total",
+        "Number of shipper restarts detected from reported metrics",
+        &["hostname"]
+    )
+    .unwrap();
     pub static ref COLLECTOR_INDEXED_COUNT: IntCounter = register_int_counter!(
         "rlog_collector_indexed_count",
         "Number of elements output to various systems",
     )
     .unwrap();
+    pub static ref COLLECTOR_REJECTED_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_rejected_count",
+        "Number of elements rejected by output systems",
+    )
+    .unwrap();
     pub static ref COLLECTOR_OUTPUT_COUNT: IntCounterVec = register_int_counter_vec!(
         "rlog_collector_output_request_count",
         "Number of output requests",
-        &["system", "status"]
+        &["system", "destination", "status"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_MIRROR_DROPPED_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_collector_mirror_dropped_count",
+        "Number of log entries not sent to a quickwit mirror",
+        &["destination", "reason"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_OUTPUT_DROPPED_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_collector_output_dropped_count",
+        "Number of log entries not sent to an output once its retries are exhausted",
+        &["system", "destination", "reason"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_EXTRA_CACHE_HIT_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_extra_cache_hit_count",
+        "Number of GELF & generic log extra fields found in the parsed extra cache",
+    )
+    .unwrap();
+    pub static ref COLLECTOR_EXTRA_CACHE_MISS_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_extra_cache_miss_count",
+        "Number of GELF & generic log extra fields parsed and added to the parsed extra cache",
+    )
+    .unwrap();
+    pub static ref COLLECTOR_SEVERITY_OVERRIDE_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_collector_severity_override_count",
+        "Number of log entries whose severity has been changed by each severity override rule",
+        &["rule"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_SLA_VIOLATION_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_sla_violation_count",
+        "Number of log entries indexed later than the indexing SLA threshold after their timestamp",
+    )
+    .unwrap();
+    pub static ref COLLECTOR_TOO_OLD_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_collector_too_old_count",
+        "Number of log entries dropped because they are older than `collector_max_log_age`",
+        &["service_name"]
+    )
+    .unwrap();
+    pub static ref COLLECTOR_TRUNCATED_FIELD_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_truncated_field_count",
+        "Number of free fields truncated or dropped because of their length",
+    )
+    .unwrap();
+    pub static ref COLLECTOR_DELIVERY_WINDOW_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_collector_delivery_window_count",
+        "Number of delivery windows reported by the shippers, by verification result",
+        &["hostname", "result"]
     )
     .unwrap();
+    pub static ref COLLECTOR_HOSTNAME_MISMATCH_COUNT: IntCounter = register_int_counter!(
+        "rlog_collector_hostname_mismatch_total",
+        "Number of metrics reports whose hostname does not match the shipper certificate",
+    )
+    .unwrap();
+    pub static ref COLLECTOR_LAST_OUTPUT_ERROR_TIMESTAMP: IntGauge = register_int_gauge!(
+        "rlog_collector_last_output_error_timestamp_seconds",
+        "Timestamp of the most recent output error",
+    )
+    .unwrap();
+    pub static ref CONFIG_RELOAD_COUNT: IntCounterVec = register_int_counter_vec!(
+        "rlog_config_reload_total",
+        "Number of configuration hot reloads",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref CONFIG_LAST_RELOAD_TIMESTAMP_GAUGE: IntGauge = register_int_gauge!(
+        "rlog_config_last_reload_timestamp",
+        "Timestamp of the load of the running configuration",
+    )
+    .unwrap();
+    /// Hostname labels of the shipper metrics, built from the configuration on first use
+    pub static ref METRICS_SANITIZER: MetricsSanitizer =
+        MetricsSanitizer::new(CONFIG.load().collector_metrics_sanitizer.clone());
 }
 
 pub const OUTPUT_STATUS_OK_LABEL_VALUE: &str = "ok";
 pub const OUTPUT_STATUS_ERROR_LABEL_VALUE: &str = "error";
 pub const OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE: &str = "toomany";
 pub const OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE: &str = "quickwit";
+pub const OUTPUT_SYSTEM_S3_LABEL_VALUE: &str = "s3";
+pub const OUTPUT_DESTINATION_PRIMARY_LABEL_VALUE: &str = "primary";
+pub const MIRROR_DROPPED_QUEUE_FULL_LABEL_VALUE: &str = "queue_full";
+/// reasons of the batches dropped by the best-effort outputs (mirrors, S3)
+pub const OUTPUT_DROPPED_RETRIES_LABEL_VALUE: &str = "retries_exhausted";
+pub const OUTPUT_DROPPED_SHUTDOWN_LABEL_VALUE: &str = "shutdown";
 
 /// Generate the content of /metrics prometheus metrics gathering endpoint.
 ///
 pub fn generate_metrics() -> String {
+    update_config_reload_metrics();
     // Gather the metrics.
     let mut buffer = vec![];
     let encoder = TextEncoder::new();
@@ -54,11 +160,104 @@ pub fn generate_metrics() -> String {
     String::from_utf8(buffer).unwrap()
 }
 
-/// Launch async process collector at specified interval. It requires a running tokio runtime!
+/// Generate the content of /metrics?format=json: the same metrics as the prometheus
+/// text format, as an array of metric families.
+pub fn generate_json_metrics() -> Value {
+    update_config_reload_metrics();
+    metric_families_to_json(&prometheus::gather())
+}
+
+/// Bring the configuration reload metrics to the counts of the configuration loaders
+fn update_config_reload_metrics() {
+    for (result, count) in [
+        (OUTPUT_STATUS_OK_LABEL_VALUE, &CONFIG_RELOAD_OK_COUNT),
+        (OUTPUT_STATUS_ERROR_LABEL_VALUE, &CONFIG_RELOAD_ERROR_COUNT),
+    ] {
+        let counter = CONFIG_RELOAD_COUNT.with_label_values(&[result]);
+        counter.inc_by(count.load(Ordering::Relaxed).saturating_sub(counter.get()));
+    }
+    CONFIG_LAST_RELOAD_TIMESTAMP_GAUGE
+        .set(CONFIG_LAST_RELOAD_TIMESTAMP.load(Ordering::Relaxed) as i64);
+}
+
+fn metric_families_to_json(metric_families: &[MetricFamily]) -> Value {
+    metric_families
+        .iter()
+        .map(|family| {
+            let field_type = family.get_field_type();
+            json!({
+                "name": family.get_name(),
+                "help": family.get_help(),
+                "type": match field_type {
+                    MetricType::COUNTER => "counter",
+                    MetricType::GAUGE => "gauge",
+                    MetricType::SUMMARY => "summary",
+                    MetricType::UNTYPED => "untyped",
+                    MetricType::HISTOGRAM => "histogram",
+                },
+                "metrics": family
+                    .get_metric()
+                    .iter()
+                    .map(|metric| metric_to_json(field_type, metric))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+fn metric_to_json(field_type: MetricType, metric: &Metric) -> Value {
+    let labels: Map<String, Value> = metric
+        .get_label()
+        .iter()
+        .map(|label| (label.get_name().to_string(), label.get_value().into()))
+        .collect();
+    match field_type {
+        MetricType::COUNTER => json!({"labels": labels, "value": metric.get_counter().get_value()}),
+        MetricType::GAUGE => json!({"labels": labels, "value": metric.get_gauge().get_value()}),
+        MetricType::UNTYPED => json!({"labels": labels, "value": metric.get_untyped().get_value()}),
+        MetricType::SUMMARY => {
+            let summary = metric.get_summary();
+            json!({
+                "labels": labels,
+                "count": summary.get_sample_count(),
+                "sum": summary.get_sample_sum(),
+                "quantiles": summary
+                    .get_quantile()
+                    .iter()
+                    .map(|q| json!({"quantile": q.get_quantile(), "value": q.get_value()}))
+                    .collect::<Vec<_>>(),
+            })
+        }
+        MetricType::HISTOGRAM => {
+            let histogram = metric.get_histogram();
+            json!({
+                "labels": labels,
+                "count": histogram.get_sample_count(),
+                "sum": histogram.get_sample_sum(),
+                "buckets": histogram
+                    .get_bucket()
+                    .iter()
+                    .map(|b| json!({"upper_bound": b.get_upper_bound(), "cumulative_count": b.get_cumulative_count()}))
+                    .collect::<Vec<_>>(),
+            })
+        }
+    }
+}
+
+/// Launch async process collector at specified interval, a zero interval disables it. It
+/// requires a running tokio runtime!
 pub fn launch_async_process_collector(interval: Duration) {
+    if interval.is_zero() {
+        tracing::info!("Process metrics collection disabled");
+        return;
+    }
+    #[cfg(target_os = "linux")]
     tokio::task::spawn(collect(interval));
+    #[cfg(not(target_os = "linux"))]
+    tracing::warn!("Collecting process info not available on this platform");
 }
-#[cfg(all(target_os = "linux"))]
+
+#[cfg(target_os = "linux")]
 async fn collect(interval: Duration) {
     use prometheus::core::Collector;
     let process_collector = prometheus::process_collector::ProcessCollector::for_self();
@@ -69,7 +268,52 @@ async fn collect(interval: Duration) {
     }
 }
 
-#[cfg(all(not(target_os = "linux")))]
-async fn collect(_: Duration) {
-    tracing::warn!("Collecting process info not available on this platform");
+#[cfg(test)]
+mod test {
+    use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+    use serde_json::json;
+
+    use super::metric_families_to_json;
+
+    #[test]
+    fn json_metrics() {
+        let registry = Registry::new();
+        let counter =
+            IntCounterVec::new(Opts::new("test_count", "Test counter"), &["queue_name"]).unwrap();
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("test_duration", "Test histogram").buckets(vec![0.1, 1.0]),
+            &[],
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        counter.with_label_values(&["syslog_in"]).inc_by(3);
+        histogram.with_label_values(&[]).observe(0.5);
+
+        assert_eq!(
+            json!([
+                {
+                    "name": "test_count",
+                    "help": "Test counter",
+                    "type": "counter",
+                    "metrics": [{"labels": {"queue_name": "syslog_in"}, "value": 3.0}],
+                },
+                {
+                    "name": "test_duration",
+                    "help": "Test histogram",
+                    "type": "histogram",
+                    "metrics": [{
+                        "labels": {},
+                        "count": 1,
+                        "sum": 0.5,
+                        "buckets": [
+                            {"upper_bound": 0.1, "cumulative_count": 0},
+                            {"upper_bound": 1.0, "cumulative_count": 1},
+                        ],
+                    }],
+                },
+            ]),
+            metric_families_to_json(&registry.gather())
+        );
+    }
 }
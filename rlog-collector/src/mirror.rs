@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::Bytes;
+use reqwest::{header::CONTENT_TYPE, Client, StatusCode, Url};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{QuickwitMirrorConfig, CONFIG};
+use crate::index::{serialize_batch, FlushDeadline, IndexLogEntry, IngestApi};
+use crate::metrics::{
+    COLLECTOR_MIRROR_DROPPED_COUNT, COLLECTOR_OUTPUT_COUNT, MIRROR_DROPPED_QUEUE_FULL_LABEL_VALUE,
+    OUTPUT_DROPPED_RETRIES_LABEL_VALUE, OUTPUT_DROPPED_SHUTDOWN_LABEL_VALUE,
+    OUTPUT_STATUS_ERROR_LABEL_VALUE, OUTPUT_STATUS_OK_LABEL_VALUE,
+    OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE, OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE,
+};
+
+/// Batch serialized once for all the mirrors, the body is shared by the mirrors & the
+/// retries without being copied
+#[derive(Clone)]
+struct MirrorBatch {
+    body: Bytes,
+    documents: usize,
+}
+
+struct MirrorQueue {
+    name: String,
+    sender: mpsc::Sender<MirrorBatch>,
+}
+
+/// Queues of the quickwit mirrors, fed by the index loop with every received batch
+pub struct Mirrors(Vec<MirrorQueue>);
+
+impl Mirrors {
+    /// Start a task sending the batches to each mirror, the tasks exit once the queues are
+    /// dropped and sent (bounded by the `collector_shutdown_flush_timeout`)
+    pub fn launch(
+        configs: &[QuickwitMirrorConfig],
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<(Self, Vec<JoinHandle<()>>)> {
+        let mut queues = Vec::with_capacity(configs.len());
+        let mut handles = Vec::with_capacity(configs.len());
+        for config in configs {
+            let quickwit_rest_url: Url = config.rest_url.parse().with_context(|| {
+                format!("invalid REST url of quickwit mirror {}", config.name())
+            })?;
+            let http_client = Client::builder()
+                .connect_timeout(Duration::from_secs(5))
+                .build()?;
+            let mirror = Mirror {
+                name: config.name().to_string(),
+                ingest_api: IngestApi::new(
+                    quickwit_rest_url,
+                    &config.index_id,
+                    http_client.clone(),
+                    CONFIG.load().collector_quickwit_api_version,
+                    CONFIG.load().collector_quickwit_commit,
+                )?,
+                http_client,
+                max_retries: config.max_retries,
+                flush_deadline: FlushDeadline::new(shutdown_token.clone()),
+            };
+            let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+            handles.push(tokio::spawn(mirror.run(receiver)));
+            queues.push(MirrorQueue {
+                name: config.name().to_string(),
+                sender,
+            });
+        }
+        Ok((Self(queues), handles))
+    }
+
+    /// Queue a copy of the batch for each mirror, dropped for the mirrors whose queue is full
+    pub fn send(&self, batch: &[IndexLogEntry]) {
+        // room is reserved in the queues first: the batch is not serialized if they are all
+        // full
+        let permits = self
+            .0
+            .iter()
+            .filter_map(|queue| match queue.sender.try_reserve() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    tracing::warn!(
+                        "Queue of quickwit mirror {} full, {} logs dropped",
+                        queue.name,
+                        batch.len()
+                    );
+                    COLLECTOR_MIRROR_DROPPED_COUNT
+                        .with_label_values(&[&queue.name, MIRROR_DROPPED_QUEUE_FULL_LABEL_VALUE])
+                        .inc_by(batch.len() as u64);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if permits.is_empty() {
+            return;
+        }
+        let batch = MirrorBatch {
+            body: serialize_batch(batch).into(),
+            documents: batch.len(),
+        };
+        for permit in permits {
+            permit.send(batch.clone());
+        }
+    }
+}
+
+struct Mirror {
+    name: String,
+    ingest_api: IngestApi,
+    http_client: Client,
+    max_retries: u32,
+    flush_deadline: FlushDeadline,
+}
+
+impl Mirror {
+    async fn run(mut self, mut receiver: mpsc::Receiver<MirrorBatch>) {
+        let mut deadline_reached = false;
+        while let Some(batch) = receiver.recv().await {
+            if deadline_reached {
+                self.drop_batch(&batch, OUTPUT_DROPPED_SHUTDOWN_LABEL_VALUE);
+            } else {
+                deadline_reached = !self.send(&batch).await;
+            }
+        }
+        tracing::info!("Exited quickwit mirror {} task.", self.name);
+    }
+
+    /// Send the batch, retried up to `max_retries` times, `false` if the shutdown flush
+    /// deadline has been reached
+    async fn send(&mut self, batch: &MirrorBatch) -> bool {
+        for attempt in 0..=self.max_retries {
+            self.ingest_api.refresh_version().await;
+            let content_type = CONFIG.load().collector_quickwit_content_type.clone();
+            let Some(response) = self
+                .flush_deadline
+                .bounded(
+                    self.http_client
+                        .post(self.ingest_api.ingest_url().clone())
+                        .header(CONTENT_TYPE, content_type)
+                        .body(batch.body.clone())
+                        .send(),
+                )
+                .await
+            else {
+                self.drop_batch(batch, OUTPUT_DROPPED_SHUTDOWN_LABEL_VALUE);
+                return false;
+            };
+            let (status, retry_delay, error) = match response {
+                Ok(response) => {
+                    let status = response.status();
+                    // consume response
+                    let body = response.text().await.unwrap_or_default();
+                    match status {
+                        StatusCode::OK => (OUTPUT_STATUS_OK_LABEL_VALUE, None, String::new()),
+                        StatusCode::TOO_MANY_REQUESTS => (
+                            OUTPUT_STATUS_TOO_MANY_REQUEST_LABEL_VALUE,
+                            Some(Duration::from_secs(5)),
+                            format!("{status}"),
+                        ),
+                        _ => (
+                            OUTPUT_STATUS_ERROR_LABEL_VALUE,
+                            Some(Duration::from_secs(1)),
+                            format!("{status} - {body}"),
+                        ),
+                    }
+                }
+                Err(e) => (
+                    OUTPUT_STATUS_ERROR_LABEL_VALUE,
+                    Some(Duration::from_secs(1)),
+                    e.to_string(),
+                ),
+            };
+            COLLECTOR_OUTPUT_COUNT
+                .with_label_values(&[OUTPUT_SYSTEM_QUICKWIT_LABEL_VALUE, &self.name, status])
+                .inc();
+            let Some(retry_delay) = retry_delay else {
+                return true;
+            };
+            tracing::warn!(
+                "Unable to send {} logs to quickwit mirror {} (attempt {}) - {error}",
+                batch.documents,
+                self.name,
+                attempt + 1
+            );
+            if attempt < self.max_retries
+                && self
+                    .flush_deadline
+                    .bounded(tokio::time::sleep(retry_delay))
+                    .await
+                    .is_none()
+            {
+                self.drop_batch(batch, OUTPUT_DROPPED_SHUTDOWN_LABEL_VALUE);
+                return false;
+            }
+        }
+        self.drop_batch(batch, OUTPUT_DROPPED_RETRIES_LABEL_VALUE);
+        true
+    }
+
+    fn drop_batch(&self, batch: &MirrorBatch, reason: &str) {
+        COLLECTOR_MIRROR_DROPPED_COUNT
+            .with_label_values(&[&self.name, reason])
+            .inc_by(batch.documents as u64);
+    }
+}
@@ -0,0 +1,137 @@
+//! Outputs of the collector: every batch is dispatched to all the outputs concurrently, a
+//! batch is processed once all the outputs have accepted it (or exhausted their retries).
+
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use crate::batch::{Batches, FlushMarker};
+use crate::index::IndexLogEntry;
+use crate::mirror::Mirrors;
+use crate::s3::S3Output;
+
+/// Destination of the log entries
+pub enum OutputBackend {
+    /// quickwit index, retried until accepted, reported as the `primary` destination of the
+    /// output metrics
+    Quickwit(QuickwitOutput),
+    /// objects of a S3 bucket (eg: cold storage), reported with the bucket as destination
+    S3(S3Output),
+}
+
+impl OutputBackend {
+    pub fn name(&self) -> String {
+        match self {
+            OutputBackend::Quickwit(quickwit) => format!("quickwit index {}", quickwit.index_id),
+            OutputBackend::S3(s3) => format!("S3 bucket {}", s3.bucket),
+        }
+    }
+}
+
+pub struct QuickwitOutput {
+    pub rest_url: String,
+    pub index_id: String,
+    /// added to every request sent to quickwit (eg: API gateway routing or authentication)
+    pub extra_headers: HashMap<String, String>,
+}
+
+/// Queue of an output, fed by the fanout task
+pub(crate) struct OutputQueue {
+    name: String,
+    batches: async_channel::Sender<Vec<IndexLogEntry>>,
+    flush_markers: mpsc::UnboundedSender<FlushMarker>,
+}
+
+impl OutputQueue {
+    /// The queue holds a single batch: the slowest output paces the others
+    pub(crate) fn new(name: String) -> (Self, Batches<IndexLogEntry>) {
+        let (batch_sender, batch_receiver) = async_channel::bounded(1);
+        let (marker_sender, marker_receiver) = mpsc::unbounded_channel();
+        (
+            Self {
+                name,
+                batches: batch_sender,
+                flush_markers: marker_sender,
+            },
+            Batches {
+                receiver: batch_receiver,
+                flush_markers: marker_receiver,
+            },
+        )
+    }
+}
+
+/// Dispatch every batch to the mirrors and to all the output queues concurrently: a failing
+/// output does not prevent the others from receiving the batch. A flush marker is done once
+/// every output has accepted the flushed batches.
+///
+/// The task exits once the batch channel is closed and every output task has exited.
+pub(crate) fn launch_fanout(
+    batches: Batches<IndexLogEntry>,
+    queues: Vec<OutputQueue>,
+    output_handles: Vec<JoinHandle<()>>,
+    mirrors: Mirrors,
+    mirror_handles: Vec<JoinHandle<()>>,
+) -> JoinHandle<()> {
+    let Batches {
+        receiver: batch_receiver,
+        mut flush_markers,
+    } = batches;
+    tokio::spawn(async move {
+        loop {
+            select! {
+                batch = batch_receiver.recv() => {
+                    // channel close (server shutdown)
+                    let Ok(batch) = batch else {
+                        break;
+                    };
+                    mirrors.send(&batch);
+                    let documents = batch.len();
+                    // the last output gets the received batch, the others a copy
+                    let mut copies = vec![batch];
+                    while copies.len() < queues.len() {
+                        copies.push(copies[0].clone());
+                    }
+                    let results =
+                        join_all(queues.iter().zip(copies).map(|(queue, batch)| queue.batches.send(batch)))
+                            .await;
+                    for (queue, result) in queues.iter().zip(results) {
+                        if result.is_err() {
+                            tracing::error!("Output {} stopped, {documents} logs lost", queue.name);
+                        }
+                    }
+                }
+                Some(marker) = flush_markers.recv() => {
+                    let accepted = queues
+                        .iter()
+                        .map(|queue| {
+                            let (done, accepted) = oneshot::channel();
+                            let _ = queue.flush_markers.send(FlushMarker {
+                                batches: marker.batches,
+                                done,
+                            });
+                            accepted
+                        })
+                        .collect::<Vec<_>>();
+                    tokio::spawn(async move {
+                        // the flush fails if an output stopped before accepting the batches
+                        if join_all(accepted).await.iter().all(Result::is_ok) {
+                            let _ = marker.done.send(());
+                        }
+                    });
+                }
+            }
+        }
+        // the outputs send their remaining batches before exiting
+        drop(queues);
+        join_all(output_handles).await;
+        drop(mirrors);
+        join_all(mirror_handles).await;
+        tracing::info!("Exited indexing task.");
+    })
+}
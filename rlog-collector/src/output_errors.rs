@@ -0,0 +1,60 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::StatusCode;
+use rlog_common::utils::truncate_str;
+use serde::Serialize;
+
+use crate::config::CONFIG;
+use crate::metrics::COLLECTOR_LAST_OUTPUT_ERROR_TIMESTAMP;
+
+/// Error bodies are truncated to this size (bytes)
+const MAX_BODY_SIZE: usize = 1024;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OutputError {
+    /// seconds from EPOCH
+    pub timestamp: u64,
+    /// `None` if no response has been received (eg: connection error)
+    pub status_code: Option<u16>,
+    /// response body or error message, truncated
+    pub body: String,
+    pub batch_size: usize,
+}
+
+/// Most recent quickwit output errors, the oldest errors are dropped once
+/// `collector_last_errors_capacity` is reached
+#[derive(Default)]
+pub struct OutputErrors {
+    errors: Mutex<VecDeque<OutputError>>,
+}
+
+impl OutputErrors {
+    pub fn report(&self, status_code: Option<StatusCode>, body: &str, batch_size: usize) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        COLLECTOR_LAST_OUTPUT_ERROR_TIMESTAMP.set(timestamp as i64);
+
+        let capacity = CONFIG.load().collector_last_errors_capacity;
+        let mut errors = self.errors.lock().unwrap();
+        errors.push_back(OutputError {
+            timestamp,
+            status_code: status_code.map(|status_code| status_code.as_u16()),
+            body: truncate_str(body, MAX_BODY_SIZE).to_string(),
+            batch_size,
+        });
+        while errors.len() > capacity {
+            errors.pop_front();
+        }
+    }
+
+    /// Most recent first
+    pub fn last_errors(&self) -> Vec<OutputError> {
+        self.errors.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
@@ -0,0 +1,221 @@
+use rlog_grpc::{rlog_service_protocol::SyslogSeverity, OTELSeverity};
+
+use crate::{
+    config::{SeverityOverride, SeverityOverrideAction, SeverityOverrideMatch},
+    metrics::COLLECTOR_SEVERITY_OVERRIDE_COUNT,
+    IndexLogEntry,
+};
+
+/// Syslog level of an open telemetry severity number: the most severe level not above it
+fn syslog_level(severity_number: u64) -> u64 {
+    (0..7)
+        .find(|level| OTELSeverity::from(SyslogSeverity::from(*level)) as u64 <= severity_number)
+        .unwrap_or(7)
+}
+
+impl SeverityOverrideMatch {
+    fn is_match(&self, entry: &IndexLogEntry) -> bool {
+        [
+            (&self.service_name, &entry.service_name),
+            (&self.hostname, &entry.hostname),
+            (&self.message, &entry.message),
+        ]
+        .into_iter()
+        .all(|(pattern, value)| {
+            pattern
+                .as_ref()
+                .map(|pattern| pattern.is_match(value))
+                .unwrap_or(true)
+        })
+    }
+}
+
+impl IndexLogEntry {
+    /// Apply the first matching rule, the replaced severity is kept in the
+    /// `original_severity` free field
+    pub(crate) fn override_severity(&mut self, rules: &[SeverityOverride]) {
+        let Some((index, rule)) = rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matcher.is_match(self))
+        else {
+            return;
+        };
+        let severity = match rule.action {
+            SeverityOverrideAction::SetMaxSeverity(max_severity) => {
+                let max_severity_number = OTELSeverity::from(SyslogSeverity::from(max_severity));
+                if self.severity_number <= max_severity_number as u64 {
+                    return;
+                }
+                max_severity.into()
+            }
+            SeverityOverrideAction::SetSeverity(severity) => severity.into(),
+            SeverityOverrideAction::ShiftSeverity(shift) => {
+                // syslog levels: 0 (emergency) to 7 (debug)
+                let level = syslog_level(self.severity_number) as i64 - shift as i64;
+                SyslogSeverity::from(level.clamp(0, 7) as u64)
+            }
+        };
+        let severity = OTELSeverity::from(severity);
+        if severity.severity_text() == self.severity_text {
+            return;
+        }
+        let original_severity =
+            std::mem::replace(&mut self.severity_text, severity.severity_text().into());
+        self.severity_number = severity as u64;
+        self.free_fields
+            .insert("original_severity".into(), original_severity.into());
+        let rule_label = match &rule.name {
+            Some(name) => name.clone(),
+            None => index.to_string(),
+        };
+        COLLECTOR_SEVERITY_OVERRIDE_COUNT
+            .with_label_values(&[&rule_label])
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        config::SeverityOverride, metrics::COLLECTOR_SEVERITY_OVERRIDE_COUNT, IndexLogEntry,
+        LogSystem,
+    };
+
+    fn entry(service_name: &str) -> IndexLogEntry {
+        IndexLogEntry {
+            message: "disk failure".into(),
+            timestamp: 1700000000000,
+            hostname: "my_host".into(),
+            service_name: service_name.into(),
+            severity_text: "ERROR".into(),
+            severity_number: 17,
+            log_system: LogSystem::Syslog,
+            hmac_verified: false,
+            indexed_fields: HashMap::new(),
+            doc_id: None,
+            doc_id_v: None,
+            free_fields: HashMap::new(),
+        }
+    }
+
+    fn rules(yaml: &str) -> Vec<SeverityOverride> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let rules = rules(
+            r#"
+- match:
+    service_name: "^appliance$"
+  set_max_severity: warning
+- set_severity: debug
+"#,
+        );
+        let mut appliance = entry("appliance");
+        appliance.override_severity(&rules);
+        assert_eq!("WARN", appliance.severity_text);
+        assert_eq!(13, appliance.severity_number);
+        assert_eq!(
+            Some(&serde_json::Value::from("ERROR")),
+            appliance.free_fields.get("original_severity")
+        );
+
+        // already below the maximum severity, the first match wins
+        let mut appliance = entry("appliance");
+        appliance.severity_text = "INFO".into();
+        appliance.severity_number = 9;
+        appliance.override_severity(&rules);
+        assert_eq!("INFO", appliance.severity_text);
+        assert!(appliance.free_fields.is_empty());
+    }
+
+    #[test]
+    fn test_set_severity() {
+        let rules = rules(
+            r#"
+- match:
+    hostname: "^my_"
+    message: "disk"
+  set_severity: critical
+"#,
+        );
+        let mut entry = entry("kernel");
+        entry.override_severity(&rules);
+        assert_eq!("FATAL", entry.severity_text);
+        assert_eq!(21, entry.severity_number);
+        assert_eq!(
+            Some(&serde_json::Value::from("ERROR")),
+            entry.free_fields.get("original_severity")
+        );
+    }
+
+    #[test]
+    fn test_shift_severity() {
+        let rules = rules(
+            r#"
+- name: noisy_cron
+  match:
+    service_name: "^legacy-cron$"
+  shift_severity: -2
+- match:
+    service_name: "^kernel$"
+  shift_severity: 10
+"#,
+        );
+        let metric = || {
+            COLLECTOR_SEVERITY_OVERRIDE_COUNT
+                .with_label_values(&["noisy_cron"])
+                .get()
+        };
+        let mut cron = entry("legacy-cron");
+        cron.override_severity(&rules);
+        // error -> warning -> notice
+        assert_eq!("INFO3", cron.severity_text);
+        assert_eq!(11, cron.severity_number);
+        assert_eq!(1, metric());
+
+        // capped to debug, unchanged
+        let mut cron = entry("legacy-cron");
+        cron.severity_text = "DEBUG".into();
+        cron.severity_number = 5;
+        cron.override_severity(&rules);
+        assert_eq!("DEBUG", cron.severity_text);
+        assert!(cron.free_fields.is_empty());
+        assert_eq!(1, metric());
+
+        // capped to emergency, unnamed rules are labeled with their index
+        let mut kernel = entry("kernel");
+        kernel.override_severity(&rules);
+        assert_eq!("FATAL4", kernel.severity_text);
+        assert_eq!(
+            1,
+            COLLECTOR_SEVERITY_OVERRIDE_COUNT
+                .with_label_values(&["1"])
+                .get()
+        );
+    }
+
+    #[test]
+    fn test_no_match() {
+        let rules = rules(
+            r#"
+- match:
+    service_name: "^appliance$"
+  set_severity: debug
+- match:
+    hostname: "^my_"
+    message: "network"
+  set_severity: debug
+"#,
+        );
+        let mut entry = entry("kernel");
+        entry.override_severity(&rules);
+        assert_eq!("ERROR", entry.severity_text);
+        assert_eq!(17, entry.severity_number);
+        assert!(entry.free_fields.is_empty());
+    }
+}
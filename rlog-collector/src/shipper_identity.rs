@@ -0,0 +1,106 @@
+//! Name of a shipper reporting metrics: the hostname of the metrics is self-reported, a
+//! shipper cloned from another host's image reports the hostname of the other host and
+//! overwrites its metrics. The common name of the client certificate can be used instead.
+
+use std::{collections::HashSet, sync::Mutex};
+
+use lazy_static::lazy_static;
+use x509_parser::parse_x509_certificate;
+
+use crate::{config::MetricsIdentity, metrics::COLLECTOR_HOSTNAME_MISMATCH_COUNT};
+
+lazy_static! {
+    /// (certificate name, reported hostname) mismatches already logged
+    static ref LOGGED_MISMATCHES: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+}
+
+/// Common name of the subject of a DER certificate
+pub fn certificate_name(der: &[u8]) -> Option<String> {
+    let (_, certificate) = parse_x509_certificate(der).ok()?;
+    let common_name = certificate.subject().iter_common_name().next()?;
+    common_name.as_str().ok().map(str::to_string)
+}
+
+/// Name keying the metrics reported under `reported_hostname` by a shipper authenticated
+/// with a certificate named `certificate_name` (if any)
+pub fn metrics_key(
+    identity: MetricsIdentity,
+    reported_hostname: String,
+    certificate_name: Option<String>,
+) -> String {
+    let Some(certificate_name) = certificate_name else {
+        return reported_hostname;
+    };
+    match identity {
+        MetricsIdentity::Report => reported_hostname,
+        MetricsIdentity::Certificate => certificate_name,
+        MetricsIdentity::Both => {
+            if certificate_name != reported_hostname {
+                COLLECTOR_HOSTNAME_MISMATCH_COUNT.inc();
+                let pair = (certificate_name.clone(), reported_hostname);
+                if LOGGED_MISMATCHES.lock().unwrap().insert(pair.clone()) {
+                    tracing::warn!(
+                        certificate_name = pair.0,
+                        reported_hostname = pair.1,
+                        "Hostname reported in the metrics does not match the shipper certificate"
+                    );
+                }
+            }
+            certificate_name
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rcgen::{CertificateParams, DnType, KeyPair};
+
+    use super::{certificate_name, metrics_key};
+    use crate::{config::MetricsIdentity, metrics::COLLECTOR_HOSTNAME_MISMATCH_COUNT};
+
+    #[test]
+    fn test_certificate_name() {
+        let mut params = CertificateParams::default();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "my_shipper");
+        let certificate = params.self_signed(&KeyPair::generate().unwrap()).unwrap();
+        assert_eq!(
+            Some("my_shipper".to_string()),
+            certificate_name(certificate.der())
+        );
+        assert_eq!(None, certificate_name(b"not a certificate"));
+    }
+
+    #[test]
+    fn test_metrics_key() {
+        let key = |identity, certificate_name: Option<&str>| {
+            metrics_key(
+                identity,
+                "reported_host".into(),
+                certificate_name.map(str::to_string),
+            )
+        };
+        let mismatches = COLLECTOR_HOSTNAME_MISMATCH_COUNT.get();
+        assert_eq!(
+            "reported_host",
+            key(MetricsIdentity::Report, Some("cert_host"))
+        );
+        assert_eq!(
+            "cert_host",
+            key(MetricsIdentity::Certificate, Some("cert_host"))
+        );
+        // not authenticated
+        assert_eq!("reported_host", key(MetricsIdentity::Certificate, None));
+        assert_eq!(mismatches, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+
+        assert_eq!("cert_host", key(MetricsIdentity::Both, Some("cert_host")));
+        assert_eq!("cert_host", key(MetricsIdentity::Both, Some("cert_host")));
+        assert_eq!(mismatches + 2, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+        assert_eq!(
+            "reported_host",
+            key(MetricsIdentity::Both, Some("reported_host"))
+        );
+        assert_eq!(mismatches + 2, COLLECTOR_HOSTNAME_MISMATCH_COUNT.get());
+    }
+}
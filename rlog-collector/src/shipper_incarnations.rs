@@ -0,0 +1,112 @@
+//! Shipper process incarnations, tracked from the `process_start_time` of the reported
+//! metrics: counters reported by a restarted shipper start again from zero while a report
+//! delivered late by a previous incarnation must not move the counters backwards.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use rlog_grpc::prost_wkt_types::Timestamp;
+
+/// Metrics are reported every 30s: a report of a previous incarnation received after this
+/// delay is not a late delivery but a restart with a clock set backwards.
+pub const STALE_REPORT_WINDOW: Duration = Duration::from_secs(90);
+
+lazy_static! {
+    pub static ref SHIPPER_INCARNATIONS: Mutex<ShipperIncarnations> =
+        Mutex::new(ShipperIncarnations::default());
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Incarnation {
+    /// first report with a start time (or no start time reported): counters are synced
+    /// with the reported values
+    Unknown,
+    /// same process than the previous report: counters never decrease
+    Current,
+    /// new process: counters are reset to the reported values
+    Restarted,
+    /// report of a previous process delivered out of order: ignored
+    Stale,
+}
+
+#[derive(Default)]
+pub struct ShipperIncarnations {
+    /// start time & time of the last report of the current incarnation of each shipper
+    start_times: HashMap<String, ((i64, i32), Instant)>,
+}
+
+impl ShipperIncarnations {
+    pub fn report(
+        &mut self,
+        hostname: &str,
+        process_start_time: Option<&Timestamp>,
+        now: Instant,
+    ) -> Incarnation {
+        let Some(start_time) = process_start_time.map(|t| (t.seconds, t.nanos)) else {
+            return Incarnation::Unknown;
+        };
+        let incarnation = match self.start_times.get(hostname) {
+            None => Incarnation::Unknown,
+            Some((known, _)) if *known == start_time => Incarnation::Current,
+            Some((known, last_report))
+                if *known > start_time
+                    && now.saturating_duration_since(*last_report) < STALE_REPORT_WINDOW =>
+            {
+                return Incarnation::Stale;
+            }
+            Some(_) => Incarnation::Restarted,
+        };
+        self.start_times
+            .insert(hostname.to_string(), (start_time, now));
+        incarnation
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use rlog_grpc::prost_wkt_types::Timestamp;
+
+    use super::{Incarnation, ShipperIncarnations, STALE_REPORT_WINDOW};
+
+    fn start(seconds: i64) -> Option<Timestamp> {
+        Some(Timestamp { seconds, nanos: 0 })
+    }
+
+    #[test]
+    fn test_incarnations() {
+        let mut incarnations = ShipperIncarnations::default();
+        let now = Instant::now();
+        let report = |incarnations: &mut ShipperIncarnations, seconds, now| {
+            incarnations.report("my_host", start(seconds).as_ref(), now)
+        };
+
+        assert_eq!(
+            Incarnation::Unknown,
+            incarnations.report("my_host", None, now)
+        );
+        assert_eq!(Incarnation::Unknown, report(&mut incarnations, 100, now));
+        assert_eq!(Incarnation::Current, report(&mut incarnations, 100, now));
+        assert_eq!(Incarnation::Restarted, report(&mut incarnations, 200, now));
+        // late report of the previous process
+        assert_eq!(Incarnation::Stale, report(&mut incarnations, 100, now));
+        assert_eq!(Incarnation::Current, report(&mut incarnations, 200, now));
+        // other shippers are tracked separately
+        assert_eq!(
+            Incarnation::Unknown,
+            incarnations.report("other_host", start(100).as_ref(), now)
+        );
+        // the clock of the shipper has been set backwards before the restart
+        let later = now + STALE_REPORT_WINDOW;
+        assert_eq!(
+            Incarnation::Restarted,
+            report(&mut incarnations, 150, later)
+        );
+        assert_eq!(Incarnation::Current, report(&mut incarnations, 150, later));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use rlog_common::utils::truncate_str;
+use serde_json::Value;
+
+use crate::config::{FreeFieldMaxLengthConfig, NestedFieldTruncation};
+
+/// Truncate the free field values longer than their configured maximum length, the marker
+/// is appended to truncated strings within the maximum length. Objects & arrays are measured once serialized to JSON
+/// and are either stringified and truncated or dropped. Other scalars are untouched.
+///
+/// Returns the number of truncated or dropped fields.
+pub(crate) fn truncate_fields(
+    fields: &mut HashMap<String, Value>,
+    config: &FreeFieldMaxLengthConfig,
+) -> u64 {
+    if config.max_length == 0 && config.fields.is_empty() {
+        return 0;
+    }
+    let mut truncated = 0;
+    fields.retain(|key, value| {
+        let max_length = config.fields.get(key).copied().unwrap_or(config.max_length);
+        if max_length == 0 {
+            return true;
+        }
+        match value {
+            Value::String(string) if string.len() > max_length => {
+                truncate_string(string, max_length, &config.marker);
+                truncated += 1;
+                true
+            }
+            Value::Object(_) | Value::Array(_) => {
+                let mut json = value.to_string();
+                if json.len() <= max_length {
+                    return true;
+                }
+                truncated += 1;
+                match config.nested {
+                    NestedFieldTruncation::Stringify => {
+                        truncate_string(&mut json, max_length, &config.marker);
+                        *value = Value::String(json);
+                        true
+                    }
+                    NestedFieldTruncation::Drop => false,
+                }
+            }
+            _ => true,
+        }
+    });
+    truncated
+}
+
+/// The marker is counted in the maximum length, truncated strings end without it if it
+/// doesn't fit
+fn truncate_string(string: &mut String, max_length: usize, marker: &str) {
+    let (max_length, marker) = match max_length.checked_sub(marker.len()) {
+        Some(length) if length > 0 => (length, marker),
+        _ => (max_length, ""),
+    };
+    string.truncate(truncate_str(string, max_length).len());
+    string.push_str(marker);
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use super::truncate_fields;
+    use crate::config::{FreeFieldMaxLengthConfig, NestedFieldTruncation};
+
+    fn truncate(fields: Value, config: &FreeFieldMaxLengthConfig) -> (Value, u64) {
+        let mut fields: HashMap<String, Value> = serde_json::from_value(fields).unwrap();
+        let truncated = truncate_fields(&mut fields, config);
+        (serde_json::to_value(fields).unwrap(), truncated)
+    }
+
+    #[test]
+    fn test_truncate_strings() {
+        let config = FreeFieldMaxLengthConfig {
+            max_length: 5,
+            fields: [("body".to_string(), 8), ("stack".to_string(), 0)].into(),
+            marker: "~".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            (
+                json!({
+                    "short": "abcde",
+                    "long": "abcd~",
+                    // not split inside a character
+                    "utf8": "héh~",
+                    "body": "1234567~",
+                    "stack": "no limit for this field",
+                    "number": 1234567890,
+                }),
+                3
+            ),
+            truncate(
+                json!({
+                    "short": "abcde",
+                    "long": "abcdefgh",
+                    "utf8": "héhého",
+                    "body": "1234567890",
+                    "stack": "no limit for this field",
+                    "number": 1234567890,
+                }),
+                &config
+            )
+        );
+        // the marker doesn't fit
+        let config = FreeFieldMaxLengthConfig {
+            max_length: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            (json!({"long": "ab"}), 1),
+            truncate(json!({"long": "abcdefgh"}), &config)
+        );
+        // disabled
+        assert_eq!(
+            (json!({"long": "abcdefgh"}), 0),
+            truncate(
+                json!({"long": "abcdefgh"}),
+                &FreeFieldMaxLengthConfig::default()
+            )
+        );
+    }
+
+    #[test]
+    fn test_truncate_nested() {
+        let mut config = FreeFieldMaxLengthConfig {
+            max_length: 10,
+            marker: "~".into(),
+            ..Default::default()
+        };
+        let fields =
+            json!({"small": {"a": 1}, "headers": {"accept": "*/*"}, "tags": [1, 2, 3, 4, 5]});
+        assert_eq!(
+            (
+                json!({
+                    "small": {"a": 1},
+                    "headers": "{\"accept\"~",
+                    "tags": "[1,2,3,4,~",
+                }),
+                2
+            ),
+            truncate(fields.clone(), &config)
+        );
+        config.nested = NestedFieldTruncation::Drop;
+        assert_eq!((json!({"small": {"a": 1}}), 2), truncate(fields, &config));
+    }
+}
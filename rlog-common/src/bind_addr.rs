@@ -0,0 +1,113 @@
+use std::{
+    fmt::Display,
+    net::{SocketAddr, ToSocketAddrs},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+/// Address a server listens to, validated when parsed.
+///
+/// Either an `ip:port` socket address or a `hostname:port` resolved once at parse
+/// time: if the hostname resolves to several addresses, the first one is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BindAddr(SocketAddr);
+
+impl BindAddr {
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.0
+    }
+}
+
+impl FromStr for BindAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(addr) = SocketAddr::from_str(s) {
+            return Ok(Self(addr));
+        }
+        s.to_socket_addrs()
+            .with_context(|| format!("Invalid bind address {s}"))?
+            .next()
+            .map(Self)
+            .ok_or_else(|| anyhow!("Invalid bind address {s}: hostname does not resolve"))
+    }
+}
+
+impl TryFrom<String> for BindAddr {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<BindAddr> for String {
+    fn from(value: BindAddr) -> Self {
+        value.to_string()
+    }
+}
+
+impl From<SocketAddr> for BindAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self(addr)
+    }
+}
+
+impl Display for BindAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use super::BindAddr;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "127.0.0.1:1234".parse::<SocketAddr>().unwrap(),
+            "127.0.0.1:1234".parse::<BindAddr>().unwrap().socket_addr()
+        );
+        assert_eq!(
+            "[::1]:1234".parse::<SocketAddr>().unwrap(),
+            "[::1]:1234".parse::<BindAddr>().unwrap().socket_addr()
+        );
+        assert_eq!(
+            1234,
+            "localhost:1234"
+                .parse::<BindAddr>()
+                .unwrap()
+                .socket_addr()
+                .port()
+        );
+
+        for invalid in [
+            "",
+            "127.0.0.1",
+            "127.0.0.1:",
+            "127.0.0.1:99999",
+            "127.0.0.1:port",
+            ":1234",
+            "not a hostname:1234",
+        ] {
+            let error = invalid.parse::<BindAddr>().unwrap_err();
+            assert!(error.to_string().contains(invalid), "{invalid} -> {error}");
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let addr: BindAddr = serde_yaml::from_str("127.0.0.1:1234").unwrap();
+        assert_eq!(
+            "127.0.0.1:1234",
+            serde_yaml::to_string(&addr).unwrap().trim()
+        );
+        assert!(serde_yaml::from_str::<BindAddr>("127.0.0.1").is_err());
+    }
+}
@@ -1,26 +1,62 @@
 use std::{
     fs::File,
+    io::Read,
     path::Path,
-    sync::Arc,
-    time::{Duration, SystemTime},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use arc_swap::ArcSwap;
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::sync::watch::{self, Receiver};
 
 use crate::utils::format_error;
 
+pub use rlog_config_derive::ConfigDoc;
+
 const CONFIG_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
 
+static STRICT_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Number of configuration hot reloads applied
+pub static CONFIG_RELOAD_OK_COUNT: AtomicU64 = AtomicU64::new(0);
+/// Number of configuration hot reloads failed, the previous configuration is kept (retried
+/// every 5s until the configuration is fixed)
+pub static CONFIG_RELOAD_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+/// Seconds from EPOCH of the load of the running configuration, 0 if not loaded from files
+pub static CONFIG_LAST_RELOAD_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+
 pub mod dir;
 
+/// Record the load of the running configuration
+fn config_loaded() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    CONFIG_LAST_RELOAD_TIMESTAMP.store(now, Ordering::Relaxed);
+}
+
+/// Record the outcome of a hot reload
+fn config_reloaded(ok: bool) {
+    if ok {
+        CONFIG_RELOAD_OK_COUNT.fetch_add(1, Ordering::Relaxed);
+        config_loaded();
+    } else {
+        CONFIG_RELOAD_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 pub fn setup_config_from_file<C: DeserializeOwned + Serialize + Send + Sync>(
     path: &str,
     config: &'static ArcSwap<C>,
 ) -> anyhow::Result<Receiver<()>> {
     let mut last_modified = load_and_swap_config(path, config)?;
+    config_loaded();
 
     let (sender, receiver) = watch::channel(());
 
@@ -33,6 +69,7 @@ pub fn setup_config_from_file<C: DeserializeOwned + Serialize + Send + Sync>(
                     tracing::info!("Config file modified, reloading it!");
                     match load_and_swap_config(&path, &config) {
                         Ok(m) => {
+                            config_reloaded(true);
                             last_modified = m;
                             tracing::info!(
                                 "New config:\n{}",
@@ -43,7 +80,10 @@ pub fn setup_config_from_file<C: DeserializeOwned + Serialize + Send + Sync>(
                                 return;
                             }
                         }
-                        Err(e) => tracing::error!("Unable to reload config: {}", format_error(e)),
+                        Err(e) => {
+                            config_reloaded(false);
+                            tracing::error!(error = %format_error(e), "Unable to reload config")
+                        }
                     }
                 }
             }
@@ -53,7 +93,7 @@ pub fn setup_config_from_file<C: DeserializeOwned + Serialize + Send + Sync>(
     Ok(receiver)
 }
 
-fn load_and_swap_config<P: AsRef<Path>, C: DeserializeOwned>(
+fn load_and_swap_config<P: AsRef<Path>, C: DeserializeOwned + Serialize>(
     path: P,
     config_store: &ArcSwap<C>,
 ) -> anyhow::Result<SystemTime> {
@@ -64,8 +104,10 @@ fn load_and_swap_config<P: AsRef<Path>, C: DeserializeOwned>(
     Ok(last_modified)
 }
 
-fn load_config<P: AsRef<Path>, C: DeserializeOwned>(path: P) -> anyhow::Result<(C, SystemTime)> {
-    let file = File::open(path.as_ref()).with_context(|| {
+fn load_config<P: AsRef<Path>, C: DeserializeOwned + Serialize>(
+    path: P,
+) -> anyhow::Result<(C, SystemTime)> {
+    let mut file = File::open(path.as_ref()).with_context(|| {
         format!(
             "Cannot open config file at: {}",
             path.as_ref().to_string_lossy()
@@ -74,13 +116,246 @@ fn load_config<P: AsRef<Path>, C: DeserializeOwned>(path: P) -> anyhow::Result<(
 
     let last_modified = file.metadata()?.modified()?;
 
-    Ok((
-        serde_yaml::from_reader(file).with_context(|| {
-            format!(
-                "Invalid YAML in config file at: {}",
-                path.as_ref().to_string_lossy()
-            )
-        })?,
-        last_modified,
-    ))
+    let config = if STRICT_MODE.load(Ordering::Relaxed) {
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        parse_config_strict(&content)
+    } else {
+        serde_yaml::from_reader(file).map_err(anyhow::Error::from)
+    }
+    .with_context(|| {
+        format!(
+            "Invalid YAML in config file at: {}",
+            path.as_ref().to_string_lossy()
+        )
+    })?;
+
+    Ok((config, last_modified))
+}
+
+/// In strict mode, config files containing unknown keys (eg: typos) are rejected
+/// instead of having these keys silently ignored.
+pub fn set_strict_mode(strict: bool) {
+    STRICT_MODE.store(strict, Ordering::Relaxed);
+}
+
+/// Parse `content`, failing if it contains keys unknown to `C`.
+///
+/// Unknown keys are the keys lost when the parsed config is serialized back
+/// (this also works with `#[serde(flatten)]` unlike `deny_unknown_fields`).
+pub fn parse_config_strict<C: DeserializeOwned + Serialize>(content: &str) -> anyhow::Result<C> {
+    let config: C = serde_yaml::from_str(content)?;
+    let raw: serde_yaml::Value = serde_yaml::from_str(content)?;
+    let mut unknown = vec![];
+    unknown_keys(&raw, &serde_yaml::to_value(&config)?, "", &mut unknown);
+    if !unknown.is_empty() {
+        bail!("Unknown config keys: {}", unknown.join(", "));
+    }
+    Ok(config)
+}
+
+fn unknown_keys(
+    raw: &serde_yaml::Value,
+    known: &serde_yaml::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    use serde_yaml::Value;
+    match (raw, known) {
+        (Value::Mapping(raw), Value::Mapping(known)) => {
+            for (key, value) in raw {
+                let key_path = match key.as_str() {
+                    Some(key) if path.is_empty() => key.to_string(),
+                    Some(key) => format!("{path}.{key}"),
+                    None => format!("{path}.{key:?}"),
+                };
+                match known.get(key) {
+                    Some(known) => unknown_keys(value, known, &key_path, unknown),
+                    // empty values may have been skipped by `skip_serializing_if`
+                    None if is_empty(value) => {}
+                    None => unknown.push(key_path),
+                }
+            }
+        }
+        (Value::Sequence(raw), Value::Sequence(known)) => {
+            for (i, (raw, known)) in raw.iter().zip(known).enumerate() {
+                unknown_keys(raw, known, &format!("{path}[{i}]"), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Documentation of the options of a configuration section, the comments of the
+/// configuration template (see [yaml_template])
+pub trait ConfigDoc {
+    /// Push the `(key, comment)` of each documented option, nested keys are referenced by
+    /// their dotted path (eg: `grpc_out.timeout`) prefixed by `prefix`
+    fn field_docs(prefix: &str, docs: &mut Vec<(String, String)>);
+}
+
+/// The options of an optional section are the ones of the section
+impl<T: ConfigDoc> ConfigDoc for Option<T> {
+    fn field_docs(prefix: &str, docs: &mut Vec<(String, String)>) {
+        T::field_docs(prefix, docs)
+    }
+}
+
+/// YAML template of a configuration: the doc comment of each key (see [ConfigDoc]) is
+/// inserted before the key. Keys of sequence items are not commented.
+pub fn yaml_template<C: Serialize + ConfigDoc>(config: &C) -> anyhow::Result<String> {
+    let mut comments = Vec::new();
+    C::field_docs("", &mut comments);
+    let yaml = serde_yaml::to_string(config)?;
+    let mut template = String::new();
+    // (indentation, key) of the current line and its parent keys, `None` for sequence items
+    let mut path: Vec<(usize, Option<&str>)> = Vec::new();
+    for line in yaml.lines() {
+        let mut content = line.trim_start_matches(' ');
+        let mut indent = line.len() - content.len();
+        // sequences are not indented below their key
+        let is_item = content.starts_with("- ");
+        path.retain(|(parent_indent, _)| {
+            *parent_indent < indent || (is_item && *parent_indent == indent)
+        });
+        while let Some(item) = content.strip_prefix("- ") {
+            path.push((indent + 1, None));
+            content = item;
+            indent += 2;
+        }
+        if let Some((key, _)) = content.split_once(':') {
+            path.push((indent, Some(key)));
+        }
+        let comment = path
+            .iter()
+            .map(|(_, key)| *key)
+            .collect::<Option<Vec<_>>>()
+            .filter(|_| content.contains(':'))
+            .map(|keys| keys.join("."))
+            .and_then(|dotted_path| comments.iter().find(|(name, _)| *name == dotted_path));
+        if let Some((_, comment)) = comment {
+            if indent == 0 && !template.is_empty() {
+                template.push('\n');
+            }
+            for comment_line in comment.lines() {
+                template.push_str(&" ".repeat(indent));
+                template.push_str("# ");
+                template.push_str(comment_line);
+                template.push('\n');
+            }
+        }
+        template.push_str(line);
+        template.push('\n');
+    }
+    Ok(template)
+}
+
+fn is_empty(value: &serde_yaml::Value) -> bool {
+    match value {
+        serde_yaml::Value::Null => true,
+        serde_yaml::Value::Mapping(mapping) => mapping.is_empty(),
+        serde_yaml::Value::Sequence(sequence) => sequence.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use super::{parse_config_strict, yaml_template, ConfigDoc};
+
+    #[derive(Serialize, Deserialize, ConfigDoc, Default, Debug)]
+    struct Common {
+        /// queue size
+        /// not hot reloaded
+        #[serde(default)]
+        max_buffer_size: usize,
+    }
+
+    #[derive(Serialize, Deserialize, ConfigDoc, Debug)]
+    struct Input {
+        #[serde(flatten)]
+        common: Common,
+        /// exclusion filters
+        #[serde(default, rename = "filters")]
+        exclusion_filters: Vec<Filter>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Filter {
+        pattern: String,
+    }
+
+    #[derive(Serialize, Deserialize, ConfigDoc, Debug)]
+    struct TestConfig {
+        /// input configuration
+        /// remove to disable
+        #[config_doc(nested)]
+        input: Option<Input>,
+    }
+
+    #[test]
+    fn test_strict() {
+        let config: TestConfig = parse_config_strict(
+            r#"
+input:
+  max_buffer_size: 12
+  name: ~
+  filters:
+    - pattern: "foo"
+"#,
+        )
+        .unwrap();
+        let input = config.input.unwrap();
+        assert_eq!(12, input.common.max_buffer_size);
+        assert_eq!("foo", input.exclusion_filters[0].pattern);
+
+        for (yaml, unknown_key) in [
+            ("inptu: {}\nfoo: bar", "foo"),
+            ("input:\n  filter:\n    - pattern: foo", "input.filter"),
+            ("input:\n  max_bufer_size: 12", "input.max_bufer_size"),
+            (
+                "input:\n  filters:\n    - pattern: foo\n      patern: bar",
+                "input.filters[0].patern",
+            ),
+        ] {
+            let error = parse_config_strict::<TestConfig>(yaml).unwrap_err();
+            assert_eq!(
+                format!("Unknown config keys: {unknown_key}"),
+                error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_yaml_template() {
+        let config = TestConfig {
+            input: Some(Input {
+                common: Common::default(),
+                exclusion_filters: vec![Filter {
+                    pattern: "foo".into(),
+                }],
+                name: None,
+            }),
+        };
+        let mut docs = vec![];
+        TestConfig::field_docs("", &mut docs);
+        assert_eq!(
+            vec![
+                ("input", "input configuration\nremove to disable"),
+                ("input.max_buffer_size", "queue size\nnot hot reloaded"),
+                ("input.filters", "exclusion filters"),
+            ],
+            docs.iter()
+                .map(|(key, doc)| (key.as_str(), doc.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "# input configuration\n# remove to disable\ninput:\n  # queue size\n  # not hot reloaded\n  max_buffer_size: 0\n  # exclusion filters\n  filters:\n  - pattern: foo\n",
+            yaml_template(&config).unwrap()
+        );
+    }
 }
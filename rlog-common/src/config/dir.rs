@@ -18,7 +18,7 @@ use tokio::{
 };
 
 use crate::{
-    config::{load_config, CONFIG_REFRESH_INTERVAL},
+    config::{config_loaded, config_reloaded, load_config, CONFIG_REFRESH_INTERVAL},
     utils::format_error,
 };
 
@@ -53,6 +53,7 @@ where
     let initial_config = read_config(&glob)?;
 
     config_store.swap(Arc::new(initial_config));
+    config_loaded();
 
     let (sender, receiver) = watch::channel(());
     tokio::spawn(async move {
@@ -65,16 +66,20 @@ where
                         // new config!!
                         tracing::debug!("Refreshed configuration from {glob}");
                         config_store.store(Arc::new(new_config));
+                        config_reloaded(true);
                         if let Err(_e) = sender.send(()) {
                             // channel closed,
                             return;
                         }
                     }
                 }
-                Err(e) => tracing::error!(
-                    "Unable to read configuration from {glob}: {}",
-                    format_error(e)
-                ),
+                Err(e) => {
+                    config_reloaded(false);
+                    tracing::error!(
+                        error = %format_error(e),
+                        "Unable to read configuration from {glob}"
+                    )
+                }
             }
         }
     });
@@ -0,0 +1,125 @@
+//! Count & hash of the log lines accepted by the collector between 2 metrics reports of a
+//! shipper, computed on both sides when the shipper verifies its deliveries
+//! (`grpc_out.delivery_verification`).
+
+use rlog_grpc::{prost::Message, rlog_service_protocol::LogLine};
+use xxhash_rust::xxh64::{xxh64, Xxh64};
+
+/// gRPC metadata of the `Log` requests: incarnation id of the shipper process
+pub const INCARNATION_METADATA: &str = "rlog-incarnation";
+
+/// Hash of a log line, independent of the encoding order of its labels
+pub fn log_line_hash(log_line: &mut LogLine) -> u64 {
+    let labels = std::mem::take(&mut log_line.labels);
+    let mut labels_hasher = Xxh64::new(0);
+    let mut sorted_labels = labels.iter().collect::<Vec<_>>();
+    sorted_labels.sort();
+    for (name, value) in sorted_labels {
+        labels_hasher.update(name.as_bytes());
+        labels_hasher.update(&[0]);
+        labels_hasher.update(value.as_bytes());
+        labels_hasher.update(&[0]);
+    }
+    let hash = xxh64(&log_line.encode_to_vec(), labels_hasher.digest());
+    log_line.labels = labels;
+    hash
+}
+
+/// Log lines of a window, in order
+#[derive(Clone)]
+pub struct WindowDigest {
+    count: u64,
+    hasher: Xxh64,
+}
+
+impl Default for WindowDigest {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            hasher: Xxh64::new(0),
+        }
+    }
+}
+
+impl WindowDigest {
+    /// Add a log line by its [log_line_hash]
+    pub fn add(&mut self, log_line_hash: u64) {
+        self.count += 1;
+        self.hasher.update(&log_line_hash.to_le_bytes());
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hasher.digest()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rlog_grpc::rlog_service_protocol::{log_line::Line, GenericLogLine, LogLine};
+
+    use super::{log_line_hash, WindowDigest};
+
+    fn log_line(message: &str, labels: &[(&str, &str)]) -> LogLine {
+        LogLine {
+            host: "my_host".into(),
+            labels: labels
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            line: Some(Line::GenericLog(GenericLogLine {
+                message: message.into(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_log_line_hash() {
+        let labels = (0..20)
+            .map(|i| (format!("label_{i}"), i.to_string()))
+            .collect::<Vec<_>>();
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let mut first = log_line("hello", &labels);
+        let hash = log_line_hash(&mut first);
+        // the labels are restored
+        assert_eq!(20, first.labels.len());
+        // another map, another iteration order
+        let reversed = labels.iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(hash, log_line_hash(&mut log_line("hello", &reversed)));
+
+        assert_ne!(hash, log_line_hash(&mut log_line("hello!", &labels)));
+        assert_ne!(hash, log_line_hash(&mut log_line("hello", &labels[1..])));
+        // label names & values are delimited
+        assert_ne!(
+            log_line_hash(&mut log_line("hello", &[("ab", "c")])),
+            log_line_hash(&mut log_line("hello", &[("a", "bc")]))
+        );
+    }
+
+    #[test]
+    fn test_window_digest() {
+        let hashes = [1, 2, 3].map(|i| log_line_hash(&mut log_line(&i.to_string(), &[])));
+        let digest = |order: &[usize]| {
+            let mut digest = WindowDigest::default();
+            for i in order {
+                digest.add(hashes[*i]);
+            }
+            digest
+        };
+        let window = digest(&[0, 1, 2]);
+        assert_eq!(3, window.count());
+        assert_eq!(window.hash(), digest(&[0, 1, 2]).hash());
+        // order matters
+        assert_ne!(window.hash(), digest(&[0, 2, 1]).hash());
+        assert_ne!(window.hash(), digest(&[0, 1]).hash());
+        assert_ne!(WindowDigest::default().hash(), digest(&[0]).hash());
+    }
+}
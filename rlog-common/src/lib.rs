@@ -1,2 +1,10 @@
+// `#[derive(ConfigDoc)]` refers to `::rlog_common`, also in this crate
+extern crate self as rlog_common;
+
+pub mod bind_addr;
 pub mod config;
+pub mod delivery_window;
+pub mod log_signature;
+pub mod metrics_sanitizer;
+pub mod tls;
 pub mod utils;
@@ -0,0 +1,114 @@
+//! Sanitization of the label values of Prometheus metrics: every distinct label value is a new
+//! time series, ephemeral hostnames (containers, autoscaled instances) must be rewritten or
+//! capped to keep the number of series bounded.
+
+use std::{collections::HashSet, sync::Mutex};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigDoc;
+
+/// Label of the hostnames above the cardinality limit
+pub const OVERFLOW_LABEL: &str = "_overflow";
+
+#[derive(Serialize, Deserialize, ConfigDoc, Clone, Debug, Default)]
+pub struct MetricsSanitizerConfig {
+    /// Rewrite rules (`pattern`, `replacement`) of the hostname label, all the rules are
+    /// applied in order
+    #[serde(default)]
+    pub hostname_rules: Vec<LabelRewriteRule>,
+    /// Maximum number of distinct hostname labels, hostnames seen once the limit is reached
+    /// are reported under the `_overflow` label, 0 means no limit
+    #[serde(default)]
+    pub max_cardinality: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LabelRewriteRule {
+    #[serde(with = "serde_regex")]
+    pub pattern: Regex,
+    /// Replacement of the matches of `pattern`, may reference capture groups (`$1`)
+    #[serde(default)]
+    pub replacement: String,
+}
+
+pub struct MetricsSanitizer {
+    config: MetricsSanitizerConfig,
+    hostnames: Mutex<HashSet<String>>,
+}
+
+impl MetricsSanitizer {
+    pub fn new(config: MetricsSanitizerConfig) -> Self {
+        Self {
+            config,
+            hostnames: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Hostname label value of the metrics of the given host
+    pub fn sanitize_hostname(&self, hostname: &str) -> String {
+        let mut label = hostname.to_string();
+        for rule in &self.config.hostname_rules {
+            label = rule
+                .pattern
+                .replace_all(&label, rule.replacement.as_str())
+                .into_owned();
+        }
+        if self.config.max_cardinality == 0 {
+            return label;
+        }
+        let mut hostnames = self.hostnames.lock().unwrap();
+        if hostnames.contains(&label) {
+            label
+        } else if hostnames.len() < self.config.max_cardinality {
+            hostnames.insert(label.clone());
+            label
+        } else {
+            OVERFLOW_LABEL.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use regex::Regex;
+
+    use super::{LabelRewriteRule, MetricsSanitizer, MetricsSanitizerConfig, OVERFLOW_LABEL};
+
+    #[test]
+    fn test_sanitize_hostname() {
+        let sanitizer = MetricsSanitizer::new(MetricsSanitizerConfig {
+            hostname_rules: vec![
+                LabelRewriteRule {
+                    pattern: Regex::new("-[0-9a-f]{8,10}-[0-9a-z]{5}$").unwrap(),
+                    replacement: "".into(),
+                },
+                LabelRewriteRule {
+                    pattern: Regex::new(r"^ip-(\d+)-.*$").unwrap(),
+                    replacement: "ip-$1-x".into(),
+                },
+            ],
+            max_cardinality: 3,
+        });
+
+        assert_eq!("api", sanitizer.sanitize_hostname("api-7d9f8b6c4d-x2x7q"));
+        assert_eq!("api", sanitizer.sanitize_hostname("api-5b8c7d9f6e-k9zpl"));
+        assert_eq!("ip-10-x", sanitizer.sanitize_hostname("ip-10-0-1-12"));
+        assert_eq!("db-1", sanitizer.sanitize_hostname("db-1"));
+        // limit reached
+        assert_eq!(OVERFLOW_LABEL, sanitizer.sanitize_hostname("db-2"));
+        assert_eq!(OVERFLOW_LABEL, sanitizer.sanitize_hostname("db-3"));
+        // known labels are still reported
+        assert_eq!("db-1", sanitizer.sanitize_hostname("db-1"));
+        assert_eq!("ip-10-x", sanitizer.sanitize_hostname("ip-10-0-3-4"));
+
+        let unlimited = MetricsSanitizer::new(MetricsSanitizerConfig::default());
+        for i in 0..10 {
+            assert_eq!(
+                format!("host-{i}"),
+                unlimited.sanitize_hostname(&format!("host-{i}"))
+            );
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::bail;
+use tokio_rustls::rustls::{
+    version::{TLS12, TLS13},
+    SupportedProtocolVersion,
+};
+
+/// Minimum TLS protocol version, versions older than TLS 1.2 are never supported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    #[default]
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Versions allowed by this minimum version
+    pub fn protocol_versions(self) -> &'static [&'static SupportedProtocolVersion] {
+        static FROM_TLS12: &[&SupportedProtocolVersion] = &[&TLS13, &TLS12];
+        static FROM_TLS13: &[&SupportedProtocolVersion] = &[&TLS13];
+        match self {
+            TlsVersion::Tls12 => FROM_TLS12,
+            TlsVersion::Tls13 => FROM_TLS13,
+        }
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => bail!("Invalid TLS version {s}, expected 1.2 or 1.3"),
+        }
+    }
+}
+
+impl Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsVersion::Tls12 => f.write_str("1.2"),
+            TlsVersion::Tls13 => f.write_str("1.3"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TlsVersion;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(TlsVersion::Tls12, "1.2".parse().unwrap());
+        assert_eq!(TlsVersion::Tls13, "1.3".parse().unwrap());
+        assert!("1.1".parse::<TlsVersion>().is_err());
+        assert!(TlsVersion::Tls12 < TlsVersion::Tls13);
+        assert_eq!(2, TlsVersion::Tls12.protocol_versions().len());
+        assert_eq!(1, TlsVersion::Tls13.protocol_versions().len());
+    }
+}
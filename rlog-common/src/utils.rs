@@ -1,17 +1,45 @@
-use std::path::Path;
+use std::{path::Path, sync::OnceLock};
 
 use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{fmt::SubscriberBuilder, util::SubscriberInitExt, EnvFilter};
 
-/// Read a file.
+/// Read a file, or an environment variable if `path` is `env://VAR_NAME` (eg: secrets
+/// injected by a secrets manager). A variable value starting with `base64://` is decoded.
 ///
 /// In case of error, a human readable context is added to the underlying error.
 pub fn read_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<u8>> {
     let path = path.as_ref();
+    if let Some(var_name) = path.to_str().and_then(|path| path.strip_prefix("env://")) {
+        return read_env(var_name);
+    }
     std::fs::read(path).with_context(|| format!("Cannot open file {}", path.to_string_lossy()))
 }
 
+fn read_env(var_name: &str) -> anyhow::Result<Vec<u8>> {
+    let value = std::env::var(var_name)
+        .with_context(|| format!("Cannot read environment variable {var_name}"))?;
+    match value.strip_prefix("base64://") {
+        Some(encoded) => STANDARD
+            .decode(encoded.trim())
+            .with_context(|| format!("Invalid base64 in environment variable {var_name}")),
+        None => Ok(value.into_bytes()),
+    }
+}
+
+/// Longest prefix of `s` of at most `max_bytes` bytes, not split inside a character
+pub fn truncate_str(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
 pub fn init_logging() {
     SubscriberBuilder::default()
         // only enable colored output on real terminals
@@ -25,7 +53,19 @@ pub fn init_logging() {
         .init();
 }
 
+/// `RLOG_LOG_FORMAT=json`: errors are formatted as JSON for machine consumption
+fn json_log_format() -> bool {
+    static JSON_LOG_FORMAT: OnceLock<bool> = OnceLock::new();
+    *JSON_LOG_FORMAT.get_or_init(|| {
+        std::env::var("RLOG_LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+    })
+}
+
+/// Human readable error chain, or [`format_error_json`] if `RLOG_LOG_FORMAT=json`.
 pub fn format_error(error: anyhow::Error) -> String {
+    if json_log_format() {
+        return format_error_json(error.as_ref()).to_string();
+    }
     error
         .chain()
         .enumerate()
@@ -33,3 +73,85 @@ pub fn format_error(error: anyhow::Error) -> String {
         .collect::<Vec<_>>()
         .join("\nCaused by:\n    ")
 }
+
+/// Lowercase hexadecimal encoding of the bytes (eg: digests)
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Error chain as nested JSON objects:
+/// `{"message": "...", "cause": [{"message": "...", "cause": []}]}`
+pub fn format_error_json(error: &dyn std::error::Error) -> serde_json::Value {
+    serde_json::json!({
+        "message": error.to_string(),
+        "cause": error
+            .source()
+            .map(|source| vec![format_error_json(source)])
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{anyhow, Context};
+    use serde_json::json;
+
+    use super::{format_error_json, hex, read_file, truncate_str};
+
+    #[test]
+    fn error_json() {
+        let error = Err::<(), _>(anyhow!("file not found"))
+            .context("Cannot open file config.yaml")
+            .context("Unable to reload config")
+            .unwrap_err();
+        assert_eq!(
+            json!({
+                "message": "Unable to reload config",
+                "cause": [{
+                    "message": "Cannot open file config.yaml",
+                    "cause": [{"message": "file not found", "cause": []}],
+                }],
+            }),
+            format_error_json(error.as_ref())
+        );
+    }
+
+    #[test]
+    fn hex_encoding() {
+        assert_eq!("", hex(&[]));
+        assert_eq!("00ff0a", hex(&[0x00, 0xff, 0x0a]));
+    }
+
+    #[test]
+    fn truncate() {
+        assert_eq!("hello", truncate_str("hello", 10));
+        assert_eq!("hello", truncate_str("hello", 5));
+        assert_eq!("hel", truncate_str("hello", 3));
+        assert_eq!("", truncate_str("hello", 0));
+        // `é` is 2 bytes long
+        assert_eq!("h", truncate_str("hé", 2));
+        assert_eq!("hé", truncate_str("hé", 3));
+    }
+
+    #[test]
+    fn read_file_from_env() {
+        let pem = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+        std::env::set_var("RLOG_TEST_READ_ENV_PEM", pem);
+        std::env::set_var(
+            "RLOG_TEST_READ_ENV_BASE64",
+            "base64://LS0tLS1CRUdJTiBDRVJUSUZJQ0FURS0tLS0tCk1JSUIKLS0tLS1FTkQgQ0VSVElGSUNBVEUtLS0tLQo=",
+        );
+        std::env::set_var("RLOG_TEST_READ_ENV_INVALID", "base64://not base64!");
+
+        assert_eq!(
+            pem.as_bytes(),
+            read_file("env://RLOG_TEST_READ_ENV_PEM").unwrap()
+        );
+        assert_eq!(
+            pem.as_bytes(),
+            read_file("env://RLOG_TEST_READ_ENV_BASE64").unwrap()
+        );
+        assert!(read_file("env://RLOG_TEST_READ_ENV_INVALID").is_err());
+        assert!(read_file("env://RLOG_TEST_READ_ENV_MISSING").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! `#[derive(ConfigDoc)]`: the doc comments of the fields of a configuration struct are the
+//! comments of its keys in the configuration template (`--generate-config`).
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, LitStr, Meta};
+
+/// Implement `rlog_common::config::ConfigDoc` from the doc comments of the fields.
+///
+/// Fields named by `#[serde(rename = "...")]` are documented under this name, the fields
+/// of `#[serde(flatten)]` fields are documented as fields of the struct and the fields of
+/// `#[config_doc(nested)]` fields (whose type implements `ConfigDoc`) are documented under
+/// the key of the field.
+#[proc_macro_derive(ConfigDoc, attributes(config_doc))]
+pub fn derive_config_doc(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match config_doc(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn config_doc(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "ConfigDoc requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "ConfigDoc requires a struct",
+            ))
+        }
+    };
+
+    let mut pushes = vec![];
+    for field in fields {
+        let ty = &field.ty;
+        let serde = SerdeField::parse(field)?;
+        if serde.skip {
+            continue;
+        }
+        if serde.flatten {
+            pushes.push(quote! {
+                <#ty as ::rlog_common::config::ConfigDoc>::field_docs(prefix, docs);
+            });
+            continue;
+        }
+        let name = match serde.rename {
+            Some(rename) => rename,
+            None => field
+                .ident
+                .as_ref()
+                .expect("named field")
+                .to_string()
+                .trim_start_matches("r#")
+                .to_string(),
+        };
+        let doc = doc_comment(&field.attrs);
+        if !doc.is_empty() {
+            pushes.push(quote! {
+                docs.push((format!("{prefix}{}", #name), #doc.to_string()));
+            });
+        }
+        if is_nested(field)? {
+            pushes.push(quote! {
+                <#ty as ::rlog_common::config::ConfigDoc>::field_docs(
+                    &format!("{prefix}{}.", #name),
+                    docs,
+                );
+            });
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rlog_common::config::ConfigDoc for #ident #ty_generics #where_clause {
+            fn field_docs(prefix: &str, docs: &mut Vec<(String, String)>) {
+                #(#pushes)*
+            }
+        }
+    })
+}
+
+/// Lines of the `///` comments, without the space following the slashes
+fn doc_comment(attrs: &[syn::Attribute]) -> String {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(name_value) => match &name_value.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(line) => Some(line.value()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+        .collect::<Vec<_>>();
+    lines.join("\n").trim().to_string()
+}
+
+fn is_nested(field: &syn::Field) -> syn::Result<bool> {
+    let mut nested = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("config_doc"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("nested") {
+                nested = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported config_doc attribute"))
+            }
+        })?;
+    }
+    Ok(nested)
+}
+
+/// The serde attributes of a field changing its documented keys
+#[derive(Default)]
+struct SerdeField {
+    rename: Option<String>,
+    flatten: bool,
+    skip: bool,
+}
+
+impl SerdeField {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut serde = Self::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") && meta.input.peek(syn::Token![=]) {
+                    serde.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("flatten") {
+                    serde.flatten = true;
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                    serde.skip = true;
+                } else if meta.input.peek(syn::Token![=]) {
+                    // other attributes, eg: `default = "..."`
+                    meta.value()?.parse::<Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    // eg: `rename(serialize = "...")`
+                    meta.parse_nested_meta(|meta| {
+                        meta.value()?.parse::<Expr>()?;
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(serde)
+    }
+}
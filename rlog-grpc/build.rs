@@ -1,9 +1,18 @@
 fn main() {
     println!("protoc path: {}", protobuf_src::protoc().to_string_lossy());
     std::env::set_var("PROTOC", protobuf_src::protoc());
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
     tonic_build::configure()
         .out_dir("src/")
+        // used by the gRPC reflection service
+        .file_descriptor_set_path(out_dir.join("rlog_service_descriptor.bin"))
         .extern_path(".google.protobuf.Timestamp", "::prost_wkt_types::Timestamp")
+        // JSON representation (see `LogLine::to_json_string`)
+        .type_attribute(
+            ".",
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(rename_all = \"camelCase\")]",
+        )
+        .message_attribute(".", "#[serde(default)]")
         .compile(&["proto/rlog-service.proto"], &["proto"])
         .unwrap();
 }
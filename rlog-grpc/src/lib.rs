@@ -1,13 +1,34 @@
 pub mod rlog_service_protocol;
 
-use std::fmt::{Debug, Display};
+use std::fmt::Display;
 
 // re-export prost & tonic so all dependents crate will use the right prost/tonic version
 pub use prost;
 pub use prost_wkt_types;
-use rlog_service_protocol::SyslogSeverity;
+use rlog_service_protocol::{LogLine, SyslogSeverity};
 pub use tonic;
 
+/// Encoded file descriptor set of the rlog service protocol, for gRPC reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/rlog_service_descriptor.bin"));
+
+/// JSON representation of log lines, for debugging tools and files.
+///
+/// Field names are the protobuf field names in camelCase, the `line` oneof is an object
+/// with a single `gelf`, `syslog` or `genericLog` key, enums are numbers and timestamps
+/// RFC 3339 strings. Missing fields take their protobuf default value. This is not the
+/// proto3 canonical JSON mapping: the representation stays stable as long as protobuf
+/// fields are not renamed, which is already required for the wire format compatibility.
+impl LogLine {
+    pub fn to_json_string(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json_str(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
 impl From<SyslogSeverity> for OTELSeverity {
     fn from(value: SyslogSeverity) -> Self {
         match value {
@@ -72,8 +93,173 @@ pub enum OTELSeverity {
     FATAL4 = 24,
 }
 
+impl OTELSeverity {
+    /// Severity text of the variant, without allocation
+    pub const fn severity_text(&self) -> &'static str {
+        match self {
+            Self::UNSPECIFIED => "UNSPECIFIED",
+            Self::TRACE => "TRACE",
+            Self::TRACE2 => "TRACE2",
+            Self::TRACE3 => "TRACE3",
+            Self::TRACE4 => "TRACE4",
+            Self::DEBUG => "DEBUG",
+            Self::DEBUG2 => "DEBUG2",
+            Self::DEBUG3 => "DEBUG3",
+            Self::DEBUG4 => "DEBUG4",
+            Self::INFO => "INFO",
+            Self::INFO2 => "INFO2",
+            Self::INFO3 => "INFO3",
+            Self::INFO4 => "INFO4",
+            Self::WARN => "WARN",
+            Self::WARN2 => "WARN2",
+            Self::WARN3 => "WARN3",
+            Self::WARN4 => "WARN4",
+            Self::ERROR => "ERROR",
+            Self::ERROR2 => "ERROR2",
+            Self::ERROR3 => "ERROR3",
+            Self::ERROR4 => "ERROR4",
+            Self::FATAL => "FATAL",
+            Self::FATAL2 => "FATAL2",
+            Self::FATAL3 => "FATAL3",
+            Self::FATAL4 => "FATAL4",
+        }
+    }
+}
+
 impl Display for OTELSeverity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(&self, f)
+        f.write_str(self.severity_text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost_wkt_types::Timestamp;
+
+    use crate::{
+        rlog_service_protocol::{
+            log_line::Line, GelfLogLine, GenericLogLine, LogLine, SyslogFacility, SyslogLogLine,
+            SyslogSeverity,
+        },
+        OTELSeverity,
+    };
+
+    fn round_trip(line: Line) -> serde_json::Value {
+        let log_line = LogLine {
+            host: "my_host".into(),
+            raw_host: None,
+            hmac: Vec::new(),
+            sequence: None,
+            labels: Default::default(),
+            timestamp: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 123_000_000,
+            }),
+            line: Some(line),
+        };
+        let json = log_line.to_json_string().unwrap();
+        assert_eq!(log_line, LogLine::from_json_str(&json).unwrap());
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn gelf_json() {
+        let json = round_trip(Line::Gelf(GelfLogLine {
+            short_message: "hello".into(),
+            full_message: Some("hello world".into()),
+            severity: SyslogSeverity::Warning as i32,
+            extra: r#"{"request_id":"abc"}"#.into(),
+        }));
+        assert_eq!("my_host", json["host"]);
+        assert_eq!("2023-11-14T22:13:20.123Z", json["timestamp"]);
+        assert_eq!("hello", json["line"]["gelf"]["shortMessage"]);
+        assert_eq!("hello world", json["line"]["gelf"]["fullMessage"]);
+        assert_eq!(4, json["line"]["gelf"]["severity"]);
+    }
+
+    #[test]
+    fn syslog_json() {
+        let json = round_trip(Line::Syslog(SyslogLogLine {
+            facility: SyslogFacility::Mail as i32,
+            severity: SyslogSeverity::Info as i32,
+            appname: Some("postfix".into()),
+            proc_pid: Some(1234),
+            proc_name: None,
+            msgid: None,
+            msg: "connect from localhost".into(),
+            service_name: Some("mail".into()),
+        }));
+        assert_eq!(1234, json["line"]["syslog"]["procPid"]);
+        assert_eq!("mail", json["line"]["syslog"]["serviceName"]);
+        assert!(json["line"]["syslog"]["procName"].is_null());
+    }
+
+    #[test]
+    fn generic_log_json() {
+        let json = round_trip(Line::GenericLog(GenericLogLine {
+            message: "GET /".into(),
+            severity: SyslogSeverity::Error as i32,
+            service_name: "nginx".into(),
+            extra: r#"{"status":500}"#.into(),
+            log_system: "nginx_access".into(),
+        }));
+        assert_eq!("nginx_access", json["line"]["genericLog"]["logSystem"]);
+        assert_eq!(r#"{"status":500}"#, json["line"]["genericLog"]["extra"]);
+    }
+
+    #[test]
+    fn missing_fields() {
+        let log_line =
+            LogLine::from_json_str(r#"{"host":"my_host","line":{"gelf":{"shortMessage":"hi"}}}"#)
+                .unwrap();
+        assert_eq!(None, log_line.timestamp);
+        let Some(Line::Gelf(gelf)) = log_line.line else {
+            panic!("not a gelf line")
+        };
+        assert_eq!("hi", gelf.short_message);
+        assert_eq!(SyslogSeverity::Emergency as i32, gelf.severity);
+    }
+
+    #[test]
+    fn severity_text() {
+        use OTELSeverity::*;
+        // the text was the Debug representation
+        for severity in [
+            UNSPECIFIED,
+            TRACE,
+            TRACE2,
+            TRACE3,
+            TRACE4,
+            DEBUG,
+            DEBUG2,
+            DEBUG3,
+            DEBUG4,
+            INFO,
+            INFO2,
+            INFO3,
+            INFO4,
+            WARN,
+            WARN2,
+            WARN3,
+            WARN4,
+            ERROR,
+            ERROR2,
+            ERROR3,
+            ERROR4,
+            FATAL,
+            FATAL2,
+            FATAL3,
+            FATAL4,
+        ] {
+            assert_eq!(format!("{severity:?}"), severity.severity_text());
+            assert_eq!(format!("{severity:?}"), severity.to_string());
+        }
+        let texts = (0..8)
+            .map(|level| OTELSeverity::from(SyslogSeverity::from(level)).severity_text())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["FATAL4", "FATAL3", "FATAL", "ERROR", "WARN", "INFO3", "INFO", "DEBUG"],
+            texts
+        );
     }
 }
@@ -0,0 +1,2 @@
+pub mod ping;
+pub mod quickwit_schema;
@@ -1,14 +1,22 @@
 use std::{
     error::Error,
     fs::{create_dir_all, File},
-    io::{Read, Write},
+    io::{IsTerminal, Read, Write},
     path::Path,
 };
 
-use anyhow::Context;
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand};
 use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use rlog_common::{log_signature, utils::read_file};
+use rlog_grpc::tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity, Uri};
+use rlog_helper::{ping, quickwit_schema};
 use time::OffsetDateTime;
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+mod cert_list;
+mod parse_test;
 
 #[derive(Parser)]
 struct Opts {
@@ -28,6 +36,102 @@ enum Command {
     },
     /// Minimal quickwit index schema
     PrintQuickwitSchema,
+    /// Compare the field mappings of a live quickwit index with the bundled schema.
+    ///
+    /// Exits with an error if a field type differs, differences are only reported otherwise.
+    CheckQuickwitSchema {
+        /// Quickwit REST API url
+        #[arg(long)]
+        quickwit_rest_url: String,
+        #[arg(long, default_value = "rlog")]
+        index_id: String,
+    },
+    /// Parse sample lines with a `files_in` entry of a shipper configuration and print
+    /// the resulting quickwit documents (or the parse errors).
+    ParseTest {
+        /// Shipper configuration file
+        #[arg(long)]
+        config: String,
+        /// Sample log lines
+        #[arg(long)]
+        file: String,
+        /// `files_in` entry to use, optional if the configuration has a single entry
+        #[arg(long)]
+        input: Option<String>,
+    },
+    /// Test the connectivity (mTLS, latency) with a collector using the gRPC `Ping` RPC.
+    ///
+    /// Exits with an error if the connection fails or if no ping is answered.
+    Ping(PingArgs),
+}
+
+#[derive(Args)]
+struct PingArgs {
+    /// URL of the collector gRPC endpoint, TLS is not used for `http://` URLs
+    #[arg(long, env)]
+    grpc_collector_url: String,
+    /// trusted CA certficate used for mTLS connection
+    #[arg(long, env)]
+    tls_ca_certificate: Option<String>,
+    /// private key used for mTLS connection
+    #[arg(long, env)]
+    tls_private_key: Option<String>,
+    /// certificate, signed by the CA corresponding to the private key
+    #[arg(long, env)]
+    tls_certificate: Option<String>,
+    /// Remote server hostname used for the server identity verification (SNI) instead of
+    /// the host of the gRPC collector URL
+    #[arg(long, env)]
+    tls_remote_hostname: Option<String>,
+    /// Number of pings
+    #[arg(short, long, default_value_t = 4)]
+    count: usize,
+    /// Interval between pings, in human time format (eg. "500ms")
+    #[arg(short, long, default_value = "1s", value_parser = humantime::parse_duration)]
+    interval: std::time::Duration,
+}
+
+impl PingArgs {
+    fn run(&self) -> anyhow::Result<()> {
+        let uri: Uri = self
+            .grpc_collector_url
+            .parse()
+            .context("Invalid gRPC collector URL")?;
+        let mut endpoint = Channel::builder(uri.clone());
+        if uri.scheme_str() != Some("http") {
+            let (Some(ca), Some(certificate), Some(private_key)) = (
+                &self.tls_ca_certificate,
+                &self.tls_certificate,
+                &self.tls_private_key,
+            ) else {
+                bail!("--tls-ca-certificate, --tls-certificate & --tls-private-key are required with TLS");
+            };
+            let mut tls_config = ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(
+                    read_file(ca).context("Cannot open ca certificate")?,
+                ))
+                .identity(Identity::from_pem(
+                    read_file(certificate).context("Cannot open certificate")?,
+                    read_file(private_key).context("Cannot open private key")?,
+                ));
+            if let Some(domain_name) = &self.tls_remote_hostname {
+                tls_config = tls_config.domain_name(domain_name);
+            }
+            endpoint = endpoint
+                .tls_config(tls_config)
+                .context("Invalid TLS configuration")?;
+        }
+        let stats = tokio::runtime::Runtime::new()?.block_on(ping::ping(
+            endpoint,
+            self.count,
+            self.interval,
+            &mut std::io::stdout(),
+        ))?;
+        if stats.rtts.is_empty() {
+            bail!("No reply from the collector");
+        }
+        Ok(())
+    }
 }
 
 #[derive(Subcommand)]
@@ -74,6 +178,25 @@ enum CertificateCommand {
         /// Name of the client (common name)
         client_name: String,
     },
+    /// List certificates of output_dir with their type and expiration date.
+    ///
+    /// Certificates expiring within 30 days are highlighted.
+    List {
+        /// Machine readable JSON output
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the details of a certificate, including its SHA-256 fingerprint.
+    Inspect {
+        /// Path of the PEM certificate
+        certificate: String,
+    },
+    /// Print the log signing key of a shipper (`sign_log_entries`) derived from its private
+    /// key, to be set in the collector `collector_log_signature_keys`.
+    SigningKey {
+        /// Path of the PEM private key of the shipper
+        private_key: String,
+    },
 }
 
 impl CertificateCommand {
@@ -254,6 +377,22 @@ impl CertificateCommand {
                     );
                 }
             }
+            CertificateCommand::List { json } => {
+                cert_list::list_certificates(&output_dir, *json)?;
+            }
+            CertificateCommand::Inspect { certificate } => {
+                cert_list::inspect_certificate(certificate)?;
+            }
+            CertificateCommand::SigningKey { private_key } => {
+                let private_key = std::fs::read(private_key)
+                    .with_context(|| format!("Unable to open private key from {private_key}"))?;
+                println!(
+                    "{}",
+                    log_signature::encode_signing_key(&log_signature::derive_signing_key(
+                        &private_key
+                    )?)
+                );
+            }
         }
         Ok(())
     }
@@ -297,8 +436,28 @@ fn parse_ca_certificate(output_dir: &str) -> anyhow::Result<(CertificateParams,
 
 fn main() -> Result<(), Box<dyn Error>> {
     let opts = Opts::parse();
+    // stdout is kept for the command outputs (eg: `cert list --json`)
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .from_env_lossy(),
+        )
+        .init();
     match opts.command {
-        Command::PrintQuickwitSchema => println!("{}", include_str!("schema.yaml")),
+        Command::PrintQuickwitSchema => println!("{}", quickwit_schema::BUNDLED_SCHEMA),
+        Command::CheckQuickwitSchema {
+            quickwit_rest_url,
+            index_id,
+        } => quickwit_schema::check_quickwit_schema(&quickwit_rest_url, &index_id)?,
+        Command::ParseTest {
+            config,
+            file,
+            input,
+        } => parse_test::parse_test(&config, &file, input.as_deref())?,
+        Command::Ping(args) => args.run()?,
         Command::Cert {
             output_dir,
             command,
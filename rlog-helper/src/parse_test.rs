@@ -0,0 +1,57 @@
+use anyhow::{anyhow, bail, Context};
+use rlog_collector::IndexLogEntry;
+use rlog_grpc::rlog_service_protocol::LogLine;
+use rlog_shipper::config::Config;
+
+/// Parse each line of `file` with a `files_in` entry of the shipper configuration and print
+/// the resulting quickwit documents, using the same conversions as the shipper & collector.
+///
+/// `input` is the `files_in` key to use, optional if the configuration has a single entry.
+pub fn parse_test(config: &str, file: &str, input: Option<&str>) -> anyhow::Result<()> {
+    let content = std::fs::read_to_string(config)
+        .with_context(|| format!("Unable to read config file {config}"))?;
+    let config: Config = rlog_common::config::parse_config_strict(&content)
+        .with_context(|| format!("Invalid config file {config}"))?;
+
+    let (path, parse_config) = match input {
+        Some(input) => config
+            .files_in
+            .get_key_value(input)
+            .ok_or_else(|| anyhow!("No files_in entry for {input}"))?,
+        None if config.files_in.len() == 1 => config.files_in.iter().next().unwrap(),
+        None if config.files_in.is_empty() => bail!("No files_in entry in the config file"),
+        None => bail!(
+            "Several files_in entries, use --input to select one of: {}",
+            config
+                .files_in
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let lines = std::fs::read_to_string(file)
+        .with_context(|| format!("Unable to read sample file {file}"))?;
+    let mut failures = 0;
+    for (i, line) in lines.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = parse_config
+            .to_log(line, path)
+            .and_then(LogLine::try_from)
+            .and_then(IndexLogEntry::try_from);
+        match entry {
+            Ok(entry) => println!("{}: {}", i + 1, serde_json::to_string_pretty(&entry)?),
+            Err(e) => {
+                failures += 1;
+                println!("{}: ERROR {e:#}", i + 1);
+            }
+        }
+    }
+    if failures > 0 {
+        bail!("{failures} line(s) could not be parsed");
+    }
+    Ok(())
+}
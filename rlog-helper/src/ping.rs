@@ -0,0 +1,90 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use rlog_grpc::{
+    rlog_service_protocol::{log_collector_client::LogCollectorClient, PingRequest},
+    tonic::{transport::Endpoint, Request},
+};
+
+#[derive(Debug, Default)]
+pub struct PingStats {
+    pub transmitted: usize,
+    /// round trip times of the replies
+    pub rtts: Vec<Duration>,
+}
+
+/// Send `count` pings to the collector, one every `interval`. Results are written to `out`
+/// like `ping` does.
+///
+/// Connection errors (eg: certificate mismatch) are returned, failed pings are only reported.
+pub async fn ping(
+    endpoint: Endpoint,
+    count: usize,
+    interval: Duration,
+    out: &mut impl Write,
+) -> anyhow::Result<PingStats> {
+    let uri = endpoint.uri().clone();
+    writeln!(out, "PING {uri}")?;
+    let started = Instant::now();
+    let channel = endpoint
+        .connect()
+        .await
+        .with_context(|| format!("Unable to connect to collector {uri}"))?;
+    writeln!(
+        out,
+        "connected in {:.3} ms",
+        started.elapsed().as_secs_f64() * 1000.0
+    )?;
+    let mut client = LogCollectorClient::new(channel);
+
+    let mut stats = PingStats::default();
+    for seq in 1..=count {
+        if seq > 1 {
+            tokio::time::sleep(interval).await;
+        }
+        let message = format!("rlog-helper ping {seq}");
+        let started = Instant::now();
+        stats.transmitted += 1;
+        match client.ping(Request::new(PingRequest { message })).await {
+            Ok(response) => {
+                let rtt = started.elapsed();
+                stats.rtts.push(rtt);
+                let response = response.into_inner();
+                let server_time = response
+                    .server_timestamp
+                    .map(|timestamp| timestamp.to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "{} bytes from {uri}: seq={seq} time={:.3} ms server_time={server_time}",
+                    response.message.len(),
+                    rtt.as_secs_f64() * 1000.0
+                )?;
+            }
+            Err(status) => writeln!(out, "error from {uri}: seq={seq} {status}")?,
+        }
+    }
+
+    writeln!(out, "\n--- {uri} ping statistics ---")?;
+    let received = stats.rtts.len();
+    writeln!(
+        out,
+        "{} requests transmitted, {received} received, {:.0}% loss",
+        stats.transmitted,
+        (stats.transmitted - received) as f64 * 100.0 / stats.transmitted.max(1) as f64
+    )?;
+    if let (Some(min), Some(max)) = (stats.rtts.iter().min(), stats.rtts.iter().max()) {
+        let avg = stats.rtts.iter().sum::<Duration>() / received as u32;
+        writeln!(
+            out,
+            "rtt min/avg/max = {:.3}/{:.3}/{:.3} ms",
+            min.as_secs_f64() * 1000.0,
+            avg.as_secs_f64() * 1000.0,
+            max.as_secs_f64() * 1000.0
+        )?;
+    }
+    Ok(stats)
+}
@@ -0,0 +1,365 @@
+use std::fmt::Display;
+
+use anyhow::Context;
+use reqwest::Url;
+use serde::{Deserialize, Deserializer};
+
+/// Minimal quickwit index schema bundled with rlog
+pub const BUNDLED_SCHEMA: &str = include_str!("schema.yaml");
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexConfig {
+    pub doc_mapping: DocMapping,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DocMapping {
+    #[serde(default)]
+    pub field_mappings: Vec<FieldMapping>,
+}
+
+/// Only the parts of a quickwit field mapping that are compared, other attributes
+/// (tokenizer, record, ...) are ignored
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldMapping {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    /// quickwit reports text fast fields as an object (`{normalizer: raw}`)
+    #[serde(default, deserialize_with = "deserialize_fast")]
+    pub fast: bool,
+    #[serde(default = "default_stored")]
+    pub stored: bool,
+}
+
+fn default_stored() -> bool {
+    true
+}
+
+fn deserialize_fast<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(fast) => fast,
+        serde_json::Value::Null => false,
+        _ => true,
+    })
+}
+
+/// Index metadata returned by `GET /api/v1/indexes/{index_id}`
+#[derive(Deserialize)]
+struct IndexMetadata {
+    index_config: IndexConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaDifference {
+    /// field of the bundled schema absent from the live index
+    MissingField { name: String, field_type: String },
+    /// field of the live index absent from the bundled schema
+    ExtraField { name: String, field_type: String },
+    TypeChanged {
+        name: String,
+        bundled: String,
+        live: String,
+    },
+    FastChanged {
+        name: String,
+        bundled: bool,
+        live: bool,
+    },
+    StoredChanged {
+        name: String,
+        bundled: bool,
+        live: bool,
+    },
+}
+
+impl SchemaDifference {
+    /// Type changes break the ingestion, other differences are additive
+    pub fn is_incompatible(&self) -> bool {
+        matches!(self, SchemaDifference::TypeChanged { .. })
+    }
+}
+
+impl Display for SchemaDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaDifference::MissingField { name, field_type } => {
+                write!(f, "+ {name} ({field_type}): missing from the live index")
+            }
+            SchemaDifference::ExtraField { name, field_type } => {
+                write!(f, "- {name} ({field_type}): not in the bundled schema")
+            }
+            SchemaDifference::TypeChanged {
+                name,
+                bundled,
+                live,
+            } => write!(
+                f,
+                "! {name}: type {live} in the live index, {bundled} expected"
+            ),
+            SchemaDifference::FastChanged {
+                name,
+                bundled,
+                live,
+            } => write!(
+                f,
+                "~ {name}: fast {live} in the live index, {bundled} expected"
+            ),
+            SchemaDifference::StoredChanged {
+                name,
+                bundled,
+                live,
+            } => write!(
+                f,
+                "~ {name}: stored {live} in the live index, {bundled} expected"
+            ),
+        }
+    }
+}
+
+pub fn bundled_doc_mapping() -> anyhow::Result<DocMapping> {
+    let index_config: IndexConfig =
+        serde_yaml::from_str(BUNDLED_SCHEMA).context("Invalid bundled quickwit schema")?;
+    Ok(index_config.doc_mapping)
+}
+
+/// Fetch the doc mapping of the live `index_id` index
+pub async fn fetch_doc_mapping(
+    quickwit_rest_url: &str,
+    index_id: &str,
+) -> anyhow::Result<DocMapping> {
+    let url = Url::parse(quickwit_rest_url)
+        .with_context(|| format!("Invalid quickwit REST url {quickwit_rest_url}"))?
+        .join(&format!("api/v1/indexes/{index_id}"))?;
+    let metadata: IndexMetadata = reqwest::get(url.clone())
+        .await
+        .with_context(|| format!("Unable to fetch index metadata from {url}"))?
+        .error_for_status()
+        .with_context(|| format!("Unable to fetch index metadata from {url}"))?
+        .json()
+        .await
+        .context("Invalid index metadata")?;
+    Ok(metadata.index_config.doc_mapping)
+}
+
+/// Differences of the top level field mappings, in the bundled schema order then in the
+/// live index order for extra fields
+pub fn compare(bundled: &DocMapping, live: &DocMapping) -> Vec<SchemaDifference> {
+    let mut differences = vec![];
+    for expected in &bundled.field_mappings {
+        let Some(actual) = live
+            .field_mappings
+            .iter()
+            .find(|field| field.name == expected.name)
+        else {
+            differences.push(SchemaDifference::MissingField {
+                name: expected.name.clone(),
+                field_type: expected.field_type.clone(),
+            });
+            continue;
+        };
+        if actual.field_type != expected.field_type {
+            differences.push(SchemaDifference::TypeChanged {
+                name: expected.name.clone(),
+                bundled: expected.field_type.clone(),
+                live: actual.field_type.clone(),
+            });
+            // flags of a different type are not comparable
+            continue;
+        }
+        if actual.fast != expected.fast {
+            differences.push(SchemaDifference::FastChanged {
+                name: expected.name.clone(),
+                bundled: expected.fast,
+                live: actual.fast,
+            });
+        }
+        if actual.stored != expected.stored {
+            differences.push(SchemaDifference::StoredChanged {
+                name: expected.name.clone(),
+                bundled: expected.stored,
+                live: actual.stored,
+            });
+        }
+    }
+    for actual in &live.field_mappings {
+        if !bundled
+            .field_mappings
+            .iter()
+            .any(|field| field.name == actual.name)
+        {
+            differences.push(SchemaDifference::ExtraField {
+                name: actual.name.clone(),
+                field_type: actual.field_type.clone(),
+            });
+        }
+    }
+    differences
+}
+
+/// Print the differences between the live index and the bundled schema, fails if
+/// any of them is incompatible
+pub fn check_quickwit_schema(quickwit_rest_url: &str, index_id: &str) -> anyhow::Result<()> {
+    let bundled = bundled_doc_mapping()?;
+    let live =
+        tokio::runtime::Runtime::new()?.block_on(fetch_doc_mapping(quickwit_rest_url, index_id))?;
+    let differences = compare(&bundled, &live);
+    if differences.is_empty() {
+        println!("{index_id} index schema matches the bundled schema");
+        return Ok(());
+    }
+    for difference in &differences {
+        println!("{difference}");
+    }
+    let incompatible = differences.iter().filter(|d| d.is_incompatible()).count();
+    if incompatible > 0 {
+        anyhow::bail!("{incompatible} incompatible difference(s) in the {index_id} index schema");
+    }
+    println!("warning: {index_id} index schema differs from the bundled schema");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field(name: &str, field_type: &str) -> FieldMapping {
+        FieldMapping {
+            name: name.into(),
+            field_type: field_type.into(),
+            fast: false,
+            stored: true,
+        }
+    }
+
+    fn doc_mapping(field_mappings: Vec<FieldMapping>) -> DocMapping {
+        DocMapping { field_mappings }
+    }
+
+    #[test]
+    fn bundled_schema() {
+        let bundled = bundled_doc_mapping().unwrap();
+        let timestamp = &bundled.field_mappings[0];
+        assert_eq!("timestamp", timestamp.name);
+        assert_eq!("datetime", timestamp.field_type);
+        assert!(timestamp.fast);
+        assert!(timestamp.stored);
+        // defaults
+        let hostname = &bundled.field_mappings[1];
+        assert_eq!("hostname", hostname.name);
+        assert!(!hostname.fast);
+        assert!(hostname.stored);
+
+        assert_eq!(Vec::<SchemaDifference>::new(), compare(&bundled, &bundled));
+    }
+
+    #[test]
+    fn live_metadata() {
+        let metadata: IndexMetadata = serde_json::from_str(
+            r#"{
+                "index_uid": "rlog:01HB",
+                "index_config": {
+                    "index_id": "rlog",
+                    "doc_mapping": {
+                        "mode": "dynamic",
+                        "field_mappings": [
+                            {"name": "hostname", "type": "text", "fast": {"normalizer": "raw"}, "stored": false, "tokenizer": "raw"},
+                            {"name": "severity_number", "type": "u64", "fast": false, "stored": true}
+                        ]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut hostname = field("hostname", "text");
+        hostname.fast = true;
+        hostname.stored = false;
+        assert_eq!(
+            doc_mapping(vec![hostname, field("severity_number", "u64")]),
+            metadata.index_config.doc_mapping
+        );
+    }
+
+    #[test]
+    fn additive_differences() {
+        let bundled = doc_mapping(vec![
+            field("hostname", "text"),
+            field("trace_id", "text"),
+            field("logger", "text"),
+        ]);
+        let live = doc_mapping(vec![field("custom", "u64"), field("hostname", "text")]);
+        let differences = compare(&bundled, &live);
+        assert_eq!(
+            vec![
+                SchemaDifference::MissingField {
+                    name: "trace_id".into(),
+                    field_type: "text".into()
+                },
+                SchemaDifference::MissingField {
+                    name: "logger".into(),
+                    field_type: "text".into()
+                },
+                SchemaDifference::ExtraField {
+                    name: "custom".into(),
+                    field_type: "u64".into()
+                },
+            ],
+            differences
+        );
+        assert!(!differences.iter().any(SchemaDifference::is_incompatible));
+    }
+
+    #[test]
+    fn flag_differences() {
+        let mut timestamp = field("timestamp", "datetime");
+        timestamp.fast = true;
+        let bundled = doc_mapping(vec![timestamp, field("message", "text")]);
+        let mut message = field("message", "text");
+        message.stored = false;
+        let live = doc_mapping(vec![field("timestamp", "datetime"), message]);
+        let differences = compare(&bundled, &live);
+        assert_eq!(
+            vec![
+                SchemaDifference::FastChanged {
+                    name: "timestamp".into(),
+                    bundled: true,
+                    live: false
+                },
+                SchemaDifference::StoredChanged {
+                    name: "message".into(),
+                    bundled: true,
+                    live: false
+                },
+            ],
+            differences
+        );
+        assert!(!differences.iter().any(SchemaDifference::is_incompatible));
+        assert_eq!(
+            "~ timestamp: fast false in the live index, true expected",
+            differences[0].to_string()
+        );
+    }
+
+    #[test]
+    fn type_change() {
+        let bundled = doc_mapping(vec![field("severity_number", "u64")]);
+        let mut severity_number = field("severity_number", "i64");
+        // not reported: the type differs
+        severity_number.fast = true;
+        let live = doc_mapping(vec![severity_number]);
+        let differences = compare(&bundled, &live);
+        assert_eq!(
+            vec![SchemaDifference::TypeChanged {
+                name: "severity_number".into(),
+                bundled: "u64".into(),
+                live: "i64".into()
+            }],
+            differences
+        );
+        assert!(differences[0].is_incompatible());
+        assert_eq!(
+            "! severity_number: type i64 in the live index, u64 expected",
+            differences[0].to_string()
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Normalization of one second of messages at 50,000 messages/s, with every normalization
+//! enabled and without normalization: the difference must stay a negligible fraction of
+//! that second.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rlog_inputs::{config::MessageNormalization, normalization::normalize_message};
+
+const MESSAGES_PER_SECOND: usize = 50_000;
+
+/// Half of the messages have nothing to normalize
+fn messages() -> Vec<String> {
+    (0..MESSAGES_PER_SECOND)
+        .map(|i| {
+            if i % 2 == 0 {
+                format!("GET /api/users/{i} 200 in 12ms user=john.doe request_id={i:08x}")
+            } else {
+                format!(
+                    "  POST /api/orders/{i}   500 in 120ms\r\nerror:  connection  reset\t(retry {})\r\n",
+                    i % 3
+                )
+            }
+        })
+        .collect()
+}
+
+fn normalize(c: &mut Criterion) {
+    let messages = messages();
+    let all = MessageNormalization {
+        normalize_line_endings: true,
+        collapse_internal_whitespace: true,
+        trim_whitespace: true,
+    };
+    for (name, normalization) in [("none", None), ("all", Some(&all))] {
+        c.bench_function(&format!("normalize_{name}_50k_messages"), |b| {
+            b.iter_batched(
+                || messages.clone(),
+                |messages| {
+                    for message in messages {
+                        criterion::black_box(normalize_message(normalization, message));
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, normalize);
+criterion_main!(benches);
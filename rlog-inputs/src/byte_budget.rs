@@ -0,0 +1,155 @@
+//! Byte budget of in-flight log messages.
+//!
+//! Each message accepted by an input reserves its approximate size from the budget
+//! of the host (shipper or collector). The reservation follows the message through
+//! the input channel and the host pipeline and is released on drop, when the message
+//! leaves the pipeline (shipped, rejected or discarded).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 256MB
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Reserve `size` bytes from the host budget, `None` if the budget is exceeded
+pub type Reserve = fn(usize) -> Option<Reservation>;
+
+pub struct ByteBudget {
+    used: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl ByteBudget {
+    pub const fn new() -> Self {
+        Self {
+            used: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Reserve `size` bytes, if this exceeds `max_bytes`, nothing is reserved
+    /// and the dropped counter is incremented.
+    pub fn try_reserve(&'static self, size: usize, max_bytes: usize) -> Option<Reservation> {
+        let size = size as u64;
+        let used = self.used.fetch_add(size, Ordering::Relaxed) + size;
+        if used > max_bytes as u64 {
+            self.used.fetch_sub(size, Ordering::Relaxed);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            None
+        } else {
+            Some(Reservation { budget: self, size })
+        }
+    }
+
+    /// Number of bytes currently reserved
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages dropped because the budget was exceeded
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for ByteBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bytes reserved from a budget, given back when dropped
+pub struct Reservation {
+    budget: &'static ByteBudget,
+    size: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.used.fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
+/// A message and the bytes it has reserved
+pub struct Budgeted<T> {
+    pub value: T,
+    /// number of the message in its input, gaps are messages discarded by the input
+    pub sequence: Option<u64>,
+    reservation: Reservation,
+}
+
+impl<T> Budgeted<T> {
+    pub fn new(value: T, reservation: Reservation) -> Self {
+        Self {
+            value,
+            sequence: None,
+            reservation,
+        }
+    }
+
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Convert the message, keeping the same reservation
+    pub fn try_map<U, E>(self, f: impl FnOnce(T) -> Result<U, E>) -> Result<Budgeted<U>, E> {
+        Ok(Budgeted {
+            value: f(self.value)?,
+            sequence: self.sequence,
+            reservation: self.reservation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Budgeted, ByteBudget};
+
+    #[tokio::test]
+    async fn test_budget() {
+        let budget: &'static ByteBudget = Box::leak(Box::new(ByteBudget::new()));
+        let (sender, receiver) = async_channel::bounded(10);
+
+        sender
+            .send(Budgeted::new(
+                "first",
+                budget.try_reserve(600, 1000).unwrap(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(600, budget.used());
+
+        // oversized messages are dropped even if the channel has slots remaining
+        assert!(budget.try_reserve(2000, 1000).is_none());
+        assert!(budget.try_reserve(500, 1000).is_none());
+        assert_eq!(2, budget.dropped());
+        assert_eq!(600, budget.used());
+
+        sender
+            .send(Budgeted::new(
+                "second",
+                budget.try_reserve(400, 1000).unwrap(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(1000, budget.used());
+
+        // conversion keeps the reservation
+        let first = receiver
+            .recv()
+            .await
+            .unwrap()
+            .try_map(|v| Ok::<_, ()>(v.len()))
+            .unwrap();
+        assert_eq!(5, first.value);
+        assert_eq!(1000, budget.used());
+        drop(first);
+        assert_eq!(400, budget.used());
+
+        // drain
+        drop(sender);
+        while receiver.recv().await.is_ok() {}
+        assert_eq!(0, budget.used());
+        assert_eq!(2, budget.dropped());
+    }
+}
@@ -0,0 +1,334 @@
+use rlog_common::config::ConfigDoc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
+
+use self::eqregex::EqRegex;
+
+fn default_buffer_size() -> usize {
+    20_000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize, ConfigDoc, PartialEq, Eq)]
+pub struct CommonInputConfig {
+    /// queue size (log lines) of the input, > 0 (not hot reloaded: the queue is allocated
+    /// at the start of the application)
+    #[serde(default = "default_buffer_size")]
+    pub max_buffer_size: usize,
+    /// a disabled input is not started and does not bind its port (not hot reloaded)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// static labels of the log lines of this input, override the global labels of the
+    /// shipper
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    /// normalization of the message of the log lines, not normalized if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize_message: Option<MessageNormalization>,
+}
+
+impl Default for CommonInputConfig {
+    fn default() -> Self {
+        Self {
+            max_buffer_size: 20_000,
+            enabled: true,
+            labels: HashMap::new(),
+            normalize_message: None,
+        }
+    }
+}
+
+/// Normalizations of the message of the log lines, applied in this order
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageNormalization {
+    /// `\r\n` & `\r` are replaced by `\n`
+    #[serde(default)]
+    pub normalize_line_endings: bool,
+    /// runs of whitespace (line breaks included) are replaced by a single space
+    #[serde(default)]
+    pub collapse_internal_whitespace: bool,
+    /// leading & trailing whitespace is removed
+    #[serde(default)]
+    pub trim_whitespace: bool,
+}
+
+#[derive(Deserialize, Default, Serialize, ConfigDoc, PartialEq, Eq)]
+pub struct SyslogInputConfig {
+    #[serde(flatten, default)]
+    pub common: CommonInputConfig,
+    /// messages matching all the regexes (`appname`, `facility`, `message`) of a filter
+    /// are discarded
+    pub exclusion_filters: Vec<SyslogExclusionFilter>,
+    /// if set, syslog messages are reported to the collector as generic logs of this log system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_system: Option<String>,
+    /// source of the service name: appname, facility, proc_name or `static: <name>`
+    #[serde(default)]
+    pub service_name: SyslogServiceName,
+    /// encoding of the received datagrams: utf8, latin1 or detect (guessed for each
+    /// datagram)
+    #[serde(default)]
+    pub encoding: SyslogEncoding,
+    /// file of regexes (one per line) matched against the message, checked after the
+    /// exclusion filters and reloaded when modified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_pattern_file: Option<String>,
+    /// requested receive buffer (`SO_RCVBUF`) of the UDP socket, the kernel may clamp it,
+    /// kernel default if not set (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recv_buffer_bytes: Option<usize>,
+    /// size of the datagram read buffer, default: 65507, datagrams filling it are flagged
+    /// as possibly truncated (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_datagram_bytes: Option<usize>,
+}
+
+/// An UDP datagram cannot be larger than 65507 bytes
+pub const DEFAULT_MAX_DATAGRAM_BYTES: usize = 65507;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogEncoding {
+    /// invalid sequences are replaced by `U+FFFD` and counted as errors
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is a valid character
+    Latin1,
+    /// guessed for each datagram: UTF-8 if it is valid UTF-8 or contains a UTF-8 BOM,
+    /// ISO-8859-1 if a few bytes only are not ASCII, UTF-8 otherwise (unknown encoding)
+    Detect,
+}
+
+/// Source of the service name of syslog messages, `_syslog` if the message does not
+/// have the selected field
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogServiceName {
+    /// the application name (`APP-NAME`/`TAG`)
+    #[default]
+    Appname,
+    /// the facility name (eg: `mail`)
+    Facility,
+    /// the process name, when the message carries a process name instead of a pid
+    ProcName,
+    /// the given service name for all messages
+    Static(String),
+}
+
+/// Exclusion filter patterns for syslog.
+///
+/// If more than one pattern is specified, all the pattern specified must match for
+/// the log entry to be excluded
+#[derive(Deserialize, Default, Serialize, PartialEq, Eq)]
+pub struct SyslogExclusionFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub appname: Option<EqRegex>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facility: Option<EqRegex>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<EqRegex>,
+}
+
+/// Last raw inputs of each source kept in memory (eg: to debug a parse issue), see
+/// [crate::recent_inputs]
+#[derive(Deserialize, Serialize, ConfigDoc, PartialEq, Eq, Clone)]
+pub struct RecentInputsConfig {
+    /// number of raw inputs kept per source
+    #[serde(default = "default_recent_inputs_max_entries")]
+    pub max_entries: usize,
+    /// hard cap of the bytes stored for all the sources, the oldest inputs are evicted first
+    #[serde(default = "default_recent_inputs_max_bytes")]
+    pub max_bytes: usize,
+    /// applied in order to the raw inputs before they are stored, eg: to mask credentials
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Redaction>,
+}
+
+impl Default for RecentInputsConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: default_recent_inputs_max_entries(),
+            max_bytes: default_recent_inputs_max_bytes(),
+            redactions: Vec::new(),
+        }
+    }
+}
+
+fn default_recent_inputs_max_entries() -> usize {
+    20
+}
+
+fn default_recent_inputs_max_bytes() -> usize {
+    1024 * 1024
+}
+
+/// Every match of `pattern` is replaced by `replacement`, capture groups can be referenced
+/// in `replacement` (`$1`, `${name}`)
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Redaction {
+    pub pattern: EqRegex,
+    #[serde(default = "default_redaction_replacement")]
+    pub replacement: String,
+}
+
+fn default_redaction_replacement() -> String {
+    "***".into()
+}
+
+pub mod eqregex {
+    use regex::Regex;
+    use serde::{Deserialize, Serialize};
+    use std::ops::Deref;
+
+    #[derive(Clone, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct EqRegex {
+        #[serde(with = "serde_regex")]
+        inner: Regex,
+    }
+
+    impl EqRegex {
+        pub fn new(regex: &str) -> Result<Self, regex::Error> {
+            Ok(Self {
+                inner: Regex::new(regex)?,
+            })
+        }
+    }
+    impl PartialEq for EqRegex {
+        fn eq(&self, other: &Self) -> bool {
+            self.inner.as_str() == other.inner.as_str()
+        }
+    }
+    impl Eq for EqRegex {}
+
+    impl Deref for EqRegex {
+        type Target = Regex;
+
+        fn deref(&self) -> &Self::Target {
+            &self.inner
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ConfigDoc, PartialEq, Eq)]
+pub struct GelfInputConfig {
+    #[serde(flatten, default)]
+    pub common: CommonInputConfig,
+    /// messages with a `version` lower than this one are discarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<GelfVersion>,
+    /// messages with a `version` greater than this one are discarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<GelfVersion>,
+    /// if set, GELF messages are reported to the collector as generic logs of this log system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_system: Option<String>,
+    /// messages without `short_message`: error (discarded), use_full_message, use_empty
+    /// or `use_field: <field>`
+    #[serde(default)]
+    pub short_message_fallback: ShortMessageFallback,
+    /// fields tried in order for the service name, the first non empty string is used
+    #[serde(default = "default_service_name_fields")]
+    pub service_name_fields: Vec<String>,
+    /// fields tried in order for the severity, the first number or string is used
+    #[serde(default = "default_severity_field_aliases")]
+    pub severity_field_aliases: Vec<String>,
+    /// add the IP of the peer to the messages as the `_source_ip` additional field
+    #[serde(default)]
+    pub add_source_ip: bool,
+    /// cap of the read buffers of all the connections (frames not terminated yet), the
+    /// connections with the largest buffers are closed first when exceeded
+    #[serde(default = "default_max_connection_buffers_bytes")]
+    pub max_connection_buffers_bytes: u64,
+}
+
+impl Default for GelfInputConfig {
+    fn default() -> Self {
+        Self {
+            common: Default::default(),
+            min_version: None,
+            max_version: None,
+            log_system: None,
+            short_message_fallback: Default::default(),
+            service_name_fields: default_service_name_fields(),
+            severity_field_aliases: default_severity_field_aliases(),
+            add_source_ip: false,
+            max_connection_buffers_bytes: default_max_connection_buffers_bytes(),
+        }
+    }
+}
+
+fn default_max_connection_buffers_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_service_name_fields() -> Vec<String> {
+    ["service", "_service", "application", "_application"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn default_severity_field_aliases() -> Vec<String> {
+    vec!["level".into()]
+}
+
+/// Behavior when a GELF message has no `short_message` (some senders only set `full_message`)
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortMessageFallback {
+    /// the message is discarded
+    #[default]
+    Error,
+    /// the first 80 characters of `full_message`
+    UseFullMessage,
+    /// the given additional field (with or without the leading `_`)
+    UseField(String),
+    /// an empty short message
+    UseEmpty,
+}
+
+/// Dotted GELF version (eg: `1.1`), compared numerically component by component
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct GelfVersion(Vec<u64>);
+
+impl FromStr for GelfVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s
+            .trim()
+            .split('.')
+            .map(|c| c.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid GELF version {s}: {e}"))?;
+        // 1.1 == 1.1.0
+        while components.len() > 1 && components.last() == Some(&0) {
+            components.pop();
+        }
+        Ok(Self(components))
+    }
+}
+
+impl TryFrom<String> for GelfVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<GelfVersion> for String {
+    fn from(value: GelfVersion) -> Self {
+        value
+            .0
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
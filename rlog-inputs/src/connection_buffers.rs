@@ -0,0 +1,142 @@
+//! Read buffers of the connections of an input: a peer which never terminates its frames
+//! grows its buffer without limit, the total is capped by closing the largest buffers first.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio_util::sync::CancellationToken;
+
+/// Buffer of a connection
+struct ConnectionBuffer {
+    peer: String,
+    /// capacity of the buffer
+    bytes: AtomicU64,
+    /// cancelled to close the connection
+    closed: CancellationToken,
+}
+
+#[derive(Default)]
+pub struct ConnectionBuffersRegistry {
+    connections: Mutex<HashMap<u64, Arc<ConnectionBuffer>>>,
+    next_id: AtomicU64,
+}
+
+/// Registration of a connection, unregistered on drop
+pub struct ConnectionBufferGuard<'a> {
+    registry: &'a ConnectionBuffersRegistry,
+    id: u64,
+    buffer: Arc<ConnectionBuffer>,
+}
+
+impl ConnectionBufferGuard<'_> {
+    /// Report the current capacity of the buffer of the connection
+    pub fn set_bytes(&self, bytes: usize) {
+        self.buffer.bytes.store(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Cancelled once the connection must be closed
+    pub fn closed(&self) -> &CancellationToken {
+        &self.buffer.closed
+    }
+}
+
+impl Drop for ConnectionBufferGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.connections.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl ConnectionBuffersRegistry {
+    pub fn register(&self, peer: String) -> ConnectionBufferGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let buffer = Arc::new(ConnectionBuffer {
+            peer,
+            bytes: AtomicU64::new(0),
+            closed: CancellationToken::new(),
+        });
+        self.connections.lock().unwrap().insert(id, buffer.clone());
+        ConnectionBufferGuard {
+            registry: self,
+            id,
+            buffer,
+        }
+    }
+
+    /// Total bytes of the buffers of the connections not closed yet
+    pub fn total(&self) -> u64 {
+        self.connections
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|buffer| !buffer.closed.is_cancelled())
+            .map(|buffer| buffer.bytes.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Close the connections with the largest buffers until the total is at most
+    /// `max_bytes`, returns the total of the buffers before closing any connection.
+    pub fn enforce(&self, max_bytes: u64) -> u64 {
+        let connections = self.connections.lock().unwrap();
+        let mut buffers = connections
+            .values()
+            .filter(|buffer| !buffer.closed.is_cancelled())
+            .map(|buffer| (buffer.bytes.load(Ordering::Relaxed), buffer))
+            .collect::<Vec<_>>();
+        let total = buffers.iter().map(|(bytes, _)| bytes).sum();
+        buffers.sort_by_key(|(bytes, _)| Reverse(*bytes));
+        let mut remaining = total;
+        for (bytes, buffer) in buffers {
+            if remaining <= max_bytes {
+                break;
+            }
+            tracing::error!(
+                "Connection buffers exceed {max_bytes} bytes: closing the connection of {} ({bytes} bytes buffered)",
+                buffer.peer
+            );
+            buffer.closed.cancel();
+            remaining -= bytes;
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConnectionBuffersRegistry;
+
+    #[test]
+    fn test_enforce() {
+        let registry = ConnectionBuffersRegistry::default();
+        let small = registry.register("10.0.0.1:1234".into());
+        let large = registry.register("10.0.0.2:1234".into());
+        let medium = registry.register("10.0.0.3:1234".into());
+        small.set_bytes(10);
+        large.set_bytes(50);
+        medium.set_bytes(30);
+
+        assert_eq!(90, registry.enforce(100));
+        assert!(!large.closed().is_cancelled());
+
+        // the largest buffer is enough
+        assert_eq!(90, registry.enforce(45));
+        assert!(large.closed().is_cancelled());
+        assert!(!medium.closed().is_cancelled());
+        assert!(!small.closed().is_cancelled());
+        // closed connections are not counted anymore
+        assert_eq!(40, registry.total());
+
+        assert_eq!(40, registry.enforce(5));
+        assert!(medium.closed().is_cancelled());
+        assert!(small.closed().is_cancelled());
+        assert_eq!(0, registry.total());
+
+        drop(large);
+        assert_eq!(2, registry.connections.lock().unwrap().len());
+    }
+}
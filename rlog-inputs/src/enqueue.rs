@@ -0,0 +1,82 @@
+//! Hand over of the received messages to the bounded queue of an input, shared by the
+//! servers so that they cannot diverge on what a full queue means.
+
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use async_channel::{Sender, TrySendError};
+
+use crate::byte_budget::Budgeted;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Enqueued {
+    /// the message has been queued, the input goes on
+    Continue,
+    /// the queue is full, the message has been dropped, the input goes on
+    Dropped,
+    /// the queue is closed (shutdown), the input must stop
+    Stop,
+}
+
+/// Queue the message without waiting: if the queue is full the message is dropped (and
+/// counted as an error), the input only stops if the queue is closed.
+pub fn enqueue_or_drop<T: Display>(
+    sender: &Sender<Budgeted<T>>,
+    message: Budgeted<T>,
+    error_count: &AtomicU64,
+    queue_count: &AtomicU64,
+) -> Enqueued {
+    match sender.try_send(message) {
+        Ok(()) => {
+            queue_count.fetch_add(1, Ordering::Relaxed);
+            Enqueued::Continue
+        }
+        Err(TrySendError::Full(message)) => {
+            error_count.fetch_add(1, Ordering::Relaxed);
+            tracing::error!("Send buffer full: discarding value {}", message.value);
+            Enqueued::Dropped
+        }
+        Err(TrySendError::Closed(message)) => {
+            error_count.fetch_add(1, Ordering::Relaxed);
+            tracing::error!("Channel closed, discarding value {}", message.value);
+            Enqueued::Stop
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::{enqueue_or_drop, Enqueued};
+    use crate::byte_budget::{Budgeted, ByteBudget};
+
+    static BUDGET: ByteBudget = ByteBudget::new();
+
+    fn message(value: &str) -> Budgeted<String> {
+        Budgeted::new(value.into(), BUDGET.try_reserve(1, 100).unwrap())
+    }
+
+    #[test]
+    fn test_full_and_closed() {
+        let (sender, receiver) = async_channel::bounded(1);
+        let (error_count, queue_count) = (AtomicU64::new(0), AtomicU64::new(0));
+        let enqueue = |value| enqueue_or_drop(&sender, message(value), &error_count, &queue_count);
+
+        assert_eq!(Enqueued::Continue, enqueue("first"));
+        assert_eq!(1, queue_count.load(Ordering::Relaxed));
+        // full: dropped, the input goes on
+        assert_eq!(Enqueued::Dropped, enqueue("second"));
+        assert_eq!(1, error_count.load(Ordering::Relaxed));
+        assert_eq!("first", receiver.try_recv().unwrap().value);
+        assert_eq!(Enqueued::Continue, enqueue("third"));
+        assert_eq!(2, queue_count.load(Ordering::Relaxed));
+
+        receiver.close();
+        assert_eq!(Enqueued::Stop, enqueue("fourth"));
+        assert_eq!(2, error_count.load(Ordering::Relaxed));
+        assert_eq!(2, queue_count.load(Ordering::Relaxed));
+    }
+}
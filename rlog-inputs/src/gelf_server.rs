@@ -0,0 +1,773 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::IpAddr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use anyhow::Context;
+use arc_swap::access::Access;
+use async_channel::Receiver;
+use bytes::BytesMut;
+use chrono::{TimeZone, Utc};
+use futures::FutureExt;
+use rlog_common::bind_addr::BindAddr;
+use rlog_grpc::rlog_service_protocol::{GelfLogLine, LogLine, SyslogSeverity};
+use serde_json::Value;
+use tokio::{io::AsyncReadExt, net::TcpListener, select};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::{
+    byte_budget::{Budgeted, Reserve},
+    config::{GelfInputConfig, GelfVersion, ShortMessageFallback},
+    enqueue::{enqueue_or_drop, Enqueued},
+    generic_log::GenericLog,
+    metrics::{
+        self, GELF_CONNECTION_BUFFERS, GELF_CONNECTION_BUFFERS_BYTES, GELF_DISCARDED_COUNT,
+        GELF_ERROR_COUNT, GELF_PEERS, GELF_QUEUE_CAPACITY, GELF_QUEUE_COUNT, GELF_SEQUENCE,
+        GELF_VERSION_REJECTED_COUNT,
+    },
+    normalization::normalize_message,
+    recent_inputs::RECENT_INPUTS,
+};
+
+pub struct GelfLog(pub serde_json::Value);
+
+impl GelfLog {
+    pub fn to_json(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl Display for GelfLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// Initial capacity of the read buffer of a connection
+const INITIAL_BUFFER_CAPACITY: usize = 4096;
+
+/// A read buffer grown past this capacity by a large frame is reallocated once the frame is
+/// processed, instead of holding its peak capacity until the connection is closed
+const RECLAIM_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Interval of the accounting of the read buffers of the connections (see
+/// `GelfInputConfig::max_connection_buffers_bytes`)
+const BUFFER_ACCOUNTING_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `config` gives access to the (hot reloaded) `gelf_in` section of the host config
+pub async fn launch_gelf_server<C>(
+    bind_address: BindAddr,
+    config: C,
+    reserve: Reserve,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<Receiver<Budgeted<GelfLog>>>
+where
+    C: Access<Option<GelfInputConfig>> + Send + Sync + 'static,
+{
+    // shared by the connection handlers
+    let config = Arc::new(config);
+    let max_buffer_size = match config.load().as_ref() {
+        Some(config) => config.common.max_buffer_size,
+        None => GelfInputConfig::default().common.max_buffer_size,
+    };
+    GELF_QUEUE_CAPACITY.store(max_buffer_size as u64, Ordering::Relaxed);
+    let (sender, receiver) = async_channel::bounded(max_buffer_size);
+
+    let listener = TcpListener::bind(bind_address.socket_addr())
+        .await
+        .with_context(|| format!("Unable to bind GELF TCP server to {bind_address}"))?;
+
+    tracing::info!("GELF TCP server listening at {bind_address}");
+
+    launch_buffer_accounting(config.clone(), shutdown_token.clone());
+
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = shutdown_token.cancelled() => {
+                    return;
+                }
+                res = listener.accept() => {
+                    let (mut socket, r) = match res {
+                        Ok(connection) => connection,
+                        Err(e) => {
+                            tracing::error!("Unable to accept incoming connection! {e}");
+                            return;
+                        }
+                    };
+                    let shutdown_token = shutdown_token.child_token();
+                    let sender = sender.clone();
+                    let config = config.clone();
+                    let remote_addr = format!("{r}");
+                    let peer_ip = r.ip().to_canonical();
+                    let peer = GELF_PEERS.peer(peer_ip);
+                    let buffer_guard = GELF_CONNECTION_BUFFERS.register(remote_addr.clone());
+                    tokio::spawn(
+                        async move {
+                            tracing::info!("new connection");
+                            let mut buffer = BytesMut::with_capacity(INITIAL_BUFFER_CAPACITY);
+                            loop {
+                                select!{
+                                    // `max_connection_buffers_bytes` exceeded
+                                    _ = buffer_guard.closed().cancelled() => {
+                                        break;
+                                    }
+                                    _ = shutdown_token.cancelled() => {
+                                        if buffer.len()>0 {
+                                            // wait for more bytes to come before shutting down
+                                            tracing::debug!("Buffer not empty!");
+                                        } else {
+                                            return;
+                                        }
+                                    }
+                                    res = socket.read_buf(&mut buffer) => {
+                                        let _n = match res {
+                                            // graceful shutdown
+                                            Ok(n) if n == 0 && buffer.len() == 0 => break,
+                                            // connection closed during transmission of a frame
+                                            Ok(n) if n == 0 => {
+                                                tracing::error!("Connection reset by peer");
+                                                break;
+                                            }
+                                            Ok(n) => n,
+                                            Err(e) => {
+                                                tracing::error!("failed to read from socket; {e}");
+                                                return;
+                                            }
+                                        };
+                                        let read_capacity = buffer.capacity();
+                                        // check we received a \0 bytes indicating the end of a frame
+                                        while let Some(i) = buffer
+                                            .iter()
+                                            .enumerate()
+                                            .find(|(_i, byte)| byte == &&0)
+                                            .map(|(i, _)| i)
+                                        {
+                                            let frame = buffer.split_to(i + 1);
+                                            // there is a message between 0..i (the last byte is 0x0 we must not feed the json
+                                            // parser with this)
+                                            RECENT_INPUTS.record("gelf_in", &String::from_utf8_lossy(&frame[0..i]));
+                                            peer.frames.fetch_add(1, Ordering::Relaxed);
+                                            match serde_json::from_slice::<Value>(&frame[0..i]) {
+                                                Ok(mut valid_json) => {
+                                                    let input_config = config.load();
+                                                    if let Err(e) = check_version(&valid_json, input_config.as_ref()) {
+                                                        GELF_VERSION_REJECTED_COUNT.fetch_add(1, Ordering::Relaxed);
+                                                        peer.dropped.fetch_add(1, Ordering::Relaxed);
+                                                        tracing::error!("{e}: discarding value {valid_json}");
+                                                        continue;
+                                                    }
+                                                    if input_config.as_ref().is_some_and(|config| config.add_source_ip) {
+                                                        add_source_ip(&mut valid_json, peer_ip);
+                                                    }
+                                                    let sequence = GELF_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+                                                    let Some(reservation) = reserve(i) else {
+                                                        GELF_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                                                        GELF_DISCARDED_COUNT.fetch_add(1, Ordering::Relaxed);
+                                                        peer.dropped.fetch_add(1, Ordering::Relaxed);
+                                                        tracing::error!("Buffered bytes budget exceeded: discarding value {valid_json}");
+                                                        continue;
+                                                    };
+                                                    let message = Budgeted::new(GelfLog(valid_json), reservation).with_sequence(sequence);
+                                                    match enqueue_or_drop(&sender, message, &GELF_ERROR_COUNT, &GELF_QUEUE_COUNT) {
+                                                        Enqueued::Continue => {}
+                                                        Enqueued::Dropped => {
+                                                            GELF_DISCARDED_COUNT.fetch_add(1, Ordering::Relaxed);
+                                                            peer.dropped.fetch_add(1, Ordering::Relaxed);
+                                                        }
+                                                        Enqueued::Stop => {
+                                                            GELF_DISCARDED_COUNT.fetch_add(1, Ordering::Relaxed);
+                                                            return;
+                                                        }
+                                                    }
+                                                }
+                                                Err(e) => {
+                                                    peer.parse_errors.fetch_add(1, Ordering::Relaxed);
+                                                    tracing::error!("Unable to decode json: {e}")
+                                                }
+                                            }
+                                        }
+                                        if read_capacity > RECLAIM_BUFFER_CAPACITY && buffer.len() < INITIAL_BUFFER_CAPACITY {
+                                            let mut reclaimed = BytesMut::with_capacity(INITIAL_BUFFER_CAPACITY);
+                                            reclaimed.extend_from_slice(&buffer);
+                                            buffer = reclaimed;
+                                        }
+                                        buffer_guard.set_bytes(buffer.capacity());
+                                    }
+                                }
+                            }
+                            tracing::info!("Connection closed.");
+                        }
+                        .instrument(tracing::info_span!("gelf_conn_handler", remote_addr)),
+                    );
+                }
+            }
+        }
+    }
+    .then(|_| async {
+        tracing::info!("GELF server stopped, processed: {}, errors: {}, in_queue: {}", 
+            metrics::GELF_PROCESSED_COUNT.load(Ordering::Relaxed),
+            metrics::GELF_ERROR_COUNT.load(Ordering::Relaxed),
+            metrics::GELF_QUEUE_COUNT.load(Ordering::Relaxed),
+        )
+    }));
+
+    Ok(receiver)
+}
+
+/// Sum the read buffers of the connections into `GELF_CONNECTION_BUFFERS_BYTES` and close
+/// the connections with the largest buffers if `max_connection_buffers_bytes` is exceeded
+fn launch_buffer_accounting<C>(config: Arc<C>, shutdown_token: CancellationToken)
+where
+    C: Access<Option<GelfInputConfig>> + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(BUFFER_ACCOUNTING_INTERVAL);
+        loop {
+            select! {
+                _ = shutdown_token.cancelled() => return,
+                _ = interval.tick() => {
+                    let max_bytes = match config.load().as_ref() {
+                        Some(config) => config.max_connection_buffers_bytes,
+                        None => GelfInputConfig::default().max_connection_buffers_bytes,
+                    };
+                    let total = GELF_CONNECTION_BUFFERS.enforce(max_bytes);
+                    GELF_CONNECTION_BUFFERS_BYTES.store(total, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+}
+
+/// Set the `_source_ip` additional field to the IP of the peer, overriding the one set by
+/// the sender if any
+fn add_source_ip(json: &mut Value, peer_ip: IpAddr) {
+    if let Value::Object(json_map) = json {
+        json_map.insert("_source_ip".into(), peer_ip.to_string().into());
+    }
+}
+
+/// Check the `version` field against the configured accepted range, if any
+fn check_version(json: &Value, config: Option<&GelfInputConfig>) -> anyhow::Result<()> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+    if config.min_version.is_none() && config.max_version.is_none() {
+        return Ok(());
+    }
+    let version = json
+        .get("version")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("No `version` string field"))?;
+    let parsed: GelfVersion = version.parse()?;
+    if let Some(min_version) = &config.min_version {
+        if &parsed < min_version {
+            anyhow::bail!("GELF version {version} is not supported");
+        }
+    }
+    if let Some(max_version) = &config.max_version {
+        if &parsed > max_version {
+            anyhow::bail!("GELF version {version} is not supported");
+        }
+    }
+    Ok(())
+}
+
+/// `short_message` is truncated to this number of characters when taken from `full_message`
+const SHORT_MESSAGE_FALLBACK_LENGTH: usize = 80;
+
+impl GelfLog {
+    /// Convert to a gelf log line, or to a generic log line if `log_system` is configured
+    pub fn into_log_line(self, config: Option<&GelfInputConfig>) -> anyhow::Result<LogLine> {
+        let log_system = config.and_then(|config| config.log_system.clone());
+        let json = self.0;
+        let json_map = json
+            .as_object()
+            .ok_or_else(|| anyhow::anyhow!("{json} is not an object!"))?;
+        // extract host & timestamp
+        let hostname = json_map
+            .get("host")
+            .map(|v| v.as_str())
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("{json} does not have a `host` string field!"))?;
+        let timestamp_secs = json_map
+            .get("timestamp")
+            .map(|v| v.as_f64())
+            .flatten()
+            .ok_or_else(|| anyhow::anyhow!("{json} does not have a `timestamp` number field!"))?;
+        // some gelf enabled software (java) sends timestamp with millis or micros, a f64
+        // number of seconds keeps the microseconds of current dates
+        let timestamp_micros = (timestamp_secs * 1_000_000.0).round() as i64;
+
+        let timestamp = rlog_grpc::prost_wkt_types::Timestamp {
+            seconds: timestamp_micros.div_euclid(1_000_000),
+            nanos: (timestamp_micros.rem_euclid(1_000_000) * 1_000) as i32,
+        };
+
+        let severity_field_aliases = match config {
+            Some(config) => config.severity_field_aliases.clone(),
+            None => GelfInputConfig::default().severity_field_aliases,
+        };
+        let (severity_field, severity) = match severity(json_map, &severity_field_aliases) {
+            Some((field, severity)) => (Some(field), severity),
+            None => (None, SyslogSeverity::Alert as i32), // ALERT by GELF spec
+        };
+
+        let full_message = json_map
+            .get("full_message")
+            .map(|v| v.as_str())
+            .flatten()
+            .map(ToString::to_string);
+        let short_message = match json_map.get("short_message").and_then(|v| v.as_str()) {
+            Some(short_message) => short_message.to_string(),
+            None => short_message_fallback(
+                json_map,
+                full_message.as_deref(),
+                config
+                    .map(|config| &config.short_message_fallback)
+                    .unwrap_or(&ShortMessageFallback::Error),
+            )
+            .ok_or_else(|| {
+                anyhow::anyhow!("{json} does not have a `short_message` string field!")
+            })?,
+        };
+        let short_message = normalize_message(
+            config.and_then(|config| config.common.normalize_message.as_ref()),
+            short_message,
+        );
+        let short_message = short_message.as_str();
+        let mut extra = HashMap::new();
+        for (key, value) in json_map {
+            let key = if key.starts_with('_') {
+                &key[1..]
+            } else {
+                key.as_str()
+            };
+            match key {
+                // ignore fields set elsewhere
+                "host" | "timestamp" | "facility" | "version" | "level" | "short_message"
+                | "full_message" => continue,
+                _ => {}
+            }
+            extra.insert(key, value);
+        }
+        if let Some(field) = severity_field {
+            extra.remove(field.strip_prefix('_').unwrap_or(field));
+        }
+        // picked by the collector (or `to_generic_log`) as service name
+        let service_name_fields = match config {
+            Some(config) => config.service_name_fields.clone(),
+            None => GelfInputConfig::default().service_name_fields,
+        };
+        if let Some(service_name) = service_name(json_map, &service_name_fields) {
+            for field in &service_name_fields {
+                extra.remove(field.strip_prefix('_').unwrap_or(field));
+            }
+            extra.insert("service", service_name);
+        }
+
+        if let Some(log_system) = log_system {
+            return LogLine::try_from(to_generic_log(
+                hostname,
+                timestamp,
+                severity,
+                short_message,
+                full_message,
+                extra,
+                log_system,
+            )?);
+        }
+
+        let extra = serde_json::to_string(&extra)?; // this cannot fail
+
+        Ok(LogLine {
+            host: hostname.into(),
+            raw_host: None,
+            hmac: Vec::new(),
+            sequence: None,
+            labels: Default::default(),
+            timestamp: Some(timestamp),
+            line: Some(rlog_grpc::rlog_service_protocol::log_line::Line::Gelf(
+                GelfLogLine {
+                    short_message: short_message.into(),
+                    full_message: full_message,
+                    severity,
+                    extra,
+                },
+            )),
+        })
+    }
+}
+
+/// Severity of the first field holding a number or a string, with the name of this field.
+///
+/// Strings are severity names (case insensitive, eg: `error`) or numbers, unknown names are
+/// mapped to `INFO`.
+fn severity<'a>(
+    json_map: &serde_json::Map<String, Value>,
+    severity_field_aliases: &'a [String],
+) -> Option<(&'a str, i32)> {
+    severity_field_aliases.iter().find_map(|field| {
+        let severity = match json_map.get(field)? {
+            Value::Number(level) => level.as_i64()? as i32,
+            Value::String(level) => match level.parse::<i32>() {
+                Ok(level) => level,
+                Err(_) => match SyslogSeverity::from_str_name(&level.to_ascii_uppercase()) {
+                    Some(severity) => severity as i32,
+                    None => {
+                        tracing::debug!("Unknown GELF severity {level:?} in `{field}`, using INFO");
+                        SyslogSeverity::Info as i32
+                    }
+                },
+            },
+            _ => return None,
+        };
+        Some((field.as_str(), severity))
+    })
+}
+
+/// Value of the first field holding a non empty string
+fn service_name<'a>(
+    json_map: &'a serde_json::Map<String, Value>,
+    service_name_fields: &[String],
+) -> Option<&'a Value> {
+    service_name_fields.iter().find_map(|field| {
+        json_map
+            .get(field)
+            .filter(|value| value.as_str().is_some_and(|value| !value.is_empty()))
+    })
+}
+
+fn short_message_fallback(
+    json_map: &serde_json::Map<String, Value>,
+    full_message: Option<&str>,
+    fallback: &ShortMessageFallback,
+) -> Option<String> {
+    match fallback {
+        ShortMessageFallback::Error => None,
+        ShortMessageFallback::UseFullMessage => full_message.map(|full_message| {
+            full_message
+                .chars()
+                .take(SHORT_MESSAGE_FALLBACK_LENGTH)
+                .collect()
+        }),
+        ShortMessageFallback::UseField(field) => {
+            let field = field.strip_prefix('_').unwrap_or(field);
+            json_map
+                .get(&format!("_{field}"))
+                .or_else(|| json_map.get(field))
+                .map(|value| match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+        }
+        ShortMessageFallback::UseEmpty => Some(String::new()),
+    }
+}
+
+/// Same field semantics as the collector applies to GELF log lines
+fn to_generic_log(
+    hostname: &str,
+    timestamp: rlog_grpc::prost_wkt_types::Timestamp,
+    severity: i32,
+    short_message: &str,
+    full_message: Option<String>,
+    extra: HashMap<&str, &Value>,
+    log_system: String,
+) -> anyhow::Result<GenericLog> {
+    let mut extra: serde_json::Map<String, Value> = extra
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+    let service_name = extra
+        .remove("service")
+        .and_then(|s| s.as_str().map(ToString::to_string))
+        .unwrap_or_else(|| "unknown".to_string());
+    let message = match full_message {
+        Some(full_message) if full_message != short_message => {
+            format!("{short_message}\n{full_message}")
+        }
+        _ => short_message.to_string(),
+    };
+    Ok(GenericLog {
+        host: hostname.into(),
+        timestamp: Utc
+            .timestamp_opt(timestamp.seconds, timestamp.nanos as u32)
+            .single()
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp {timestamp:?}"))?,
+        severity: SyslogSeverity::try_from(severity).unwrap_or(SyslogSeverity::Alert),
+        extra: Value::Object(extra),
+        log_system,
+        message,
+        service_name,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rlog_grpc::rlog_service_protocol::{log_line::Line, SyslogSeverity};
+    use serde_json::json;
+
+    use super::{check_version, GelfLog};
+    use crate::config::{GelfInputConfig, ShortMessageFallback};
+
+    #[test]
+    fn test_check_version() {
+        let message = |version: &str| json!({"version": version, "host": "foo"});
+
+        // permissive by default
+        assert!(check_version(&message("0.9"), None).is_ok());
+        let config = GelfInputConfig::default();
+        assert!(check_version(&message("0.9"), Some(&config)).is_ok());
+        assert!(check_version(&json!({"host": "foo"}), Some(&config)).is_ok());
+
+        let config = GelfInputConfig {
+            min_version: Some("1.1".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(check_version(&message("1.1"), Some(&config)).is_ok());
+        assert!(check_version(&message("1.1.0"), Some(&config)).is_ok());
+        assert!(check_version(&message("1.10"), Some(&config)).is_ok());
+        assert!(check_version(&message("2.0"), Some(&config)).is_ok());
+        assert!(check_version(&message("1.0"), Some(&config)).is_err());
+        assert!(check_version(&message("foo"), Some(&config)).is_err());
+        assert!(check_version(&json!({"host": "foo"}), Some(&config)).is_err());
+
+        let config = GelfInputConfig {
+            min_version: Some("1.1".parse().unwrap()),
+            max_version: Some("1.1".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(check_version(&message("1.1"), Some(&config)).is_ok());
+        assert!(check_version(&message("1.2"), Some(&config)).is_err());
+        assert!(check_version(&message("1.0"), Some(&config)).is_err());
+    }
+
+    #[test]
+    fn test_log_system_override() {
+        let gelf = || {
+            GelfLog(json!({
+                "version": "1.1",
+                "host": "my_host",
+                "timestamp": 1700000000.500123,
+                "level": 3,
+                "short_message": "short",
+                "full_message": "full",
+                "_service": "my_service",
+                "_custom": 42,
+            }))
+        };
+
+        let Some(Line::Gelf(_)) = gelf().into_log_line(None).unwrap().line else {
+            panic!("expected a gelf log line");
+        };
+
+        let config = GelfInputConfig {
+            log_system: Some("app_json".into()),
+            ..Default::default()
+        };
+        let log_line = gelf().into_log_line(Some(&config)).unwrap();
+
+        assert_eq!("my_host", log_line.host);
+        assert_eq!(
+            Some(rlog_grpc::prost_wkt_types::Timestamp {
+                seconds: 1700000000,
+                nanos: 500_123_000
+            }),
+            log_line.timestamp
+        );
+        let Some(Line::GenericLog(generic)) = log_line.line else {
+            panic!("expected a generic log line");
+        };
+        assert_eq!("app_json", generic.log_system);
+        assert_eq!("short\nfull", generic.message);
+        assert_eq!("my_service", generic.service_name);
+        assert_eq!(SyslogSeverity::Error, generic.severity());
+        assert_eq!(
+            json!({"custom": 42}),
+            serde_json::from_str::<serde_json::Value>(&generic.extra).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_short_message_fallback() {
+        let gelf = || {
+            GelfLog(json!({
+                "version": "1.1",
+                "host": "my_host",
+                "timestamp": 1700000000.500123,
+                "full_message": "é".repeat(100),
+                "_msg": "from field",
+            }))
+        };
+        let short_message = |fallback: ShortMessageFallback| {
+            let config = GelfInputConfig {
+                short_message_fallback: fallback,
+                ..Default::default()
+            };
+            gelf()
+                .into_log_line(Some(&config))
+                .map(|log_line| match log_line.line {
+                    Some(Line::Gelf(gelf)) => gelf.short_message,
+                    _ => panic!("expected a gelf log line"),
+                })
+        };
+
+        assert!(gelf().into_log_line(None).is_err());
+        assert!(short_message(ShortMessageFallback::Error).is_err());
+        assert_eq!(
+            "é".repeat(80),
+            short_message(ShortMessageFallback::UseFullMessage).unwrap()
+        );
+        assert_eq!(
+            "from field",
+            short_message(ShortMessageFallback::UseField("msg".into())).unwrap()
+        );
+        assert_eq!(
+            "from field",
+            short_message(ShortMessageFallback::UseField("_msg".into())).unwrap()
+        );
+        assert!(short_message(ShortMessageFallback::UseField("missing".into())).is_err());
+        assert_eq!("", short_message(ShortMessageFallback::UseEmpty).unwrap());
+
+        let config: GelfInputConfig =
+            serde_yaml::from_str("short_message_fallback: !use_field _msg").unwrap();
+        assert_eq!(
+            ShortMessageFallback::UseField("_msg".into()),
+            config.short_message_fallback
+        );
+    }
+
+    #[test]
+    fn test_service_name_fields() {
+        let extra = |gelf: serde_json::Value| match GelfLog(gelf).into_log_line(None).unwrap().line
+        {
+            Some(Line::Gelf(gelf)) => {
+                serde_json::from_str::<serde_json::Value>(&gelf.extra).unwrap()
+            }
+            _ => panic!("expected a gelf log line"),
+        };
+        let message = json!({
+            "version": "1.1",
+            "host": "my_host",
+            "timestamp": 1700000000.5,
+            "short_message": "short",
+        });
+        let with_fields = |fields: serde_json::Value| {
+            let mut message = message.clone();
+            message
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            message
+        };
+
+        assert_eq!(json!({}), extra(message.clone()));
+        assert_eq!(
+            json!({"service": "my_app", "app_name": "other"}),
+            extra(with_fields(
+                json!({"_application": "my_app", "_app_name": "other"})
+            ))
+        );
+        // empty values are skipped, tried fields are not kept
+        assert_eq!(
+            json!({"service": "my_app"}),
+            extra(with_fields(
+                json!({"_service": "", "application": "my_app"})
+            ))
+        );
+        assert_eq!(
+            json!({"service": "my_service"}),
+            extra(with_fields(
+                json!({"_service": "my_service", "_application": "my_app"})
+            ))
+        );
+
+        let config = GelfInputConfig {
+            service_name_fields: vec!["_app_name".into()],
+            log_system: Some("app_json".into()),
+            ..Default::default()
+        };
+        let log_line = GelfLog(with_fields(
+            json!({"_app_name": "my_app", "_application": "other"}),
+        ))
+        .into_log_line(Some(&config))
+        .unwrap();
+        let Some(Line::GenericLog(generic)) = log_line.line else {
+            panic!("expected a generic log line");
+        };
+        assert_eq!("my_app", generic.service_name);
+        assert_eq!(
+            json!({"application": "other"}),
+            serde_json::from_str::<serde_json::Value>(&generic.extra).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_severity_field_aliases() {
+        let config = GelfInputConfig {
+            severity_field_aliases: vec![
+                "level".into(),
+                "_level".into(),
+                "severity".into(),
+                "_log_level".into(),
+            ],
+            ..Default::default()
+        };
+        let gelf = |fields: serde_json::Value, config: Option<&GelfInputConfig>| {
+            let mut message = json!({
+                "version": "1.1",
+                "host": "my_host",
+                "timestamp": 1700000000.500123,
+                "short_message": "short",
+            });
+            message
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+            match GelfLog(message).into_log_line(config).unwrap().line {
+                Some(Line::Gelf(gelf)) => (
+                    gelf.severity(),
+                    serde_json::from_str::<serde_json::Value>(&gelf.extra).unwrap(),
+                ),
+                _ => panic!("expected a gelf log line"),
+            }
+        };
+
+        // `level` only by default, ALERT if missing
+        assert_eq!(SyslogSeverity::Error, gelf(json!({"level": 3}), None).0);
+        assert_eq!(
+            (SyslogSeverity::Alert, json!({"severity": "error"})),
+            gelf(json!({"severity": "error"}), None)
+        );
+
+        assert_eq!(
+            (SyslogSeverity::Warning, json!({})),
+            gelf(json!({"_level": 4}), Some(&config))
+        );
+        assert_eq!(
+            (SyslogSeverity::Error, json!({})),
+            gelf(json!({"severity": "Error"}), Some(&config))
+        );
+        assert_eq!(
+            SyslogSeverity::Debug,
+            gelf(json!({"_log_level": "7"}), Some(&config)).0
+        );
+        // null values are skipped, the other aliases are kept as extra fields
+        assert_eq!(
+            (SyslogSeverity::Notice, json!({"log_level": "debug"})),
+            gelf(
+                json!({"level": null, "severity": "notice", "_log_level": "debug"}),
+                Some(&config)
+            )
+        );
+        assert_eq!(
+            SyslogSeverity::Info,
+            gelf(json!({"severity": "verbose"}), Some(&config)).0
+        );
+    }
+}
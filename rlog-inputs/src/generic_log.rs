@@ -42,6 +42,10 @@ impl TryFrom<GenericLog> for LogLine {
 
         Ok(LogLine {
             host: value.host,
+            raw_host: None,
+            hmac: Vec::new(),
+            sequence: None,
+            labels: Default::default(),
             timestamp: Some(timestamp),
             line: Some(
                 rlog_grpc::rlog_service_protocol::log_line::Line::GenericLog(
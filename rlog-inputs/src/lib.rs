@@ -0,0 +1,17 @@
+//! GELF & syslog inputs shared by the shipper and the all-in-one collector.
+//!
+//! Servers read their hot reloaded configuration through an accessor of the host
+//! global config and reserve the received messages from the host byte budget.
+
+pub mod byte_budget;
+pub mod config;
+pub mod connection_buffers;
+pub mod enqueue;
+pub mod gelf_server;
+pub mod generic_log;
+pub mod metrics;
+pub mod normalization;
+pub mod pattern_file;
+pub mod peer_metrics;
+pub mod recent_inputs;
+pub mod syslog_server;
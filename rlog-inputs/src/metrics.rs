@@ -0,0 +1,32 @@
+use std::sync::atomic::AtomicU64;
+
+use lazy_static::lazy_static;
+
+use crate::{connection_buffers::ConnectionBuffersRegistry, peer_metrics::PeerMetricsRegistry};
+
+lazy_static! {
+    pub static ref GELF_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref GELF_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref GELF_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_INVALID_UTF8_COUNT: AtomicU64 = AtomicU64::new(0);
+    /// datagrams filling the read buffer, possibly truncated
+    pub static ref SYSLOG_TRUNCATED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref GELF_VERSION_REJECTED_COUNT: AtomicU64 = AtomicU64::new(0);
+    /// valid messages discarded before being queued (full or closed queue, exceeded byte
+    /// budget), unlike the errors it does not count the frames which are not valid JSON
+    pub static ref GELF_DISCARDED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref GELF_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    /// sequence number of the next message accepted by the input (not excluded)
+    pub static ref GELF_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    /// frames, parse errors & dropped messages of the GELF connections by peer IP
+    pub static ref GELF_PEERS: PeerMetricsRegistry = PeerMetricsRegistry::default();
+    /// read buffers of the GELF connections
+    pub static ref GELF_CONNECTION_BUFFERS: ConnectionBuffersRegistry = ConnectionBuffersRegistry::default();
+    /// total bytes of the read buffers of the GELF connections, updated periodically
+    pub static ref GELF_CONNECTION_BUFFERS_BYTES: AtomicU64 = AtomicU64::new(0);
+}
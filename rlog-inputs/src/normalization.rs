@@ -0,0 +1,113 @@
+//! Normalization of the message of the log lines (`normalize_message` of the inputs), eg:
+//! `\r\n` line endings of Windows systems or padded messages of some loggers.
+
+use crate::config::MessageNormalization;
+
+impl MessageNormalization {
+    /// Normalized message, the message is returned as is if nothing has to be changed
+    pub fn apply(&self, mut message: String) -> String {
+        if self.normalize_line_endings && message.contains('\r') {
+            message = message.replace("\r\n", "\n").replace('\r', "\n");
+        }
+        if self.collapse_internal_whitespace {
+            if let Some(collapsed) = collapse_whitespace(&message) {
+                message = collapsed;
+            }
+        }
+        if self.trim_whitespace {
+            let trimmed = message.trim();
+            if trimmed.len() != message.len() {
+                message = trimmed.to_string();
+            }
+        }
+        message
+    }
+}
+
+/// Normalize the message with the optional normalization of an input
+pub fn normalize_message(normalization: Option<&MessageNormalization>, message: String) -> String {
+    match normalization {
+        Some(normalization) => normalization.apply(message),
+        None => message,
+    }
+}
+
+/// `None` if the message has no whitespace other than single spaces
+fn collapse_whitespace(message: &str) -> Option<String> {
+    let mut previous_whitespace = false;
+    let collapsible = message.chars().any(|c| {
+        let whitespace = c.is_whitespace();
+        let collapsible = whitespace && (c != ' ' || previous_whitespace);
+        previous_whitespace = whitespace;
+        collapsible
+    });
+    if !collapsible {
+        return None;
+    }
+    let mut collapsed = String::with_capacity(message.len());
+    let mut previous_whitespace = false;
+    for c in message.chars() {
+        if c.is_whitespace() {
+            if !previous_whitespace {
+                collapsed.push(' ');
+            }
+            previous_whitespace = true;
+        } else {
+            collapsed.push(c);
+            previous_whitespace = false;
+        }
+    }
+    Some(collapsed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::MessageNormalization;
+
+    use super::normalize_message;
+
+    #[test]
+    fn test_normalizations() {
+        let message = "  first line\r\nsecond  line\rthird\t line \n";
+        let normalize = |normalize_line_endings, collapse_internal_whitespace, trim_whitespace| {
+            MessageNormalization {
+                normalize_line_endings,
+                collapse_internal_whitespace,
+                trim_whitespace,
+            }
+            .apply(message.to_string())
+        };
+
+        assert_eq!(message, normalize(false, false, false));
+        assert_eq!(
+            "  first line\nsecond  line\nthird\t line \n",
+            normalize(true, false, false)
+        );
+        assert_eq!(
+            " first line second line third line ",
+            normalize(false, true, false)
+        );
+        assert_eq!(
+            "first line\r\nsecond  line\rthird\t line",
+            normalize(false, false, true)
+        );
+        assert_eq!(
+            "first line second line third line",
+            normalize(true, true, true)
+        );
+
+        assert_eq!(
+            "single spaces",
+            normalize_message(None, "single spaces".into())
+        );
+        // unicode whitespace
+        assert_eq!(
+            "a b",
+            MessageNormalization {
+                collapse_internal_whitespace: true,
+                ..Default::default()
+            }
+            .apply("a\u{a0}\u{2003}b".into())
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Regex lists maintained in a file (one pattern per line) so that they can be managed
+//! without editing the configuration, reloaded when the file is modified.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use regex::Regex;
+
+/// How often the modification time of the file is checked
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+pub struct PatternFile {
+    loaded: Mutex<Option<Loaded>>,
+}
+
+struct Loaded {
+    path: String,
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+    patterns: Arc<Vec<Regex>>,
+}
+
+impl PatternFile {
+    /// Patterns of the file, reloaded if the path changed or if the file has been modified
+    /// since the last check. A missing file has no patterns.
+    pub fn patterns(&self, path: &str) -> Arc<Vec<Regex>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        match loaded.as_mut() {
+            Some(loaded) if loaded.path == path => {
+                if loaded.checked_at.elapsed() >= REFRESH_INTERVAL {
+                    loaded.checked_at = Instant::now();
+                    let modified = modified(path);
+                    if modified != loaded.modified {
+                        tracing::info!("Pattern file {path} modified, reloading it!");
+                        loaded.modified = modified;
+                        loaded.patterns = Arc::new(load_patterns(path));
+                    }
+                }
+                loaded.patterns.clone()
+            }
+            _ => {
+                let patterns = Arc::new(load_patterns(path));
+                *loaded = Some(Loaded {
+                    path: path.to_string(),
+                    modified: modified(path),
+                    checked_at: Instant::now(),
+                    patterns: patterns.clone(),
+                });
+                patterns
+            }
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Blank lines and lines starting with `#` are ignored, invalid patterns are skipped
+fn load_patterns(path: &str) -> Vec<Regex> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!("Unable to read pattern file {path}: {e}");
+            return vec![];
+        }
+    };
+    parse_patterns(path, &content)
+}
+
+fn parse_patterns(path: &str, content: &str) -> Vec<Regex> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match Regex::new(line) {
+            Ok(regex) => Some(regex),
+            Err(e) => {
+                tracing::error!("Invalid pattern {line:?} in {path}: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_patterns, PatternFile};
+
+    #[test]
+    fn test_parse_patterns() {
+        let patterns = parse_patterns(
+            "test",
+            "# health checks\n\nGET /health\n  (unclosed\n^CRON\\[\\d+\\]\n",
+        );
+        assert_eq!(
+            vec!["GET /health", "^CRON\\[\\d+\\]"],
+            patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let pattern_file = PatternFile::default();
+        assert!(pattern_file
+            .patterns("/nonexistent/rlog-exclude-patterns")
+            .is_empty());
+    }
+}
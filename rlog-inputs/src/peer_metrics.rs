@@ -0,0 +1,107 @@
+//! Counters of the connections of an input aggregated by peer IP (the port is stripped), to
+//! attribute the volume & the errors of an input to its sources.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+
+/// Counters of a peer, shared by its connections
+#[derive(Default, Debug)]
+pub struct PeerCounters {
+    /// received frames, valid or not
+    pub frames: AtomicU64,
+    /// frames which are not valid messages
+    pub parse_errors: AtomicU64,
+    /// valid messages discarded (rejected version, full queue, exceeded byte budget)
+    pub dropped: AtomicU64,
+}
+
+/// Snapshot of the counters of a peer
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PeerMetrics {
+    pub peer: IpAddr,
+    pub frames: u64,
+    pub parse_errors: u64,
+    pub dropped: u64,
+}
+
+#[derive(Default)]
+pub struct PeerMetricsRegistry {
+    peers: Mutex<HashMap<IpAddr, Arc<PeerCounters>>>,
+}
+
+impl PeerMetricsRegistry {
+    /// Counters of the peer, created on its first connection
+    pub fn peer(&self, peer: IpAddr) -> Arc<PeerCounters> {
+        // IPv4 clients of a dual stack listener are reported with their IPv4 address
+        let peer = peer.to_canonical();
+        self.peers.lock().unwrap().entry(peer).or_default().clone()
+    }
+
+    /// The `limit` peers which sent the most frames, by decreasing number of frames
+    pub fn top(&self, limit: usize) -> Vec<PeerMetrics> {
+        let mut peers = self
+            .peers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(peer, counters)| PeerMetrics {
+                peer: *peer,
+                frames: counters.frames.load(Ordering::Relaxed),
+                parse_errors: counters.parse_errors.load(Ordering::Relaxed),
+                dropped: counters.dropped.load(Ordering::Relaxed),
+            })
+            .collect::<Vec<_>>();
+        // ties are ordered by address for stable reports
+        peers.sort_by(|a, b| b.frames.cmp(&a.frames).then(a.peer.cmp(&b.peer)));
+        peers.truncate(limit);
+        peers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::IpAddr, sync::atomic::Ordering};
+
+    use super::PeerMetricsRegistry;
+
+    #[test]
+    fn test_top() {
+        let registry = PeerMetricsRegistry::default();
+        let first: IpAddr = "10.0.0.1".parse().unwrap();
+        let second: IpAddr = "10.0.0.2".parse().unwrap();
+        let third: IpAddr = "10.0.0.3".parse().unwrap();
+
+        registry.peer(first).frames.fetch_add(2, Ordering::Relaxed);
+        // connections of a peer share its counters
+        registry.peer(second).frames.fetch_add(3, Ordering::Relaxed);
+        let counters = registry.peer(second);
+        counters.frames.fetch_add(2, Ordering::Relaxed);
+        counters.parse_errors.fetch_add(1, Ordering::Relaxed);
+        // IPv4 mapped IPv6 address
+        registry
+            .peer("::ffff:10.0.0.3".parse().unwrap())
+            .dropped
+            .fetch_add(1, Ordering::Relaxed);
+
+        let top = registry.top(10);
+        assert_eq!(
+            vec![second, first, third],
+            top.iter().map(|peer| peer.peer).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            (5, 1, 0),
+            (top[0].frames, top[0].parse_errors, top[0].dropped)
+        );
+        assert_eq!(1, top[2].dropped);
+
+        assert_eq!(1, registry.top(1).len());
+    }
+}
@@ -0,0 +1,199 @@
+//! Last raw inputs of each source (`syslog_in`, `gelf_in` or the path of a watched file),
+//! kept in memory to debug parse issues at high volume instead of dumping every received
+//! message in debug logs: only 1 in [DEBUG_SAMPLE_RATE] raw inputs is dumped.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use arc_swap::ArcSwapOption;
+use chrono::{SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use rlog_common::utils::truncate_str;
+use serde::Serialize;
+
+use crate::config::RecentInputsConfig;
+
+lazy_static! {
+    pub static ref RECENT_INPUTS: RecentInputs = RecentInputs::default();
+}
+
+/// 1 in N raw inputs is dumped in debug logs, 0 disables the dumps
+pub static DEBUG_SAMPLE_RATE: AtomicU64 = AtomicU64::new(100);
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecentInput {
+    /// RFC 3339 reception time
+    pub received_at: String,
+    /// redacted raw input, truncated to `max_bytes`
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct RecentInputs {
+    /// recording is disabled if not set
+    config: ArcSwapOption<RecentInputsConfig>,
+    received_count: AtomicU64,
+    buffers: Mutex<Buffers>,
+}
+
+#[derive(Default)]
+struct Buffers {
+    /// inputs of each source and their insertion id, oldest first
+    by_source: HashMap<String, VecDeque<(u64, RecentInput)>>,
+    stored_bytes: usize,
+    next_id: u64,
+}
+
+impl RecentInputs {
+    /// Inputs are recorded if `config` is set, previously recorded inputs are cleared
+    pub fn configure(&self, config: Option<RecentInputsConfig>) {
+        let mut buffers = self.buffers.lock().unwrap();
+        *buffers = Buffers::default();
+        self.config.store(config.map(Into::into));
+    }
+
+    /// Dump the raw input in debug logs if it is sampled and store it if recording is
+    /// enabled, returns `true` if the input has been dumped
+    pub fn record(&self, source: &str, raw: &str) -> bool {
+        let sample_rate = DEBUG_SAMPLE_RATE.load(Ordering::Relaxed);
+        let sampled = sample_rate > 0
+            && self
+                .received_count
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(sample_rate)
+            && tracing::enabled!(tracing::Level::DEBUG);
+        if sampled {
+            tracing::debug!(source, "Received {raw}");
+        }
+
+        let config = self.config.load();
+        let Some(config) = config.as_deref() else {
+            return sampled;
+        };
+        let mut message = raw.to_string();
+        for redaction in &config.redactions {
+            message = redaction
+                .pattern
+                .replace_all(&message, redaction.replacement.as_str())
+                .into_owned();
+        }
+        message.truncate(truncate_str(&message, config.max_bytes).len());
+        let input = RecentInput {
+            received_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            message,
+        };
+        self.buffers.lock().unwrap().push(source, input, config);
+        sampled
+    }
+
+    /// Recorded inputs of the source, oldest first, `None` if recording is disabled
+    pub fn recent(&self, source: &str) -> Option<Vec<RecentInput>> {
+        self.config.load().as_ref()?;
+        let buffers = self.buffers.lock().unwrap();
+        Some(
+            buffers
+                .by_source
+                .get(source)
+                .map(|inputs| inputs.iter().map(|(_, input)| input.clone()).collect())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Sources with recorded inputs
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources = self
+            .buffers
+            .lock()
+            .unwrap()
+            .by_source
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        sources.sort();
+        sources
+    }
+}
+
+impl Buffers {
+    fn push(&mut self, source: &str, input: RecentInput, config: &RecentInputsConfig) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.stored_bytes += input.message.len();
+        let inputs = self.by_source.entry(source.to_string()).or_default();
+        inputs.push_back((id, input));
+        while inputs.len() > config.max_entries {
+            if let Some((_, evicted)) = inputs.pop_front() {
+                self.stored_bytes -= evicted.message.len();
+            }
+        }
+        while self.stored_bytes > config.max_bytes {
+            self.evict_oldest();
+        }
+        self.by_source.retain(|_, inputs| !inputs.is_empty());
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .by_source
+            .values_mut()
+            .filter(|inputs| !inputs.is_empty())
+            .min_by_key(|inputs| inputs[0].0);
+        if let Some((_, evicted)) = oldest.and_then(VecDeque::pop_front) {
+            self.stored_bytes -= evicted.message.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RecentInputs;
+    use crate::config::{eqregex::EqRegex, RecentInputsConfig, Redaction};
+
+    fn messages(recent_inputs: &RecentInputs, source: &str) -> Vec<String> {
+        recent_inputs
+            .recent(source)
+            .unwrap()
+            .into_iter()
+            .map(|input| input.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_caps() {
+        let recent_inputs = RecentInputs::default();
+        recent_inputs.record("syslog_in", "not recorded");
+        assert_eq!(None, recent_inputs.recent("syslog_in"));
+
+        recent_inputs.configure(Some(RecentInputsConfig {
+            max_entries: 2,
+            max_bytes: 10,
+            redactions: vec![Redaction {
+                pattern: EqRegex::new("password=[^ ]+").unwrap(),
+                replacement: "password=*".into(),
+            }],
+        }));
+        for message in ["s1", "s2", "s3"] {
+            recent_inputs.record("syslog_in", message);
+        }
+        assert_eq!(vec!["s2", "s3"], messages(&recent_inputs, "syslog_in"));
+
+        recent_inputs.record("gelf_in", "g1234");
+        assert_eq!(vec!["gelf_in", "syslog_in"], recent_inputs.sources());
+        // the oldest inputs of all sources are evicted to stay below max_bytes
+        recent_inputs.record("gelf_in", "g5678");
+        assert_eq!(vec!["gelf_in"], recent_inputs.sources());
+        assert!(messages(&recent_inputs, "syslog_in").is_empty());
+        assert_eq!(vec!["g1234", "g5678"], messages(&recent_inputs, "gelf_in"));
+
+        // truncated to max_bytes, after redaction
+        recent_inputs.record("gelf_in", "password=secret ok");
+        assert_eq!(vec!["password=*"], messages(&recent_inputs, "gelf_in"));
+        recent_inputs.record("gelf_in", "ééééééé");
+        assert_eq!(vec!["ééééé"], messages(&recent_inputs, "gelf_in"));
+    }
+}
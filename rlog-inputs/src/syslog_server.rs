@@ -0,0 +1,654 @@
+use std::{borrow::Cow, collections::HashMap, fmt::Display, sync::atomic::Ordering};
+
+use anyhow::{anyhow, Context};
+use arc_swap::access::Access;
+use async_channel::Receiver;
+use chrono::Utc;
+use futures::FutureExt;
+use rlog_common::bind_addr::BindAddr;
+use rlog_grpc::rlog_service_protocol::{
+    log_line::Line, LogLine, SyslogFacility, SyslogLogLine, SyslogSeverity,
+};
+use syslog_loose::{Message, Variant};
+use tokio::{net::UdpSocket, select};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    byte_budget::{Budgeted, Reserve},
+    config::{SyslogEncoding, SyslogInputConfig, SyslogServiceName, DEFAULT_MAX_DATAGRAM_BYTES},
+    enqueue::{enqueue_or_drop, Enqueued},
+    generic_log::GenericLog,
+    metrics::{
+        SYSLOG_ERROR_COUNT, SYSLOG_INVALID_UTF8_COUNT, SYSLOG_QUEUE_CAPACITY, SYSLOG_QUEUE_COUNT,
+        SYSLOG_SEQUENCE, SYSLOG_TRUNCATED_COUNT,
+    },
+    normalization::normalize_message,
+    recent_inputs::RECENT_INPUTS,
+};
+
+/// Parsed syslog message, the datagram it has been parsed from and whether this datagram
+/// may have been truncated (it filled the read buffer)
+pub struct SyslogLog(Message<String>, Vec<u8>, bool);
+
+impl SyslogLog {
+    /// Received datagram, eg: to relay the original frame
+    pub fn raw(&self) -> &[u8] {
+        &self.1
+    }
+}
+
+impl Display for SyslogLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// `config` gives access to the (hot reloaded) `syslog_in` section of the host config
+pub async fn launch_syslog_udp_server<C>(
+    bind_address: BindAddr,
+    config: C,
+    reserve: Reserve,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<Receiver<Budgeted<SyslogLog>>>
+where
+    C: Access<Option<SyslogInputConfig>> + Send + Sync + 'static,
+{
+    let (max_buffer_size, recv_buffer_bytes, max_datagram_bytes) = match config.load().as_ref() {
+        Some(config) => (
+            config.common.max_buffer_size,
+            config.recv_buffer_bytes,
+            config.max_datagram_bytes,
+        ),
+        None => (
+            SyslogInputConfig::default().common.max_buffer_size,
+            None,
+            None,
+        ),
+    };
+    // Note: RFC 5424 requires the receiver should be able to handle
+    // a minimum of 2048 bytes but we can afford to handle a bit more
+    // bytes ;)
+    let max_datagram_bytes = max_datagram_bytes.unwrap_or(DEFAULT_MAX_DATAGRAM_BYTES);
+    if max_datagram_bytes == 0 {
+        anyhow::bail!("syslog_in max_datagram_bytes must not be 0");
+    }
+    SYSLOG_QUEUE_CAPACITY.store(max_buffer_size as u64, Ordering::Relaxed);
+    let (sender, receiver) = async_channel::bounded(max_buffer_size);
+
+    let socket = UdpSocket::bind(bind_address.socket_addr())
+        .await
+        .with_context(|| format!("Unable to bind syslog UDP server to {bind_address}"))?;
+    if let Some(recv_buffer_bytes) = recv_buffer_bytes {
+        let socket = socket2::SockRef::from(&socket);
+        socket
+            .set_recv_buffer_size(recv_buffer_bytes)
+            .context("Unable to set the syslog UDP receive buffer size")?;
+        // the kernel may clamp the requested size (and doubles it on linux)
+        tracing::info!(
+            "Syslog UDP receive buffer: {} bytes ({recv_buffer_bytes} requested)",
+            socket.recv_buffer_size()?
+        );
+    }
+
+    tracing::info!("Syslog server listening UDP {bind_address}");
+
+    tokio::spawn(
+        async move {
+            let mut buf = vec![0u8; max_datagram_bytes];
+            loop {
+                select! {
+                    _ = shutdown_token.cancelled() => {
+                        return;
+                    }
+                    res = socket.recv_from(&mut buf) => {
+                        let (n, from) = match res {
+                            Ok(r) => r,
+                            Err(e) => {
+                                // this is highly unlikely!
+                                tracing::error!("Unable to read UDP socket {e}");
+                                continue;
+                            }
+                        };
+                        let from = from.to_string();
+                        let span = tracing::info_span!("syslog_in", remote_addr = from);
+                        let _entered = span.enter();
+
+                        let datagram = &buf[0..n];
+                        // the remaining bytes of a larger datagram have been discarded
+                        let truncated = n == buf.len();
+                        if truncated {
+                            SYSLOG_TRUNCATED_COUNT.fetch_add(1, Ordering::Relaxed);
+                            tracing::warn!("Syslog datagram of at least {n} bytes, possibly truncated");
+                        }
+                        let input_config = config.load();
+                        let encoding = input_config.as_ref().map(|config| config.encoding).unwrap_or_default();
+                        let message = decode(datagram, encoding);
+                        let sampled = RECENT_INPUTS.record("syslog_in", &message);
+                        let message = syslog_loose::parse_message(&message, Variant::Either);
+
+                        if filters::is_excluded(&message, input_config.as_ref()) {
+                            continue;
+                        }
+
+                        let message: Message<String> = message.into();
+                        if sampled {
+                            tracing::debug!("Decoded {}", message);
+                        }
+
+                        let sequence = SYSLOG_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+                        let Some(reservation) = reserve(n) else {
+                            SYSLOG_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                            tracing::error!("Buffered bytes budget exceeded: discarding value {}", message);
+                            continue;
+                        };
+                        let message = Budgeted::new(SyslogLog(message, datagram.to_vec(), truncated), reservation).with_sequence(sequence);
+                        if enqueue_or_drop(&sender, message, &SYSLOG_ERROR_COUNT, &SYSLOG_QUEUE_COUNT) == Enqueued::Stop {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        .then(|_| async { tracing::info!("Syslog server stopped.") }),
+    );
+
+    Ok(receiver)
+}
+
+/// Decode a datagram, datagrams which are not valid UTF-8 are counted
+fn decode(datagram: &[u8], encoding: SyslogEncoding) -> Cow<'_, str> {
+    match encoding {
+        SyslogEncoding::Utf8 => {
+            let message = String::from_utf8_lossy(datagram);
+            if let Cow::Owned(_) = message {
+                SYSLOG_INVALID_UTF8_COUNT.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!("Invalid UTF-8 in syslog datagram: {message}");
+            }
+            message
+        }
+        // ISO-8859-1 code points are the first 256 unicode code points
+        SyslogEncoding::Latin1 => Cow::Owned(datagram.iter().map(|b| *b as char).collect()),
+        SyslogEncoding::Detect => {
+            let detected = detect_encoding(datagram);
+            // the span of the datagram carries the source address
+            tracing::debug!("Detected {detected:?} encoding");
+            match detected {
+                DetectedEncoding::Latin1 => decode(datagram, SyslogEncoding::Latin1),
+                DetectedEncoding::Utf8 | DetectedEncoding::Unknown => {
+                    decode(datagram, SyslogEncoding::Utf8)
+                }
+            }
+        }
+    }
+}
+
+/// Latin-1 text (eg: from legacy Cisco IOS or HP-UX systems) only has a few accented
+/// characters, more non ASCII bytes are rather another encoding or binary data
+const LATIN1_MAX_NON_ASCII_PERCENT: usize = 30;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, PartialEq, Eq)]
+enum DetectedEncoding {
+    Utf8,
+    Latin1,
+    /// decoded as UTF-8, invalid sequences are counted
+    Unknown,
+}
+
+fn detect_encoding(datagram: &[u8]) -> DetectedEncoding {
+    // RFC 5424 messages may start with a BOM after the header
+    if std::str::from_utf8(datagram).is_ok()
+        || datagram
+            .windows(UTF8_BOM.len())
+            .any(|window| window == UTF8_BOM)
+    {
+        return DetectedEncoding::Utf8;
+    }
+    let non_ascii = datagram.iter().filter(|b| **b >= 0x80).count();
+    if non_ascii * 100 <= datagram.len() * LATIN1_MAX_NON_ASCII_PERCENT {
+        DetectedEncoding::Latin1
+    } else {
+        DetectedEncoding::Unknown
+    }
+}
+
+mod filters {
+    use lazy_static::lazy_static;
+    use syslog_loose::Message;
+
+    use crate::{
+        config::{SyslogExclusionFilter, SyslogInputConfig},
+        pattern_file::PatternFile,
+    };
+
+    lazy_static! {
+        static ref EXCLUDE_PATTERN_FILE: PatternFile = PatternFile::default();
+    }
+
+    pub(super) fn is_excluded<T: AsRef<str> + Ord + PartialEq + Clone>(
+        message: &Message<T>,
+        config: Option<&SyslogInputConfig>,
+    ) -> bool {
+        static EMPTY_FILTERS: Vec<SyslogExclusionFilter> = vec![];
+        let filters = match config {
+            Some(config) => &config.exclusion_filters,
+            None => &EMPTY_FILTERS,
+        };
+        for exclusion_filter in filters {
+            // message will be excluded only if shall_exclude==Some(true)
+            let mut shall_exclude = None;
+            if let (Some(pattern), Some(appname)) = (&exclusion_filter.appname, &message.appname) {
+                shall_exclude = Some(pattern.is_match(appname.as_ref()))
+            }
+            if let (Some(pattern), Some(facility)) = (&exclusion_filter.facility, message.facility)
+            {
+                shall_exclude = shall_exclude
+                    // previous filter has been applied, it will be excluded only if this filter applies
+                    .and_then(|excl| Some(excl && pattern.is_match(facility.as_str())))
+                    // no previous filter in the config!
+                    .or_else(|| Some(pattern.is_match(facility.as_str())));
+            }
+            if let Some(pattern) = &exclusion_filter.message {
+                shall_exclude = shall_exclude
+                    // previous filter has been applied, it will be excluded only if this filter applies
+                    .and_then(|excl| Some(excl && pattern.is_match(message.msg.as_ref())))
+                    // no previous filter in the config!
+                    .or_else(|| Some(pattern.is_match(message.msg.as_ref())));
+            }
+
+            if shall_exclude.unwrap_or(false) {
+                return true;
+            }
+        }
+        match config.and_then(|config| config.exclude_pattern_file.as_deref()) {
+            Some(path) => EXCLUDE_PATTERN_FILE
+                .patterns(path)
+                .iter()
+                .any(|pattern| pattern.is_match(message.msg.as_ref())),
+            None => false,
+        }
+    }
+
+    #[test]
+    #[cfg(test)]
+    fn test_excluded() {
+        use std::vec;
+
+        use crate::config::eqregex::EqRegex;
+
+        let message = Message {
+            protocol: syslog_loose::Protocol::RFC5424(0),
+            facility: Some(syslog_loose::SyslogFacility::LOG_AUDIT),
+            severity: None,
+            timestamp: None,
+            hostname: None,
+            appname: Some("my-ultimate-app.sh"),
+            procid: None,
+            msgid: None,
+            structured_data: vec![],
+            msg: "natty line some stuff in there",
+        };
+
+        let message2 = Message {
+            protocol: syslog_loose::Protocol::RFC5424(0),
+            facility: Some(syslog_loose::SyslogFacility::LOG_AUDIT),
+            severity: None,
+            timestamp: None,
+            hostname: None,
+            appname: Some("postfix"),
+            procid: None,
+            msgid: None,
+            structured_data: vec![],
+            msg: "natty line some stuff in there",
+        };
+
+        assert!(!is_excluded(&message, None));
+
+        let config = SyslogInputConfig {
+            exclusion_filters: vec![SyslogExclusionFilter {
+                appname: Some(EqRegex::new("my-ultimate-app.*").unwrap()),
+                facility: None,
+                message: Some(EqRegex::new("natty").unwrap()),
+            }],
+            ..Default::default()
+        };
+        assert!(!is_excluded(&message, Some(&SyslogInputConfig::default())));
+
+        assert!(is_excluded(&message, Some(&config)));
+        assert!(!is_excluded(&message2, Some(&config)));
+
+        // any filter excludes the message, not only the first one
+        let config = SyslogInputConfig {
+            exclusion_filters: vec![
+                SyslogExclusionFilter {
+                    appname: Some(EqRegex::new("other-app").unwrap()),
+                    facility: None,
+                    message: None,
+                },
+                SyslogExclusionFilter {
+                    appname: Some(EqRegex::new("postfix").unwrap()),
+                    facility: None,
+                    message: None,
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(is_excluded(&message2, Some(&config)));
+    }
+
+    #[test]
+    fn test_exclude_pattern_file() {
+        let message = Message {
+            protocol: syslog_loose::Protocol::RFC5424(0),
+            facility: Some(syslog_loose::SyslogFacility::LOG_CRON),
+            severity: None,
+            timestamp: None,
+            hostname: None,
+            appname: Some("CRON"),
+            procid: None,
+            msgid: None,
+            structured_data: vec![],
+            msg: "pam_unix(cron:session): session opened for user root",
+        };
+        let path =
+            std::env::temp_dir().join(format!("rlog-exclude-patterns-{}", std::process::id()));
+        let mut config = SyslogInputConfig {
+            exclude_pattern_file: Some("/nonexistent/rlog-exclude-patterns".into()),
+            ..Default::default()
+        };
+        assert!(!is_excluded(&message, Some(&config)));
+
+        std::fs::write(&path, "# cron sessions\nsession (opened|closed)\n").unwrap();
+        config.exclude_pattern_file = Some(path.to_string_lossy().into());
+        assert!(is_excluded(&message, Some(&config)));
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+impl SyslogLog {
+    /// Convert to a syslog log line, or to a generic log line if `log_system` is configured
+    pub fn into_log_line(self, config: Option<&SyslogInputConfig>) -> anyhow::Result<LogLine> {
+        let log_system = config.and_then(|config| config.log_system.clone());
+        let value = self.0;
+        let truncated = self.2;
+        let hostname = value
+            .hostname
+            .ok_or(anyhow::anyhow!("No hostname in syslog"))?;
+
+        let timestamp = value.timestamp.ok_or(anyhow!("No timestamp in syslog"))?;
+
+        let timestamp_secs = timestamp.timestamp();
+        let nanos = timestamp.timestamp_subsec_nanos();
+
+        let message = normalize_message(
+            config.and_then(|config| config.common.normalize_message.as_ref()),
+            value.msg,
+        );
+
+        let severity = value.severity.ok_or(anyhow!("No severity in syslog"))?;
+
+        let (proc_pid, proc_name) = value
+            .procid
+            .map(|procid| match procid {
+                syslog_loose::ProcId::PID(pid) => (Some(pid), None),
+                syslog_loose::ProcId::Name(name) => (None, Some(name)),
+            })
+            .unwrap_or((None, None));
+
+        let facility = value
+            .facility
+            .map(to_grpc_facility)
+            .unwrap_or(SyslogFacility::Local0);
+        let severity = to_grpc_severity(severity);
+
+        let service_name = match config.map(|config| &config.service_name) {
+            None | Some(SyslogServiceName::Appname) => value.appname.clone(),
+            Some(SyslogServiceName::Facility) => Some(facility.as_str_name().into()),
+            Some(SyslogServiceName::ProcName) => proc_name.clone(),
+            Some(SyslogServiceName::Static(service_name)) => Some(service_name.clone()),
+        }
+        .unwrap_or_else(|| "_syslog".into());
+
+        if let Some(log_system) = log_system {
+            // same field semantics as the collector applies to syslog log lines
+            let mut extra = serde_json::Map::new();
+            extra.insert("facility".into(), facility.as_str_name().into());
+            if let Some(pid) = proc_pid {
+                extra.insert("proc_pid".into(), pid.into());
+            }
+            if let Some(proc_name) = proc_name {
+                extra.insert("proc_name".into(), proc_name.into());
+            }
+            if let Some(msgid) = value.msgid {
+                extra.insert("msgid".into(), msgid.into());
+            }
+            if truncated {
+                extra.insert("_truncated".into(), true.into());
+            }
+            return LogLine::try_from(GenericLog {
+                host: hostname,
+                timestamp: timestamp.with_timezone(&Utc),
+                severity,
+                extra: serde_json::Value::Object(extra),
+                log_system,
+                message,
+                service_name,
+            });
+        }
+
+        let mut labels = HashMap::new();
+        if truncated {
+            // syslog log lines have no extra fields
+            labels.insert("_truncated".into(), "true".into());
+        }
+        Ok(LogLine {
+            host: hostname,
+            raw_host: None,
+            hmac: Vec::new(),
+            sequence: None,
+            labels,
+            timestamp: Some(rlog_grpc::prost_wkt_types::Timestamp {
+                seconds: timestamp_secs,
+                nanos: nanos as i32,
+            }),
+            line: Some(Line::Syslog(SyslogLogLine {
+                facility: facility as i32,
+                severity: severity as i32,
+                appname: value.appname,
+                proc_pid,
+                proc_name,
+                msgid: value.msgid,
+                msg: message,
+                service_name: Some(service_name),
+            })),
+        })
+    }
+}
+
+fn to_grpc_facility(facility: syslog_loose::SyslogFacility) -> SyslogFacility {
+    use SyslogFacility::*;
+    match facility {
+        syslog_loose::SyslogFacility::LOG_KERN => Kernel,
+        syslog_loose::SyslogFacility::LOG_USER => User,
+        syslog_loose::SyslogFacility::LOG_MAIL => Mail,
+        syslog_loose::SyslogFacility::LOG_DAEMON => Daemon,
+        syslog_loose::SyslogFacility::LOG_AUTH => Auth,
+        syslog_loose::SyslogFacility::LOG_SYSLOG => Syslog,
+        syslog_loose::SyslogFacility::LOG_LPR => Lpr,
+        syslog_loose::SyslogFacility::LOG_NEWS => News,
+        syslog_loose::SyslogFacility::LOG_UUCP => Uucp,
+        syslog_loose::SyslogFacility::LOG_CRON => Cron,
+        syslog_loose::SyslogFacility::LOG_AUTHPRIV => Authpriv,
+        syslog_loose::SyslogFacility::LOG_FTP => Ftp,
+        syslog_loose::SyslogFacility::LOG_NTP => Ntp,
+        syslog_loose::SyslogFacility::LOG_AUDIT => Audit,
+        syslog_loose::SyslogFacility::LOG_ALERT => Alert,
+        syslog_loose::SyslogFacility::LOG_CLOCKD => Clockd,
+        syslog_loose::SyslogFacility::LOG_LOCAL0 => Local0,
+        syslog_loose::SyslogFacility::LOG_LOCAL1 => Local1,
+        syslog_loose::SyslogFacility::LOG_LOCAL2 => Local2,
+        syslog_loose::SyslogFacility::LOG_LOCAL3 => Local3,
+        syslog_loose::SyslogFacility::LOG_LOCAL4 => Local4,
+        syslog_loose::SyslogFacility::LOG_LOCAL5 => Local5,
+        syslog_loose::SyslogFacility::LOG_LOCAL6 => Local6,
+        syslog_loose::SyslogFacility::LOG_LOCAL7 => Local7,
+    }
+}
+
+fn to_grpc_severity(severity: syslog_loose::SyslogSeverity) -> SyslogSeverity {
+    use SyslogSeverity::*;
+    match severity {
+        syslog_loose::SyslogSeverity::SEV_EMERG => Emergency,
+        syslog_loose::SyslogSeverity::SEV_ALERT => Alert,
+        syslog_loose::SyslogSeverity::SEV_CRIT => Critical,
+        syslog_loose::SyslogSeverity::SEV_ERR => Error,
+        syslog_loose::SyslogSeverity::SEV_WARNING => Warning,
+        syslog_loose::SyslogSeverity::SEV_NOTICE => Notice,
+        syslog_loose::SyslogSeverity::SEV_INFO => Info,
+        syslog_loose::SyslogSeverity::SEV_DEBUG => Debug,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{FixedOffset, TimeZone};
+    use rlog_grpc::rlog_service_protocol::{log_line::Line, SyslogSeverity};
+    use syslog_loose::{Message, ProcId, Protocol};
+
+    use super::{decode, detect_encoding, DetectedEncoding, SyslogLog};
+    use crate::config::{SyslogEncoding, SyslogInputConfig, SyslogServiceName};
+
+    #[test]
+    fn test_log_system_override() {
+        let syslog = || {
+            SyslogLog(
+                Message {
+                    protocol: Protocol::RFC5424(1),
+                    facility: Some(syslog_loose::SyslogFacility::LOG_MAIL),
+                    severity: Some(syslog_loose::SyslogSeverity::SEV_WARNING),
+                    timestamp: FixedOffset::east_opt(3600)
+                        .unwrap()
+                        .with_ymd_and_hms(2024, 1, 2, 3, 4, 5)
+                        .single(),
+                    hostname: Some("my_host".into()),
+                    appname: Some("postfix".into()),
+                    procid: Some(ProcId::PID(1234)),
+                    msgid: None,
+                    structured_data: vec![],
+                    msg: "connect from localhost".into(),
+                },
+                Vec::new(),
+                false,
+            )
+        };
+
+        let Some(Line::Syslog(_)) = syslog().into_log_line(None).unwrap().line else {
+            panic!("expected a syslog log line");
+        };
+
+        let config = SyslogInputConfig {
+            log_system: Some("mail".into()),
+            ..Default::default()
+        };
+        let log_line = syslog().into_log_line(Some(&config)).unwrap();
+        assert_eq!("my_host", log_line.host);
+        assert_eq!(
+            1704161045,
+            log_line.timestamp.as_ref().unwrap().seconds,
+            "2024-01-02T02:04:05Z"
+        );
+        let Some(Line::GenericLog(generic)) = log_line.line else {
+            panic!("expected a generic log line");
+        };
+        assert_eq!("mail", generic.log_system);
+        assert_eq!("connect from localhost", generic.message);
+        assert_eq!("postfix", generic.service_name);
+        assert_eq!(SyslogSeverity::Warning, generic.severity());
+        assert_eq!(
+            serde_json::json!({"facility": "mail", "proc_pid": 1234}),
+            serde_json::from_str::<serde_json::Value>(&generic.extra).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_service_name() {
+        let syslog = |procid| {
+            SyslogLog(
+                Message {
+                    protocol: Protocol::RFC3164,
+                    facility: Some(syslog_loose::SyslogFacility::LOG_MAIL),
+                    severity: Some(syslog_loose::SyslogSeverity::SEV_INFO),
+                    timestamp: FixedOffset::east_opt(0)
+                        .unwrap()
+                        .with_ymd_and_hms(2024, 1, 2, 3, 4, 5)
+                        .single(),
+                    hostname: Some("my_host".into()),
+                    appname: Some("postfix".into()),
+                    procid,
+                    msgid: None,
+                    structured_data: vec![],
+                    msg: "connect from localhost".into(),
+                },
+                Vec::new(),
+                false,
+            )
+        };
+        let service_name = |procid, service_name| {
+            let config = SyslogInputConfig {
+                service_name,
+                ..Default::default()
+            };
+            match syslog(procid).into_log_line(Some(&config)).unwrap().line {
+                Some(Line::Syslog(syslog)) => syslog.service_name.unwrap(),
+                _ => panic!("expected a syslog log line"),
+            }
+        };
+        let smtpd = || Some(ProcId::Name("smtpd".into()));
+
+        assert_eq!("postfix", service_name(None, SyslogServiceName::Appname));
+        assert_eq!("mail", service_name(None, SyslogServiceName::Facility));
+        assert_eq!("smtpd", service_name(smtpd(), SyslogServiceName::ProcName));
+        assert_eq!(
+            "_syslog",
+            service_name(Some(ProcId::PID(1234)), SyslogServiceName::ProcName)
+        );
+        assert_eq!(
+            "mta",
+            service_name(smtpd(), SyslogServiceName::Static("mta".into()))
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!("café", decode("café".as_bytes(), SyslogEncoding::Utf8));
+        assert_eq!("caf\u{FFFD}", decode(b"caf\xe9", SyslogEncoding::Utf8));
+        assert_eq!("café", decode(b"caf\xe9", SyslogEncoding::Latin1));
+
+        assert_eq!("café", decode("café".as_bytes(), SyslogEncoding::Detect));
+        assert_eq!("café", decode(b"caf\xe9", SyslogEncoding::Detect));
+        assert_eq!(
+            "\u{FFFD}\u{FFFD}\u{FFFD}",
+            decode(b"\xe9\xe8\xe0", SyslogEncoding::Detect)
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(DetectedEncoding::Utf8, detect_encoding(b"hello"));
+        assert_eq!(DetectedEncoding::Utf8, detect_encoding("élève".as_bytes()));
+        // invalid UTF-8 after a BOM
+        assert_eq!(
+            DetectedEncoding::Utf8,
+            detect_encoding(b"<13>1 - - - - - - \xEF\xBB\xBFcaf\xe9")
+        );
+        assert_eq!(
+            DetectedEncoding::Latin1,
+            detect_encoding(b"%SYS-5-CONFIG_I: Configur\xe9 par console")
+        );
+        assert_eq!(
+            DetectedEncoding::Unknown,
+            detect_encoding(b"ab\xe9\xe8\xe0")
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! Global byte budget of all in-flight log messages.
+//!
+//! Each message accepted by an input reserves its approximate size from the budget.
+//! The reservation follows the message through the input channel, the forward loop
+//! and the grpc_out channel and is released on drop, when the message leaves the
+//! pipeline (shipped, rejected or discarded).
+
+pub use rlog_inputs::byte_budget::{Budgeted, ByteBudget, Reservation, DEFAULT_MAX_BUFFERED_BYTES};
+
+use crate::config::CONFIG;
+
+/// Budget shared by all inputs & the output of the shipper
+pub static SHIPPER_BYTE_BUDGET: ByteBudget = ByteBudget::new();
+
+/// Reserve `size` bytes from the shipper budget configured by `max_buffered_bytes`
+pub fn reserve(size: usize) -> Option<Reservation> {
+    let max_bytes = CONFIG
+        .load()
+        .max_buffered_bytes
+        .unwrap_or(DEFAULT_MAX_BUFFERED_BYTES);
+    SHIPPER_BYTE_BUDGET.try_reserve(size, max_bytes)
+}
@@ -1,8 +1,17 @@
 use arc_swap::ArcSwap;
 use lazy_static::lazy_static;
+use rlog_common::config::{yaml_template, ConfigDoc};
+use rlog_grpc::rlog_service_protocol::SyslogSeverity;
+use rlog_inputs::byte_budget::DEFAULT_MAX_BUFFERED_BYTES;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+pub use rlog_inputs::config::{
+    eqregex, CommonInputConfig, GelfInputConfig, GelfVersion, MessageNormalization,
+    RecentInputsConfig, Redaction, ShortMessageFallback, SyslogEncoding, SyslogExclusionFilter,
+    SyslogInputConfig, SyslogServiceName,
+};
 
 use self::eqregex::EqRegex;
 
@@ -10,115 +19,344 @@ lazy_static! {
     pub static ref CONFIG: ArcSwap<Config> = ArcSwap::new(Arc::new(Config::default()));
 }
 
-#[derive(Serialize, Deserialize, Default, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, ConfigDoc, Default, PartialEq, Eq)]
 pub struct Config {
+    /// Syslog UDP input, enabled with the defaults if not set
+    #[config_doc(nested)]
     pub syslog_in: Option<SyslogInputConfig>,
+    /// GELF TCP input, enabled with the defaults if not set
+    #[config_doc(nested)]
     pub gelf_in: Option<GelfInputConfig>,
+    /// Output to the collector, enabled with the defaults if not set
+    #[config_doc(nested)]
     pub grpc_out: Option<GrpcOutConfig>,
+    /// Parse configuration of the watched files by path
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub files_in: HashMap<String, FileParseConfig>,
+    /// Parse configuration of the lines read from the standard input (`--stdin`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_in: Option<FileParseConfig>,
+    /// Maximum number of bytes of log messages buffered in the whole shipper
+    /// (inputs & output), default: 256MB
+    pub max_buffered_bytes: Option<usize>,
+    /// Periodic log line reporting the shipper state, disabled if not set (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[config_doc(nested)]
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Hostname rewrite rules (eg: pod name to deployment name), the first matching
+    /// rule is applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostname_mappings: Vec<HostnameMapping>,
+    /// Relay of the received syslog messages, unparsed, to a downstream syslog server
+    /// (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syslog_out: Option<SyslogOutConfig>,
+    /// Log lines are signed (HMAC-SHA256) with a key derived from the TLS private key
+    /// (not hot reloaded)
+    #[serde(default)]
+    pub sign_log_entries: bool,
+    /// Log lines are numbered per input (`_rlog_seq` field once indexed), gaps are log
+    /// lines discarded by the shipper inputs
+    #[serde(default)]
+    pub sequence_numbers: bool,
+    /// Last raw inputs of each source served by the HTTP status server (`/debug/recent`),
+    /// not recorded if not set (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[config_doc(nested)]
+    pub recent_inputs: Option<RecentInputsConfig>,
+    /// 1 in N raw inputs is dumped in debug logs, 0 disables the dumps, default: 100
+    /// (not hot reloaded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_sample_rate: Option<u64>,
+    /// Static labels added to every log line (eg: `env: prod`), indexed as free fields,
+    /// overridden by the labels of the inputs
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Eq)]
-pub struct GrpcOutConfig {
+impl Config {
+    /// Default configuration with all the optional sections set to their defaults
+    /// (except `syslog_out` which has no default destination)
+    pub fn template() -> Self {
+        Self {
+            syslog_in: Some(Default::default()),
+            gelf_in: Some(Default::default()),
+            grpc_out: Some(Default::default()),
+            max_buffered_bytes: Some(DEFAULT_MAX_BUFFERED_BYTES),
+            heartbeat: Some(Default::default()),
+            recent_inputs: Some(Default::default()),
+            debug_sample_rate: Some(100),
+            ..Default::default()
+        }
+    }
+
+    /// YAML of [Config::template], each key commented (`--generate-config`), see
+    /// `config-sample.yaml` for the options without default value
+    pub fn template_yaml() -> anyhow::Result<String> {
+        yaml_template(&Self::template())
+    }
+
+    /// YAML of this configuration with the sections & options which are not set but used
+    /// with their defaults (eg: inputs enabled if not set) filled (`--print-effective-config`),
+    /// a valid configuration file
+    pub fn effective_yaml(&self) -> anyhow::Result<String> {
+        let mut yaml = serde_yaml::to_value(self)?;
+        let mapping = yaml
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("the configuration is not a mapping"))?;
+        for (key, default) in [
+            (
+                "syslog_in",
+                serde_yaml::to_value(SyslogInputConfig::default())?,
+            ),
+            ("gelf_in", serde_yaml::to_value(GelfInputConfig::default())?),
+            ("grpc_out", serde_yaml::to_value(GrpcOutConfig::default())?),
+            ("max_buffered_bytes", DEFAULT_MAX_BUFFERED_BYTES.into()),
+            ("debug_sample_rate", 100.into()),
+        ] {
+            if mapping.get(key).is_none_or(serde_yaml::Value::is_null) {
+                mapping.insert(key.into(), default);
+            }
+        }
+        Ok(serde_yaml::to_string(&yaml)?)
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct SyslogOutConfig {
+    /// `host:port` of the downstream syslog server
+    pub address: String,
+    #[serde(default)]
+    pub protocol: SyslogOutProtocol,
+    /// syslog messages are also shipped to the collector, default: true
+    #[serde(default = "default_true")]
+    pub grpc: bool,
     #[serde(default = "default_buffer_size")]
     pub max_buffer_size: usize,
 }
-impl Default for GrpcOutConfig {
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogOutProtocol {
+    /// one datagram per message, as received
+    #[default]
+    Udp,
+    /// RFC 6587 octet counting framing
+    Tcp,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The whole hostname is replaced by `replacement` if it matches `pattern`, capture
+/// groups can be referenced in `replacement` (`$1`, `${name}`)
+#[derive(Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct HostnameMapping {
+    pub pattern: EqRegex,
+    pub replacement: String,
+}
+
+#[derive(Deserialize, Serialize, ConfigDoc, PartialEq, Eq, Clone)]
+pub struct HeartbeatConfig {
+    /// Interval of the heartbeat log lines
+    #[serde(default = "default_heartbeat_interval", with = "humantime_serde")]
+    pub interval: Duration,
+    /// Service name of the heartbeat log lines
+    #[serde(default = "default_heartbeat_service_name")]
+    pub service_name: String,
+}
+
+impl Default for HeartbeatConfig {
     fn default() -> Self {
         Self {
-            // This will not be hot reloaded (buffer is allocated at the start of the application)
-            max_buffer_size: 20_000,
+            interval: default_heartbeat_interval(),
+            service_name: default_heartbeat_service_name(),
         }
     }
 }
 
-fn default_buffer_size() -> usize {
-    20_000
+fn default_heartbeat_interval() -> Duration {
+    Duration::from_secs(60)
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Eq)]
-pub struct CommonInputConfig {
-    /// This will not be hot reloaded (buffer is allocated at the start of the application)
+fn default_heartbeat_service_name() -> String {
+    "_rlog_heartbeat".into()
+}
+
+#[derive(Deserialize, Serialize, ConfigDoc, PartialEq, Eq)]
+pub struct GrpcOutConfig {
+    /// Queue size (log lines) of the output, > 0 (not hot reloaded)
     #[serde(default = "default_buffer_size")]
     pub max_buffer_size: usize,
+    /// Timeout of the connection to the collector (not hot reloaded)
+    #[serde(default = "default_connect_timeout", with = "humantime_serde")]
+    pub connect_timeout: Duration,
+    /// Timeout of each request sent to the collector, timed out log lines are sent
+    /// again (not hot reloaded)
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Warning and more severe log lines are queued separately, sent first and not
+    /// discarded before less severe ones (not hot reloaded)
+    #[serde(default)]
+    pub priority_queues: bool,
+    /// Interval of the metrics reports sent to the collector, shifted by a random offset
+    /// drawn at startup (up to 10% of the interval) (not hot reloaded)
+    #[serde(default = "default_metrics_report_interval", with = "humantime_serde")]
+    pub metrics_report_interval: Duration,
+    /// A warning is logged every N consecutive failed metrics reports, 0 disables it
+    #[serde(default = "default_metrics_report_warn_threshold")]
+    pub metrics_report_warn_threshold: u64,
+    /// The metrics reports carry the count & hash of the log lines accepted by the
+    /// collector since the previous report, compared by the collector to what it received
+    /// (not hot reloaded)
+    #[serde(default)]
+    pub delivery_verification: bool,
 }
-
-impl Default for CommonInputConfig {
+impl Default for GrpcOutConfig {
     fn default() -> Self {
         Self {
+            // This will not be hot reloaded (buffer is allocated at the start of the application)
             max_buffer_size: 20_000,
+            connect_timeout: default_connect_timeout(),
+            timeout: default_timeout(),
+            priority_queues: false,
+            metrics_report_interval: default_metrics_report_interval(),
+            metrics_report_warn_threshold: default_metrics_report_warn_threshold(),
+            delivery_verification: false,
         }
     }
 }
 
-#[derive(Deserialize, Default, Serialize, PartialEq, Eq)]
-pub struct SyslogInputConfig {
-    #[serde(flatten, default)]
-    pub common: CommonInputConfig,
-    pub exclusion_filters: Vec<SyslogExclusionFilter>,
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_metrics_report_interval() -> Duration {
+    Duration::from_secs(30)
 }
 
-/// Exclusion filter patterns for syslog.
-///
-/// If more than one pattern is specified, all the pattern specified must match for
-/// the log entry to be excluded
-#[derive(Deserialize, Default, Serialize, PartialEq, Eq)]
-pub struct SyslogExclusionFilter {
+fn default_metrics_report_warn_threshold() -> u64 {
+    10
+}
+
+fn default_buffer_size() -> usize {
+    20_000
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileParseConfig {
+    #[serde(flatten)]
+    pub mapping: FileMappingConfig,
+    pub static_fields: HashMap<String, Value>,
+    #[serde(default)]
+    pub log_rotation_strategy: LogRotationStrategy,
+    /// log system reported to the collector, default: `file_in`
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub appname: Option<EqRegex>,
+    pub log_system: Option<String>,
+    /// level text to severity mapping (case insensitive), takes precedence over
+    /// the built-in aliases (`WARN`, `err`, `fatal`, ...)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub severity_mapping: HashMap<String, Severity>,
+    /// a disabled file is not watched (not hot reloaded)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// static labels of the log lines of this file, override the global labels
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    /// normalization of the message of the log lines, not normalized if not set
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub facility: Option<EqRegex>,
+    pub normalize_message: Option<MessageNormalization>,
+    /// retries of the watch of a file unavailable at startup, the startup fails at once
+    /// if not set (not hot reloaded)
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub message: Option<EqRegex>,
+    pub startup_retry: Option<StartupRetryConfig>,
 }
 
-pub mod eqregex {
-    use regex::Regex;
-    use serde::{Deserialize, Serialize};
-    use std::ops::Deref;
-
-    #[derive(Clone, Serialize, Deserialize)]
-    #[serde(transparent)]
-    pub struct EqRegex {
-        #[serde(with = "serde_regex")]
-        inner: Regex,
-    }
+/// Syslog severity
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
 
-    impl EqRegex {
-        pub fn new(regex: &str) -> Result<Self, regex::Error> {
-            Ok(Self {
-                inner: Regex::new(regex)?,
-            })
-        }
-    }
-    impl PartialEq for EqRegex {
-        fn eq(&self, other: &Self) -> bool {
-            self.inner.as_str() == other.inner.as_str()
+impl From<Severity> for SyslogSeverity {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Emergency => SyslogSeverity::Emergency,
+            Severity::Alert => SyslogSeverity::Alert,
+            Severity::Critical => SyslogSeverity::Critical,
+            Severity::Error => SyslogSeverity::Error,
+            Severity::Warning => SyslogSeverity::Warning,
+            Severity::Notice => SyslogSeverity::Notice,
+            Severity::Info => SyslogSeverity::Info,
+            Severity::Debug => SyslogSeverity::Debug,
         }
     }
-    impl Eq for EqRegex {}
+}
+
+/// How the watched file is rotated by external log rotators
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotationStrategy {
+    /// The file is renamed and a new file is created (default)
+    #[default]
+    RenameCreate,
+    /// The file is truncated in place (eg. logrotate `copytruncate`)
+    Truncate,
+}
 
-    impl Deref for EqRegex {
-        type Target = Regex;
+/// Retries of the watch of a file unavailable at startup (eg: created a few seconds later by
+/// another process), with an exponential backoff
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct StartupRetryConfig {
+    /// number of retries before giving up
+    #[serde(default = "default_startup_max_retries")]
+    pub max_retries: u32,
+    /// delay before the first retry, doubled at each retry
+    #[serde(default = "default_startup_initial_backoff", with = "humantime_serde")]
+    pub initial_backoff: Duration,
+    /// upper bound of the delay between two retries
+    #[serde(default = "default_startup_max_backoff", with = "humantime_serde")]
+    pub max_backoff: Duration,
+    /// once the retries are exhausted, the file is expected to appear later: it is retried
+    /// in the background every `max_backoff` instead of failing the startup
+    #[serde(default)]
+    pub wait_in_background: bool,
+}
 
-        fn deref(&self) -> &Self::Target {
-            &self.inner
+impl Default for StartupRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_startup_max_retries(),
+            initial_backoff: default_startup_initial_backoff(),
+            max_backoff: default_startup_max_backoff(),
+            wait_in_background: false,
         }
     }
 }
 
-#[derive(Deserialize, Default, Serialize, PartialEq, Eq)]
-pub struct GelfInputConfig {
-    #[serde(flatten, default)]
-    pub common: CommonInputConfig,
+fn default_startup_max_retries() -> u32 {
+    5
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct FileParseConfig {
-    #[serde(flatten)]
-    pub mapping: FileMappingConfig,
-    pub static_fields: HashMap<String, Value>,
+fn default_startup_initial_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_startup_max_backoff() -> Duration {
+    Duration::from_secs(30)
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -170,12 +408,32 @@ impl Extend<Config> for Config {
             gelf_in,
             grpc_out,
             files_in,
+            stdin_in,
+            max_buffered_bytes,
+            heartbeat,
+            hostname_mappings,
+            syslog_out,
+            sign_log_entries,
+            sequence_numbers,
+            recent_inputs,
+            debug_sample_rate,
+            labels,
         } in iter
         {
             self.syslog_in.extend_option(syslog_in);
             self.gelf_in.extend_option(gelf_in);
             self.grpc_out.extend_option(grpc_out);
             self.files_in.extend(files_in);
+            self.stdin_in.extend_option(stdin_in);
+            self.max_buffered_bytes.extend_option(max_buffered_bytes);
+            self.heartbeat.extend_option(heartbeat);
+            self.hostname_mappings.extend(hostname_mappings);
+            self.syslog_out.extend_option(syslog_out);
+            self.sign_log_entries |= sign_log_entries;
+            self.sequence_numbers |= sequence_numbers;
+            self.recent_inputs.extend_option(recent_inputs);
+            self.debug_sample_rate.extend_option(debug_sample_rate);
+            self.labels.extend(labels);
         }
     }
 }
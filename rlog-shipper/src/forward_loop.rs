@@ -1,49 +1,124 @@
 use async_channel::Receiver;
-use async_channel::Sender;
 use rlog_common::utils::format_error;
-use rlog_grpc::rlog_service_protocol::LogLine;
+use rlog_grpc::rlog_service_protocol::{LogLine, SequenceNumber};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
+use crate::byte_budget::Budgeted;
+use crate::config::{Config, CONFIG};
+use crate::grpc_out::GrpcOutSender;
+use crate::metrics::INCARNATION_ID;
+use crate::transform::LogLineTransform;
+
 pub struct ForwardMetrics {
     pub in_queue_size: &'static AtomicU64,
     pub in_processed_count: &'static AtomicU64,
     pub in_error_count: &'static AtomicU64,
+    /// log lines dropped by the transforms of the input
+    pub in_dropped_count: &'static AtomicU64,
     pub out_queue_size: &'static AtomicU64,
 }
 
+/// Input of the forwarded log lines
+pub enum Input {
+    Syslog,
+    Gelf,
+    /// path of the watched file
+    File(String),
+    Stdin,
+}
+
+impl Input {
+    /// each watched file is a distinct input: `files_in:<path>`
+    fn name(&self) -> Cow<'static, str> {
+        match self {
+            Input::Syslog => "syslog_in".into(),
+            Input::Gelf => "gelf_in".into(),
+            Input::File(path) => format!("files_in:{path}").into(),
+            Input::Stdin => "stdin_in".into(),
+        }
+    }
+
+    pub fn labels<'a>(&self, config: &'a Config) -> Option<&'a HashMap<String, String>> {
+        match self {
+            Input::Syslog => config.syslog_in.as_ref().map(|c| &c.common.labels),
+            Input::Gelf => config.gelf_in.as_ref().map(|c| &c.common.labels),
+            Input::File(path) => config.files_in.get(path).map(|c| &c.labels),
+            Input::Stdin => config.stdin_in.as_ref().map(|c| &c.labels),
+        }
+    }
+}
+
+/// Global labels, overridden by the labels of the input
+pub fn add_labels(
+    log_line: &mut LogLine,
+    labels: &HashMap<String, String>,
+    input_labels: Option<&HashMap<String, String>>,
+) {
+    for (name, value) in labels.iter().chain(input_labels.into_iter().flatten()) {
+        log_line.labels.insert(name.clone(), value.clone());
+    }
+}
+
 pub async fn forward_loop<T>(
-    input: Receiver<T>,
-    grpc_out: Sender<LogLine>,
-    input_name: &str,
+    input: Receiver<Budgeted<T>>,
+    into_log_line: fn(T) -> anyhow::Result<LogLine>,
+    transform: Option<Box<dyn LogLineTransform>>,
+    grpc_out: GrpcOutSender,
+    input_id: Input,
     fw_metrics: ForwardMetrics,
-) where
-    LogLine: TryFrom<T, Error = anyhow::Error>,
-{
+) {
+    let input_name = input_id.name();
     while let Ok(syslog) = input.recv().await {
         fw_metrics.in_queue_size.fetch_sub(1, Ordering::Relaxed);
         fw_metrics
             .in_processed_count
             .fetch_add(1, Ordering::Relaxed);
         // construct a valid LogLine from gelf stuff
-        let log_line = match LogLine::try_from(syslog) {
+        let log_line = match syslog.try_map(into_log_line) {
             Ok(l) => l,
             Err(e) => {
                 fw_metrics.in_error_count.fetch_add(1, Ordering::Relaxed);
                 tracing::error!(
-                    "received an invalid log from {input_name}! {}",
-                    format_error(e)
+                    error = %format_error(e),
+                    "received an invalid log from {input_name}!"
                 );
                 continue;
             }
         };
+        let mut log_line = match &transform {
+            Some(transform) => {
+                match log_line.try_map(|log_line| transform.apply(log_line).ok_or(())) {
+                    Ok(log_line) => log_line,
+                    Err(()) => {
+                        fw_metrics.in_dropped_count.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                }
+            }
+            None => log_line,
+        };
+        if CONFIG.load().sequence_numbers {
+            log_line.value.sequence = log_line.sequence.map(|number| SequenceNumber {
+                incarnation_id: INCARNATION_ID.clone(),
+                input: input_name.to_string(),
+                number,
+            });
+        }
         // if the channel is full, is will block here ; filling channels from each
         // server (syslog & gelf), when those channel will be full, new messages will be discarded
-        if let Err(e) = grpc_out.send(log_line).await {
-            tracing::error!("Channel closed! {e}");
-            break;
-        } else {
-            fw_metrics.out_queue_size.fetch_add(1, Ordering::Relaxed);
+        match grpc_out.send(log_line).await {
+            Ok(true) => {
+                fw_metrics.out_queue_size.fetch_add(1, Ordering::Relaxed);
+            }
+            // low priority queue full
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Channel closed! {e}");
+                break;
+            }
         }
     }
     tracing::info!("{input_name} input channel closed, {input_name} forward task stopped.");
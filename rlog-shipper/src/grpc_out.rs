@@ -1,35 +1,219 @@
 use std::{sync::atomic::Ordering, time::Duration};
 
-use async_channel::Sender;
+use async_channel::{Receiver, RecvError, SendError, Sender, TrySendError};
 use futures::FutureExt;
-use rlog_common::utils::format_error;
+use ring::hmac;
+use rlog_common::{
+    delivery_window::{log_line_hash, WindowDigest, INCARNATION_METADATA},
+    log_signature,
+    utils::format_error,
+};
 use rlog_grpc::{
-    rlog_service_protocol::{log_collector_client::LogCollectorClient, LogLine},
+    rlog_service_protocol::{
+        log_collector_client::LogCollectorClient, log_line::Line, DeliveryWindow, LogLine,
+        SyslogSeverity,
+    },
     tonic::{
+        metadata::MetadataValue,
         transport::{Channel, Endpoint},
         Code, Request, Response, Status,
     },
 };
-use tokio::{select, task::JoinHandle, time::interval};
+use tokio::{
+    select,
+    sync::oneshot,
+    task::JoinHandle,
+    time::{interval_at, Instant, MissedTickBehavior},
+};
 use tokio_stream::{wrappers::IntervalStream, StreamExt};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
+    byte_budget::Budgeted,
     config::{GrpcOutConfig, CONFIG},
-    metrics::{to_grpc_metrics, SHIPPER_ERROR_COUNT, SHIPPER_PROCESSED_COUNT, SHIPPER_QUEUE_COUNT},
+    grpc_proxy::ProxyConnector,
+    grpc_tls::PinnedTlsConnector,
+    metrics::{
+        to_grpc_metrics, INCARNATION_ID, SHIPPER_ERROR_COUNT, SHIPPER_HIGH_PRIORITY_QUEUE_CAPACITY,
+        SHIPPER_HIGH_PRIORITY_QUEUE_COUNT, SHIPPER_LOW_PRIORITY_DROPPED_COUNT,
+        SHIPPER_LOW_PRIORITY_QUEUE_CAPACITY, SHIPPER_LOW_PRIORITY_QUEUE_COUNT,
+        SHIPPER_METRICS_REPORT_FAILURE_COUNT, SHIPPER_PROCESSED_COUNT, SHIPPER_QUEUE_CAPACITY,
+        SHIPPER_QUEUE_COUNT,
+    },
 };
 
+/// A failed metrics report is sent again once after this delay
+const METRICS_REPORT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Maximum offset of the metrics reports, as a fraction of their interval
+const METRICS_REPORT_MAX_JITTER: f64 = 0.1;
+
+/// Sending half of the grpc_out queue(s)
+#[derive(Clone)]
+pub enum GrpcOutSender {
+    Single(Sender<Budgeted<LogLine>>),
+    /// warning and more severe log lines are sent to `high`
+    Priority {
+        high: Sender<Budgeted<LogLine>>,
+        low: Sender<Budgeted<LogLine>>,
+    },
+}
+
+impl GrpcOutSender {
+    /// Queue a log line, waiting for room in the queue.
+    ///
+    /// In priority mode, low priority log lines are discarded if their queue is full,
+    /// `Ok(false)` is returned.
+    pub async fn send(
+        &self,
+        log_line: Budgeted<LogLine>,
+    ) -> Result<bool, SendError<Budgeted<LogLine>>> {
+        match self {
+            GrpcOutSender::Single(sender) => sender.send(log_line).await.map(|_| true),
+            GrpcOutSender::Priority { high, .. } if is_high_priority(&log_line.value) => {
+                high.send(log_line).await?;
+                SHIPPER_HIGH_PRIORITY_QUEUE_COUNT.fetch_add(1, Ordering::Relaxed);
+                Ok(true)
+            }
+            GrpcOutSender::Priority { low, .. } => match low.try_send(log_line) {
+                Ok(()) => {
+                    SHIPPER_LOW_PRIORITY_QUEUE_COUNT.fetch_add(1, Ordering::Relaxed);
+                    Ok(true)
+                }
+                Err(TrySendError::Full(log_line)) => {
+                    SHIPPER_LOW_PRIORITY_DROPPED_COUNT.fetch_add(1, Ordering::Relaxed);
+                    tracing::error!(
+                        "Low priority send buffer full: discarding value {:?}",
+                        log_line.value
+                    );
+                    Ok(false)
+                }
+                Err(TrySendError::Closed(log_line)) => Err(SendError(log_line)),
+            },
+        }
+    }
+}
+
+/// Receiving half of the grpc_out queue(s)
+enum GrpcOutReceiver {
+    Single(Receiver<Budgeted<LogLine>>),
+    Priority {
+        high: Receiver<Budgeted<LogLine>>,
+        low: Receiver<Budgeted<LogLine>>,
+    },
+}
+
+impl GrpcOutReceiver {
+    fn new(max_buffer_size: usize, priority_queues: bool) -> (GrpcOutSender, Self) {
+        if !priority_queues {
+            SHIPPER_QUEUE_CAPACITY.store(max_buffer_size as u64, Ordering::Relaxed);
+            let (sender, receiver) = async_channel::bounded(max_buffer_size);
+            return (
+                GrpcOutSender::Single(sender),
+                GrpcOutReceiver::Single(receiver),
+            );
+        }
+        SHIPPER_QUEUE_CAPACITY.store(2 * max_buffer_size as u64, Ordering::Relaxed);
+        SHIPPER_HIGH_PRIORITY_QUEUE_CAPACITY.store(max_buffer_size as u64, Ordering::Relaxed);
+        SHIPPER_LOW_PRIORITY_QUEUE_CAPACITY.store(max_buffer_size as u64, Ordering::Relaxed);
+        let (high_sender, high_receiver) = async_channel::bounded(max_buffer_size);
+        let (low_sender, low_receiver) = async_channel::bounded(max_buffer_size);
+        (
+            GrpcOutSender::Priority {
+                high: high_sender,
+                low: low_sender,
+            },
+            GrpcOutReceiver::Priority {
+                high: high_receiver,
+                low: low_receiver,
+            },
+        )
+    }
+
+    /// High priority log lines first, cancel safe
+    async fn recv(&self) -> Result<Budgeted<LogLine>, RecvError> {
+        match self {
+            GrpcOutReceiver::Single(receiver) => receiver.recv().await,
+            GrpcOutReceiver::Priority { high, low } => {
+                // both queues are closed at the same time: once one of them is closed
+                // and empty, the other one is drained
+                let (log_line, queue_count) = select! {
+                    biased;
+                    log_line = high.recv() => match log_line {
+                        Ok(log_line) => (log_line, &*SHIPPER_HIGH_PRIORITY_QUEUE_COUNT),
+                        Err(_) => (low.recv().await?, &*SHIPPER_LOW_PRIORITY_QUEUE_COUNT),
+                    },
+                    log_line = low.recv() => match log_line {
+                        Ok(log_line) => (log_line, &*SHIPPER_LOW_PRIORITY_QUEUE_COUNT),
+                        Err(_) => (high.recv().await?, &*SHIPPER_HIGH_PRIORITY_QUEUE_COUNT),
+                    },
+                };
+                queue_count.fetch_sub(1, Ordering::Relaxed);
+                Ok(log_line)
+            }
+        }
+    }
+}
+
+/// Log lines accepted by the collector since the last accepted metrics report
+#[derive(Default)]
+struct OpenWindow {
+    number: u64,
+    digest: WindowDigest,
+}
+
+impl OpenWindow {
+    fn to_grpc(&self) -> DeliveryWindow {
+        DeliveryWindow {
+            number: self.number,
+            count: self.digest.count(),
+            hash: self.digest.hash(),
+        }
+    }
+
+    /// The window has been reported
+    fn close(&mut self) {
+        self.number += 1;
+        self.digest = WindowDigest::default();
+    }
+}
+
+/// Warning and more severe log lines
+fn is_high_priority(log_line: &LogLine) -> bool {
+    let severity = match &log_line.line {
+        Some(Line::Syslog(syslog)) => syslog.severity,
+        Some(Line::Gelf(gelf)) => gelf.severity,
+        Some(Line::GenericLog(generic)) => generic.severity,
+        None => return false,
+    };
+    severity <= SyslogSeverity::Warning as i32
+}
+
+/// `connected` is notified once the collector has been reached for the first time
 pub fn launch_grpc_shipper(
     endpoint: Endpoint,
+    proxy: Option<ProxyConnector>,
+    pinned_tls: Option<PinnedTlsConnector>,
+    signing_key: Option<hmac::Key>,
+    connected: oneshot::Sender<()>,
     shutdown_token: CancellationToken,
-) -> (Sender<LogLine>, JoinHandle<()>) {
-    let (sender, receiver) = async_channel::bounded(match CONFIG.load().grpc_out.as_ref() {
-        Some(config) => config.max_buffer_size,
-        None => GrpcOutConfig::default().max_buffer_size,
-    });
+) -> (GrpcOutSender, JoinHandle<()>) {
+    let config = CONFIG.load_full();
+    let default_config = GrpcOutConfig::default();
+    let config = config.grpc_out.as_ref().unwrap_or(&default_config);
+    let max_buffer_size = config.max_buffer_size;
+    let endpoint = endpoint
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.timeout);
+    let (sender, receiver) = GrpcOutReceiver::new(max_buffer_size, config.priority_queues);
+    let metrics_report_interval = config.metrics_report_interval;
+    let metrics_report_warn_threshold = config.metrics_report_warn_threshold;
+    let delivery_verification = config.delivery_verification;
 
     let handle = tokio::spawn(async move {
-        let mut current_log_line: Option<LogLine> = None;
+        let mut current_log_line: Option<Budgeted<LogLine>> = None;
+        let mut delivery_window = delivery_verification.then(OpenWindow::default);
+        let incarnation = MetadataValue::try_from(INCARNATION_ID.as_str())
+            .expect("Invalid incarnation id");
 
         // Connect to remote endpoint
         //
@@ -39,28 +223,64 @@ pub fn launch_grpc_shipper(
         // This is utterly odd: the tonic api answer as if the remote endpoint sent a "Unavailable"
         // code. Not sure if it's a gRPC idiom but it is very confusing.
 
-        let mut client = match connect(&endpoint, &shutdown_token).await {
+        let connector = match (pinned_tls, proxy) {
+            (Some(pinned_tls), proxy) => Some(Connector::PinnedTls(pinned_tls.with_proxy(proxy))),
+            (None, Some(proxy)) => Some(Connector::Proxy(proxy)),
+            (None, None) => None,
+        };
+        let mut client = match connect(&endpoint, connector.as_ref(), &shutdown_token).await {
             Some(client) => client,
             None => return,
         };
+        // nobody may be waiting for it
+        let _ = connected.send(());
 
-        let mut metrics_report_interval = IntervalStream::new(interval(Duration::from_secs(30)));
+        let mut metrics_report_failures = report_metrics(
+            &mut client,
+            0,
+            metrics_report_warn_threshold,
+            &mut delivery_window,
+        )
+        .await;
+        // the reports of shippers started together are spread by a random offset drawn once,
+        // the interval between the reports of a shipper is kept
+        let jitter = metrics_report_interval.mul_f64(rand::random::<f64>() * METRICS_REPORT_MAX_JITTER);
+        let mut metrics_report_interval = interval_at(
+            Instant::now() + metrics_report_interval + jitter,
+            metrics_report_interval,
+        );
+        // no burst of reports after a slow (retried) one
+        metrics_report_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut metrics_report_interval = IntervalStream::new(metrics_report_interval);
 
         loop {
             // send current log_line if any
-            if let Some(log_line) = current_log_line.take() {
+            if let Some(budgeted_log_line) = current_log_line.take() {
+                let log_line = &budgeted_log_line.value;
                 tracing::debug!("Will ship {log_line:#?}");
                 // do something
-                let request = Request::new(log_line.clone());
+                let mut request = Request::new(log_line.clone());
+                if let Some(signing_key) = &signing_key {
+                    log_signature::sign(signing_key, request.get_mut());
+                }
+                // hashed as sent, the collector hashes it as received
+                let log_line_hash = delivery_window.as_ref().map(|_| {
+                    request
+                        .metadata_mut()
+                        .insert(INCARNATION_METADATA, incarnation.clone());
+                    log_line_hash(request.get_mut())
+                });
                 let response: Result<Response<()>, Status> = client.log(request).await;
                 if let Err(status) = response {
                     SHIPPER_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
                     match status.code() {
-                        Code::InvalidArgument => {
-                            // invalid log_line, no need to disconnect nor trying to re-send it
+                        Code::InvalidArgument | Code::Unauthenticated => {
+                            // invalid log_line (or rejected signature), no need to disconnect nor trying to re-send it
                             tracing::error!(
-                                "Unable to send LogLine, collector responded invalid_argument: {} --- {log_line:?}",
-                                status.message()
+                                "Unable to send LogLine, collector responded {:?}: {} --- {}",
+                                status.code(),
+                                status.message(),
+                                log_line.to_json_string().unwrap_or_default()
                             );
                         }
                         Code::OutOfRange => {
@@ -72,6 +292,7 @@ pub fn launch_grpc_shipper(
                         }
                         // this covers:
                         // - unavailable upstream (collector reports Unavailable)
+                        // - request timeout (tonic reports Cancelled)
                         // - disconnected collector, tonic api report Unaavailble and tries to reconnect
                         //   on the background
                         _ => {
@@ -86,19 +307,30 @@ pub fn launch_grpc_shipper(
                             // collector unavailable means the upstream (quickwit) is not available
                             // wait a bit before trying to send again the log line
                             tokio::time::sleep(Duration::from_secs(1)).await;
-                            current_log_line = Some(log_line);
+                            current_log_line = Some(budgeted_log_line);
                             continue;
                         }
                     }
                 } else {
                     SHIPPER_PROCESSED_COUNT.fetch_add(1, Ordering::Relaxed);
+                    if let (Some(window), Some(log_line_hash)) =
+                        (delivery_window.as_mut(), log_line_hash)
+                    {
+                        window.digest.add(log_line_hash);
+                    }
                 }
             }
             select! {
-                _ = metrics_report_interval.next() => {
-                    if let Err(e) = client.report_metrics(Request::new(to_grpc_metrics())).await{
-                        tracing::error!("Unable to report metrics: {}", format_error(e.into()));
-                    }
+                // no more reports while shutting down: a failing report would delay the
+                // draining of the queue
+                _ = metrics_report_interval.next(), if !shutdown_token.is_cancelled() => {
+                    metrics_report_failures = report_metrics(
+                        &mut client,
+                        metrics_report_failures,
+                        metrics_report_warn_threshold,
+                        &mut delivery_window,
+                    )
+                    .await;
                 }
                 log_line = receiver.recv() => {
                     match log_line{
@@ -116,21 +348,85 @@ pub fn launch_grpc_shipper(
     (sender, handle)
 }
 
+/// Report the metrics to the collector, a failed report is sent again once.
+///
+/// `consecutive_failures` failed reports preceded this one, the updated count is returned.
+/// A warning is logged every `warn_threshold` consecutive failures: log lines may still be
+/// accepted by the collector while it considers this shipper gone.
+///
+/// The delivery window, if any, is closed once reported, a failed report leaves it open.
+async fn report_metrics(
+    client: &mut LogCollectorClient<Channel>,
+    consecutive_failures: u64,
+    warn_threshold: u64,
+    delivery_window: &mut Option<OpenWindow>,
+) -> u64 {
+    let metrics = || {
+        let mut metrics = to_grpc_metrics();
+        metrics.delivery_window = delivery_window.as_ref().map(OpenWindow::to_grpc);
+        metrics
+    };
+    let mut response = client.report_metrics(Request::new(metrics())).await;
+    if let Err(status) = &response {
+        SHIPPER_METRICS_REPORT_FAILURE_COUNT.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("Unable to report metrics, will retry: {status:?}");
+        tokio::time::sleep(METRICS_REPORT_RETRY_DELAY).await;
+        // the failure is counted in the retried report
+        response = client.report_metrics(Request::new(metrics())).await;
+    }
+    match response {
+        Ok(_) => {
+            if let Some(window) = delivery_window {
+                window.close();
+            }
+            if consecutive_failures > 0 {
+                tracing::info!("Metrics reported after {consecutive_failures} failed report(s)");
+            }
+            0
+        }
+        Err(e) => {
+            SHIPPER_METRICS_REPORT_FAILURE_COUNT.fetch_add(1, Ordering::Relaxed);
+            tracing::error!(error = %format_error(e.into()), "Unable to report metrics");
+            let consecutive_failures = consecutive_failures + 1;
+            if warn_threshold > 0 && consecutive_failures.is_multiple_of(warn_threshold) {
+                tracing::warn!(
+                    "{consecutive_failures} consecutive metrics reports failed, the collector may consider this shipper disconnected"
+                );
+            }
+            consecutive_failures
+        }
+    }
+}
+
+/// Custom connectors, tonic connector is used otherwise
+enum Connector {
+    Proxy(ProxyConnector),
+    PinnedTls(PinnedTlsConnector),
+}
+
 async fn connect(
     endpoint: &Endpoint,
+    connector: Option<&Connector>,
     shutdown_token: &CancellationToken,
 ) -> Option<LogCollectorClient<Channel>> {
     loop {
         tracing::info!("Connecting to collector");
-        match endpoint.connect().await.map(LogCollectorClient::new) {
+        let channel = match connector {
+            Some(Connector::Proxy(proxy)) => endpoint.connect_with_connector(proxy.clone()).await,
+            Some(Connector::PinnedTls(pinned_tls)) => {
+                endpoint.connect_with_connector(pinned_tls.clone()).await
+            }
+            None => endpoint.connect().await,
+        };
+        match channel.map(LogCollectorClient::new) {
             Ok(client) => {
                 tracing::info!("Connected to collector");
                 return Some(client);
             }
             Err(e) => {
                 tracing::error!(
-                    "Unable to connect to collector gRPC endpoint: {}",
-                    format_error(e.into())
+                    error = %format_error(e.into()),
+                    "Unable to connect to collector gRPC endpoint"
                 );
                 tokio::time::sleep(Duration::from_secs(1)).await;
                 if shutdown_token.is_cancelled() {
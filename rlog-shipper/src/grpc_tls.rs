@@ -0,0 +1,238 @@
+use std::{
+    future::Future,
+    io::{Error, ErrorKind},
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    task::{Context, Poll},
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use rlog_common::tls::TlsVersion;
+use rlog_grpc::tonic::{codegen::Service, transport::Uri};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+
+use crate::{grpc_proxy::ProxyConnector, metrics::SHIPPER_FINGERPRINT_MISMATCH_COUNT};
+
+/// h2 alpn, as negotiated by tonic
+const ALPN_H2: &[u8] = b"h2";
+
+/// mTLS connector pinning the SHA-256 fingerprint of the collector certificate and/or
+/// enforcing a minimum TLS version.
+///
+/// Tonic does not give access to the peer certificate nor to the protocol versions, so the
+/// TLS session is established by this connector: the endpoint must use the `http` scheme
+/// and must not have a tonic TLS configuration. The certificate is still verified against
+/// the CA, the handshake is aborted if the leaf certificate fingerprint does not match.
+#[derive(Clone)]
+pub struct PinnedTlsConnector {
+    connector: TlsConnector,
+    /// SNI & certificate verification, host of the endpoint URI if not set
+    domain_name: Option<String>,
+    proxy: Option<ProxyConnector>,
+}
+
+impl PinnedTlsConnector {
+    pub fn new(
+        ca_certificate_pem: &[u8],
+        certificate_pem: &[u8],
+        private_key_pem: &[u8],
+        expected_fingerprint: Option<&str>,
+        domain_name: Option<String>,
+        min_tls_version: TlsVersion,
+    ) -> anyhow::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for ca_certificate in rustls_pemfile::certs(&mut &ca_certificate_pem[..]) {
+            roots
+                .add(ca_certificate.context("Invalid ca certificate")?)
+                .context("Invalid ca certificate")?;
+        }
+        let verifier = PinningVerifier {
+            inner: WebPkiServerVerifier::builder(Arc::new(roots)).build()?,
+            expected_fingerprint: expected_fingerprint.map(parse_fingerprint).transpose()?,
+        };
+        let certificates = rustls_pemfile::certs(&mut &certificate_pem[..])
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid certificate")?;
+        let private_key = rustls_pemfile::private_key(&mut &private_key_pem[..])
+            .context("Invalid private key")?
+            .ok_or_else(|| anyhow!("No private key found"))?;
+
+        let mut config =
+            ClientConfig::builder_with_protocol_versions(min_tls_version.protocol_versions())
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_client_auth_cert(certificates, private_key)?;
+        config.alpn_protocols.push(ALPN_H2.into());
+
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            domain_name,
+            proxy: None,
+        })
+    }
+
+    /// Tunnel the TLS session through this proxy
+    pub fn with_proxy(mut self, proxy: Option<ProxyConnector>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    async fn connect(self, target: Uri) -> Result<TlsStream<TcpStream>, Error> {
+        let host = target
+            .host()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Missing collector host"))?
+            .to_string();
+        let stream = match self.proxy {
+            Some(proxy) => proxy.connect(target.clone()).await?,
+            None => {
+                let port = target.port_u16().unwrap_or(443);
+                let stream = TcpStream::connect((host.as_str(), port)).await?;
+                stream.set_nodelay(true)?;
+                stream
+            }
+        };
+        let domain_name = ServerName::try_from(self.domain_name.unwrap_or(host))
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let stream = self.connector.connect(domain_name, stream).await?;
+        if stream.get_ref().1.alpn_protocol() != Some(ALPN_H2) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "HTTP/2 was not negotiated",
+            ));
+        }
+        Ok(stream)
+    }
+}
+
+impl Service<Uri> for PinnedTlsConnector {
+    type Response = TlsStream<TcpStream>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, target: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(target))
+    }
+}
+
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    /// the certificate is only verified against the CA if not set
+    expected_fingerprint: Option<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let Some(expected_fingerprint) = &self.expected_fingerprint else {
+            return Ok(verified);
+        };
+        let fingerprint = ring::digest::digest(&ring::digest::SHA256, end_entity);
+        if fingerprint.as_ref() != expected_fingerprint {
+            SHIPPER_FINGERPRINT_MISMATCH_COUNT.fetch_add(1, Ordering::Relaxed);
+            tracing::error!(
+                "Collector certificate fingerprint mismatch: expected {}, got {}",
+                format_fingerprint(expected_fingerprint),
+                format_fingerprint(fingerprint.as_ref())
+            );
+            return Err(tokio_rustls::rustls::Error::General(
+                "Collector certificate fingerprint mismatch".into(),
+            ));
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// SHA-256 fingerprint in hex, with or without `:` separators (case insensitive)
+fn parse_fingerprint(fingerprint: &str) -> anyhow::Result<Vec<u8>> {
+    let hex = fingerprint.replace(':', "");
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid SHA-256 fingerprint {fingerprint}: 32 hex encoded bytes expected");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .with_context(|| format!("Invalid SHA-256 fingerprint {fingerprint}"))
+        })
+        .collect()
+}
+
+/// Same format as `openssl x509 -fingerprint -sha256` and `rlog-helper cert inspect`
+fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_fingerprint, parse_fingerprint};
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let fingerprint = (0..32).collect::<Vec<u8>>();
+        let formatted = format_fingerprint(&fingerprint);
+        assert!(formatted.starts_with("00:01:02:"));
+        assert_eq!(fingerprint, parse_fingerprint(&formatted).unwrap());
+        assert_eq!(
+            fingerprint,
+            parse_fingerprint(&formatted.replace(':', "").to_lowercase()).unwrap()
+        );
+        assert!(parse_fingerprint("00:01").is_err());
+        assert!(parse_fingerprint(&formatted.replace("1F", "1G")).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Periodic log line reporting the shipper state, allows to detect shippers that are
+//! running but not shipping anything.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use rlog_common::utils::format_error;
+use rlog_grpc::{
+    prost::Message,
+    rlog_service_protocol::{LogLine, SyslogSeverity},
+};
+use rlog_inputs::generic_log::GenericLog;
+use serde_json::json;
+use tokio::{select, task::JoinHandle, time::interval};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    byte_budget::{self, Budgeted},
+    config::{HeartbeatConfig, CONFIG},
+    forward_loop::add_labels,
+    grpc_out::GrpcOutSender,
+    metrics::{FILES_QUEUE_COUNT, GELF_QUEUE_COUNT, SHIPPER_QUEUE_COUNT, SYSLOG_QUEUE_COUNT},
+    VERSION,
+};
+
+pub const HEARTBEAT_LOG_SYSTEM: &str = "rlog";
+
+/// Heartbeat log lines are queued directly in the grpc_out queue: input exclusion filters
+/// are not applied.
+pub fn launch_heartbeat(
+    config: HeartbeatConfig,
+    grpc_out: GrpcOutSender,
+    shutdown_token: CancellationToken,
+) -> JoinHandle<()> {
+    let started = Instant::now();
+    tokio::spawn(async move {
+        let mut interval = interval(config.interval);
+        loop {
+            select! {
+                _ = interval.tick() => {}
+                _ = shutdown_token.cancelled() => break,
+            }
+            let mut log_line = match heartbeat_log_line(&config.service_name, started.elapsed()) {
+                Ok(log_line) => log_line,
+                Err(e) => {
+                    tracing::error!(error = %format_error(e), "Unable to build heartbeat log line");
+                    continue;
+                }
+            };
+            add_labels(&mut log_line, &CONFIG.load().labels, None);
+            let Some(reservation) = byte_budget::reserve(log_line.encoded_len()) else {
+                tracing::error!("Buffered bytes budget exceeded: discarding heartbeat");
+                continue;
+            };
+            let log_line = Budgeted::new(log_line, reservation);
+            match grpc_out.send(log_line).await {
+                Ok(true) => {
+                    SHIPPER_QUEUE_COUNT.fetch_add(1, Ordering::Relaxed);
+                }
+                // low priority queue full
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!("Channel closed! {e}");
+                    break;
+                }
+            }
+        }
+        tracing::info!("heartbeat task stopped.");
+    })
+}
+
+fn heartbeat_log_line(service_name: &str, uptime: Duration) -> anyhow::Result<LogLine> {
+    LogLine::try_from(GenericLog {
+        host: hostname::get()?.to_string_lossy().to_string(),
+        timestamp: chrono::Utc::now(),
+        severity: SyslogSeverity::Info,
+        extra: json!({
+            "shipper_version": VERSION,
+            "uptime_secs": uptime.as_secs(),
+            "queue_count": {
+                "files_in": FILES_QUEUE_COUNT.load(Ordering::Relaxed),
+                "gelf_in": GELF_QUEUE_COUNT.load(Ordering::Relaxed),
+                "syslog_in": SYSLOG_QUEUE_COUNT.load(Ordering::Relaxed),
+                "grpc_out": SHIPPER_QUEUE_COUNT.load(Ordering::Relaxed),
+            },
+        }),
+        log_system: HEARTBEAT_LOG_SYSTEM.into(),
+        message: "heartbeat".into(),
+        service_name: service_name.into(),
+    })
+}
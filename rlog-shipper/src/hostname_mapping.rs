@@ -0,0 +1,75 @@
+use rlog_grpc::rlog_service_protocol::LogLine;
+
+use crate::config::HostnameMapping;
+
+/// Apply the first matching mapping to the log line host, the original hostname is sent
+/// in `raw_host` (`raw_hostname` field once indexed)
+pub fn map_hostname(log_line: &mut LogLine, mappings: &[HostnameMapping]) {
+    for mapping in mappings {
+        let Some(captures) = mapping.pattern.captures(&log_line.host) else {
+            continue;
+        };
+        let mut host = String::new();
+        captures.expand(&mapping.replacement, &mut host);
+        if host != log_line.host {
+            log_line.raw_host = Some(std::mem::replace(&mut log_line.host, host));
+        }
+        return;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rlog_grpc::rlog_service_protocol::LogLine;
+
+    use super::map_hostname;
+    use crate::config::{eqregex::EqRegex, HostnameMapping};
+
+    fn mapping(pattern: &str, replacement: &str) -> HostnameMapping {
+        HostnameMapping {
+            pattern: EqRegex::new(pattern).unwrap(),
+            replacement: replacement.into(),
+        }
+    }
+
+    fn mapped(host: &str, mappings: &[HostnameMapping]) -> (String, Option<String>) {
+        let mut log_line = LogLine {
+            host: host.into(),
+            ..Default::default()
+        };
+        map_hostname(&mut log_line, mappings);
+        (log_line.host, log_line.raw_host)
+    }
+
+    #[test]
+    fn test_map_hostname() {
+        let mappings = [
+            mapping(r"^(.+)-[0-9a-f]{6,10}-[0-9a-z]{5}$", "$1"),
+            mapping(r"^db-(?<index>\d+)\.", "database-${index}"),
+            mapping(r"^db-", "database"),
+        ];
+        assert_eq!(
+            (
+                "myapp-deployment".into(),
+                Some("myapp-deployment-7d6f4b9-xkqj2".into())
+            ),
+            mapped("myapp-deployment-7d6f4b9-xkqj2", &mappings)
+        );
+        // the whole hostname is replaced, the first matching mapping is applied
+        assert_eq!(
+            ("database-12".into(), Some("db-12.example.com".into())),
+            mapped("db-12.example.com", &mappings)
+        );
+        assert_eq!(
+            ("database".into(), Some("db-main".into())),
+            mapped("db-main", &mappings)
+        );
+        assert_eq!(("web-1".into(), None), mapped("web-1", &mappings));
+        assert_eq!(("web-1".into(), None), mapped("web-1", &[]));
+        // unchanged
+        assert_eq!(
+            ("web-1".into(), None),
+            mapped("web-1", &[mapping("^web-(.*)$", "web-$1")])
+        );
+    }
+}
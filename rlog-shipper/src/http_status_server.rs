@@ -0,0 +1,74 @@
+use anyhow::Context;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Json, Router};
+use rlog_common::bind_addr::BindAddr;
+use rlog_inputs::{metrics::GELF_PEERS, recent_inputs::RECENT_INPUTS};
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::VERSION;
+
+#[derive(Deserialize)]
+struct RecentQuery {
+    /// `syslog_in`, `gelf_in` or the path of a watched file
+    source: Option<String>,
+}
+
+/// Recorded raw inputs of the source, or the sources with recorded inputs
+async fn recent(Query(query): Query<RecentQuery>) -> Response {
+    let Some(source) = query.source else {
+        return Json(RECENT_INPUTS.sources()).into_response();
+    };
+    match RECENT_INPUTS.recent(&source) {
+        Some(inputs) => Json(inputs).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            "Recent inputs are not recorded, see the `recent_inputs` configuration section",
+        )
+            .into_response(),
+    }
+}
+
+/// Launch the HTTP status server, bound to a free port if the port of `bind_address` is 0:
+/// the bound port is written to `port_file` if set
+pub fn launch_http_status_server(
+    bind_address: BindAddr,
+    port_file: Option<&str>,
+    shutdown_token: CancellationToken,
+) -> anyhow::Result<JoinHandle<()>> {
+    // bind early to report errors to the caller
+    let listener = std::net::TcpListener::bind(bind_address.socket_addr())
+        .and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        })
+        .and_then(tokio::net::TcpListener::from_std)
+        .with_context(|| format!("Unable to bind HTTP status server to {bind_address}"))?;
+    let bind_address = listener.local_addr()?;
+    if let Some(port_file) = port_file {
+        std::fs::write(port_file, bind_address.port().to_string()).with_context(|| {
+            format!("Unable to write the HTTP status server port to {port_file}")
+        })?;
+    }
+
+    Ok(tokio::spawn(async move {
+        let app = Router::new()
+            .route("/version", get(|| async { VERSION }))
+            .route("/health", get(|| async { "OK" }))
+            .route("/debug/recent", get(recent))
+            .route(
+                "/gelf_peers",
+                get(|| async { Json(GELF_PEERS.top(usize::MAX)) }),
+            );
+        tracing::info!("Starting HTTP status server {bind_address}");
+        if let Err(e) = axum::serve(listener, app.into_make_service())
+            .with_graceful_shutdown(shutdown_token.cancelled_owned())
+            .await
+        {
+            tracing::error!("HTTP status server error: {e}");
+        }
+    }))
+}
@@ -1,116 +1,309 @@
-use config::CONFIG;
-use forward_loop::{forward_loop, ForwardMetrics};
+use std::{sync::atomic::Ordering, time::Duration};
+
+use anyhow::{bail, Context};
+use config::{Config, CONFIG};
+use forward_loop::{forward_loop, ForwardMetrics, Input};
 use futures::future::join_all;
-use gelf_server::launch_gelf_server;
 use grpc_out::launch_grpc_shipper;
+use grpc_proxy::ProxyConnector;
+use grpc_tls::PinnedTlsConnector;
+use heartbeat::launch_heartbeat;
+use http_status_server::launch_http_status_server;
 use log_file::watch_log;
 use metrics::{
-    FILES_ERROR_COUNT, FILES_PROCESSED_COUNT, FILES_QUEUE_COUNT, GELF_ERROR_COUNT,
-    GELF_PROCESSED_COUNT, GELF_QUEUE_COUNT, SHIPPER_QUEUE_COUNT, SYSLOG_ERROR_COUNT,
-    SYSLOG_PROCESSED_COUNT, SYSLOG_QUEUE_COUNT,
+    FILES_DROPPED_COUNT, FILES_ERROR_COUNT, FILES_PROCESSED_COUNT, FILES_QUEUE_COUNT,
+    GELF_DROPPED_COUNT, GELF_ERROR_COUNT, GELF_PROCESSED_COUNT, GELF_QUEUE_COUNT,
+    SHIPPER_QUEUE_COUNT, STDIN_DROPPED_COUNT, STDIN_ERROR_COUNT, STDIN_PROCESSED_COUNT,
+    STDIN_QUEUE_COUNT, SYSLOG_DROPPED_COUNT, SYSLOG_ERROR_COUNT, SYSLOG_PROCESSED_COUNT,
+    SYSLOG_QUEUE_COUNT,
+};
+use rlog_common::{
+    bind_addr::BindAddr,
+    log_signature::{hmac_key, SIGNING_KEY_LEN},
+};
+use rlog_grpc::{
+    rlog_service_protocol::LogLine,
+    tonic::transport::{Endpoint, Uri},
+};
+use rlog_inputs::{
+    config::CommonInputConfig,
+    gelf_server::launch_gelf_server,
+    recent_inputs::{DEBUG_SAMPLE_RATE, RECENT_INPUTS},
+    syslog_server::launch_syslog_udp_server,
 };
-use rlog_grpc::tonic::transport::Endpoint;
-use syslog_server::launch_syslog_udp_server;
-use tokio::{join, task::JoinHandle};
+use stdin_in::{read_stdin, StdinReader};
+use syslog_out::launch_syslog_out;
+use tokio::{join, sync::oneshot, task::JoinHandle, time::timeout};
 use tokio_util::sync::CancellationToken;
+use transform::ConfigTransform;
 
+mod byte_budget;
 pub mod config;
 mod forward_loop;
-mod gelf_server;
-mod generic_log;
 mod grpc_out;
+pub mod grpc_proxy;
+pub mod grpc_tls;
+mod heartbeat;
+mod hostname_mapping;
+mod http_status_server;
 mod log_file;
 mod metrics;
-mod syslog_server;
+pub mod stdin_in;
+mod syslog_out;
+mod transform;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 pub struct ServerConfig {
     pub grpc_collector_endpoint: Endpoint,
-    pub syslog_udp_bind_address: String,
-    pub gelf_tcp_bind_address: String,
+    /// if set, the collector is reached through this HTTP proxy
+    pub grpc_proxy: Option<ProxyConnector>,
+    /// if set, TLS is handled by this connector instead of the endpoint TLS config
+    pub grpc_pinned_tls: Option<PinnedTlsConnector>,
+    /// HTTP/2 `:authority` sent to the collector instead of the endpoint host (eg: virtual
+    /// hosting behind a load balancer), independent from the TLS server name
+    pub grpc_authority: Option<String>,
+    pub syslog_udp_bind_address: BindAddr,
+    pub gelf_tcp_bind_address: BindAddr,
+    /// if set, startup fails if the collector cannot be reached within this duration,
+    /// otherwise the shipper keeps trying to connect in background
+    pub require_collector_on_start: Option<Duration>,
+    /// key derived from the TLS private key, mandatory if `sign_log_entries` is enabled
+    pub log_signing_key: Option<[u8; SIGNING_KEY_LEN]>,
+    /// HTTP status server (`/health`, `/debug/recent`), disabled if not set, a free port is
+    /// picked if its port is 0
+    pub http_status_bind_address: Option<BindAddr>,
+    /// file the port of the HTTP status server is written to once bound (service discovery)
+    pub port_file: Option<String>,
+    /// if set, lines of this stream (`--stdin`) are parsed with the `stdin_in`
+    /// configuration, the input stops at the end of the stream
+    pub stdin: Option<StdinReader>,
 }
 pub struct ShipperServer {
-    syslog_in: JoinHandle<()>,
-    gelf_in: JoinHandle<()>,
+    syslog_in: Option<JoinHandle<()>>,
+    gelf_in: Option<JoinHandle<()>>,
     grpc_out: JoinHandle<()>,
     files_in: Vec<JoinHandle<()>>,
+    stdin_in: Option<JoinHandle<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+    syslog_out: Vec<JoinHandle<()>>,
+    http_status: Option<JoinHandle<()>>,
     shutdown_token: CancellationToken,
 }
 impl ShipperServer {
     pub async fn start_shipper_server(server_config: ServerConfig) -> anyhow::Result<Self> {
+        lazy_static::initialize(&metrics::PROCESS_START_TIME);
+        tracing::info!("Shipper incarnation id: {}", *metrics::INCARNATION_ID);
+        let signing_key = match (
+            CONFIG.load().sign_log_entries,
+            &server_config.log_signing_key,
+        ) {
+            (false, _) => None,
+            (true, Some(signing_key)) => Some(hmac_key(signing_key)),
+            (true, None) => bail!("sign_log_entries requires the TLS private key of the shipper"),
+        };
+        if server_config.stdin.is_some() && CONFIG.load().stdin_in.is_none() {
+            bail!("--stdin requires the stdin_in configuration section");
+        }
+        RECENT_INPUTS.configure(CONFIG.load().recent_inputs.clone());
+        if let Some(debug_sample_rate) = CONFIG.load().debug_sample_rate {
+            DEBUG_SAMPLE_RATE.store(debug_sample_rate, Ordering::Relaxed);
+        }
         let shutdown_token = CancellationToken::new();
-        let gelf_receiver = launch_gelf_server(
-            &server_config.gelf_tcp_bind_address,
-            shutdown_token.child_token(),
-        )
-        .await?;
+        // inputs without configuration section are enabled with the defaults
+        let input_enabled =
+            |common: Option<&CommonInputConfig>| common.is_none_or(|common| common.enabled);
+        let gelf_receiver = if input_enabled(CONFIG.load().gelf_in.as_ref().map(|c| &c.common)) {
+            Some(
+                launch_gelf_server(
+                    server_config.gelf_tcp_bind_address,
+                    CONFIG.map(|config: &Config| &config.gelf_in),
+                    byte_budget::reserve,
+                    shutdown_token.child_token(),
+                )
+                .await?,
+            )
+        } else {
+            tracing::info!("gelf_in input disabled");
+            None
+        };
 
-        let syslog_receiver = launch_syslog_udp_server(
-            &server_config.syslog_udp_bind_address,
-            shutdown_token.child_token(),
-        )
-        .await?;
+        let mut syslog_receiver =
+            if input_enabled(CONFIG.load().syslog_in.as_ref().map(|c| &c.common)) {
+                Some(
+                    launch_syslog_udp_server(
+                        server_config.syslog_udp_bind_address,
+                        CONFIG.map(|config: &Config| &config.syslog_in),
+                        byte_budget::reserve,
+                        shutdown_token.child_token(),
+                    )
+                    .await?,
+                )
+            } else {
+                tracing::info!("syslog_in input disabled");
+                None
+            };
+        let mut syslog_out = Vec::new();
+        match (CONFIG.load().syslog_out.clone(), syslog_receiver.take()) {
+            (Some(config), Some(receiver)) => {
+                let (receiver, tee, relay) =
+                    launch_syslog_out(config, receiver, shutdown_token.child_token());
+                syslog_receiver = Some(receiver);
+                syslog_out.extend([tee, relay]);
+            }
+            (Some(_), None) => tracing::warn!("syslog_out ignored: syslog_in input disabled"),
+            (None, receiver) => syslog_receiver = receiver,
+        }
 
+        let mut endpoint = server_config.grpc_collector_endpoint;
+        if let Some(authority) = &server_config.grpc_authority {
+            // the pinning connector handles TLS of an http endpoint
+            let scheme = match &server_config.grpc_pinned_tls {
+                Some(_) => "https",
+                None => endpoint.uri().scheme_str().unwrap_or("https"),
+            };
+            let origin = Uri::builder()
+                .scheme(scheme)
+                .authority(authority.as_str())
+                .path_and_query("/")
+                .build()
+                .with_context(|| format!("Invalid gRPC authority {authority}"))?;
+            endpoint = endpoint.origin(origin);
+        }
+        let (connected_sender, connected) = oneshot::channel();
         let (grpc_log_line_sender, grpc_out) = launch_grpc_shipper(
-            server_config.grpc_collector_endpoint,
+            endpoint,
+            server_config.grpc_proxy,
+            server_config.grpc_pinned_tls,
+            signing_key,
+            connected_sender,
             shutdown_token.child_token(),
         );
-        let gelf_in = tokio::spawn(forward_loop(
-            gelf_receiver,
-            grpc_log_line_sender.clone(),
-            "gelf_in",
-            ForwardMetrics {
-                in_queue_size: &GELF_QUEUE_COUNT,
-                in_processed_count: &GELF_PROCESSED_COUNT,
-                in_error_count: &GELF_ERROR_COUNT,
-                out_queue_size: &SHIPPER_QUEUE_COUNT,
-            },
-        ));
+        let gelf_in = gelf_receiver.map(|gelf_receiver| {
+            tokio::spawn(forward_loop(
+                gelf_receiver,
+                |log| log.into_log_line(CONFIG.load().gelf_in.as_ref()),
+                Some(Box::new(ConfigTransform::new(Input::Gelf))),
+                grpc_log_line_sender.clone(),
+                Input::Gelf,
+                ForwardMetrics {
+                    in_queue_size: &GELF_QUEUE_COUNT,
+                    in_processed_count: &GELF_PROCESSED_COUNT,
+                    in_error_count: &GELF_ERROR_COUNT,
+                    in_dropped_count: &GELF_DROPPED_COUNT,
+                    out_queue_size: &SHIPPER_QUEUE_COUNT,
+                },
+            ))
+        });
 
-        let syslog_in = tokio::spawn(forward_loop(
-            syslog_receiver,
-            grpc_log_line_sender.clone(),
-            "syslog_in",
-            ForwardMetrics {
-                in_queue_size: &SYSLOG_QUEUE_COUNT,
-                in_processed_count: &SYSLOG_PROCESSED_COUNT,
-                in_error_count: &SYSLOG_ERROR_COUNT,
-                out_queue_size: &SHIPPER_QUEUE_COUNT,
-            },
-        ));
+        let syslog_in = syslog_receiver.map(|syslog_receiver| {
+            tokio::spawn(forward_loop(
+                syslog_receiver,
+                |log| log.into_log_line(CONFIG.load().syslog_in.as_ref()),
+                Some(Box::new(ConfigTransform::new(Input::Syslog))),
+                grpc_log_line_sender.clone(),
+                Input::Syslog,
+                ForwardMetrics {
+                    in_queue_size: &SYSLOG_QUEUE_COUNT,
+                    in_processed_count: &SYSLOG_PROCESSED_COUNT,
+                    in_error_count: &SYSLOG_ERROR_COUNT,
+                    in_dropped_count: &SYSLOG_DROPPED_COUNT,
+                    out_queue_size: &SHIPPER_QUEUE_COUNT,
+                },
+            ))
+        });
         let mut files_in = Vec::new();
-        for (path, _) in &CONFIG.load().files_in {
+        for (path, file_config) in &CONFIG.load().files_in {
+            if !file_config.enabled {
+                tracing::info!("files_in input {path} disabled");
+                continue;
+            }
             files_in.push(tokio::spawn(forward_loop(
                 watch_log(path, shutdown_token.child_token()).await?,
+                LogLine::try_from,
+                Some(Box::new(ConfigTransform::new(Input::File(path.clone())))),
                 grpc_log_line_sender.clone(),
-                "files_in",
+                Input::File(path.clone()),
                 ForwardMetrics {
                     in_queue_size: &FILES_QUEUE_COUNT,
                     in_processed_count: &FILES_PROCESSED_COUNT,
                     in_error_count: &FILES_ERROR_COUNT,
+                    in_dropped_count: &FILES_DROPPED_COUNT,
                     out_queue_size: &SHIPPER_QUEUE_COUNT,
                 },
             )));
         }
+        let stdin_in = server_config.stdin.map(|reader| {
+            tokio::spawn(forward_loop(
+                read_stdin(reader, shutdown_token.child_token()),
+                LogLine::try_from,
+                Some(Box::new(ConfigTransform::new(Input::Stdin))),
+                grpc_log_line_sender.clone(),
+                Input::Stdin,
+                ForwardMetrics {
+                    in_queue_size: &STDIN_QUEUE_COUNT,
+                    in_processed_count: &STDIN_PROCESSED_COUNT,
+                    in_error_count: &STDIN_ERROR_COUNT,
+                    in_dropped_count: &STDIN_DROPPED_COUNT,
+                    out_queue_size: &SHIPPER_QUEUE_COUNT,
+                },
+            ))
+        });
+
+        let heartbeat = CONFIG.load().heartbeat.clone().map(|config| {
+            launch_heartbeat(
+                config,
+                grpc_log_line_sender.clone(),
+                shutdown_token.child_token(),
+            )
+        });
+
+        let http_status = server_config
+            .http_status_bind_address
+            .map(|bind_address| {
+                launch_http_status_server(
+                    bind_address,
+                    server_config.port_file.as_deref(),
+                    shutdown_token.child_token(),
+                )
+            })
+            .transpose()?;
 
-        Ok(Self {
+        let server = Self {
             syslog_in,
             gelf_in,
             grpc_out,
             files_in,
+            stdin_in,
+            heartbeat,
+            syslog_out,
+            http_status,
             shutdown_token,
-        })
+        };
+
+        if let Some(startup_timeout) = server_config.require_collector_on_start {
+            tracing::info!("Waiting for the collector to be reachable ({startup_timeout:?})");
+            if !matches!(timeout(startup_timeout, connected).await, Ok(Ok(()))) {
+                server.shutdown().await;
+                bail!("Collector unreachable after {startup_timeout:?}");
+            }
+        }
+
+        Ok(server)
     }
 
     /// Gracefully shutdown the server, waiting for queues to empty
     pub async fn shutdown(self) {
         self.shutdown_token.cancel();
         let _ = join!(
-            self.syslog_in,
-            self.gelf_in,
+            join_all(self.syslog_in),
+            join_all(self.gelf_in),
             self.grpc_out,
-            join_all(self.files_in)
+            join_all(self.files_in),
+            join_all(self.stdin_in),
+            join_all(self.heartbeat),
+            join_all(self.syslog_out),
+            join_all(self.http_status)
         );
     }
 }
@@ -1,4 +1,10 @@
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::io::{ErrorKind, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context};
 use async_channel::Receiver;
@@ -10,19 +16,26 @@ use linemux::MuxedLines;
 use num_traits::FromPrimitive;
 use rlog_common::utils::format_error;
 use rlog_grpc::rlog_service_protocol::SyslogSeverity;
+use rlog_inputs::recent_inputs::RECENT_INPUTS;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::select;
 use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
 
-use crate::config::{FieldType, FileParseConfig};
+use crate::byte_budget::{self, Budgeted};
+use crate::config::{
+    FieldType, FileParseConfig, LogRotationStrategy, Severity, StartupRetryConfig,
+};
 use crate::config::{FileMappingConfig, CONFIG};
-use crate::generic_log::GenericLog;
+use crate::metrics::{FILES_LAG_BYTES, FILES_QUEUE_CAPACITY, FILES_QUEUE_COUNT};
+use rlog_inputs::generic_log::GenericLog;
+use rlog_inputs::normalization::normalize_message;
 
 // Note: let's use the Gelf log repr which seems flexible enough ;)
 pub async fn watch_log(
     path: &str,
     shutdown_token: CancellationToken,
-) -> anyhow::Result<Receiver<GenericLog>> {
+) -> anyhow::Result<Receiver<Budgeted<GenericLog>>> {
     // for now this is not configurable, we have only 1 buffer size
     let (sender, receiver) = async_channel::bounded(1);
 
@@ -32,63 +45,407 @@ pub async fn watch_log(
         .and_then(|f| Some(f.to_string_lossy().to_string()))
         .unwrap_or_else(|| path.clone());
     let file = path.clone(); // used in tracing span
-    let mut lines = MuxedLines::new()?;
-    lines.add_file(&path).await?;
-    tracing::info!("Watching new lines of {path}");
+    let (rotation_strategy, startup_retry) = CONFIG
+        .load()
+        .files_in
+        .get(&path)
+        .map(|c| (c.log_rotation_strategy, c.startup_retry.clone()))
+        .unwrap_or_default();
+    let lines = match open_file_lines(&path, rotation_strategy, startup_retry.as_ref()).await {
+        Ok(lines) => Some(lines),
+        Err(e) if startup_retry.as_ref().is_some_and(|r| r.wait_in_background) => {
+            tracing::warn!("Unable to watch {path}, retrying in the background: {e}");
+            None
+        }
+        Err(e) => return Err(e).with_context(|| format!("Unable to watch {path}")),
+    };
 
+    // released when the watch task stops
+    FILES_QUEUE_CAPACITY.fetch_add(1, Ordering::Relaxed);
     tokio::spawn(
         async move {
-            select! {
-                _ = shutdown_token.cancelled() => {
-                    // shutting down
-                    return;
+            let mut lines = match lines {
+                Some(lines) => lines,
+                None => {
+                    // startup_retry is set if the file was not opened
+                    let interval = startup_retry.unwrap_or_default().max_backoff;
+                    match wait_file_lines(&path, rotation_strategy, interval, &shutdown_token).await {
+                        Some(lines) => lines,
+                        // shutting down
+                        None => return,
+                    }
                 }
-                line = lines.next_line() => {
-                    match line {
-                        Ok(line)=>{
-                            match line {
-                                Some(line)=> {
-                                    tracing::debug!("new line {}", line.line());
-                                    // find right config ; if config cannot be found, stop watching the file
-                                    match CONFIG.load().files_in.get(&path){
-                                        Some(parse_config) => {
-                                            match parse_config.to_log(line.line(), &filename) {
-                                                Ok(log) => match sender.send(log).await {
-                                                    Ok(_) => {},
-                                                    Err(_closed) => tracing::error!("out channel closed"),
-                                                },
-                                                Err(e) => tracing::error!("Unable to parse file line {} - {}", line.line(), format_error(e)),
-                                            }
-                                        },
-                                        None => {
-                                            tracing::info!("Config changed: {path} is not monitored anymore!");
-                                            return;
-                                        },
+            };
+            tracing::info!("Watching new lines of {path}");
+
+            let handed_offset = Arc::new(AtomicU64::new(lines.start_offset()));
+            let lag_token = shutdown_token.child_token();
+            tokio::spawn(monitor_lag(
+                path.clone(),
+                handed_offset.clone(),
+                lines.summed_offset(),
+                lag_token.clone(),
+            ));
+            // stop the lag monitoring with the watch task
+            let _lag_token = lag_token.drop_guard();
+            // sequence number of the next line read from the file
+            let mut next_sequence = 0;
+            loop {
+                let line = select! {
+                    _ = shutdown_token.cancelled() => {
+                        // shutting down
+                        return;
+                    }
+                    line = lines.next_line() => line,
+                };
+                match line {
+                    Ok(Some((line, end_offset))) => {
+                        RECENT_INPUTS.record(&path, &line);
+                        // find right config ; if config cannot be found, stop watching the file
+                        match CONFIG.load().files_in.get(&path) {
+                            Some(parse_config) => {
+                                match parse_config.to_log(&line, &filename) {
+                                    Ok(log) => {
+                                        let sequence = next_sequence;
+                                        next_sequence += 1;
+                                        match byte_budget::reserve(line.len()) {
+                                            Some(reservation) => match sender
+                                                .send(
+                                                    Budgeted::new(log, reservation)
+                                                        .with_sequence(sequence),
+                                                )
+                                                .await
+                                            {
+                                                Ok(_) => {
+                                                    FILES_QUEUE_COUNT
+                                                        .fetch_add(1, Ordering::Relaxed);
+                                                }
+                                                Err(_closed) => {
+                                                    tracing::error!("out channel closed")
+                                                }
+                                            },
+                                            None => tracing::error!(
+                                                "Buffered bytes budget exceeded: discarding line {line}"
+                                            ),
+                                        }
                                     }
+                                    Err(e) => tracing::error!(
+                                        error = %format_error(e),
+                                        "Unable to parse file line {line}"
+                                    ),
                                 }
-                                None=> {
-                                    tracing::error!("This is not possible by contruction");
-                                    return;
-                                }
+                                // sent or discarded, the line is not lagging anymore
+                                handed_offset.store(end_offset, Ordering::Relaxed);
+                            }
+                            None => {
+                                tracing::info!("Config changed: {path} is not monitored anymore!");
+                                return;
                             }
-
-                        }
-                        Err(e)=>{
-                            tracing::error!("Unable to read log line! {e}");
-                            return;
                         }
                     }
+                    Ok(None) => {
+                        tracing::error!("This is not possible by contruction");
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::error!("Unable to read log line! {e}");
+                        return;
+                    }
                 }
-
             }
         }
-        .then(|_| async  { tracing::info!("Watch task stopped!") })
+        .then(|_| async {
+            FILES_QUEUE_CAPACITY.fetch_sub(1, Ordering::Relaxed);
+            tracing::info!("Watch task stopped!")
+        })
         .instrument(tracing::info_span!("files_in", file)),
     );
 
     Ok(receiver)
 }
 
+/// Open the lines of a watched file, retried with an exponential backoff while the file is
+/// unavailable if `startup_retry` is set
+async fn open_file_lines(
+    path: &str,
+    rotation_strategy: LogRotationStrategy,
+    startup_retry: Option<&StartupRetryConfig>,
+) -> std::io::Result<FileLines> {
+    let Some(startup_retry) = startup_retry else {
+        return FileLines::new(path, rotation_strategy).await;
+    };
+    let mut backoff = startup_retry.initial_backoff;
+    let mut retries = 0;
+    loop {
+        match FileLines::new(path, rotation_strategy).await {
+            Ok(lines) => return Ok(lines),
+            Err(e) if retries < startup_retry.max_retries => {
+                retries += 1;
+                tracing::warn!(
+                    "Unable to watch {path}, retry {retries}/{} in {}: {e}",
+                    startup_retry.max_retries,
+                    humantime::format_duration(backoff)
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(startup_retry.max_backoff);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Open the lines of a watched file every `interval` until it succeeds, `None` on shutdown
+async fn wait_file_lines(
+    path: &str,
+    rotation_strategy: LogRotationStrategy,
+    interval: Duration,
+    shutdown_token: &CancellationToken,
+) -> Option<FileLines> {
+    loop {
+        select! {
+            _ = shutdown_token.cancelled() => return None,
+            _ = tokio::time::sleep(interval) => {}
+        }
+        match FileLines::new(path, rotation_strategy).await {
+            Ok(lines) => return Some(lines),
+            Err(e) => tracing::debug!("{path} still unavailable: {e}"),
+        }
+    }
+}
+
+/// Interval of the watched files lag computation
+const LAG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Expose the number of bytes between the end of the file and `handed_offset`, the end
+/// offset of the last line handed to the pipeline, as `files_in:<path>:lag_bytes`.
+///
+/// `summed_offset` (linemux, see [`FileLines::summed_offset`]) is reset when the file
+/// is re-created.
+async fn monitor_lag(
+    path: String,
+    handed_offset: Arc<AtomicU64>,
+    summed_offset: Option<Arc<AtomicU64>>,
+    shutdown_token: CancellationToken,
+) {
+    let mut inode = None;
+    let mut interval = tokio::time::interval(LAG_INTERVAL);
+    loop {
+        select! {
+            _ = shutdown_token.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        // the file may be missing while being rotated
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        if let Some(summed_offset) = &summed_offset {
+            if inode.is_some_and(|inode| inode != metadata.ino()) {
+                summed_offset.store(0, Ordering::Relaxed);
+                handed_offset.store(0, Ordering::Relaxed);
+            }
+            inode = Some(metadata.ino());
+        }
+        let lag = metadata
+            .len()
+            .saturating_sub(handed_offset.load(Ordering::Relaxed));
+        FILES_LAG_BYTES.lock().unwrap().insert(path.clone(), lag);
+    }
+    FILES_LAG_BYTES.lock().unwrap().remove(&path);
+}
+
+/// New lines of a watched file with their end offset, following the file rotation strategy
+enum FileLines {
+    /// linemux follows renamed & re-created files, it does not expose offsets: they are
+    /// computed by summing line lengths
+    Muxed {
+        lines: Box<MuxedLines>,
+        /// watched path, as returned by linemux
+        source: PathBuf,
+        start_offset: u64,
+        offset: Arc<AtomicU64>,
+    },
+    Truncate(TruncatedFileLines),
+}
+
+impl FileLines {
+    async fn new(path: &str, rotation_strategy: LogRotationStrategy) -> std::io::Result<Self> {
+        Ok(match rotation_strategy {
+            LogRotationStrategy::RenameCreate => {
+                // linemux accepts a missing file: like with `Truncate`, it must exist so that
+                // `startup_retry` applies, and only the lines appended once opened are read
+                let start_offset = tokio::fs::metadata(path).await?.len();
+                let mut lines = MuxedLines::new()?;
+                let source = lines.add_file(path).await?;
+                // linemux watches the renamed file path again: if the new file is already
+                // there, its creation is missed and the renamed file keeps being read. A
+                // missing file of the same directory keeps the directory watched so that
+                // the new file is read from its start once created.
+                lines.add_file(rotation_sibling(&source)).await?;
+                Self::Muxed {
+                    lines: Box::new(lines),
+                    source,
+                    start_offset,
+                    offset: Arc::new(AtomicU64::new(start_offset)),
+                }
+            }
+            LogRotationStrategy::Truncate => Self::Truncate(TruncatedFileLines::new(path).await?),
+        })
+    }
+
+    /// Offset of the first line read
+    fn start_offset(&self) -> u64 {
+        match self {
+            FileLines::Muxed { start_offset, .. } => *start_offset,
+            FileLines::Truncate(lines) => lines.offset,
+        }
+    }
+
+    /// Offset computed by summing line lengths (linemux), to be reset when the file is
+    /// re-created
+    fn summed_offset(&self) -> Option<Arc<AtomicU64>> {
+        match self {
+            FileLines::Muxed { offset, .. } => Some(offset.clone()),
+            FileLines::Truncate(_) => None,
+        }
+    }
+
+    async fn next_line(&mut self) -> std::io::Result<Option<(String, u64)>> {
+        match self {
+            FileLines::Muxed {
+                lines,
+                source,
+                offset,
+                ..
+            } => loop {
+                let Some(line) = lines.next_line().await? else {
+                    return Ok(None);
+                };
+                if line.source() != source {
+                    // the rotation sibling was created
+                    continue;
+                }
+                let line = line.line().to_string();
+                // the line terminator is not part of the line
+                let end_offset = offset.fetch_add(line.len() as u64 + 1, Ordering::Relaxed)
+                    + line.len() as u64
+                    + 1;
+                return Ok(Some((line, end_offset)));
+            },
+            FileLines::Truncate(lines) => lines.next_line().await.map(Some),
+        }
+    }
+}
+
+/// Missing file watched next to a file renamed and re-created on rotation, see
+/// [`FileLines::new`]
+fn rotation_sibling(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".rlog-rotation");
+    path.with_file_name(file_name)
+}
+
+/// Poll a file which is truncated in place on rotation: when the file size
+/// goes below the current offset, reading restarts from the start of the file.
+struct TruncatedFileLines {
+    path: PathBuf,
+    offset: u64,
+    /// bytes of an incomplete line
+    partial: Vec<u8>,
+    /// the incomplete line exceeds `MAX_LINE_BYTES`: its bytes are discarded up to its end
+    discarding: bool,
+    /// read lines with their end offset
+    lines: VecDeque<(String, u64)>,
+}
+
+impl TruncatedFileLines {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+    /// An incomplete line longer than this is discarded
+    const MAX_LINE_BYTES: u64 = 1024 * 1024;
+
+    async fn new(path: &str) -> std::io::Result<Self> {
+        // like linemux, only new lines are read
+        let offset = tokio::fs::metadata(path).await?.len();
+        Ok(Self {
+            path: path.into(),
+            offset,
+            partial: Vec::new(),
+            discarding: false,
+            lines: VecDeque::new(),
+        })
+    }
+
+    async fn next_line(&mut self) -> std::io::Result<(String, u64)> {
+        loop {
+            if let Some(line) = self.lines.pop_front() {
+                return Ok(line);
+            }
+            self.read_new_lines().await?;
+            if self.lines.is_empty() {
+                tokio::time::sleep(Self::POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    async fn read_new_lines(&mut self) -> std::io::Result<()> {
+        let mut file = match tokio::fs::File::open(&self.path).await {
+            Ok(file) => file,
+            // the file may be missing while being rotated
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let size = file.metadata().await?.len();
+        if size < self.offset {
+            tracing::info!("File truncated, reading it from the start");
+            self.offset = 0;
+            self.partial.clear();
+            self.discarding = false;
+        }
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        // read by chunks: a line which never ends is discarded before being fully read
+        while self.offset < size && self.lines.is_empty() {
+            let read = (&mut file)
+                .take(Self::MAX_LINE_BYTES.min(size - self.offset))
+                .read_to_end(&mut self.partial)
+                .await?;
+            if read == 0 {
+                break;
+            }
+            self.offset += read as u64;
+            self.split_lines();
+        }
+        Ok(())
+    }
+
+    fn split_lines(&mut self) {
+        // offset of the start of the incomplete line
+        let mut line_offset = self.offset - self.partial.len() as u64;
+        while let Some(i) = self.partial.iter().position(|b| *b == b'\n') {
+            let line = self.partial.drain(..=i).collect::<Vec<_>>();
+            line_offset += line.len() as u64;
+            if std::mem::take(&mut self.discarding) {
+                // end of a discarded line
+                continue;
+            }
+            self.lines.push_back((
+                String::from_utf8_lossy(&line[..i])
+                    .trim_end_matches('\r')
+                    .to_string(),
+                line_offset,
+            ));
+        }
+        if self.partial.len() as u64 > Self::MAX_LINE_BYTES {
+            if !self.discarding {
+                tracing::error!(
+                    "Line exceeding {} bytes: discarding it up to its end",
+                    Self::MAX_LINE_BYTES
+                );
+            }
+            self.discarding = true;
+            self.partial.clear();
+        }
+    }
+}
+
 lazy_static! {
     static ref HOSTNAME: String = hostname::get()
         .expect("Unable to get system hostname")
@@ -142,7 +499,7 @@ impl FileParseConfig {
                         continue;
                     }
                     if field_name == "severity" {
-                        severity = SyslogSeverity::from_str_name(field_value.trim());
+                        severity = parse_severity(field_value, &self.severity_mapping);
                         continue;
                     }
                     let field_value = match &mapping[i].field_type {
@@ -160,7 +517,7 @@ impl FileParseConfig {
                             })?)
                         }
                         FieldType::SyslogLevelText => serde_json::Value::Number(
-                            (SyslogSeverity::from_str_name(field_value.trim())
+                            (parse_severity(field_value, &self.severity_mapping)
                                 .unwrap_or(SyslogSeverity::Info)
                                 as u32)
                                 .into(),
@@ -174,8 +531,11 @@ impl FileParseConfig {
                     host: host.unwrap_or(HOSTNAME.to_string()),
                     timestamp: timestamp.unwrap_or_else(|| Utc::now()),
                     severity: severity.unwrap_or(SyslogSeverity::Info),
-                    log_system: "file_in".into(),
-                    message: message.ok_or_else(|| anyhow!("No message field defined!"))?,
+                    log_system: self.log_system.as_deref().unwrap_or("file_in").into(),
+                    message: normalize_message(
+                        self.normalize_message.as_ref(),
+                        message.ok_or_else(|| anyhow!("No message field defined!"))?,
+                    ),
                     extra: map.into(),
                     service_name: service_name.unwrap_or_else(|| file.to_string()),
                 })
@@ -184,6 +544,26 @@ impl FileParseConfig {
     }
 }
 
+/// Parse a level text: configured mapping first, then built-in aliases (case insensitive)
+fn parse_severity(level: &str, mapping: &HashMap<String, Severity>) -> Option<SyslogSeverity> {
+    let level = level.trim();
+    if let Some((_, severity)) = mapping.iter().find(|(k, _)| k.eq_ignore_ascii_case(level)) {
+        return Some((*severity).into());
+    }
+    use SyslogSeverity::*;
+    match level.to_ascii_lowercase().as_str() {
+        "emerg" | "emergency" | "panic" | "fatal" => Some(Emergency),
+        "alert" => Some(Alert),
+        "crit" | "critical" => Some(Critical),
+        "err" | "error" | "severe" => Some(Error),
+        "warn" | "warning" => Some(Warning),
+        "notice" => Some(Notice),
+        "info" | "informational" | "config" => Some(Info),
+        "debug" | "trace" | "fine" | "finer" | "finest" | "verbose" => Some(Debug),
+        _ => None,
+    }
+}
+
 fn parse_timestamp(ts: &str) -> anyhow::Result<DateTime<Utc>> {
     iso8601::datetime(ts)
         .map(|dt| {
@@ -225,3 +605,292 @@ fn parse_timestamp(ts: &str) -> anyhow::Result<DateTime<Utc>> {
         .or_else(|_| DateTime::parse_from_rfc2822(ts).context("Unable to parse date"))
         .map(|dt| dt.into())
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::OpenOptions,
+        io::Write,
+        path::Path,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use tempfile::tempdir;
+    use tokio::time::timeout;
+    use tokio_util::sync::CancellationToken;
+
+    use std::collections::HashMap;
+
+    use super::{
+        monitor_lag, open_file_lines, parse_severity, wait_file_lines, FileLines,
+        TruncatedFileLines, LAG_INTERVAL,
+    };
+    use crate::config::{LogRotationStrategy, Severity, StartupRetryConfig};
+    use crate::metrics::FILES_LAG_BYTES;
+
+    fn append(path: &Path, content: &str) {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+    }
+
+    async fn next_line(lines: &mut FileLines) -> String {
+        timeout(Duration::from_secs(5), lines.next_line())
+            .await
+            .expect("Timed out waiting for a line")
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_severity_aliases() {
+        use rlog_grpc::rlog_service_protocol::SyslogSeverity::*;
+
+        let no_mapping = HashMap::new();
+        for (level, severity) in [
+            // java (log4j, logback, java.util.logging)
+            ("FATAL", Emergency),
+            ("ERROR", Error),
+            ("SEVERE", Error),
+            ("WARN", Warning),
+            ("INFO", Info),
+            ("CONFIG", Info),
+            ("DEBUG", Debug),
+            ("TRACE", Debug),
+            ("FINEST", Debug),
+            // python
+            ("CRITICAL", Critical),
+            ("WARNING", Warning),
+            ("Error", Error),
+            // go
+            ("panic", Emergency),
+            ("fatal", Emergency),
+            ("warn", Warning),
+            ("trace", Debug),
+            // syslog
+            ("err", Error),
+            ("crit", Critical),
+            ("notice", Notice),
+            ("emerg", Emergency),
+            (" info ", Info),
+        ] {
+            assert_eq!(
+                Some(severity),
+                parse_severity(level, &no_mapping),
+                "level {level}"
+            );
+        }
+        assert_eq!(None, parse_severity("whatever", &no_mapping));
+
+        let mut mapping = HashMap::new();
+        mapping.insert("fatal".to_string(), Severity::Critical);
+        mapping.insert("W".to_string(), Severity::Warning);
+        assert_eq!(Some(Critical), parse_severity("FATAL", &mapping));
+        assert_eq!(Some(Warning), parse_severity("w", &mapping));
+        assert_eq!(Some(Error), parse_severity("error", &mapping));
+    }
+
+    #[tokio::test]
+    async fn test_truncate_rotation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "existing line, not read\n");
+
+        let mut lines = FileLines::new(path.to_str().unwrap(), LogRotationStrategy::Truncate)
+            .await
+            .unwrap();
+
+        append(&path, "line 1\nline 2\n");
+        assert_eq!("line 1", next_line(&mut lines).await);
+        assert_eq!("line 2", next_line(&mut lines).await);
+
+        // rotate: truncate the file then write a new line
+        std::fs::write(&path, "rotated\n").unwrap();
+        assert_eq!("rotated", next_line(&mut lines).await);
+
+        append(&path, "line 3\n");
+        assert_eq!("line 3", next_line(&mut lines).await);
+    }
+
+    #[tokio::test]
+    async fn test_rename_create_rotation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "existing line, not read\n");
+
+        let mut lines = FileLines::new(path.to_str().unwrap(), LogRotationStrategy::RenameCreate)
+            .await
+            .unwrap();
+
+        append(&path, "line 1\n");
+        assert_eq!("line 1", next_line(&mut lines).await);
+
+        // rotate: rename the file and create a new one
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        append(&path, "new file line\n");
+        assert_eq!("new file line", next_line(&mut lines).await);
+
+        // the new file is larger than the renamed one
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        append(&path, &format!("{}\n", "a".repeat(100)));
+        assert_eq!("a".repeat(100), next_line(&mut lines).await);
+        append(&path, "line 2\n");
+        assert_eq!("line 2", next_line(&mut lines).await);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_max_line_bytes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "");
+        let mut lines = TruncatedFileLines::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        // never terminated for a while
+        let max_line_bytes = TruncatedFileLines::MAX_LINE_BYTES as usize;
+        for _ in 0..3 {
+            append(&path, &"a".repeat(max_line_bytes));
+            lines.read_new_lines().await.unwrap();
+            assert!(lines.partial.len() <= max_line_bytes);
+        }
+        append(&path, "end of the long line\nline 1\n");
+        let (line, end_offset) = lines.next_line().await.unwrap();
+        assert_eq!("line 1", line);
+        assert_eq!(3 * max_line_bytes as u64 + 28, end_offset);
+    }
+
+    #[tokio::test]
+    async fn test_startup_retry() {
+        for rotation_strategy in [
+            LogRotationStrategy::RenameCreate,
+            LogRotationStrategy::Truncate,
+        ] {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("app.log");
+            let path_str = path.to_str().unwrap();
+
+            // no retry: fails at once
+            assert!(open_file_lines(path_str, rotation_strategy, None)
+                .await
+                .is_err());
+
+            let startup_retry = StartupRetryConfig {
+                max_retries: 2,
+                initial_backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_millis(100),
+                wait_in_background: false,
+            };
+            // retries exhausted
+            assert!(
+                open_file_lines(path_str, rotation_strategy, Some(&startup_retry))
+                    .await
+                    .is_err()
+            );
+
+            // created while retrying
+            let created_path = path.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+                append(&created_path, "existing line, not read\n");
+            });
+            let mut lines = open_file_lines(path_str, rotation_strategy, Some(&startup_retry))
+                .await
+                .unwrap();
+            append(&path, "line 1\n");
+            assert_eq!("line 1", next_line(&mut lines).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_file_lines() {
+        for rotation_strategy in [
+            LogRotationStrategy::RenameCreate,
+            LogRotationStrategy::Truncate,
+        ] {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("app.log");
+            let path_str = path.to_str().unwrap();
+            let interval = Duration::from_millis(50);
+
+            let shutdown_token = CancellationToken::new();
+            shutdown_token.cancel();
+            assert!(
+                wait_file_lines(path_str, rotation_strategy, interval, &shutdown_token)
+                    .await
+                    .is_none()
+            );
+
+            let created_path = path.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                append(&created_path, "existing line, not read\n");
+            });
+            let shutdown_token = CancellationToken::new();
+            let mut lines = timeout(
+                Duration::from_secs(5),
+                wait_file_lines(path_str, rotation_strategy, interval, &shutdown_token),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            append(&path, "line 1\n");
+            assert_eq!("line 1", next_line(&mut lines).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lag() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let path_str = path.to_str().unwrap().to_string();
+        append(&path, "existing line, not read\n");
+
+        let mut lines = FileLines::new(&path_str, LogRotationStrategy::Truncate)
+            .await
+            .unwrap();
+        let handed_offset = Arc::new(AtomicU64::new(lines.start_offset()));
+        let shutdown_token = CancellationToken::new();
+        tokio::spawn(monitor_lag(
+            path_str.clone(),
+            handed_offset.clone(),
+            lines.summed_offset(),
+            shutdown_token.clone(),
+        ));
+        let lag = || FILES_LAG_BYTES.lock().unwrap().get(&path_str).copied();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(Some(0), lag());
+
+        // burst of 100 lines of 9 bytes, 10 lines are consumed
+        for i in 0..100 {
+            append(&path, &format!("line {i:03}\n"));
+        }
+        for _ in 0..10 {
+            let (_, end_offset) = lines.next_line().await.unwrap().unwrap();
+            handed_offset.store(end_offset, Ordering::Relaxed);
+        }
+        tokio::time::sleep(LAG_INTERVAL + Duration::from_millis(100)).await;
+        assert_eq!(Some(90 * 9), lag());
+
+        for _ in 0..90 {
+            let (_, end_offset) = lines.next_line().await.unwrap().unwrap();
+            handed_offset.store(end_offset, Ordering::Relaxed);
+        }
+        tokio::time::sleep(LAG_INTERVAL).await;
+        assert_eq!(Some(0), lag());
+
+        shutdown_token.cancel();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(None, lag());
+    }
+}
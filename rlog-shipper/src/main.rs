@@ -1,43 +1,117 @@
-use std::{process, str::FromStr, time::Duration};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    process,
+    str::FromStr,
+    time::Duration,
+};
 
 use anyhow::Context;
 use clap::Parser;
 use rlog_common::{
-    config::{dir::setup_config_from_dir, setup_config_from_file},
+    bind_addr::BindAddr,
+    config::{dir::setup_config_from_dir, set_strict_mode, setup_config_from_file},
+    log_signature::derive_signing_key,
+    tls::TlsVersion,
     utils::{init_logging, read_file},
 };
 use rlog_grpc::tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity, Uri};
-use rlog_shipper::{config::CONFIG, ServerConfig, ShipperServer};
+use rlog_shipper::{
+    config::{Config, CONFIG},
+    grpc_proxy::proxy_from_env,
+    grpc_tls::PinnedTlsConnector,
+    stdin_in::StdinReader,
+    ServerConfig, ShipperServer,
+};
 use tokio::{select, signal::unix::SignalKind};
 
 /// Collects logs locally and ship them to a remote destination
 #[derive(Debug, Parser)]
 struct Opts {
     /// trusted CA certficate used for mTLS connection
-    #[arg(long, env)]
-    tls_ca_certificate: String,
+    #[arg(
+        long,
+        env,
+        required_unless_present_any = ["check_config", "generate_config", "print_effective_config"]
+    )]
+    tls_ca_certificate: Option<String>,
     /// private key used for mTLS connection
-    #[arg(long, env)]
-    tls_private_key: String,
+    #[arg(
+        long,
+        env,
+        required_unless_present_any = ["check_config", "generate_config", "print_effective_config"]
+    )]
+    tls_private_key: Option<String>,
     /// certificate, signed by the CA corresponding to the private key
-    #[arg(long, env)]
-    tls_certificate: String,
+    #[arg(
+        long,
+        env,
+        required_unless_present_any = ["check_config", "generate_config", "print_effective_config"]
+    )]
+    tls_certificate: Option<String>,
     /// Remote server hostname, if present it will be used for remote
     /// server identify verification (SNI) instead of the host part
     /// of the gRPC collector URL.
     #[arg(long, env)]
     tls_remote_hostname: Option<String>,
+    /// Expected SHA-256 fingerprint of the collector certificate (hex, `:` separators
+    /// are optional), see `rlog-helper cert inspect`. If set, connections to a collector
+    /// presenting another certificate are aborted, even if signed by the CA.
+    #[arg(long, env)]
+    tls_expected_fingerprint: Option<String>,
+    /// Minimum TLS version of the connection to the collector: `1.2` or `1.3`
+    #[arg(long, env, default_value = "1.2")]
+    tls_min_version: TlsVersion,
 
     /// URL of the gRPC endpoint that collects logs
+    #[arg(
+        long,
+        env,
+        required_unless_present_any = ["check_config", "generate_config", "print_effective_config"]
+    )]
+    grpc_collector_url: Option<String>,
+
+    /// HTTP/2 `:authority` (`host[:port]`) sent to the collector instead of the host of
+    /// the gRPC collector URL, eg: virtual hosting behind a shared load balancer. Unlike
+    /// `--tls-remote-hostname`, it does not change the TLS server name.
     #[arg(long, env)]
-    grpc_collector_url: String,
+    grpc_authority: Option<String>,
+
+    /// HTTP proxy used to reach the collector: `http://[user:password@]host[:port]`.
+    /// Connections are tunneled using the HTTP `CONNECT` method, https and SOCKS
+    /// proxies are not supported. If not set, `HTTPS_PROXY` is used unless the collector
+    /// host matches `NO_PROXY`.
+    #[arg(long, env = "RLOG_GRPC_PROXY")]
+    grpc_proxy: Option<String>,
+
+    /// Exit with an error if the collector cannot be reached within
+    /// `--collector-startup-timeout` at startup, instead of retrying in background.
+    #[arg(long, env)]
+    require_collector_on_start: bool,
+    #[arg(long, env, default_value = "30s", value_parser = humantime::parse_duration)]
+    collector_startup_timeout: Duration,
 
     /// syslog udp protocol bind address
     #[arg(long, env, default_value = "127.0.0.1:21054")]
-    syslog_udp_bind_address: String,
+    syslog_udp_bind_address: BindAddr,
     /// gelf tcp protocol bind address
     #[arg(long, env, default_value = "127.0.0.1:12201")]
-    gelf_tcp_bind_address: String,
+    gelf_tcp_bind_address: BindAddr,
+    /// HTTP status server (/health, /debug/recent) bind address, disabled if not set
+    #[arg(long, env)]
+    http_status_bind_address: Option<BindAddr>,
+    /// Bind the HTTP status server to a free port of the --http-status-bind-address host
+    /// (127.0.0.1 if not set), eg: to run several shippers on the same host. The chosen
+    /// address is logged, see also --port-file.
+    #[arg(long, env)]
+    http_status_bind_address_auto: bool,
+    /// File the port of the HTTP status server is written to once bound, for service
+    /// discovery by monitoring agents
+    #[arg(long, env)]
+    port_file: Option<String>,
+    /// Read log lines from the standard input, parsed with the `stdin_in` configuration
+    /// section. The end of the standard input only stops this input.
+    #[arg(long, env)]
+    stdin: bool,
 
     /// Configuration file, if not provided, a minimal default configuration will be used.
     /// This option cannot be used if a configuration directory is provided
@@ -52,6 +126,25 @@ struct Opts {
 
     #[arg(long, env, default_value = "*.yml")]
     config_directory_files_pattern: String,
+
+    /// Reject configuration files containing unknown keys (eg: typos) instead of
+    /// silently ignoring them.
+    #[arg(long, env)]
+    strict_config: bool,
+
+    /// Load the configuration in strict mode, print it and exit.
+    #[arg(long)]
+    check_config: bool,
+
+    /// Print the configuration with the default values, each option commented (purpose,
+    /// valid values, hot reload support), and exit.
+    #[arg(long, alias = "print-default-config")]
+    generate_config: bool,
+
+    /// Load the configuration (file or directory), print it with the defaults of the
+    /// options which are not set, and exit. The output is a valid configuration file.
+    #[arg(long)]
+    print_effective_config: bool,
 }
 
 #[tokio::main]
@@ -64,11 +157,18 @@ async fn main() -> anyhow::Result<()> {
 
     let opts = Opts::parse();
 
+    if opts.generate_config {
+        print!("{}", Config::template_yaml()?);
+        return Ok(());
+    }
+
     if opts.config.is_some() && opts.config_directory.is_some() {
         eprintln!("Invalid options: both a configuration file and a configuration directory has been provided\nPlease make a choice!");
         process::exit(1);
     }
 
+    set_strict_mode(opts.strict_config || opts.check_config);
+
     if let Some(path) = opts.config.as_ref() {
         setup_config_from_file(path, &CONFIG)?;
     } else if let Some(path) = opts.config_directory.as_ref() {
@@ -77,40 +177,109 @@ async fn main() -> anyhow::Result<()> {
         tracing::debug!("No configuration provided, using default.")
     }
 
+    if opts.check_config {
+        print!("{}", serde_yaml::to_string(CONFIG.load().as_ref())?);
+        return Ok(());
+    }
+    if opts.print_effective_config {
+        print!("{}", CONFIG.load().effective_yaml()?);
+        return Ok(());
+    }
+
     tracing::info!(
         "Starting rlog-shipper {} with config:\n{}",
         rlog_shipper::VERSION,
         serde_yaml::to_string(CONFIG.load().as_ref())?
     );
 
-    let endpoint = Channel::builder(
-        Uri::from_str(&opts.grpc_collector_url)
-            .with_context(|| format!("cannot parse {}", opts.grpc_collector_url))?,
-    )
+    // only optional with --check-config, --generate-config & --print-effective-config
+    let grpc_collector_url = opts.grpc_collector_url.unwrap_or_default();
+    let tls_certificate = opts.tls_certificate.unwrap_or_default();
+    let tls_private_key = opts.tls_private_key.unwrap_or_default();
+    let tls_ca_certificate = opts.tls_ca_certificate.unwrap_or_default();
+
+    let grpc_collector_uri = Uri::from_str(&grpc_collector_url)
+        .with_context(|| format!("cannot parse {grpc_collector_url}"))?;
+    let grpc_proxy = match opts.grpc_proxy.as_deref() {
+        Some(grpc_proxy) => Some(grpc_proxy.parse().context("Invalid gRPC proxy")?),
+        None => proxy_from_env(&grpc_collector_uri)?,
+    };
+    let tls_certificate = read_file(&tls_certificate).context("Cannot open certificate")?;
+    let tls_private_key = read_file(&tls_private_key).context("Cannot open private key")?;
+    let tls_ca_certificate =
+        read_file(&tls_ca_certificate).context("Cannot open ca certificate")?;
+
+    let log_signing_key = CONFIG
+        .load()
+        .sign_log_entries
+        .then(|| derive_signing_key(&tls_private_key))
+        .transpose()
+        .context("Unable to derive the log signing key")?;
+
+    // tonic TLS is TLS 1.2+
+    let custom_tls =
+        opts.tls_expected_fingerprint.is_some() || opts.tls_min_version > TlsVersion::Tls12;
+    let (endpoint, grpc_pinned_tls) = if custom_tls {
+        // TLS is handled by the pinning connector
+        (
+            Channel::builder(without_tls(&grpc_collector_uri)?).origin(grpc_collector_uri),
+            Some(
+                PinnedTlsConnector::new(
+                    &tls_ca_certificate,
+                    &tls_certificate,
+                    &tls_private_key,
+                    opts.tls_expected_fingerprint.as_deref(),
+                    opts.tls_remote_hostname.clone(),
+                    opts.tls_min_version,
+                )
+                .context("Invalid TLS configuration")?,
+            ),
+        )
+    } else {
+        (
+            Channel::builder(grpc_collector_uri)
+                .tls_config({
+                    let mut client_tls_config = ClientTlsConfig::new()
+                        .identity(Identity::from_pem(tls_certificate, tls_private_key))
+                        .ca_certificate(Certificate::from_pem(tls_ca_certificate));
+                    if let Some(hostname) = &opts.tls_remote_hostname {
+                        client_tls_config = client_tls_config.domain_name(hostname);
+                    }
+                    client_tls_config
+                })
+                .context("Invalid TLS configuration")?,
+            None,
+        )
+    };
     // always setup tcp keepalive
-    .tcp_keepalive(Some(Duration::from_secs(60)))
-    // tls config
-    .tls_config({
-        let mut client_tls_config = ClientTlsConfig::new();
-        client_tls_config = client_tls_config
-            .identity(Identity::from_pem(
-                read_file(&opts.tls_certificate).context("Cannot open certificate")?,
-                read_file(&opts.tls_private_key).context("Cannot open private key")?,
-            ))
-            .ca_certificate(Certificate::from_pem(
-                read_file(&opts.tls_ca_certificate).context("Cannot open ca certificate")?,
-            ));
-        if let Some(hostname) = &opts.tls_remote_hostname {
-            client_tls_config = client_tls_config.domain_name(hostname);
-        }
-        Ok::<_, anyhow::Error>(client_tls_config)
-    }?)
-    .context("Invalid TLS configuration")?;
+    let endpoint = endpoint.tcp_keepalive(Some(Duration::from_secs(60)));
 
     let shipper_server = ShipperServer::start_shipper_server(ServerConfig {
         grpc_collector_endpoint: endpoint,
+        grpc_proxy,
+        grpc_pinned_tls,
+        grpc_authority: opts.grpc_authority,
         syslog_udp_bind_address: opts.syslog_udp_bind_address,
         gelf_tcp_bind_address: opts.gelf_tcp_bind_address,
+        require_collector_on_start: opts
+            .require_collector_on_start
+            .then_some(opts.collector_startup_timeout),
+        log_signing_key,
+        http_status_bind_address: match (
+            opts.http_status_bind_address,
+            opts.http_status_bind_address_auto,
+        ) {
+            (bind_address, true) => {
+                let ip = bind_address.map_or(Ipv4Addr::LOCALHOST.into(), |b| b.socket_addr().ip());
+                // the port is picked when binding
+                Some(SocketAddr::new(ip, 0).into())
+            }
+            (bind_address, false) => bind_address,
+        },
+        port_file: opts.port_file,
+        stdin: opts
+            .stdin
+            .then(|| Box::new(tokio::io::stdin()) as StdinReader),
     })
     .await?;
 
@@ -129,3 +298,16 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("All tasks successfully exited!");
     Ok(())
 }
+
+/// Same host & port with the `http` scheme: tonic must not handle TLS
+fn without_tls(uri: &Uri) -> anyhow::Result<Uri> {
+    let host = uri
+        .host()
+        .with_context(|| format!("Missing host in {uri}"))?;
+    let port = uri.port_u16().unwrap_or(443);
+    Ok(Uri::builder()
+        .scheme("http")
+        .authority(format!("{host}:{port}"))
+        .path_and_query("/")
+        .build()?)
+}
@@ -1,51 +1,196 @@
 use std::{
     collections::HashMap,
-    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+    time::SystemTime,
 };
 
 use lazy_static::lazy_static;
+use rlog_common::config::{CONFIG_RELOAD_ERROR_COUNT, CONFIG_RELOAD_OK_COUNT};
 use rlog_grpc::rlog_service_protocol::Metrics;
+use uuid::Uuid;
+
+pub use rlog_inputs::metrics::{
+    GELF_CONNECTION_BUFFERS_BYTES, GELF_DISCARDED_COUNT, GELF_ERROR_COUNT, GELF_PEERS,
+    GELF_PROCESSED_COUNT, GELF_QUEUE_CAPACITY, GELF_QUEUE_COUNT, GELF_VERSION_REJECTED_COUNT,
+    SYSLOG_ERROR_COUNT, SYSLOG_INVALID_UTF8_COUNT, SYSLOG_PROCESSED_COUNT, SYSLOG_QUEUE_CAPACITY,
+    SYSLOG_QUEUE_COUNT, SYSLOG_TRUNCATED_COUNT,
+};
+
+use crate::byte_budget::SHIPPER_BYTE_BUDGET;
+
+/// GELF peers reported with the metrics, the ones which sent the most frames
+pub const GELF_PEERS_REPORTED: usize = 10;
 
 lazy_static! {
     pub static ref FILES_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref GELF_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref SYSLOG_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
     pub static ref SHIPPER_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref GELF_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_HIGH_PRIORITY_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_LOW_PRIORITY_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
     pub static ref FILES_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref SYSLOG_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
     pub static ref SHIPPER_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
     pub static ref SHIPPER_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref GELF_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
-    pub static ref SYSLOG_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
     pub static ref FILES_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+    /// log lines dropped by the transforms of each input
+    pub static ref FILES_DROPPED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref STDIN_DROPPED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref GELF_DROPPED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_DROPPED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_FINGERPRINT_MISMATCH_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_LOW_PRIORITY_DROPPED_COUNT: AtomicU64 = AtomicU64::new(0);
+    /// metrics reports not accepted by the collector (retries included)
+    pub static ref SHIPPER_METRICS_REPORT_FAILURE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref FILES_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_HIGH_PRIORITY_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    pub static ref SHIPPER_LOW_PRIORITY_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_OUT_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_OUT_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_OUT_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref SYSLOG_OUT_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    /// bytes between the end of each watched file and the last line handed to the pipeline
+    pub static ref FILES_LAG_BYTES: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+    /// reported with the metrics, initialized when the shipper server starts
+    pub static ref PROCESS_START_TIME: SystemTime = SystemTime::now();
+    /// random id of the shipper process, sent with the metrics & the sequence numbers
+    pub static ref INCARNATION_ID: String = Uuid::new_v4().to_string();
+    pub static ref STDIN_QUEUE_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref STDIN_PROCESSED_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref STDIN_ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static ref STDIN_QUEUE_CAPACITY: AtomicU64 = AtomicU64::new(0);
+    /// sequence number of the next line read from the standard input
+    pub static ref STDIN_SEQUENCE: AtomicU64 = AtomicU64::new(0);
 }
 
 pub(crate) fn to_grpc_metrics() -> Metrics {
+    let gelf_peers = GELF_PEERS.top(GELF_PEERS_REPORTED);
     Metrics {
         hostname: hostname::get().unwrap().to_string_lossy().to_string(),
         queue_count: {
             let mut map = HashMap::new();
+            map.insert("files_in".into(), FILES_QUEUE_COUNT.load(Relaxed));
+            map.insert("stdin_in".into(), STDIN_QUEUE_COUNT.load(Relaxed));
             map.insert("glef_in".into(), GELF_QUEUE_COUNT.load(Relaxed));
             map.insert("syslog_in".into(), SYSLOG_QUEUE_COUNT.load(Relaxed));
             map.insert("grpc_out".into(), SHIPPER_QUEUE_COUNT.load(Relaxed));
+            map.insert("syslog_out".into(), SYSLOG_OUT_QUEUE_COUNT.load(Relaxed));
+            map.insert(
+                "grpc_out_high".into(),
+                SHIPPER_HIGH_PRIORITY_QUEUE_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "grpc_out_low".into(),
+                SHIPPER_LOW_PRIORITY_QUEUE_COUNT.load(Relaxed),
+            );
+            map.insert("buffered_bytes".into(), SHIPPER_BYTE_BUDGET.used());
+            map.insert(
+                "gelf_in_connection_buffers_bytes".into(),
+                GELF_CONNECTION_BUFFERS_BYTES.load(Relaxed),
+            );
+            for (path, lag) in FILES_LAG_BYTES.lock().unwrap().iter() {
+                map.insert(format!("files_in:{path}:lag_bytes"), *lag);
+            }
             map
         },
         processed_count: {
             let mut map = HashMap::new();
             map.insert("files_in".into(), FILES_PROCESSED_COUNT.load(Relaxed));
+            map.insert("stdin_in".into(), STDIN_PROCESSED_COUNT.load(Relaxed));
             map.insert("glef_in".into(), GELF_PROCESSED_COUNT.load(Relaxed));
             map.insert("syslog_in".into(), SYSLOG_PROCESSED_COUNT.load(Relaxed));
             map.insert("grpc_out".into(), SHIPPER_PROCESSED_COUNT.load(Relaxed));
+            map.insert(
+                "syslog_out".into(),
+                SYSLOG_OUT_PROCESSED_COUNT.load(Relaxed),
+            );
+            map.insert("config_reload".into(), CONFIG_RELOAD_OK_COUNT.load(Relaxed));
+            for peer in &gelf_peers {
+                map.insert(format!("gelf_in:{}", peer.peer), peer.frames);
+            }
             map
         },
         error_count: {
             let mut map = HashMap::new();
             map.insert("files_in".into(), FILES_ERROR_COUNT.load(Relaxed));
+            map.insert("stdin_in".into(), STDIN_ERROR_COUNT.load(Relaxed));
             map.insert("glef_in".into(), GELF_ERROR_COUNT.load(Relaxed));
             map.insert("syslog_in".into(), SYSLOG_ERROR_COUNT.load(Relaxed));
             map.insert("grpc_out".into(), SHIPPER_ERROR_COUNT.load(Relaxed));
+            map.insert("syslog_out".into(), SYSLOG_OUT_ERROR_COUNT.load(Relaxed));
+            map.insert("byte_budget".into(), SHIPPER_BYTE_BUDGET.dropped());
+            map.insert(
+                "files_in_transform".into(),
+                FILES_DROPPED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "stdin_in_transform".into(),
+                STDIN_DROPPED_COUNT.load(Relaxed),
+            );
+            map.insert("gelf_in_transform".into(), GELF_DROPPED_COUNT.load(Relaxed));
+            map.insert(
+                "syslog_in_transform".into(),
+                SYSLOG_DROPPED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "gelf_in_discarded".into(),
+                GELF_DISCARDED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "gelf_in_version".into(),
+                GELF_VERSION_REJECTED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "syslog_in_invalid_utf8".into(),
+                SYSLOG_INVALID_UTF8_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "syslog_in_truncated".into(),
+                SYSLOG_TRUNCATED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "grpc_out_fingerprint".into(),
+                SHIPPER_FINGERPRINT_MISMATCH_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "grpc_out_low".into(),
+                SHIPPER_LOW_PRIORITY_DROPPED_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "metrics_report".into(),
+                SHIPPER_METRICS_REPORT_FAILURE_COUNT.load(Relaxed),
+            );
+            map.insert(
+                "config_reload".into(),
+                CONFIG_RELOAD_ERROR_COUNT.load(Relaxed),
+            );
+            for peer in &gelf_peers {
+                map.insert(format!("gelf_in:{}", peer.peer), peer.parse_errors);
+                map.insert(format!("gelf_in:{}:dropped", peer.peer), peer.dropped);
+            }
+            map
+        },
+        queue_capacity: {
+            let mut map = HashMap::new();
+            map.insert("files_in".into(), FILES_QUEUE_CAPACITY.load(Relaxed));
+            map.insert("stdin_in".into(), STDIN_QUEUE_CAPACITY.load(Relaxed));
+            map.insert("glef_in".into(), GELF_QUEUE_CAPACITY.load(Relaxed));
+            map.insert("syslog_in".into(), SYSLOG_QUEUE_CAPACITY.load(Relaxed));
+            map.insert("grpc_out".into(), SHIPPER_QUEUE_CAPACITY.load(Relaxed));
+            map.insert("syslog_out".into(), SYSLOG_OUT_QUEUE_CAPACITY.load(Relaxed));
+            map.insert(
+                "grpc_out_high".into(),
+                SHIPPER_HIGH_PRIORITY_QUEUE_CAPACITY.load(Relaxed),
+            );
+            map.insert(
+                "grpc_out_low".into(),
+                SHIPPER_LOW_PRIORITY_QUEUE_CAPACITY.load(Relaxed),
+            );
             map
         },
+        process_start_time: Some((*PROCESS_START_TIME).into()),
+        incarnation_id: INCARNATION_ID.clone(),
+        delivery_window: None,
     }
 }
@@ -0,0 +1,93 @@
+//! Lines read from the standard input (`--stdin`), eg: a containerized app piped into
+//! the shipper, parsed with the `stdin_in` configuration like the lines of a watched file.
+
+use std::sync::atomic::Ordering;
+
+use async_channel::Receiver;
+use futures::FutureExt;
+use rlog_common::utils::format_error;
+use rlog_inputs::{generic_log::GenericLog, recent_inputs::RECENT_INPUTS};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    select,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::byte_budget::{self, Budgeted};
+use crate::config::CONFIG;
+use crate::metrics::{STDIN_ERROR_COUNT, STDIN_QUEUE_CAPACITY, STDIN_QUEUE_COUNT, STDIN_SEQUENCE};
+
+/// Stream read line by line by the `stdin_in` input
+pub type StdinReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Service name of the log lines without `service_name` field
+const SERVICE_NAME: &str = "stdin";
+
+/// Read the lines of `reader` until the end of the stream, which only stops this input
+pub fn read_stdin(
+    reader: StdinReader,
+    shutdown_token: CancellationToken,
+) -> Receiver<Budgeted<GenericLog>> {
+    let (sender, receiver) = async_channel::bounded(1);
+    STDIN_QUEUE_CAPACITY.store(1, Ordering::Relaxed);
+
+    tokio::spawn(
+        async move {
+            let mut reader = BufReader::new(reader);
+            let mut buffer = Vec::new();
+            loop {
+                buffer.clear();
+                let read = select! {
+                    _ = shutdown_token.cancelled() => return,
+                    read = reader.read_until(b'\n', &mut buffer) => read,
+                };
+                match read {
+                    Ok(0) => {
+                        tracing::info!("End of the standard input");
+                        return;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!("Unable to read the standard input: {e}");
+                        return;
+                    }
+                }
+                let line = String::from_utf8_lossy(&buffer);
+                let line = line.trim_end_matches(['\n', '\r']);
+                RECENT_INPUTS.record("stdin_in", line);
+                let log = match CONFIG.load().stdin_in.as_ref() {
+                    Some(parse_config) => parse_config.to_log(line, SERVICE_NAME),
+                    None => {
+                        STDIN_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                        tracing::error!("No stdin_in configuration: discarding line {line}");
+                        continue;
+                    }
+                };
+                let log = match log {
+                    Ok(log) => log,
+                    Err(e) => {
+                        STDIN_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                        tracing::error!(error = %format_error(e), "Unable to parse line {line}");
+                        continue;
+                    }
+                };
+                let sequence = STDIN_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+                let Some(reservation) = byte_budget::reserve(line.len()) else {
+                    tracing::error!("Buffered bytes budget exceeded: discarding line {line}");
+                    continue;
+                };
+                let message = Budgeted::new(log, reservation).with_sequence(sequence);
+                if sender.send(message).await.is_err() {
+                    tracing::error!("out channel closed");
+                    return;
+                }
+                STDIN_QUEUE_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        .then(|_| async { tracing::info!("stdin_in input stopped") })
+        .instrument(tracing::info_span!("stdin_in")),
+    );
+
+    receiver
+}
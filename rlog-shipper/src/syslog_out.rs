@@ -0,0 +1,196 @@
+//! Relay of the received syslog datagrams, unparsed, to a downstream syslog server: the
+//! original frame is sent as is, alongside or instead of the gRPC output.
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use async_channel::{Receiver, Sender, TrySendError};
+use rlog_common::utils::format_error;
+use rlog_inputs::syslog_server::SyslogLog;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{lookup_host, TcpStream, UdpSocket},
+    select,
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    byte_budget::Budgeted,
+    config::{SyslogOutConfig, SyslogOutProtocol},
+    metrics::{
+        SYSLOG_OUT_ERROR_COUNT, SYSLOG_OUT_PROCESSED_COUNT, SYSLOG_OUT_QUEUE_CAPACITY,
+        SYSLOG_OUT_QUEUE_COUNT, SYSLOG_PROCESSED_COUNT, SYSLOG_QUEUE_COUNT,
+    },
+};
+
+/// Start the relay configured by `config`, the returned receiver yields the syslog logs to
+/// ship through gRPC (none if `config.grpc` is false).
+pub fn launch_syslog_out(
+    config: SyslogOutConfig,
+    syslog_receiver: Receiver<Budgeted<SyslogLog>>,
+    shutdown_token: CancellationToken,
+) -> (
+    Receiver<Budgeted<SyslogLog>>,
+    JoinHandle<()>,
+    JoinHandle<()>,
+) {
+    SYSLOG_OUT_QUEUE_CAPACITY.store(config.max_buffer_size as u64, Ordering::Relaxed);
+    let (relay_sender, relay_receiver) = async_channel::bounded(config.max_buffer_size);
+    // same capacity than the syslog input queue, messages stay accounted in its metrics
+    let (grpc_sender, grpc_receiver) =
+        async_channel::bounded(syslog_receiver.capacity().unwrap_or(1));
+    let tee = tokio::spawn(tee_loop(
+        syslog_receiver,
+        relay_sender,
+        config.grpc.then_some(grpc_sender),
+    ));
+    let relay = tokio::spawn(relay_loop(config, relay_receiver, shutdown_token));
+    (grpc_receiver, tee, relay)
+}
+
+async fn tee_loop(
+    input: Receiver<Budgeted<SyslogLog>>,
+    relay: Sender<Vec<u8>>,
+    grpc: Option<Sender<Budgeted<SyslogLog>>>,
+) {
+    while let Ok(log) = input.recv().await {
+        match relay.try_send(log.value.raw().to_vec()) {
+            Ok(()) => {
+                SYSLOG_OUT_QUEUE_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                SYSLOG_OUT_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                tracing::error!("syslog_out buffer full: discarding value {}", log.value);
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::error!("syslog_out channel closed!");
+                break;
+            }
+        }
+        match &grpc {
+            Some(grpc) => {
+                if grpc.send(log).await.is_err() {
+                    tracing::error!("syslog_in forward channel closed!");
+                    break;
+                }
+            }
+            // not shipped through gRPC: the log leaves the syslog input pipeline here
+            None => {
+                SYSLOG_QUEUE_COUNT.fetch_sub(1, Ordering::Relaxed);
+                SYSLOG_PROCESSED_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    tracing::info!("syslog_in input channel closed, syslog_out tee task stopped.");
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    async fn connect(config: &SyslogOutConfig) -> anyhow::Result<Self> {
+        let address = lookup_host(&config.address)
+            .await
+            .with_context(|| format!("Unable to resolve {}", config.address))?
+            .next()
+            .ok_or_else(|| anyhow!("No address found for {}", config.address))?;
+        Ok(match config.protocol {
+            SyslogOutProtocol::Udp => {
+                let local_address: SocketAddr = match address {
+                    SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+                    SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+                };
+                let socket = UdpSocket::bind(local_address).await?;
+                socket.connect(address).await?;
+                Self::Udp(socket)
+            }
+            SyslogOutProtocol::Tcp => Self::Tcp(TcpStream::connect(address).await?),
+        })
+    }
+
+    async fn send(&mut self, frame: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Udp(socket) => {
+                socket.send(frame).await?;
+            }
+            // RFC 6587 octet counting: the frame is sent as is, even if it contains new lines
+            Self::Tcp(stream) => {
+                let mut buf = format!("{} ", frame.len()).into_bytes();
+                buf.extend_from_slice(frame);
+                stream.write_all(&buf).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn relay_loop(
+    config: SyslogOutConfig,
+    input: Receiver<Vec<u8>>,
+    shutdown_token: CancellationToken,
+) {
+    let mut connection = None;
+    // frame that could not be sent, sent again once reconnected
+    let mut pending = None;
+    loop {
+        let frame = match pending.take() {
+            Some(frame) => frame,
+            None => match input.recv().await {
+                Ok(frame) => {
+                    SYSLOG_OUT_QUEUE_COUNT.fetch_sub(1, Ordering::Relaxed);
+                    frame
+                }
+                Err(_) => break,
+            },
+        };
+        let connected = match &mut connection {
+            Some(connected) => connected,
+            None => match Connection::connect(&config).await {
+                Ok(connected) => {
+                    tracing::info!("syslog_out connected to {}", config.address);
+                    connection.insert(connected)
+                }
+                Err(e) => {
+                    tracing::error!(
+                        error = %format_error(e),
+                        "Unable to connect to syslog server {}", config.address
+                    );
+                    pending = Some(frame);
+                    select! {
+                        _ = tokio::time::sleep(Duration::from_secs(1)) => continue,
+                        _ = shutdown_token.cancelled() => break,
+                    }
+                }
+            },
+        };
+        match connected.send(&frame).await {
+            Ok(()) => {
+                SYSLOG_OUT_PROCESSED_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                SYSLOG_OUT_ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+                tracing::error!(
+                    error = %format_error(e),
+                    "Unable to send syslog message to {}", config.address
+                );
+                connection = None;
+                // a datagram that cannot be sent will most likely never be
+                if config.protocol == SyslogOutProtocol::Tcp {
+                    pending = Some(frame);
+                }
+            }
+        }
+    }
+    let discarded = pending.map_or(0, |_| 1) + input.len();
+    if discarded > 0 {
+        tracing::warn!("syslog_out stopped, {discarded} messages not relayed");
+    }
+    tracing::info!("syslog_out relay task stopped.");
+}
@@ -0,0 +1,218 @@
+//! Transforms applied by the forward loops to the log lines of an input, between their
+//! conversion and the grpc_out channel.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use rlog_grpc::rlog_service_protocol::LogLine;
+
+use crate::{
+    config::{Config, HostnameMapping, CONFIG},
+    forward_loop::{add_labels, Input},
+    hostname_mapping::map_hostname,
+};
+
+pub trait LogLineTransform: Send + Sync {
+    /// Transformed log line, `None` if the log line is dropped
+    fn apply(&self, log_line: LogLine) -> Option<LogLine>;
+}
+
+/// Transforms applied in order, the first one dropping the log line stops the chain
+#[derive(Default)]
+pub struct Chain(Vec<Box<dyn LogLineTransform>>);
+
+impl Chain {
+    pub fn then(mut self, transform: impl LogLineTransform + 'static) -> Self {
+        self.0.push(Box::new(transform));
+        self
+    }
+}
+
+impl LogLineTransform for Chain {
+    fn apply(&self, log_line: LogLine) -> Option<LogLine> {
+        self.0
+            .iter()
+            .try_fold(log_line, |log_line, transform| transform.apply(log_line))
+    }
+}
+
+/// Host override by the first matching `hostname_mappings` rule
+struct HostnameMappings(Vec<HostnameMapping>);
+
+impl LogLineTransform for HostnameMappings {
+    fn apply(&self, mut log_line: LogLine) -> Option<LogLine> {
+        map_hostname(&mut log_line, &self.0);
+        Some(log_line)
+    }
+}
+
+/// Static labels, global labels overridden by the labels of the input
+struct Labels(HashMap<String, String>);
+
+impl LogLineTransform for Labels {
+    fn apply(&self, mut log_line: LogLine) -> Option<LogLine> {
+        add_labels(&mut log_line, &self.0, None);
+        Some(log_line)
+    }
+}
+
+/// Transforms of the input configured in `config`: host overrides then labels, filters are
+/// expected to come first so that dropped log lines are not transformed
+pub fn input_transform(config: &Config, input: &Input) -> Chain {
+    let mut chain = Chain::default();
+    if !config.hostname_mappings.is_empty() {
+        chain = chain.then(HostnameMappings(config.hostname_mappings.clone()));
+    }
+    let mut labels = config.labels.clone();
+    if let Some(input_labels) = input.labels(config) {
+        labels.extend(input_labels.clone());
+    }
+    if !labels.is_empty() {
+        chain = chain.then(Labels(labels));
+    }
+    chain
+}
+
+/// Transforms of an input, rebuilt from [CONFIG] when the configuration is reloaded
+pub struct ConfigTransform {
+    input: Input,
+    /// configuration the transforms were built from
+    current: Mutex<(Arc<Config>, Arc<Chain>)>,
+}
+
+impl ConfigTransform {
+    pub fn new(input: Input) -> Self {
+        let config = CONFIG.load_full();
+        let chain = Arc::new(input_transform(&config, &input));
+        Self {
+            input,
+            current: Mutex::new((config, chain)),
+        }
+    }
+
+    fn chain(&self) -> Arc<Chain> {
+        let mut current = self.current.lock().unwrap();
+        let config = CONFIG.load_full();
+        // a reload swaps the configuration
+        if !Arc::ptr_eq(&current.0, &config) {
+            let chain = Arc::new(input_transform(&config, &self.input));
+            *current = (config, chain);
+        }
+        current.1.clone()
+    }
+}
+
+impl LogLineTransform for ConfigTransform {
+    fn apply(&self, log_line: LogLine) -> Option<LogLine> {
+        self.chain().apply(log_line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use rlog_grpc::rlog_service_protocol::LogLine;
+
+    use super::{input_transform, Chain, LogLineTransform};
+    use crate::{
+        config::{eqregex::EqRegex, Config, GelfInputConfig, HostnameMapping},
+        forward_loop::Input,
+    };
+
+    /// Appends a suffix to the host
+    struct Append(&'static str);
+
+    impl LogLineTransform for Append {
+        fn apply(&self, mut log_line: LogLine) -> Option<LogLine> {
+            log_line.host.push_str(self.0);
+            Some(log_line)
+        }
+    }
+
+    /// Drops the log lines of this host, counts the others
+    struct DropMessage(&'static str, Arc<AtomicU64>);
+
+    impl LogLineTransform for DropMessage {
+        fn apply(&self, log_line: LogLine) -> Option<LogLine> {
+            if log_line.host == self.0 {
+                return None;
+            }
+            self.1.fetch_add(1, Ordering::Relaxed);
+            Some(log_line)
+        }
+    }
+
+    fn apply(chain: &Chain, host: &str) -> Option<String> {
+        let log_line = LogLine {
+            host: host.into(),
+            ..Default::default()
+        };
+        chain.apply(log_line).map(|log_line| log_line.host)
+    }
+
+    #[test]
+    fn test_chain() {
+        let chain = Chain::default().then(Append("a")).then(Append("b"));
+        assert_eq!(Some("ab".into()), apply(&chain, ""));
+
+        let kept_count = Arc::new(AtomicU64::new(0));
+        let chain = Chain::default()
+            .then(Append("a"))
+            .then(DropMessage("a", kept_count.clone()))
+            .then(Append("c"));
+        // the drop sees the output of the previous transform
+        assert_eq!(None, apply(&chain, ""));
+        assert_eq!(Some("bac".into()), apply(&chain, "b"));
+        assert_eq!(None, apply(&chain, ""));
+        assert_eq!(1, kept_count.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_input_transform() {
+        let mut config = Config {
+            hostname_mappings: vec![HostnameMapping {
+                pattern: EqRegex::new(r"^(web)-\d+$").unwrap(),
+                replacement: "$1".into(),
+            }],
+            labels: [("env", "prod"), ("team", "ops")]
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+            gelf_in: Some(GelfInputConfig::default()),
+            ..Default::default()
+        };
+        config
+            .gelf_in
+            .as_mut()
+            .unwrap()
+            .common
+            .labels
+            .insert("team".into(), "web".into());
+
+        let log_line = LogLine {
+            host: "web-12".into(),
+            ..Default::default()
+        };
+        let gelf = input_transform(&config, &Input::Gelf)
+            .apply(log_line.clone())
+            .unwrap();
+        assert_eq!("web", gelf.host);
+        assert_eq!("prod", gelf.labels["env"]);
+        assert_eq!("web", gelf.labels["team"]);
+
+        let syslog = input_transform(&config, &Input::Syslog)
+            .apply(log_line)
+            .unwrap();
+        assert_eq!("ops", syslog.labels["team"]);
+
+        assert!(input_transform(&Config::default(), &Input::Stdin)
+            .0
+            .is_empty());
+    }
+}